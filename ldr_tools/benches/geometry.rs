@@ -0,0 +1,56 @@
+use std::{fmt::Write, path::Path};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use ldr_tools::{load_file, GeometrySettings};
+
+/// Write an LDraw library with one very large part and many small parts
+/// to measure how evenly geometry creation is distributed across threads.
+fn write_library(root: &Path, small_part_count: usize) -> String {
+    let parts = root.join("parts");
+    std::fs::create_dir_all(&parts).unwrap();
+
+    let mut large = String::new();
+    for i in 0..100000 {
+        writeln!(large, "3 16 {i} 0 0 0 1 0 0 0 1").unwrap();
+    }
+    std::fs::write(parts.join("large.dat"), large).unwrap();
+
+    let mut model = "1 16 0 0 0 1 0 0 0 1 0 0 0 1 large.dat\n".to_string();
+    for i in 0..small_part_count {
+        let mut small = String::new();
+        for j in 0..100 {
+            writeln!(small, "4 16 {j} 0 0 {j} 1 0 {j} 1 1 {j} 0 1").unwrap();
+        }
+        std::fs::write(parts.join(format!("small{i}.dat")), small).unwrap();
+        writeln!(model, "1 16 {i} 0 0 1 0 0 0 1 0 0 0 1 small{i}.dat").unwrap();
+    }
+
+    let model_path = root.join("model.ldr");
+    std::fs::write(&model_path, model).unwrap();
+    model_path.to_string_lossy().to_string()
+}
+
+fn load_uneven_parts(c: &mut Criterion) {
+    let root = tempfile::tempdir().unwrap();
+    let model_path = write_library(root.path(), 500);
+    let ldraw_path = root.path().to_string_lossy().to_string();
+
+    let mut group = c.benchmark_group("load uneven parts");
+    for thread_count in [Some(1), None] {
+        let settings = GeometrySettings {
+            thread_count,
+            ..Default::default()
+        };
+        group.bench_function(format!("{thread_count:?} threads"), |b| {
+            b.iter(|| load_file(&model_path, &ldraw_path, &[], &settings))
+        });
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = load_uneven_parts
+}
+criterion_main!(benches);
@@ -0,0 +1,65 @@
+use std::{collections::HashMap, fmt::Write, path::Path};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use ldr_tools::ldraw::{parse, FileRefResolver, ResolveError, SourceMap};
+
+struct MemoryResolver {
+    files: HashMap<String, Vec<u8>>,
+}
+
+impl FileRefResolver for MemoryResolver {
+    fn resolve<P: AsRef<Path>>(&self, filename: P) -> Result<Vec<u8>, ResolveError> {
+        let filename = filename.as_ref().to_string_lossy();
+        self.files
+            .get(filename.as_ref())
+            .cloned()
+            .ok_or_else(|| ResolveError::new_raw(&filename))
+    }
+}
+
+/// An MPD model referencing thousands of unique parts
+/// that each reference a shared primitive.
+fn large_model_resolver(part_count: usize) -> MemoryResolver {
+    let mut files = HashMap::new();
+
+    let mut model = "0 FILE main.ldr\n".to_string();
+    for i in 0..part_count {
+        writeln!(model, "1 16 {i} 0 0 1 0 0 0 1 0 0 0 1 part{i}.dat").unwrap();
+    }
+    files.insert("main.ldr".to_string(), model.into_bytes());
+
+    for i in 0..part_count {
+        let mut part = String::new();
+        for j in 0..200 {
+            writeln!(part, "3 16 {j} 0 0 0 1 0 0 0 1").unwrap();
+            writeln!(part, "2 24 {j} 0 0 0 1 0").unwrap();
+        }
+        writeln!(part, "1 16 0 0 0 1 0 0 0 1 0 0 0 1 prim.dat").unwrap();
+        files.insert(format!("part{i}.dat"), part.into_bytes());
+    }
+    files.insert(
+        "prim.dat".to_string(),
+        b"4 16 0 0 0 1 0 0 1 1 0 0 1 0".to_vec(),
+    );
+
+    MemoryResolver { files }
+}
+
+fn parse_large_model(c: &mut Criterion) {
+    let resolver = large_model_resolver(4000);
+
+    // Compare with RAYON_NUM_THREADS=1 to measure the speedup from parallel parsing.
+    c.bench_function("parse 4000 parts", |b| {
+        b.iter(|| {
+            let mut source_map = SourceMap::new();
+            parse("main.ldr", &resolver, &mut source_map).unwrap()
+        })
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = parse_large_model
+}
+criterion_main!(benches);
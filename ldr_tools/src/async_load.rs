@@ -0,0 +1,87 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::{GeometrySettings, LDrawScene, LoadError, LoadOptions};
+
+/// Same as [try_load_file](crate::try_load_file) but runs on a blocking thread
+/// with [tokio::task::spawn_blocking] to avoid blocking async tasks like a UI during the load.
+///
+/// This must be awaited within a tokio runtime and panics otherwise.
+/// Dropping the returned future cancels the load at the next checkpoint.
+pub async fn load_file_async(
+    path: &str,
+    ldraw_path: &str,
+    additional_paths: &[String],
+    settings: &GeometrySettings,
+) -> Result<LDrawScene, LoadError> {
+    let path = path.to_string();
+    let ldraw_path = ldraw_path.to_string();
+    let additional_paths = additional_paths.to_vec();
+    let settings = settings.clone();
+
+    let cancel = CancelOnDrop(Arc::new(AtomicBool::new(false)));
+    let cancelled = cancel.0.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        crate::try_load_file(
+            &path,
+            &ldraw_path,
+            &additional_paths,
+            &settings,
+            &mut crate::ldraw::SourceMap::new(),
+            LoadOptions {
+                cancel: Some(&cancelled),
+                ..Default::default()
+            },
+        )
+    })
+    .await;
+
+    match result {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        // The runtime is shutting down.
+        Err(_) => Err(LoadError::Cancelled),
+    }
+}
+
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_file_async_part() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("main.ldr"),
+            "1 4 0 0 0 1 0 0 0 1 0 0 0 1 a.dat\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("a.dat"), "3 16 1 0 0 0 1 0 0 0 1\n").unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let scene = runtime
+            .block_on(load_file_async(
+                dir.path().join("main.ldr").to_str().unwrap(),
+                "",
+                &[],
+                &GeometrySettings::default(),
+            ))
+            .unwrap();
+
+        assert_eq!(4, scene.root_node.children[0].current_color);
+        assert_eq!(vec![3], scene.geometry_cache["a.dat"].face_sizes);
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashSet;
+
+use crate::ldraw::{BfcCommand, Command, SourceFile, SourceMap, Winding};
+
+/// A problem with the [BFC](https://www.ldraw.org/article/415) commands of a file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BfcIssue {
+    /// The name of the file with the issue.
+    pub filename: String,
+    pub kind: BfcIssueKind,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BfcIssueKind {
+    /// The file has faces without first using `0 BFC CERTIFY`.
+    NotCertified,
+    /// The file is certified but uses both clockwise and counter-clockwise winding.
+    MixedWinding,
+    /// `0 BFC INVERTNEXT` is not followed by a subfile reference.
+    InvertNextWithoutSubfile,
+}
+
+impl std::fmt::Display for BfcIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let description = match self.kind {
+            BfcIssueKind::NotCertified => "faces are not BFC certified",
+            BfcIssueKind::MixedWinding => "certified file uses both CW and CCW winding",
+            BfcIssueKind::InvertNextWithoutSubfile => "INVERTNEXT is not followed by a subfile",
+        };
+        write!(f, "{}: {description}", self.filename)
+    }
+}
+
+/// Check `filename` and all of its subfiles for BFC issues.
+/// Each file is only checked once even if it is referenced multiple times.
+pub fn check_bfc(filename: &str, source_map: &SourceMap) -> Vec<BfcIssue> {
+    let mut issues = Vec::new();
+
+    let mut visited = HashSet::new();
+    let mut stack = vec![filename.to_string()];
+    while let Some(filename) = stack.pop() {
+        if !visited.insert(filename.to_lowercase()) {
+            continue;
+        }
+
+        if let Some(source_file) = source_map.get(&filename) {
+            for kind in check_file(source_file) {
+                issues.push(BfcIssue {
+                    filename: filename.clone(),
+                    kind,
+                });
+            }
+
+            for cmd in source_file.cmds.iter().rev() {
+                if let Command::SubFileRef(sfr_cmd) = cmd {
+                    stack.push(sfr_cmd.file.clone());
+                }
+            }
+        }
+    }
+
+    issues
+}
+
+fn check_file(source_file: &SourceFile) -> Vec<BfcIssueKind> {
+    let mut issues = Vec::new();
+
+    let mut certified = false;
+    let mut has_uncertified_faces = false;
+    let mut windings = HashSet::new();
+    let mut invert_next = false;
+
+    for cmd in &source_file.cmds {
+        let is_comment = matches!(
+            cmd,
+            Command::Comment(_)
+                | Command::Name(_)
+                | Command::Author(_)
+                | Command::License(_)
+                | Command::Help(_)
+                | Command::History(_)
+                | Command::LPub(_)
+                | Command::LeoCadModel(_)
+                | Command::LeoCadPiece(_)
+                | Command::Step
+                | Command::RotStep(_)
+                | Command::BufExchg(_)
+                | Command::TexMap(_)
+        );
+        if invert_next && !is_comment {
+            if !matches!(cmd, Command::SubFileRef(_)) {
+                issues.push(BfcIssueKind::InvertNextWithoutSubfile);
+            }
+            invert_next = false;
+        }
+
+        match cmd {
+            Command::Bfc(bfc_cmd) => match bfc_cmd {
+                BfcCommand::NoCertify => certified = false,
+                BfcCommand::Certify(winding) => {
+                    certified = true;
+                    windings.insert(winding.unwrap_or(Winding::Ccw));
+                }
+                BfcCommand::Winding(winding) => {
+                    windings.insert(*winding);
+                }
+                BfcCommand::NoClip => (),
+                BfcCommand::Clip(winding) => {
+                    if let Some(winding) = winding {
+                        windings.insert(*winding);
+                    }
+                }
+                BfcCommand::InvertNext => invert_next = true,
+            },
+            Command::Triangle(_) | Command::Quad(_) if !certified => {
+                has_uncertified_faces = true;
+            }
+            _ => (),
+        }
+    }
+
+    if invert_next {
+        issues.push(BfcIssueKind::InvertNextWithoutSubfile);
+    }
+    if has_uncertified_faces {
+        issues.push(BfcIssueKind::NotCertified);
+    }
+    if certified && windings.len() > 1 {
+        issues.push(BfcIssueKind::MixedWinding);
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use indoc::indoc;
+
+    fn source_file(document: &str) -> SourceFile {
+        SourceFile {
+            cmds: crate::ldraw::parse_raw(document.as_bytes()).unwrap(),
+        }
+    }
+
+    #[test]
+    fn check_bfc_certified() {
+        let mut source_map = SourceMap::new();
+        source_map.insert(
+            "a.dat",
+            source_file(indoc! {"
+                0 BFC CERTIFY CCW
+                0 BFC INVERTNEXT
+                1 16 0 0 0 1 0 0 0 1 0 0 0 1 b.dat
+                3 16 1 0 0 0 1 0 0 0 1
+            "}),
+        );
+        source_map.insert(
+            "b.dat",
+            source_file(indoc! {"
+                0 BFC CERTIFY CCW
+                4 16 1 0 0 0 1 0 0 0 1 1 1 1
+            "}),
+        );
+
+        assert!(check_bfc("a.dat", &source_map).is_empty());
+    }
+
+    #[test]
+    fn check_bfc_issues() {
+        let mut source_map = SourceMap::new();
+        source_map.insert(
+            "a.dat",
+            source_file(indoc! {"
+                0 BFC CERTIFY CCW
+                0 BFC CW
+                0 BFC INVERTNEXT
+                3 16 1 0 0 0 1 0 0 0 1
+                1 16 0 0 0 1 0 0 0 1 0 0 0 1 b.dat
+                1 16 0 0 0 1 0 0 0 1 0 0 0 1 B.DAT
+            "}),
+        );
+        source_map.insert("b.dat", source_file("3 16 1 0 0 0 1 0 0 0 1"));
+
+        assert_eq!(
+            vec![
+                BfcIssue {
+                    filename: "a.dat".to_string(),
+                    kind: BfcIssueKind::InvertNextWithoutSubfile
+                },
+                BfcIssue {
+                    filename: "a.dat".to_string(),
+                    kind: BfcIssueKind::MixedWinding
+                },
+                BfcIssue {
+                    filename: "b.dat".to_string(),
+                    kind: BfcIssueKind::NotCertified
+                },
+            ],
+            check_bfc("a.dat", &source_map)
+        );
+    }
+
+    fn invert_next_issues(meta: &str) -> Vec<BfcIssue> {
+        let mut source_map = SourceMap::new();
+        source_map.insert(
+            "a.dat",
+            source_file(&format!(
+                "0 BFC CERTIFY CCW\n0 BFC INVERTNEXT\n{meta}\n1 16 0 0 0 1 0 0 0 1 0 0 0 1 b.dat\n"
+            )),
+        );
+        source_map.insert("b.dat", source_file("0 BFC CERTIFY CCW"));
+        check_bfc("a.dat", &source_map)
+    }
+
+    #[test]
+    fn check_bfc_invert_next_lpub() {
+        assert!(invert_next_issues("0 !LPUB PLI BEGIN IGN").is_empty());
+    }
+
+    #[test]
+    fn check_bfc_invert_next_step() {
+        assert!(invert_next_issues("0 STEP").is_empty());
+        assert!(invert_next_issues("0 ROTSTEP 10 20 0 ABS").is_empty());
+    }
+
+    #[test]
+    fn check_bfc_invert_next_buffer_exchange() {
+        assert!(invert_next_issues("0 BUFEXCHG A STORE").is_empty());
+    }
+
+    #[test]
+    fn check_bfc_invert_next_texmap() {
+        assert!(invert_next_issues("0 !TEXMAP END").is_empty());
+    }
+}
@@ -1,28 +1,94 @@
-use std::{collections::HashMap, path::Path};
+use std::collections::{BTreeSet, HashMap, HashSet};
+#[cfg(feature = "fs")]
+use std::path::Path;
 
+use crate::{
+    ldraw::{Command, SourceMap},
+    replace_color, ColorCode, LDrawNode, EDGE_COLOR,
+};
+
+#[derive(Debug, Clone)]
 pub struct LDrawColor {
     pub name: String,
     pub finish_name: String,
     pub rgba_linear: [f32; 4],
+    /// The same color as [rgba_linear](#structfield.rgba_linear) in sRGB space
+    /// for applications that don't convert from linear.
+    /// Alpha is not gamma encoded and is the same for both.
+    pub rgba_srgb: [f32; 4],
+    /// The contrasting color used for edges of parts with this color.
+    pub edge_rgba_linear: [f32; 4],
     pub speckle_rgba_linear: Option<[f32; 4]>,
+    /// The secondary color of glitter grains for the "Glitter" finish.
+    pub glitter_rgba_linear: Option<[f32; 4]>,
+    /// The `[min, max]` size of speckle or glitter grains.
+    pub grain_size: Option<[f32; 2]>,
+    /// The fraction of the surface covered by speckle or glitter grains.
+    pub grain_surface_fraction: Option<f32>,
+    /// The fraction of the volume filled by glitter grains.
+    pub grain_volume_fraction: Option<f32>,
+    /// The brightness from 0 to 255 for colors that glow in the dark.
+    pub luminance: Option<u8>,
+    /// The [luminance](#structfield.luminance) scaled to the range 0.0 to 1.0
+    /// or 0.0 if the color does not glow.
+    pub emission_strength: f32,
 }
 
+#[cfg(feature = "fs")]
 pub fn load_color_table(ldraw_path: &str) -> HashMap<u32, LDrawColor> {
     let config_path = Path::new(ldraw_path).join("LDConfig.ldr");
     let cmds = crate::ldraw::parse_raw(&std::fs::read(config_path).unwrap()).unwrap();
 
+    color_table(cmds)
+}
+
+/// Load the alternate color table with more realistic colors like `LDCfgalt.ldr`.
+/// This uses [load_color_table] if there is no alternate color table in `ldraw_path`.
+#[cfg(feature = "fs")]
+pub fn load_alt_color_table(ldraw_path: &str) -> HashMap<u32, LDrawColor> {
+    // The file name varies between LDraw library distributions.
+    let alt_path = ["LDCfgalt.ldr", "LDConfigalt.ldr", "LDConfig-alt.ldr"]
+        .into_iter()
+        .map(|name| Path::new(ldraw_path).join(name))
+        .find(|path| path.exists());
+
+    match alt_path {
+        Some(path) => {
+            let cmds = crate::ldraw::parse_raw(&std::fs::read(path).unwrap()).unwrap();
+            color_table(cmds)
+        }
+        None => {
+            log::warn!("Unable to find an alternate color table in {ldraw_path:?}");
+            load_color_table(ldraw_path)
+        }
+    }
+}
+
+pub(crate) fn color_table(cmds: Vec<crate::ldraw::Command>) -> HashMap<u32, LDrawColor> {
     cmds.into_iter()
         .filter_map(|cmd| match cmd {
             crate::ldraw::Command::Colour(c) => {
                 // LDraw colors are in sRGB space.
+                let edge_rgba_linear = rgba_linear(&c.edge, c.alpha);
                 let rgba_linear = rgba_linear(&c.value, c.alpha);
+                let rgba_srgb = rgba_srgb(&c.value, c.alpha);
                 let speckle_rgba_linear = speckle_rgba_linear(&c);
+                let glitter_rgba_linear = glitter_rgba_linear(&c);
+                let (grain_size, grain_surface_fraction, grain_volume_fraction) = grain(&c);
                 let finish_name = finish_name(&c).to_string();
                 let color = LDrawColor {
                     name: c.name,
                     rgba_linear,
+                    rgba_srgb,
+                    edge_rgba_linear,
                     speckle_rgba_linear,
+                    glitter_rgba_linear,
+                    grain_size,
+                    grain_surface_fraction,
+                    grain_volume_fraction,
                     finish_name,
+                    luminance: c.luminance,
+                    emission_strength: emission_strength(c.luminance),
                 };
                 Some((c.code, color))
             }
@@ -31,11 +97,260 @@ pub fn load_color_table(ldraw_path: &str) -> HashMap<u32, LDrawColor> {
         .collect()
 }
 
+/// Find colors defined with `!COLOUR` commands in `main_model_name` and all referenced files.
+/// Later definitions replace earlier definitions with the same code.
+pub(crate) fn model_colors(
+    source_map: &SourceMap,
+    main_model_name: &str,
+) -> HashMap<u32, LDrawColor> {
+    let mut cmds = Vec::new();
+    let mut visited = HashSet::new();
+    let mut stack = vec![main_model_name.to_lowercase()];
+    while let Some(name) = stack.pop() {
+        if let Some(source_file) = source_map.get(&name) {
+            for cmd in &source_file.cmds {
+                match cmd {
+                    Command::Colour(_) => cmds.push(cmd.clone()),
+                    Command::SubFileRef(sfr_cmd) => {
+                        let name = sfr_cmd.file.to_lowercase();
+                        if visited.insert(name.clone()) {
+                            stack.push(name);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    color_table(cmds)
+}
+
+/// Find codes defined in more than one of the `color_tables` with different
+/// color or edge values sorted by code.
+/// Only the last definition of a conflicting code is used when loading.
+pub fn conflicting_colors(color_tables: &[&HashMap<u32, LDrawColor>]) -> Vec<ColorCode> {
+    let mut conflicts = BTreeSet::new();
+    for (i, table) in color_tables.iter().enumerate() {
+        for other in &color_tables[i + 1..] {
+            for (code, color) in table.iter() {
+                if let Some(other_color) = other.get(code) {
+                    if color.rgba_linear != other_color.rgba_linear
+                        || color.edge_rgba_linear != other_color.edge_rgba_linear
+                    {
+                        conflicts.insert(*code);
+                    }
+                }
+            }
+        }
+    }
+    conflicts.into_iter().collect()
+}
+
+/// Load the color table like [load_color_table] for the model file at `path`.
+///
+/// Studio `.io` files may contain a `CustomColorDefinition.txt` with custom colors.
+/// These colors replace colors with the same code from the LDraw library.
+#[cfg(feature = "fs")]
+pub fn load_color_table_for_file(ldraw_path: &str, path: &str) -> HashMap<u32, LDrawColor> {
+    with_file_colors(load_color_table(ldraw_path), path)
+}
+
+/// Load the color table like [load_alt_color_table] for the model file at `path`.
+/// See [load_color_table_for_file] for details.
+#[cfg(feature = "fs")]
+pub fn load_alt_color_table_for_file(ldraw_path: &str, path: &str) -> HashMap<u32, LDrawColor> {
+    with_file_colors(load_alt_color_table(ldraw_path), path)
+}
+
+#[cfg(feature = "fs")]
+fn with_file_colors(color_table: HashMap<u32, LDrawColor>, path: &str) -> HashMap<u32, LDrawColor> {
+    #[cfg(feature = "io")]
+    if Path::new(path).extension() == Some("io".as_ref()) {
+        return with_io_colors(color_table, path);
+    }
+    #[cfg(not(feature = "io"))]
+    let _ = path;
+
+    color_table
+}
+
+/// Load only the custom colors from the `CustomColorDefinition.txt` in the Studio `.io` file at `io_path`.
+/// This does not require an LDraw library but uses a default edge color for all colors.
+/// Returns an empty table if the file has no custom colors or can't be read.
+#[cfg(feature = "io")]
+pub fn load_io_color_table(io_path: &str) -> HashMap<u32, LDrawColor> {
+    io_colors(io_path, &HashMap::new()).into_iter().collect()
+}
+
+#[cfg(feature = "io")]
+fn with_io_colors(
+    mut color_table: HashMap<u32, LDrawColor>,
+    io_path: &str,
+) -> HashMap<u32, LDrawColor> {
+    let colors = io_colors(io_path, &color_table);
+    color_table.extend(colors);
+    color_table
+}
+
+#[cfg(feature = "io")]
+fn io_colors(io_path: &str, color_table: &HashMap<u32, LDrawColor>) -> Vec<(u32, LDrawColor)> {
+    match crate::read_io_entry(io_path, "CustomColorDefinition.txt") {
+        Ok(bytes) => {
+            let text = String::from_utf8_lossy(&bytes);
+            parse_studio_colors(&text, color_table)
+        }
+        Err(e) => {
+            log::debug!("No custom color definition in {io_path:?}: {e}");
+            Vec::new()
+        }
+    }
+}
+
+/// Parse tab separated Studio color definitions with a header row.
+/// Columns are found by name, and colors without an LDraw code or RGB value are skipped.
+/// Edge colors come from `color_table` if the code is already defined.
+#[cfg(feature = "io")]
+fn parse_studio_colors(
+    text: &str,
+    color_table: &HashMap<u32, LDrawColor>,
+) -> Vec<(u32, LDrawColor)> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+    let Some(header) = lines.next() else {
+        return Vec::new();
+    };
+
+    let columns: Vec<_> = header
+        .split('\t')
+        .map(|c| c.trim().to_lowercase())
+        .collect();
+    let column = |f: &dyn Fn(&str) -> bool| columns.iter().position(|c| f(c));
+    let code_column = column(&|c| c.contains("ldraw") && c.contains("code"));
+    let name_column = column(&|c| c.contains("name") && !c.contains("ldraw"))
+        .or_else(|| column(&|c| c.contains("name")));
+    let rgb_column = column(&|c| c.contains("rgb"));
+    let alpha_column = column(&|c| c.contains("alpha"));
+    let (Some(code_column), Some(rgb_column)) = (code_column, rgb_column) else {
+        log::warn!("Unrecognized custom color definition header: {header:?}");
+        return Vec::new();
+    };
+
+    lines
+        .filter_map(|line| {
+            let values: Vec<_> = line.split('\t').map(|v| v.trim()).collect();
+            let code = values.get(code_column)?.parse().ok()?;
+            let rgb = parse_hex_rgb(values.get(rgb_column)?)?;
+            // Alpha may be stored as either a fraction or a byte value.
+            let alpha = alpha_column
+                .and_then(|i| values.get(i)?.parse::<f32>().ok())
+                .map(|a| if a > 1.0 { a / 255.0 } else { a })
+                .unwrap_or(1.0);
+
+            let edge_rgba_linear = color_table
+                .get(&code)
+                .map(|c| c.edge_rgba_linear)
+                .unwrap_or_else(|| rgba_linear(&crate::ldraw::Color::new(51, 51, 51), None));
+
+            let value = crate::ldraw::Color::new(rgb[0], rgb[1], rgb[2]);
+            let mut rgba_linear = rgba_linear(&value, None);
+            rgba_linear[3] = alpha;
+            let mut rgba_srgb = rgba_srgb(&value, None);
+            rgba_srgb[3] = alpha;
+
+            let name = name_column
+                .and_then(|i| values.get(i))
+                .map(|n| n.to_string())
+                .unwrap_or_default();
+
+            Some((
+                code,
+                LDrawColor {
+                    name,
+                    finish_name: String::new(),
+                    rgba_linear,
+                    rgba_srgb,
+                    edge_rgba_linear,
+                    speckle_rgba_linear: None,
+                    glitter_rgba_linear: None,
+                    grain_size: None,
+                    grain_surface_fraction: None,
+                    grain_volume_fraction: None,
+                    // Studio color definitions don't have luminance.
+                    luminance: None,
+                    emission_strength: 0.0,
+                },
+            ))
+        })
+        .collect()
+}
+
+#[cfg(feature = "io")]
+fn parse_hex_rgb(value: &str) -> Option<[u8; 3]> {
+    let value = value.trim_start_matches('#');
+    if value.len() != 6 {
+        return None;
+    }
+    let rgb = u32::from_str_radix(value, 16).ok()?;
+    Some([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8])
+}
+
+/// Find the color for an edge with `color` on a part with `current_color`.
+/// Code 24 uses the edge color of `current_color`.
+pub fn edge_color_rgba_linear(
+    color_table: &HashMap<ColorCode, LDrawColor>,
+    color: ColorCode,
+    current_color: ColorCode,
+) -> Option<[f32; 4]> {
+    if color == EDGE_COLOR {
+        color_table.get(&current_color).map(|c| c.edge_rgba_linear)
+    } else {
+        color_table
+            .get(&replace_color(color, current_color))
+            .map(|c| c.rgba_linear)
+    }
+}
+
+/// Find the color for `node` in `color_table`.
+/// A node with the current color 16 inherits `parent_color`.
+///
+/// The edge color 24 has no full color definition of its own.
+/// Use [edge_color_rgba_linear] to get the edge color of `parent_color` instead.
+pub fn resolve_node_color<'a>(
+    node: &LDrawNode,
+    parent_color: ColorCode,
+    color_table: &'a HashMap<ColorCode, LDrawColor>,
+) -> Option<&'a LDrawColor> {
+    color_table.get(&replace_color(node.current_color, parent_color))
+}
+
+/// Set [LDrawNode::rgba_linear] for `node` and its children recursively.
+pub(crate) fn resolve_node_colors(
+    node: &mut LDrawNode,
+    parent_color: ColorCode,
+    color_table: &HashMap<ColorCode, LDrawColor>,
+) {
+    node.rgba_linear = edge_color_rgba_linear(color_table, node.current_color, parent_color);
+
+    let current_color = replace_color(node.current_color, parent_color);
+    for child in &mut node.children {
+        resolve_node_colors(child, current_color, color_table);
+    }
+}
+
+fn emission_strength(luminance: Option<u8>) -> f32 {
+    luminance.map(|l| l as f32 / 255.0).unwrap_or_default()
+}
+
 fn rgba_linear(value: &crate::ldraw::Color, alpha: Option<u8>) -> [f32; 4] {
+    let [r, g, b, a] = rgba_srgb(value, alpha);
+    [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+}
+
+fn rgba_srgb(value: &crate::ldraw::Color, alpha: Option<u8>) -> [f32; 4] {
     [
-        srgb_to_linear(value.red as f32 / 255.0),
-        srgb_to_linear(value.green as f32 / 255.0),
-        srgb_to_linear(value.blue as f32 / 255.0),
+        value.red as f32 / 255.0,
+        value.green as f32 / 255.0,
+        value.blue as f32 / 255.0,
         alpha.unwrap_or(255) as f32 / 255.0,
     ]
 }
@@ -49,6 +364,35 @@ fn speckle_rgba_linear(c: &crate::ldraw::ColourCmd) -> Option<[f32; 4]> {
     })
 }
 
+fn glitter_rgba_linear(c: &crate::ldraw::ColourCmd) -> Option<[f32; 4]> {
+    c.finish.as_ref().and_then(|f| match f {
+        crate::ldraw::ColorFinish::Material(crate::ldraw::MaterialFinish::Glitter(glitter)) => {
+            Some(rgba_linear(&glitter.value, glitter.alpha))
+        }
+        _ => None,
+    })
+}
+
+/// The grain size, surface fraction, and volume fraction for speckle or glitter.
+fn grain(c: &crate::ldraw::ColourCmd) -> (Option<[f32; 2]>, Option<f32>, Option<f32>) {
+    let grain_size = |size: &crate::ldraw::GrainSize| match size {
+        crate::ldraw::GrainSize::Size(size) => [*size, *size],
+        crate::ldraw::GrainSize::MinMaxSize((min, max)) => [*min, *max],
+    };
+
+    match &c.finish {
+        Some(crate::ldraw::ColorFinish::Material(crate::ldraw::MaterialFinish::Speckle(s))) => {
+            (Some(grain_size(&s.size)), Some(s.surface_fraction), None)
+        }
+        Some(crate::ldraw::ColorFinish::Material(crate::ldraw::MaterialFinish::Glitter(g))) => (
+            Some(grain_size(&g.size)),
+            Some(g.surface_fraction),
+            Some(g.volume_fraction),
+        ),
+        _ => (None, None, None),
+    }
+}
+
 fn finish_name(c: &crate::ldraw::ColourCmd) -> &str {
     match &c.finish {
         Some(finish) => match finish {
@@ -74,3 +418,242 @@ fn srgb_to_linear(srgb: f32) -> f32 {
         ((srgb + 0.055) / 1.055).powf(2.4)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color(rgba_linear: [f32; 4], edge_rgba_linear: [f32; 4]) -> LDrawColor {
+        LDrawColor {
+            name: String::new(),
+            finish_name: String::new(),
+            rgba_linear,
+            rgba_srgb: rgba_linear,
+            edge_rgba_linear,
+            speckle_rgba_linear: None,
+            glitter_rgba_linear: None,
+            grain_size: None,
+            grain_surface_fraction: None,
+            grain_volume_fraction: None,
+            luminance: None,
+            emission_strength: 0.0,
+        }
+    }
+
+    #[cfg(feature = "io")]
+    #[test]
+    fn parse_studio_colors_header() {
+        let color_table = HashMap::from([(4, color([0.5, 0.0, 0.0, 1.0], [0.2, 0.2, 0.2, 1.0]))]);
+        let text = "Studio Color Code\tLDraw Color Code\tStudio Color Name\tRGB value\tAlpha\n\
+                    1000\t4\tCustom Red\t#FF0000\t1\n\
+                    1001\t10000\tCustom Clear\t000000\t0.5\n\
+                    1002\t\tMissing Code\t#FFFFFF\t1\n";
+
+        let colors = parse_studio_colors(text, &color_table);
+        assert_eq!(2, colors.len());
+
+        let (code, red) = &colors[0];
+        assert_eq!(4, *code);
+        assert_eq!("Custom Red", red.name);
+        assert_eq!([1.0, 0.0, 0.0, 1.0], red.rgba_linear);
+        assert_eq!([0.2, 0.2, 0.2, 1.0], red.edge_rgba_linear);
+
+        let (code, clear) = &colors[1];
+        assert_eq!(10000, *code);
+        assert_eq!([0.0, 0.0, 0.0, 0.5], clear.rgba_linear);
+    }
+
+    #[cfg(feature = "io")]
+    #[test]
+    fn load_io_color_table_missing_file() {
+        assert!(load_io_color_table("missing.io").is_empty());
+    }
+
+    #[cfg(feature = "io")]
+    #[test]
+    fn load_io_color_table_custom_color() {
+        let dir = tempfile::tempdir().unwrap();
+        let io_path = dir.path().join("model.io");
+
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&io_path).unwrap());
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        writer.start_file("model.ldr", options).unwrap();
+        std::io::Write::write_all(&mut writer, b"1 4 0 0 0 1 0 0 0 1 0 0 0 1 3001.dat\n").unwrap();
+        writer
+            .start_file("CustomColorDefinition.txt", options)
+            .unwrap();
+        std::io::Write::write_all(
+            &mut writer,
+            "\u{FEFF}Studio Color Code\tLDraw Color Code\tStudio Color Name\tRGB value\tAlpha\n\
+             1000\t10000\tCustom Blue\t#0000FF\t1\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        writer.finish().unwrap();
+
+        let colors = load_io_color_table(io_path.to_str().unwrap());
+        assert_eq!(1, colors.len());
+        let blue = &colors[&10000];
+        assert_eq!("Custom Blue", blue.name);
+        assert_eq!([0.0, 0.0, 1.0, 1.0], blue.rgba_linear);
+    }
+
+    #[cfg(feature = "io")]
+    #[test]
+    fn parse_studio_colors_unknown_header() {
+        assert!(parse_studio_colors("a\tb\n1\t2", &HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn conflicting_colors_different_values() {
+        let red = color([1.0, 0.0, 0.0, 1.0], [0.2, 0.2, 0.2, 1.0]);
+        let ldraw = HashMap::from([(4, red.clone()), (15, color([1.0; 4], [0.0; 4]))]);
+        let studio = HashMap::from([(4, color([0.8, 0.0, 0.0, 1.0], [0.2, 0.2, 0.2, 1.0]))]);
+        let model = HashMap::from([
+            (4, red),
+            (15, color([1.0; 4], [0.5; 4])),
+            (20, color([0.0; 4], [0.0; 4])),
+        ]);
+
+        assert_eq!(vec![4, 15], conflicting_colors(&[&ldraw, &studio, &model]));
+        assert_eq!(vec![15], conflicting_colors(&[&ldraw, &model]));
+        assert!(conflicting_colors(&[&ldraw, &ldraw]).is_empty());
+    }
+
+    #[test]
+    fn color_table_speckle_glitter() {
+        let cmds = crate::ldraw::parse_raw(indoc::indoc! {b"
+            0 !COLOUR Opal_Trans_Dark_Blue               CODE 10366 VALUE #0020A0   EDGE #000B38   ALPHA 200   LUMINANCE  5    MATERIAL GLITTER VALUE #001D38 FRACTION 0.8 VFRACTION 0.6 MINSIZE 0.02 MAXSIZE 0.1
+            0 !COLOUR Speckle_Black_Silver               CODE 132   VALUE #000000   EDGE #898788                               MATERIAL SPECKLE VALUE #898788 FRACTION 0.4 MINSIZE 1 MAXSIZE 3
+        "})
+        .unwrap();
+        let color_table = color_table(cmds);
+
+        let opal = &color_table[&10366];
+        assert_eq!("Glitter", opal.finish_name);
+        assert_eq!(None, opal.speckle_rgba_linear);
+        assert_eq!(
+            Some(rgba_linear(
+                &crate::ldraw::Color::new(0x00, 0x1D, 0x38),
+                None
+            )),
+            opal.glitter_rgba_linear
+        );
+        assert_eq!(Some([0.02, 0.1]), opal.grain_size);
+        assert_eq!(Some(0.8), opal.grain_surface_fraction);
+        assert_eq!(Some(0.6), opal.grain_volume_fraction);
+        assert_eq!(Some(5), opal.luminance);
+
+        let opal_green = 0x20 as f32 / 255.0;
+        assert_eq!(
+            [0.0, opal_green, 0xA0 as f32 / 255.0, 200.0 / 255.0],
+            opal.rgba_srgb
+        );
+        assert_eq!(srgb_to_linear(opal_green), opal.rgba_linear[1]);
+        assert_eq!(opal.rgba_srgb[3], opal.rgba_linear[3]);
+
+        let speckle = &color_table[&132];
+        assert_eq!("Speckle", speckle.finish_name);
+        assert_eq!(None, speckle.glitter_rgba_linear);
+        let silver = srgb_to_linear(0x89 as f32 / 255.0);
+        assert_eq!(Some(silver), speckle.speckle_rgba_linear.map(|c| c[0]));
+        assert_eq!(Some([1.0, 3.0]), speckle.grain_size);
+        assert_eq!(Some(0.4), speckle.grain_surface_fraction);
+        assert_eq!(None, speckle.grain_volume_fraction);
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn load_alt_color_table_fallback() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("LDConfig.ldr"),
+            "0 !COLOUR Red CODE 4 VALUE #C91A09 EDGE #333333",
+        )
+        .unwrap();
+        let ldraw_path = dir.path().to_str().unwrap();
+
+        let fallback = load_alt_color_table(ldraw_path);
+        assert_eq!("Red", fallback[&4].name);
+
+        std::fs::write(
+            dir.path().join("LDCfgalt.ldr"),
+            "0 !COLOUR Red CODE 4 VALUE #B40000 EDGE #333333",
+        )
+        .unwrap();
+        let alt = load_alt_color_table(ldraw_path);
+
+        assert_ne!(fallback[&4].rgba_srgb, alt[&4].rgba_srgb);
+        assert_eq!([180.0 / 255.0, 0.0, 0.0, 1.0], alt[&4].rgba_srgb);
+    }
+
+    #[test]
+    fn emission_strength_luminance() {
+        assert_eq!(0.0, emission_strength(None));
+        assert_eq!(0.0, emission_strength(Some(0)));
+        assert_eq!(1.0, emission_strength(Some(255)));
+    }
+
+    #[test]
+    fn edge_color_rgba_linear_complement() {
+        let color_table = HashMap::from([
+            (0, color([0.0, 0.0, 0.0, 1.0], [0.1, 0.1, 0.1, 1.0])),
+            (4, color([0.5, 0.0, 0.0, 1.0], [0.2, 0.2, 0.2, 1.0])),
+        ]);
+
+        assert_eq!(
+            Some([0.2, 0.2, 0.2, 1.0]),
+            edge_color_rgba_linear(&color_table, 24, 4)
+        );
+        assert_eq!(
+            Some([0.5, 0.0, 0.0, 1.0]),
+            edge_color_rgba_linear(&color_table, 16, 4)
+        );
+        assert_eq!(
+            Some([0.0, 0.0, 0.0, 1.0]),
+            edge_color_rgba_linear(&color_table, 0, 4)
+        );
+        assert_eq!(None, edge_color_rgba_linear(&color_table, 24, 1));
+    }
+
+    fn node(current_color: ColorCode, children: Vec<LDrawNode>) -> LDrawNode {
+        LDrawNode {
+            name: String::new(),
+            unique_name: String::new(),
+            transform: glam::Mat4::IDENTITY,
+            geometry_name: None,
+            current_color,
+            is_mirrored: false,
+            rgba_linear: None,
+            piece_metadata: Default::default(),
+            children,
+        }
+    }
+
+    #[test]
+    fn resolve_node_colors_inherit() {
+        let color_table = HashMap::from([
+            (4, color([0.5, 0.0, 0.0, 1.0], [0.2, 0.2, 0.2, 1.0])),
+            (16, color([1.0, 1.0, 0.0, 1.0], [0.3, 0.3, 0.3, 1.0])),
+        ]);
+
+        let mut root = node(
+            16,
+            vec![node(4, vec![node(16, Vec::new()), node(24, Vec::new())])],
+        );
+        resolve_node_colors(&mut root, 16, &color_table);
+
+        assert_eq!(Some([1.0, 1.0, 0.0, 1.0]), root.rgba_linear);
+        let child = &root.children[0];
+        assert_eq!(Some([0.5, 0.0, 0.0, 1.0]), child.rgba_linear);
+        assert_eq!(Some([0.5, 0.0, 0.0, 1.0]), child.children[0].rgba_linear);
+        assert_eq!(Some([0.2, 0.2, 0.2, 1.0]), child.children[1].rgba_linear);
+
+        assert_eq!(
+            Some([0.5, 0.0, 0.0, 1.0]),
+            resolve_node_color(&child.children[0], 4, &color_table).map(|c| c.rgba_linear)
+        );
+        assert!(resolve_node_color(&node(1, Vec::new()), 4, &color_table).is_none());
+    }
+}
@@ -0,0 +1,56 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use crate::{LDrawGeometry, LDrawNode};
+
+/// Remove geometry identical to an earlier entry in `geometry_cache`.
+/// Returns the name of the remaining entry for each removed name.
+///
+/// Geometry is compared exactly, so the same shape with a different vertex order is not merged.
+pub fn deduplicate_geometry(
+    geometry_cache: &mut BTreeMap<String, LDrawGeometry>,
+) -> HashMap<String, String> {
+    let mut buckets: HashMap<u64, Vec<&String>> = HashMap::new();
+    let mut renamed = HashMap::new();
+    for (name, geometry) in geometry_cache.iter() {
+        let bucket = buckets.entry(geometry_hash(geometry)).or_default();
+
+        // Different geometry can still have the same hash.
+        match bucket
+            .iter()
+            .find(|other| &geometry_cache[**other] == geometry)
+        {
+            Some(canonical) => {
+                renamed.insert(name.clone(), (*canonical).clone());
+            }
+            None => bucket.push(name),
+        }
+    }
+
+    geometry_cache.retain(|name, _| !renamed.contains_key(name));
+    renamed
+}
+
+fn geometry_hash(geometry: &LDrawGeometry) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for v in &geometry.vertices {
+        v.to_array().map(f32::to_bits).hash(&mut hasher);
+    }
+    geometry.vertex_indices.hash(&mut hasher);
+    geometry.face_sizes.hash(&mut hasher);
+    geometry.face_colors.hash(&mut hasher);
+    hasher.finish()
+}
+
+pub fn rename_node_geometry(node: &mut LDrawNode, renamed: &HashMap<String, String>) {
+    if let Some(name) = &mut node.geometry_name {
+        if let Some(canonical) = renamed.get(name) {
+            *name = canonical.clone();
+        }
+    }
+    for child in &mut node.children {
+        rename_node_geometry(child, renamed);
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crate::ldraw::{FileRefResolver, ResolveError};
+
+/// The official LDraw parts library used by [DownloadResolver] by default.
+pub const LDRAW_LIBRARY_URL: &str = "https://library.ldraw.org/library/official";
+
+// Library folders to check in order with the same layout as a local library.
+const LIBRARY_FOLDERS: [&str; 2] = ["parts", "p"];
+
+/// Download files not found by `resolver` from the LDraw parts library
+/// and save them in a cache folder with the same layout as a local library.
+///
+/// Cached files are used before downloading and are never downloaded again.
+/// Only `.dat` files are downloaded since model files are never in the library.
+pub struct DownloadResolver<R> {
+    resolver: R,
+    cache_path: PathBuf,
+    base_url: String,
+    agent: ureq::Agent,
+    // Avoid repeated requests for files not in the library.
+    failed: Mutex<HashSet<String>>,
+    // Avoid downloading the same file from multiple threads at once.
+    downloads: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl<R> DownloadResolver<R> {
+    /// Wrap `resolver` and cache downloaded files in the folder `cache_path`.
+    pub fn new(resolver: R, cache_path: impl Into<PathBuf>) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(30)))
+            .build()
+            .into();
+        Self {
+            resolver,
+            cache_path: cache_path.into(),
+            base_url: LDRAW_LIBRARY_URL.to_string(),
+            agent,
+            failed: Mutex::new(HashSet::new()),
+            downloads: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Download from a library at `base_url` instead of [LDRAW_LIBRARY_URL].
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    fn cached(&self, name: &str) -> Option<Vec<u8>> {
+        LIBRARY_FOLDERS
+            .iter()
+            .find_map(|folder| std::fs::read(self.cache_path.join(folder).join(name)).ok())
+    }
+
+    fn cached_or_downloaded(&self, filename: &Path) -> Option<Vec<u8>> {
+        let name = library_name(filename)?;
+
+        if let Some(bytes) = self.cached(&name) {
+            return Some(bytes);
+        }
+
+        if self.failed.lock().unwrap().contains(&name) {
+            return None;
+        }
+
+        // Wait for any other thread downloading this file and check the cache again.
+        let download = self
+            .downloads
+            .lock()
+            .unwrap()
+            .entry(name.clone())
+            .or_default()
+            .clone();
+        let _guard = download.lock().unwrap();
+        if let Some(bytes) = self.cached(&name) {
+            return Some(bytes);
+        }
+        if self.failed.lock().unwrap().contains(&name) {
+            return None;
+        }
+
+        for folder in LIBRARY_FOLDERS {
+            let url = format!("{}/{folder}/{name}", self.base_url.trim_end_matches('/'));
+            match self.download(&url) {
+                Ok(bytes) => {
+                    let path = self.cache_path.join(folder).join(&name);
+                    if let Err(e) = write_cached(&path, &bytes) {
+                        log::warn!("Error caching {path:?}: {e}");
+                    }
+                    return Some(bytes);
+                }
+                Err(e) => log::debug!("Error downloading {url:?}: {e}"),
+            }
+        }
+
+        log::warn!("Error downloading {name:?} from {:?}", self.base_url);
+        self.failed.lock().unwrap().insert(name);
+        None
+    }
+
+    fn download(&self, url: &str) -> Result<Vec<u8>, ureq::Error> {
+        self.agent.get(url).call()?.body_mut().read_to_vec()
+    }
+}
+
+impl<R: FileRefResolver> FileRefResolver for DownloadResolver<R> {
+    fn resolve<P: AsRef<Path>>(&self, filename: P) -> Result<Vec<u8>, ResolveError> {
+        let filename = filename.as_ref();
+        match self.resolver.resolve(filename) {
+            Ok(bytes) if bytes.is_empty() => {
+                Ok(self.cached_or_downloaded(filename).unwrap_or(bytes))
+            }
+            result => result,
+        }
+    }
+}
+
+// The relative path in the library like "s/3001s01.dat" or None for files not in the library.
+fn library_name(filename: &Path) -> Option<String> {
+    let name = filename.to_str()?.replace('\\', "/").to_lowercase();
+    let is_relative = filename.is_relative() && !name.split('/').any(|c| c == "..");
+    (is_relative && name.ends_with(".dat")).then_some(name)
+}
+
+fn write_cached(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    // Write to a temporary file first so other processes never read a partially written file.
+    static TEMP_COUNT: AtomicUsize = AtomicUsize::new(0);
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        TEMP_COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = std::fs::write(&temp_path, bytes).and_then(|_| std::fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct EmptyResolver;
+
+    impl FileRefResolver for EmptyResolver {
+        fn resolve<P: AsRef<Path>>(&self, _filename: P) -> Result<Vec<u8>, ResolveError> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn resolve_cached_without_download() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("parts").join("s")).unwrap();
+        std::fs::write(
+            dir.path().join("parts").join("s").join("a.dat"),
+            "3 16 1 0 0",
+        )
+        .unwrap();
+
+        // Requests to this address fail immediately.
+        let resolver =
+            DownloadResolver::new(EmptyResolver, dir.path()).with_base_url("http://127.0.0.1:9");
+
+        let cached = resolver.resolve("S\\a.dat").unwrap();
+        let model = resolver.resolve("model.ldr").unwrap();
+        let missing = resolver.resolve("b.dat").unwrap();
+
+        assert_eq!(b"3 16 1 0 0".to_vec(), cached);
+        assert!(model.is_empty());
+        assert!(missing.is_empty());
+        assert!(!dir.path().join("p").join("b.dat").exists());
+    }
+
+    #[test]
+    fn write_cached_replace() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("parts").join("a.dat");
+        write_cached(&path, b"old").unwrap();
+        write_cached(&path, b"new").unwrap();
+
+        assert_eq!(b"new".to_vec(), std::fs::read(&path).unwrap());
+        // No temporary files are left behind.
+        assert_eq!(
+            1,
+            std::fs::read_dir(path.parent().unwrap()).unwrap().count()
+        );
+    }
+}
@@ -1,15 +1,59 @@
-use std::collections::{BTreeMap, BTreeSet};
+//! Mesh operations for splitting sharp edges similar to Blender's "edge split".
+//!
+//! Meshes use the same layout as [LDrawGeometry](crate::LDrawGeometry).
+//! Each face is a range of `vertex_indices` starting at `face_starts[i]`
+//! with `face_sizes[i]` indices into `vertices`.
+//! Edges are pairs of indices into `vertices` and are treated as undirected.
+//!
+//! Splitting relies on faces sharing vertices to find adjacent faces.
+//! Use [split_edges_unwelded] for meshes with duplicate vertices at the same position.
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet};
 
 use glam::Vec3;
+use rstar::{primitives::GeomWithData, RTree};
 
 use crate::normal::face_normals;
 
 /// Calculate new vertices and indices by splitting the edges in `edges_to_split`.
-/// The geometry must be triangulated!
+/// The input vertices should already be welded so that adjacent faces share vertices.
 ///
 /// This works similarly to Blender's "edge split" for calculating normals.
+/// Each side of a split edge has its own vertices,
+/// so smooth normals calculated from the result will have hard edges.
 ///
-/// The current implementation hardcodes a normal angle threshold of 89 degrees to split sharp edges.
+/// Edges are also split if the angle between adjacent faces is at least `angle_threshold` radians.
+///
+/// ```rust
+/// use ldr_tools::edge_split::split_edges;
+/// use ldr_tools::glam::{vec3, Vec3};
+///
+/// // Two quads of two triangles and one sharp edge.
+/// // 2 - 3 - 4
+/// // | \ | \ |
+/// // 0 - 1 - 5
+/// let vertices = [
+///     vec3(0.0, 0.0, 0.0),
+///     vec3(1.0, 0.0, 0.0),
+///     vec3(0.0, 1.0, 0.0),
+///     vec3(1.0, 1.0, 0.0),
+///     vec3(2.0, 1.0, 0.0),
+///     vec3(2.0, 0.0, 0.0),
+/// ];
+/// let indices = [0, 1, 2, 2, 1, 3, 3, 1, 5, 3, 5, 4];
+///
+/// let (new_vertices, new_indices) = split_edges(
+///     &vertices,
+///     &indices,
+///     &[0, 3, 6, 9],
+///     &[3, 3, 3, 3],
+///     &[[1, 3]],
+///     89f32.to_radians(),
+/// );
+///
+/// // The vertices on the edge 1-3 are duplicated for the right quad.
+/// assert_eq!(8, new_vertices.len());
+/// assert_eq!(vec![0, 1, 2, 2, 1, 3, 7, 6, 5, 7, 5, 4], new_indices);
+/// ```
 // https://github.com/blender/blender/blob/a32dbb8/source/blender/geometry/intern/mesh_split_edges.cc
 pub fn split_edges(
     vertices: &[Vec3],
@@ -17,6 +61,7 @@ pub fn split_edges(
     face_starts: &[u32],
     face_sizes: &[u32],
     edges_to_split: &[[u32; 2]],
+    angle_threshold: f32,
 ) -> (Vec<Vec3>, Vec<u32>) {
     let old_adjacent_faces = adjacent_faces(vertices, vertex_indices, face_starts, face_sizes);
 
@@ -32,7 +77,7 @@ pub fn split_edges(
         face_sizes,
         &old_adjacent_faces,
         normals,
-        89f32.to_radians(),
+        angle_threshold,
     );
 
     let mut should_split_vertex = vec![false; vertices.len()];
@@ -80,6 +125,231 @@ pub fn split_edges(
     remove_loose_vertices(&split_vertices, &split_vertex_indices)
 }
 
+/// Same as [split_edges] but first merges vertices within `tolerance` of each other.
+///
+/// Use this for meshes where adjacent faces don't share vertices like triangle soups.
+/// Indices in `edges_to_split` refer to the original `vertices`.
+///
+/// ```rust
+/// use ldr_tools::edge_split::split_edges_unwelded;
+/// use ldr_tools::glam::vec3;
+///
+/// // Two separate triangles that share the edge 1-2 by position.
+/// let vertices = [
+///     vec3(0.0, 0.0, 0.0),
+///     vec3(1.0, 0.0, 0.0),
+///     vec3(0.0, 1.0, 0.0),
+///     vec3(0.0, 1.0, 0.0),
+///     vec3(1.0, 0.0, 0.0),
+///     vec3(1.0, 1.0, 0.0),
+/// ];
+///
+/// let (new_vertices, new_indices) = split_edges_unwelded(
+///     &vertices,
+///     &[0, 1, 2, 3, 4, 5],
+///     &[0, 3],
+///     &[3, 3],
+///     &[],
+///     89f32.to_radians(),
+///     0.001,
+/// );
+///
+/// // The triangles are coplanar and stay connected.
+/// assert_eq!(4, new_vertices.len());
+/// assert_eq!(vec![0, 1, 2, 2, 1, 3], new_indices);
+/// ```
+pub fn split_edges_unwelded(
+    vertices: &[Vec3],
+    vertex_indices: &[u32],
+    face_starts: &[u32],
+    face_sizes: &[u32],
+    edges_to_split: &[[u32; 2]],
+    angle_threshold: f32,
+    tolerance: f32,
+) -> (Vec<Vec3>, Vec<u32>) {
+    let (welded_vertices, remap) = weld_vertices(vertices, tolerance);
+    let welded_indices: Vec<_> = vertex_indices.iter().map(|i| remap[*i as usize]).collect();
+    let welded_edges: Vec<_> = edges_to_split
+        .iter()
+        .map(|[v0, v1]| [remap[*v0 as usize], remap[*v1 as usize]])
+        .collect();
+
+    split_edges(
+        &welded_vertices,
+        &welded_indices,
+        face_starts,
+        face_sizes,
+        &welded_edges,
+        angle_threshold,
+    )
+}
+
+/// Merge vertices within `tolerance` and return the new vertices
+/// and the new index for each of the original `vertices`.
+fn weld_vertices(vertices: &[Vec3], tolerance: f32) -> (Vec<Vec3>, Vec<u32>) {
+    let mut rtree: RTree<GeomWithData<[f32; 3], u32>> = RTree::new();
+    let mut welded = Vec::new();
+    let remap = vertices
+        .iter()
+        .map(|v| {
+            let point = v.to_array();
+            let existing = rtree
+                .locate_within_distance(point, tolerance * tolerance)
+                .next()
+                .map(|p| p.data);
+            existing.unwrap_or_else(|| {
+                let index = welded.len() as u32;
+                welded.push(*v);
+                rtree.insert(GeomWithData::new(point, index));
+                index
+            })
+        })
+        .collect();
+    (welded, remap)
+}
+
+/// Find the unique edges of all faces and whether each edge is sharp.
+/// An edge is sharp if it is in `sharp_edges`
+/// or the angle between its adjacent faces is at least `angle_threshold` radians.
+///
+/// ```rust
+/// use ldr_tools::edge_split::mesh_edges;
+/// use ldr_tools::glam::vec3;
+///
+/// // Two triangles folded 90 degrees along the edge 1-2.
+/// let vertices = [
+///     vec3(0.0, 0.0, 0.0),
+///     vec3(1.0, 0.0, 0.0),
+///     vec3(0.0, 1.0, 0.0),
+///     vec3(0.5, 0.5, 1.0),
+/// ];
+///
+/// let (edges, is_sharp) = mesh_edges(
+///     &vertices,
+///     &[0, 1, 2, 2, 1, 3],
+///     &[0, 3],
+///     &[3, 3],
+///     &[],
+///     89f32.to_radians(),
+/// );
+///
+/// assert_eq!(vec![[0, 1], [1, 2], [0, 2], [1, 3], [2, 3]], edges);
+/// assert_eq!(vec![false, true, false, false, false], is_sharp);
+/// ```
+pub fn mesh_edges(
+    vertices: &[Vec3],
+    vertex_indices: &[u32],
+    face_starts: &[u32],
+    face_sizes: &[u32],
+    sharp_edges: &[[u32; 2]],
+    angle_threshold: f32,
+) -> (Vec<[u32; 2]>, Vec<bool>) {
+    let adjacent_faces = adjacent_faces(vertices, vertex_indices, face_starts, face_sizes);
+    let normals = face_normals(vertices, vertex_indices, face_starts, face_sizes);
+
+    // Treat edges as undirected.
+    let sharp_edges: BTreeSet<_> = sharp_edges
+        .iter()
+        .map(|[v0, v1]| sorted_edge(*v0, *v1))
+        .collect();
+
+    let mut visited = BTreeSet::new();
+    let mut edges = Vec::new();
+    let mut is_sharp = Vec::new();
+    for i in 0..face_starts.len() {
+        let face = face_indices(i, vertex_indices, face_starts, face_sizes);
+        for j in 0..face.len() {
+            let edge = sorted_edge(face[j], face[(j + 1) % face.len()]);
+            if !visited.insert(edge) {
+                continue;
+            }
+
+            let [v0, v1] = edge;
+            let mut faces = adjacent_faces[v0 as usize].intersection(&adjacent_faces[v1 as usize]);
+            let exceeds_angle = match (faces.next(), faces.next()) {
+                (Some(f0), Some(f1)) => normals[*f0].angle_between(normals[*f1]) >= angle_threshold,
+                _ => false,
+            };
+
+            edges.push(edge);
+            is_sharp.push(sharp_edges.contains(&edge) || exceeds_angle);
+        }
+    }
+
+    (edges, is_sharp)
+}
+
+/// Count the faces using each unique edge after merging vertices within `tolerance`.
+/// Edges are sorted pairs of indices into `vertices` in the order they first appear
+/// and use the first vertex at each merged position.
+///
+/// Each edge of a closed manifold mesh is shared by exactly two faces.
+///
+/// ```rust
+/// use ldr_tools::edge_split::edge_face_counts;
+/// use ldr_tools::glam::vec3;
+///
+/// // Two triangles sharing the edge 1-2 with a duplicate vertex 3 at the position of 2.
+/// let vertices = [
+///     vec3(0.0, 0.0, 0.0),
+///     vec3(1.0, 0.0, 0.0),
+///     vec3(0.0, 1.0, 0.0),
+///     vec3(0.0, 1.0, 0.0),
+///     vec3(1.0, 1.0, 0.0),
+/// ];
+///
+/// let counts = edge_face_counts(&vertices, &[0, 1, 2, 3, 1, 4], &[0, 3], &[3, 3], 0.001);
+///
+/// assert_eq!(
+///     vec![([0, 1], 1), ([1, 2], 2), ([0, 2], 1), ([1, 4], 1), ([2, 4], 1)],
+///     counts
+/// );
+/// ```
+pub fn edge_face_counts(
+    vertices: &[Vec3],
+    vertex_indices: &[u32],
+    face_starts: &[u32],
+    face_sizes: &[u32],
+    tolerance: f32,
+) -> Vec<([u32; 2], usize)> {
+    let (welded_vertices, remap) = weld_vertices(vertices, tolerance);
+
+    // Welded indices are assigned in order, so the first vertex for each index is unique.
+    let mut original_indices = vec![0; welded_vertices.len()];
+    for (i, welded) in remap.iter().enumerate().rev() {
+        original_indices[*welded as usize] = i as u32;
+    }
+
+    let mut edge_indices: BTreeMap<[u32; 2], usize> = BTreeMap::new();
+    let mut counts: Vec<([u32; 2], usize)> = Vec::new();
+    for i in 0..face_starts.len() {
+        let face = face_indices(i, vertex_indices, face_starts, face_sizes);
+        for j in 0..face.len() {
+            let v0 = remap[face[j] as usize];
+            let v1 = remap[face[(j + 1) % face.len()] as usize];
+            // Collapsed edges don't connect faces.
+            if v0 == v1 {
+                continue;
+            }
+
+            match edge_indices.entry(sorted_edge(v0, v1)) {
+                Entry::Occupied(e) => counts[*e.get()].1 += 1,
+                Entry::Vacant(e) => {
+                    e.insert(counts.len());
+                    let edge =
+                        sorted_edge(original_indices[v0 as usize], original_indices[v1 as usize]);
+                    counts.push((edge, 1));
+                }
+            }
+        }
+    }
+    counts
+}
+
+fn sorted_edge(v0: u32, v1: u32) -> [u32; 2] {
+    [v0.min(v1), v0.max(v1)]
+}
+
 fn add_sharp_edges(
     edges_to_split: &mut Vec<[u32; 2]>,
     vertex_indices: &[u32],
@@ -142,6 +412,7 @@ fn adjacent_faces<T>(
     adjacent_faces
 }
 
+#[allow(clippy::too_many_arguments)]
 fn merge_duplicate_edges(
     split_vertex_indices: &mut [u32],
     vertex_indices: &[u32],
@@ -179,6 +450,7 @@ fn merge_duplicate_edges(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn merge_verts_in_faces(
     v0: u32,
     v1: u32,
@@ -377,7 +649,14 @@ mod tests {
 
         assert_eq!(
             (vec![v3(0.0), v3(1.0), v3(2.0)], vec![0, 1, 2]),
-            split_edges(&[v3(0.0), v3(1.0), v3(2.0)], &[0, 1, 2], &[0], &[3], &[])
+            split_edges(
+                &[v3(0.0), v3(1.0), v3(2.0)],
+                &[0, 1, 2],
+                &[0],
+                &[3],
+                &[],
+                89f32.to_radians()
+            )
         );
     }
 
@@ -400,7 +679,8 @@ mod tests {
                 &indices,
                 &[0, 3],
                 &[3, 3],
-                &[[2, 3]]
+                &[[2, 3]],
+                89f32.to_radians()
             )
         );
     }
@@ -424,7 +704,8 @@ mod tests {
                 &indices,
                 &[0, 3, 6, 9],
                 &[3, 3, 3, 3],
-                &[[2, 3], [3, 5], [0, 1], [1, 4]]
+                &[[2, 3], [3, 5], [0, 1], [1, 4]],
+                89f32.to_radians()
             )
         );
     }
@@ -461,7 +742,8 @@ mod tests {
                 &indices,
                 &[0, 3, 6, 9],
                 &[3, 3, 3, 3],
-                &[[1, 3]]
+                &[[1, 3]],
+                89f32.to_radians()
             )
         );
     }
@@ -498,7 +780,8 @@ mod tests {
                 &indices,
                 &[0, 4],
                 &[4, 4],
-                &[[1, 2]]
+                &[[1, 2]],
+                89f32.to_radians()
             )
         );
     }
@@ -524,7 +807,8 @@ mod tests {
                 &[2, 1, 0, 3, 2, 0, 1, 5, 4, 0, 1, 4],
                 &[0, 3, 6, 9],
                 &[3, 3, 3, 3],
-                &[[2, 1], [0, 3], [1, 5], [4, 0]]
+                &[[2, 1], [0, 3], [1, 5], [4, 0]],
+                89f32.to_radians()
             )
         );
     }
@@ -559,8 +843,53 @@ mod tests {
                 &[0, 3, 1, 0, 1, 2, 1, 3, 2, 2, 3, 0],
                 &[0, 3, 6, 9],
                 &[3, 3, 3, 3],
-                &[]
+                &[],
+                89f32.to_radians()
+            )
+        );
+    }
+
+    #[test]
+    fn mesh_edges_two_quads() {
+        // Two quads folded 90 degrees along 1-2 and one sharp edge.
+        // 3 - 2 - 5
+        // |   |   |
+        // 0 - 1 - 4
+        let vertices = [
+            vec3(0.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            vec3(1.0, 0.0, 1.0),
+            vec3(1.0, 1.0, 1.0),
+        ];
+        let indices = [0, 1, 2, 3, 1, 4, 5, 2];
+
+        assert_eq!(
+            (
+                vec![[0, 1], [1, 2], [2, 3], [0, 3], [1, 4], [4, 5], [2, 5]],
+                vec![false, true, false, true, false, false, false]
+            ),
+            mesh_edges(
+                &vertices,
+                &indices,
+                &[0, 4],
+                &[4, 4],
+                &[[3, 0]],
+                89f32.to_radians()
+            )
+        );
+        assert_eq!(
+            vec![false, false, false, true, false, false, false],
+            mesh_edges(
+                &vertices,
+                &indices,
+                &[0, 4],
+                &[4, 4],
+                &[[3, 0]],
+                91f32.to_radians()
             )
+            .1
         );
     }
 
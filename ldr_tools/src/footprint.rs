@@ -0,0 +1,149 @@
+use glam::Vec3;
+
+use crate::{GeometrySettings, LDrawGeometry};
+
+/// The width and depth of one stud in LDU.
+pub const STUD_SIZE_LDU: f32 = 20.0;
+
+/// The height of one plate in LDU. A brick is three plates tall.
+pub const PLATE_HEIGHT_LDU: f32 = 8.0;
+
+/// The size of a part in studs and plates like a 2x4 brick with a height of 3 plates.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct StudFootprint {
+    /// The size along the X axis in studs.
+    pub width: u32,
+    /// The size along the Z axis in studs.
+    pub depth: u32,
+    /// The size along the Y axis in plates.
+    pub height: u32,
+}
+
+/// Calculate the size in studs and plates for a bounding box in LDU rounded to the nearest unit.
+/// Sizes are not scaled by [GeometrySettings::scene_scale](crate::GeometrySettings::scene_scale).
+pub fn stud_footprint(min: Vec3, max: Vec3) -> StudFootprint {
+    let size = (max - min).abs();
+    StudFootprint {
+        width: (size.x / STUD_SIZE_LDU).round() as u32,
+        depth: (size.z / STUD_SIZE_LDU).round() as u32,
+        height: (size.y / PLATE_HEIGHT_LDU).round() as u32,
+    }
+}
+
+impl LDrawGeometry {
+    /// The [stud_footprint] of the bounding box of the faces excluding studs
+    /// so that studs don't count towards the height.
+    /// Returns `None` if there are no faces.
+    ///
+    /// The `settings` should match the settings used to create the geometry
+    /// to convert the vertices back to LDU.
+    pub fn stud_footprint(&self, settings: &GeometrySettings) -> Option<StudFootprint> {
+        let (min, max) = self
+            .faces()
+            .filter(|f| !f.is_stud)
+            .flat_map(|f| f.vertex_indices.iter())
+            .map(|i| self.vertices[*i as usize])
+            .fold(None, |bounds, v| match bounds {
+                Some((min, max)) => Some((Vec3::min(min, v), Vec3::max(max, v))),
+                None => Some((v, v)),
+            })?;
+        // Gaps between parts only slightly reduce the size and don't affect rounding.
+        Some(stud_footprint(
+            min / settings.scene_scale,
+            max / settings.scene_scale,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stud_footprint_brick() {
+        assert_eq!(
+            StudFootprint {
+                width: 2,
+                depth: 4,
+                height: 3
+            },
+            stud_footprint(Vec3::new(-20.0, -24.0, -40.0), Vec3::new(20.0, 0.0, 40.0))
+        );
+
+        // Gaps between parts slightly reduce the size.
+        assert_eq!(
+            StudFootprint {
+                width: 1,
+                depth: 1,
+                height: 1
+            },
+            stud_footprint(Vec3::new(-9.9, -7.9, -9.9), Vec3::new(9.9, 0.0, 9.9))
+        );
+    }
+
+    #[test]
+    fn stud_footprint_excludes_studs() {
+        let geometry = LDrawGeometry {
+            vertices: vec![
+                Vec3::new(-10.0, 0.0, -10.0),
+                Vec3::new(10.0, -8.0, 10.0),
+                Vec3::new(-10.0, -8.0, 10.0),
+                Vec3::new(-6.0, -12.0, -6.0),
+                Vec3::new(6.0, -8.0, -6.0),
+                Vec3::new(6.0, -12.0, 6.0),
+            ],
+            vertex_indices: vec![0, 1, 2, 3, 4, 5],
+            face_start_indices: vec![0, 3],
+            face_sizes: vec![3, 3],
+            face_normals: vec![Vec3::Y; 2],
+            face_colors: vec![16],
+            is_face_stud: vec![false, true],
+            is_face_double_sided: vec![false, false],
+            ..Default::default()
+        };
+        assert_eq!(
+            Some(StudFootprint {
+                width: 1,
+                depth: 1,
+                height: 1
+            }),
+            geometry.stud_footprint(&GeometrySettings::default())
+        );
+        assert_eq!(
+            None,
+            LDrawGeometry::default().stud_footprint(&GeometrySettings::default())
+        );
+    }
+
+    #[test]
+    fn stud_footprint_scene_scale() {
+        let source_file = crate::ldraw::SourceFile {
+            cmds: crate::ldraw::parse_raw(indoc::indoc! {b"
+                4 16 -20 -24 -10 20 -24 -10 20 -24 10 -20 -24 10
+                4 16 20 -24 -10 20 0 -10 20 0 10 20 -24 10
+            "})
+            .unwrap(),
+        };
+        let settings = GeometrySettings {
+            scene_scale: 0.01,
+            add_gap_between_parts: true,
+            ..Default::default()
+        };
+        let geometry = crate::geometry::create_geometry(
+            &source_file,
+            &crate::ldraw::SourceMap::new(),
+            "3004.dat",
+            16,
+            true,
+            &settings,
+        );
+        assert_eq!(
+            Some(StudFootprint {
+                width: 2,
+                depth: 1,
+                height: 3
+            }),
+            geometry.stud_footprint(&settings)
+        );
+    }
+}
@@ -1,32 +1,64 @@
-use crate::ldraw::{BfcCommand, Command, Winding};
-use glam::{Mat4, Vec2, Vec3};
+use crate::ldraw::{BfcCommand, Command, TexMapCommand, Winding};
+use glam::{Mat3, Mat4, Vec2, Vec3};
 use rstar::{primitives::GeomWithData, RTree};
+use std::collections::HashSet;
 
 use crate::{
-    edge_split::split_edges,
-    pe_tex_info::{project_texture, LDrawTextureInfo, PendingStudioTexture},
+    edge_split::{mesh_edges, split_edges},
+    pe_tex_info::{project_texture, texture_name, LDrawTextureInfo, PendingStudioTexture},
     replace_color,
     slope::is_slope_piece,
+    substitute::substitute_part,
     ColorCode, GeometrySettings, StudType,
 };
 
 // TODO: Document the data layout for these fields.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Default)]
 pub struct LDrawGeometry {
     pub vertices: Vec<Vec3>,
     pub vertex_indices: Vec<u32>,
     pub face_start_indices: Vec<u32>,
     pub face_sizes: Vec<u32>,
+    /// The normal of each face based on the face winding in the LDraw files.
+    /// This accounts for mirrored transforms independent of the vertex order.
+    pub face_normals: Vec<Vec3>,
     /// The colors of each face or a single element if all faces share a color.
     pub face_colors: Vec<ColorCode>,
     pub is_face_stud: Vec<bool>,
+    /// `true` for faces from files without BFC certification or after `BFC NOCLIP`
+    /// if [GeometrySettings::double_side_uncertified] is enabled.
+    /// These faces have unreliable winding and should render both sides.
+    pub is_face_double_sided: Vec<bool>,
+    /// The winding of each face in the LDraw files after applying `BFC INVERTNEXT`
+    /// if [GeometrySettings::record_face_windings] is enabled and empty otherwise.
+    /// Faces with [Winding::Cw] had their vertices reversed to use counter-clockwise winding.
+    pub face_windings: Vec<Winding>,
     /// Indices for the end points of line type 2 edges.
     pub edge_line_indices: Vec<[u32; 2]>,
+    /// The color of each edge in [edge_line_indices](#structfield.edge_line_indices).
+    /// Code 24 uses the edge color of the current color.
+    pub edge_line_colors: Vec<ColorCode>,
+    /// Indices for the end points and control points of line type 5 optional edges.
+    /// An edge should only be drawn if both control points are on the same side of the edge
+    /// when projected to screen space.
+    pub optional_line_edges: Vec<([u32; 2], [u32; 2])>,
+    /// Indices for the end points of each unique edge of the faces.
+    /// Edges already split with [GeometrySettings::weld_vertices] appear as boundary edges.
+    /// This is empty unless [GeometrySettings::calculate_mesh_edges] is enabled.
+    pub mesh_edge_indices: Vec<[u32; 2]>,
+    /// `true` for edges in [mesh_edge_indices](#structfield.mesh_edge_indices)
+    /// that are line type 2 edges
+    /// or exceed [GeometrySettings::sharp_angle_threshold].
+    pub is_mesh_edge_sharp: Vec<bool>,
     /// `true` if the geometry is part of a slope piece with grainy faces.
     /// Some applications may want to apply a separate texture to faces
     /// based on an angle threshold.
     pub has_grainy_slopes: bool,
     pub texture_info: Option<LDrawTextureInfo>,
+    /// The number of face vertices merged into an existing vertex
+    /// with [GeometrySettings::weld_vertices].
+    /// This does not include vertices added back when splitting sharp edges.
+    pub welded_vertex_count: usize,
 }
 
 impl LDrawGeometry {
@@ -35,6 +67,364 @@ impl LDrawGeometry {
             LDrawTextureInfo::new(self.face_start_indices.len(), self.vertex_indices.len())
         })
     }
+
+    /// Create an interleaved `[position, normal, uv]` vertex buffer and a triangle index buffer.
+    /// See [interleave](crate::interleave) for details.
+    pub fn interleaved(&self) -> (Vec<f32>, Vec<u32>) {
+        crate::interleave(
+            &self.vertices,
+            &self.vertex_indices,
+            &self.face_start_indices,
+            &self.face_sizes,
+            &self.face_normals,
+            self.texture_info.as_ref().map(|t| t.uvs.as_slice()),
+        )
+    }
+
+    /// The color of each triangle in the index buffer from [interleaved](Self::interleaved).
+    pub fn triangle_colors(&self) -> Vec<ColorCode> {
+        crate::triangle_colors(&self.face_sizes, &self.face_colors)
+    }
+
+    /// Iterate over the data for each face in order.
+    pub fn faces(&self) -> impl Iterator<Item = Face<'_>> + '_ {
+        (0..self.face_sizes.len()).map(|i| self.face(i))
+    }
+
+    /// The data for the face at index `i`.
+    /// Panics if `i` is out of range.
+    pub fn face(&self, i: usize) -> Face<'_> {
+        let range = self.face_range(i);
+        let texture_index = self
+            .texture_info
+            .as_ref()
+            .map(|t| t.indices[i])
+            .filter(|t| *t != u8::MAX);
+        Face {
+            vertex_indices: &self.vertex_indices[range.clone()],
+            normal: self.face_normals[i],
+            color: if self.face_colors.len() == 1 {
+                self.face_colors[0]
+            } else {
+                self.face_colors[i]
+            },
+            is_stud: self.is_face_stud[i],
+            is_double_sided: self.is_face_double_sided[i],
+            texture_index,
+            uvs: self.texture_info.as_ref().map(|t| &t.uvs[range]),
+        }
+    }
+
+    /// The sharp edges in [mesh_edge_indices](#structfield.mesh_edge_indices)
+    /// as sorted and deduplicated `[min, max]` pairs of indices into [vertices](#structfield.vertices).
+    /// This matches the unordered edges in applications like Blender.
+    pub fn sharp_edges(&self) -> Vec<[u32; 2]> {
+        let mut edges: Vec<_> = self
+            .mesh_edge_indices
+            .iter()
+            .zip(&self.is_mesh_edge_sharp)
+            .filter(|(_, is_sharp)| **is_sharp)
+            .map(|([a, b], _)| [*a.min(b), *a.max(b)])
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+        edges
+    }
+
+    /// The indices into [vertices](#structfield.vertices) for the face at index `i`.
+    /// Panics if `i` is out of range.
+    pub fn face_vertices(&self, i: usize) -> &[u32] {
+        &self.vertex_indices[self.face_range(i)]
+    }
+
+    /// Apply `transform` to the vertices and face normals.
+    /// Faces are reversed for mirrored transforms to preserve the winding.
+    pub fn transform(&mut self, transform: Mat4) {
+        self.transform_from(transform, 0, 0);
+    }
+
+    /// Append the faces and edges of `other` after applying `offset`.
+    pub fn merge(&mut self, other: &LDrawGeometry, offset: Mat4) {
+        let vertex_start = self.vertices.len();
+        let face_start = self.face_sizes.len();
+        let vertex_offset = vertex_start as u32;
+        let index_offset = self.vertex_indices.len() as u32;
+
+        // Faces share a single color only if both geometries use the same color.
+        let is_same_color = self.face_colors.len() == 1 && self.face_colors == other.face_colors;
+        if self.face_sizes.is_empty() {
+            self.face_colors.clone_from(&other.face_colors);
+        } else if !is_same_color {
+            self.face_colors = expanded_colors(&self.face_colors, self.face_sizes.len());
+            self.face_colors
+                .extend(expanded_colors(&other.face_colors, other.face_sizes.len()));
+        }
+
+        if other.texture_info.is_some() || self.texture_info.is_some() {
+            let face_count = other.face_sizes.len();
+            let vertex_count = other.vertex_indices.len();
+            let texture_info = self.texture_info();
+            match &other.texture_info {
+                Some(other) => {
+                    // Merged instances of the same part share the same textures.
+                    let texture_indices: Vec<_> = (0..other.textures.len())
+                        .map(|i| merge_texture(texture_info, other, i))
+                        .collect();
+                    texture_info.indices.extend(
+                        other
+                            .indices
+                            .iter()
+                            .map(|i| texture_indices.get(*i as usize).copied().unwrap_or(u8::MAX)),
+                    );
+                    texture_info.uvs.extend(&other.uvs);
+                }
+                None => {
+                    texture_info.indices.extend(vec![u8::MAX; face_count]);
+                    texture_info.uvs.extend(vec![Vec2::ZERO; vertex_count]);
+                }
+            }
+        }
+
+        self.vertices.extend(&other.vertices);
+        self.vertex_indices
+            .extend(other.vertex_indices.iter().map(|i| i + vertex_offset));
+        self.face_start_indices
+            .extend(other.face_start_indices.iter().map(|i| i + index_offset));
+        self.face_sizes.extend(&other.face_sizes);
+        self.face_normals.extend(&other.face_normals);
+        self.is_face_stud.extend(&other.is_face_stud);
+        self.is_face_double_sided
+            .extend(&other.is_face_double_sided);
+        self.face_windings.extend(&other.face_windings);
+
+        let offset_edge = |[a, b]: &[u32; 2]| [a + vertex_offset, b + vertex_offset];
+        self.edge_line_indices
+            .extend(other.edge_line_indices.iter().map(offset_edge));
+        self.edge_line_colors.extend(&other.edge_line_colors);
+        self.optional_line_edges.extend(
+            other
+                .optional_line_edges
+                .iter()
+                .map(|(e, c)| (offset_edge(e), offset_edge(c))),
+        );
+        self.mesh_edge_indices
+            .extend(other.mesh_edge_indices.iter().map(offset_edge));
+        self.is_mesh_edge_sharp.extend(&other.is_mesh_edge_sharp);
+        self.has_grainy_slopes |= other.has_grainy_slopes;
+        self.welded_vertex_count += other.welded_vertex_count;
+
+        self.transform_from(offset, vertex_start, face_start);
+    }
+
+    fn transform_from(&mut self, transform: Mat4, vertex_start: usize, face_start: usize) {
+        for v in &mut self.vertices[vertex_start..] {
+            *v = transform.transform_point3(*v);
+        }
+
+        let normal_matrix = Mat3::from_mat4(transform).inverse().transpose();
+        for n in &mut self.face_normals[face_start..] {
+            *n = (normal_matrix * *n).normalize_or_zero();
+        }
+
+        if transform.determinant() < 0.0 {
+            for i in face_start..self.face_sizes.len() {
+                let range = self.face_range(i);
+                self.vertex_indices[range.clone()].reverse();
+                if let Some(texture_info) = &mut self.texture_info {
+                    texture_info.uvs[range].reverse();
+                }
+            }
+        }
+    }
+
+    /// Tangents for each face corner using [crate::tangents] with the normals from [vertex_normals](Self::vertex_normals)
+    /// or `None` if the geometry has no UVs.
+    pub fn tangents(&self) -> Option<Vec<glam::Vec4>> {
+        let texture_info = self.texture_info.as_ref()?;
+        Some(crate::tangents(
+            &self.vertices,
+            &self.vertex_indices,
+            &self.face_start_indices,
+            &self.face_sizes,
+            &self.vertex_normals(),
+            &texture_info.uvs,
+        ))
+    }
+
+    /// Smooth normals for each vertex using [crate::vertex_normals].
+    pub fn vertex_normals(&self) -> Vec<Vec3> {
+        crate::vertex_normals(
+            self.vertices.len(),
+            &self.vertex_indices,
+            &self.face_start_indices,
+            &self.face_sizes,
+            &self.face_normals,
+        )
+    }
+
+    /// Copy the geometry with only the faces matching `include_face`.
+    /// Vertices and edges not used by the remaining faces are removed.
+    pub(crate) fn filter_faces(&self, include_face: impl Fn(&Face) -> bool) -> LDrawGeometry {
+        let mut vertex_remap = vec![None; self.vertices.len()];
+        let mut vertices = Vec::new();
+        let mut remap = |i: u32| {
+            *vertex_remap[i as usize].get_or_insert_with(|| {
+                vertices.push(self.vertices[i as usize]);
+                vertices.len() as u32 - 1
+            })
+        };
+
+        let mut vertex_indices = Vec::new();
+        let mut face_start_indices = Vec::new();
+        let mut face_sizes = Vec::new();
+        let mut face_normals = Vec::new();
+        let mut face_colors = Vec::new();
+        let mut is_face_stud = Vec::new();
+        let mut is_face_double_sided = Vec::new();
+        let mut face_windings = Vec::new();
+        let mut texture_info = self.texture_info.as_ref().map(|t| LDrawTextureInfo {
+            textures: t.textures.clone(),
+            texture_sizes: t.texture_sizes.clone(),
+            texture_names: t.texture_names.clone(),
+            glossmaps: t.glossmaps.clone(),
+            indices: Vec::new(),
+            uvs: Vec::new(),
+        });
+
+        for (i, (start, size)) in self
+            .face_start_indices
+            .iter()
+            .zip(&self.face_sizes)
+            .enumerate()
+        {
+            if !include_face(&self.face(i)) {
+                continue;
+            }
+
+            let range = *start as usize..(*start + *size) as usize;
+            face_start_indices.push(vertex_indices.len() as u32);
+            face_sizes.push(*size);
+            vertex_indices.extend(self.vertex_indices[range.clone()].iter().map(|v| remap(*v)));
+            face_normals.push(self.face_normals[i]);
+            if self.face_colors.len() > 1 {
+                face_colors.push(self.face_colors[i]);
+            }
+            is_face_stud.push(self.is_face_stud[i]);
+            is_face_double_sided.push(self.is_face_double_sided[i]);
+            if let Some(winding) = self.face_windings.get(i) {
+                face_windings.push(*winding);
+            }
+            if let (Some(new), Some(old)) = (&mut texture_info, &self.texture_info) {
+                new.indices.push(old.indices[i]);
+                new.uvs.extend_from_slice(&old.uvs[range]);
+            }
+        }
+
+        if self.face_colors.len() <= 1 {
+            face_colors = self.face_colors.clone();
+        }
+
+        // Only keep edges with all points on the remaining faces.
+        let kept = |i: &u32| vertex_remap[*i as usize];
+        let kept_edge = |e: &[u32; 2]| Some([kept(&e[0])?, kept(&e[1])?]);
+
+        let (edge_line_indices, edge_line_colors) = self
+            .edge_line_indices
+            .iter()
+            .zip(&self.edge_line_colors)
+            .filter_map(|(e, c)| Some((kept_edge(e)?, *c)))
+            .unzip();
+        let optional_line_edges = self
+            .optional_line_edges
+            .iter()
+            .filter_map(|(e, c)| Some((kept_edge(e)?, kept_edge(c)?)))
+            .collect();
+        let (mesh_edge_indices, is_mesh_edge_sharp) = self
+            .mesh_edge_indices
+            .iter()
+            .zip(&self.is_mesh_edge_sharp)
+            .filter_map(|(e, s)| Some((kept_edge(e)?, *s)))
+            .unzip();
+
+        LDrawGeometry {
+            vertices,
+            is_face_stud,
+            vertex_indices,
+            face_start_indices,
+            face_sizes,
+            face_normals,
+            face_colors,
+            is_face_double_sided,
+            face_windings,
+            edge_line_indices,
+            edge_line_colors,
+            optional_line_edges,
+            mesh_edge_indices,
+            is_mesh_edge_sharp,
+            has_grainy_slopes: self.has_grainy_slopes,
+            texture_info,
+            welded_vertex_count: self.welded_vertex_count,
+        }
+    }
+
+    fn face_range(&self, i: usize) -> std::ops::Range<usize> {
+        let start = self.face_start_indices[i] as usize;
+        start..start + self.face_sizes[i] as usize
+    }
+}
+
+/// Returns the index of texture `i` from `other` in `texture_info` after adding it if needed.
+/// Returns `u8::MAX` if there are too many textures to fit in the face indices.
+fn merge_texture(texture_info: &mut LDrawTextureInfo, other: &LDrawTextureInfo, i: usize) -> u8 {
+    let name = &other.texture_names[i];
+    if let Some(index) = texture_info
+        .texture_names
+        .iter()
+        .zip(&texture_info.textures)
+        .position(|(n, texture)| n == name && texture == &other.textures[i])
+    {
+        return index as u8;
+    }
+
+    // The last index is reserved for faces without a texture.
+    let index = texture_info.textures.len();
+    if index >= u8::MAX as usize {
+        log::warn!(
+            "Ignoring texture {name:?} since merged geometry has more than {} textures",
+            u8::MAX
+        );
+        return u8::MAX;
+    }
+
+    texture_info.textures.push(other.textures[i].clone());
+    texture_info.texture_sizes.push(other.texture_sizes[i]);
+    texture_info.texture_names.push(name.clone());
+    texture_info.glossmaps.push(other.glossmaps[i].clone());
+    index as u8
+}
+
+fn expanded_colors(face_colors: &[ColorCode], face_count: usize) -> Vec<ColorCode> {
+    if face_colors.len() == 1 {
+        vec![face_colors[0]; face_count]
+    } else {
+        face_colors.to_vec()
+    }
+}
+
+/// A single face from [LDrawGeometry::faces].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Face<'a> {
+    /// The indices into [LDrawGeometry::vertices] in winding order.
+    pub vertex_indices: &'a [u32],
+    pub normal: Vec3,
+    pub color: ColorCode,
+    pub is_stud: bool,
+    pub is_double_sided: bool,
+    /// The index into [LDrawTextureInfo::textures] or `None` if the face is not textured.
+    pub texture_index: Option<u8>,
+    /// The UV coordinates for each of the [vertex_indices](#structfield.vertex_indices)
+    /// if the geometry has any textured faces.
+    pub uvs: Option<&'a [Vec2]>,
 }
 
 /// Settings that inherit or accumulate when recursing into subfiles.
@@ -44,7 +434,9 @@ struct GeometryContext {
     inverted: bool,
     is_stud: bool,
     is_slope: bool,
+    is_double_sided: bool,
     studio_textures: Vec<PendingStudioTexture>,
+    depth: usize,
 }
 
 struct VertexMap {
@@ -94,45 +486,93 @@ pub fn create_geometry(
     recursive: bool,
     settings: &GeometrySettings,
 ) -> LDrawGeometry {
+    if settings.weld_vertices && is_weld_excluded(name, source_file, settings) {
+        let settings = GeometrySettings {
+            weld_vertices: false,
+            ..settings.clone()
+        };
+        return create_geometry(
+            source_file,
+            source_map,
+            name,
+            current_color,
+            recursive,
+            &settings,
+        );
+    }
+
     let mut geometry = LDrawGeometry {
         vertices: Vec::new(),
         vertex_indices: Vec::new(),
         face_start_indices: Vec::new(),
         face_sizes: Vec::new(),
+        face_normals: Vec::new(),
         face_colors: Vec::new(),
         is_face_stud: Vec::new(),
+        is_face_double_sided: Vec::new(),
+        face_windings: Vec::new(),
         edge_line_indices: Vec::new(),
-        has_grainy_slopes: is_slope_piece(name),
+        edge_line_colors: Vec::new(),
+        optional_line_edges: Vec::new(),
+        mesh_edge_indices: Vec::new(),
+        is_mesh_edge_sharp: Vec::new(),
+        has_grainy_slopes: is_slope(name, source_file),
         texture_info: None,
+        welded_vertex_count: 0,
     };
 
     // Start with inverted set to false since parts should never be inverted.
-    // TODO: Is this also correct for geometry within an MPD file?
+    // This also applies to geometry drawn inline in MPD models,
+    // since mirrored node transforms are handled separately with LDrawNode::is_mirrored.
     let ctx = GeometryContext {
         current_color,
         transform: Mat4::IDENTITY,
         inverted: false,
-        is_stud: is_stud(name),
-        is_slope: is_slope_piece(name),
+        is_stud: is_stud(name, Some(source_file), settings),
+        is_slope: is_slope(name, source_file),
+        is_double_sided: false,
         studio_textures: vec![],
+        depth: 0,
     };
 
     let mut vertex_map = VertexMap::new();
     let mut hard_edges = Vec::new();
+    let mut optional_edges = Vec::new();
 
     // TODO: Cache geometry creation for studs?
     append_geometry(
         &mut geometry,
         &mut hard_edges,
+        &mut optional_edges,
         &mut vertex_map,
         source_file,
+        name,
         source_map,
         ctx,
         recursive,
         settings,
     );
 
-    geometry.edge_line_indices = edge_indices(&hard_edges, &vertex_map);
+    if let Some(texture_info) = &mut geometry.texture_info {
+        repair_texture_info(
+            texture_info,
+            geometry.face_sizes.len(),
+            geometry.vertex_indices.len(),
+            name,
+        );
+    }
+
+    if settings.remove_duplicate_faces {
+        remove_duplicate_faces(&mut geometry);
+    }
+
+    if settings.triangulate {
+        geometry.triangulate_polygons();
+    }
+
+    (geometry.edge_line_indices, geometry.edge_line_colors) =
+        edge_indices(&hard_edges, &vertex_map).into_iter().unzip();
+    geometry.optional_line_edges = optional_edge_indices(&optional_edges, &vertex_map);
 
     // TODO: make this optional.
     if settings.weld_vertices && !geometry.edge_line_indices.is_empty() {
@@ -142,12 +582,24 @@ pub fn create_geometry(
             &geometry.face_start_indices,
             &geometry.face_sizes,
             &geometry.edge_line_indices,
+            settings.sharp_angle_threshold.to_radians(),
         );
         // The edge indices are still valid since splitting only adds new vertices.
         geometry.vertices = split_positions;
         geometry.vertex_indices = split_indices;
     }
 
+    if settings.calculate_mesh_edges {
+        (geometry.mesh_edge_indices, geometry.is_mesh_edge_sharp) = mesh_edges(
+            &geometry.vertices,
+            &geometry.vertex_indices,
+            &geometry.face_start_indices,
+            &geometry.face_sizes,
+            &geometry.edge_line_indices,
+            settings.sharp_angle_threshold.to_radians(),
+        );
+    }
+
     // Optimize the case where all face colors are the same.
     // This reduces overhead when processing data in Python.
     // A single color can be applied per object rather than per face.
@@ -171,8 +623,9 @@ pub fn create_geometry(
         .unwrap_or_default();
     let dimensions = max - min;
 
-    let scale = if settings.add_gap_between_parts {
-        gaps_scale(dimensions) * settings.scene_scale
+    let is_gap_excluded = settings.gap_excludes_flexible && is_flexible(source_file);
+    let scale = if settings.add_gap_between_parts && !is_gap_excluded {
+        gaps_scale(dimensions, settings.gap_distance) * settings.scene_scale
     } else {
         Vec3::splat(settings.scene_scale)
     };
@@ -182,21 +635,132 @@ pub fn create_geometry(
     for vertex in &mut geometry.vertices {
         *vertex *= scale;
     }
+    for normal in &mut geometry.face_normals {
+        *normal = (*normal / scale).normalize_or_zero();
+    }
+
+    debug_assert_face_arrays(&geometry);
 
     geometry
 }
 
-fn is_stud(name: &str) -> bool {
-    // TODO: find a more accurate way to check this.
-    name.contains("stu")
+/// Check that splitting or removing faces kept the per face arrays in sync.
+fn debug_assert_face_arrays(geometry: &LDrawGeometry) {
+    let face_count = geometry.face_sizes.len();
+    debug_assert_eq!(face_count, geometry.face_start_indices.len());
+    debug_assert_eq!(face_count, geometry.face_normals.len());
+    debug_assert_eq!(face_count, geometry.is_face_stud.len());
+    debug_assert_eq!(face_count, geometry.is_face_double_sided.len());
+    // Windings are only recorded if enabled.
+    debug_assert!(geometry.face_windings.is_empty() || geometry.face_windings.len() == face_count);
+    // Faces may share a single color.
+    debug_assert!(
+        geometry.face_colors.len() == face_count || geometry.face_colors.len() == 1,
+        "{} face colors for {face_count} faces",
+        geometry.face_colors.len()
+    );
+    if let Some(texture_info) = &geometry.texture_info {
+        debug_assert_eq!(face_count, texture_info.indices.len());
+        debug_assert_eq!(geometry.vertex_indices.len(), texture_info.uvs.len());
+    }
+}
+
+/// Check for stud primitives like `stud.dat`, `stud4a.dat`, or `stud-logo4.dat`.
+/// Files with an `!LDRAW_ORG` header must also be primitives.
+fn is_stud(
+    name: &str,
+    source_file: Option<&crate::ldraw::SourceFile>,
+    settings: &GeometrySettings,
+) -> bool {
+    let file_name = file_name(name).to_lowercase();
+    let is_stud_name = file_name
+        .strip_prefix("stud")
+        .and_then(|n| n.strip_suffix(".dat"))
+        .is_some_and(|n| n.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'));
+
+    if is_stud_name {
+        // Unofficial files may not have a header.
+        source_file
+            .and_then(ldraw_org_type)
+            .is_none_or(|t| t.ends_with("primitive"))
+    } else {
+        settings.stud_substring_fallback && file_name.contains("stu")
+    }
+}
+
+/// The name without any folders like `3001.dat` for `s\3001.dat`
+/// or the path of a part loaded directly from disk like `C:/models/3001.dat`.
+fn file_name(name: &str) -> &str {
+    name.rsplit(['/', '\\']).next().unwrap_or_default()
+}
+
+/// The lowercase type from `0 !LDRAW_ORG <type> ...` like `"primitive"` or `"part"`.
+fn ldraw_org_type(source_file: &crate::ldraw::SourceFile) -> Option<String> {
+    source_file.cmds.iter().find_map(|c| match c {
+        Command::Comment(c) => c
+            .text
+            .strip_prefix("!LDRAW_ORG")?
+            .split_whitespace()
+            .next()
+            .map(|t| t.to_lowercase()),
+        _ => None,
+    })
+}
+
+// Categories for parts that bend or connect to other parts along their length.
+const FLEXIBLE_CATEGORIES: [&str; 6] = ["cable", "chain", "hose", "rope", "string", "tube"];
+
+fn is_flexible(source_file: &crate::ldraw::SourceFile) -> bool {
+    part_category(source_file).is_some_and(|category| {
+        FLEXIBLE_CATEGORIES
+            .iter()
+            .any(|c| category.eq_ignore_ascii_case(c))
+    })
+}
+
+fn is_weld_excluded(
+    name: &str,
+    source_file: &crate::ldraw::SourceFile,
+    settings: &GeometrySettings,
+) -> bool {
+    let name = name.to_lowercase().replace('\\', "/");
+    settings.weld_excluded_parts.contains(&name)
+        || settings.weld_excluded_parts.contains(file_name(&name))
+        || (settings.weld_excludes_flexible && is_flexible(source_file))
+}
+
+/// Check the list of slope parts and then the category for slopes not in the list.
+fn is_slope(name: &str, source_file: &crate::ldraw::SourceFile) -> bool {
+    is_slope_piece(file_name(name))
+        || (crate::is_part(source_file, name)
+            && part_category(source_file).is_some_and(|c| c.eq_ignore_ascii_case("slope")))
+}
+
+/// Find the `!CATEGORY` or the first word of the description
+/// since LDraw uses the first word as the category by default.
+fn part_category(source_file: &crate::ldraw::SourceFile) -> Option<&str> {
+    source_file
+        .cmds
+        .iter()
+        .find_map(|c| match c {
+            Command::Category(c) => Some(c.category.as_str()),
+            _ => None,
+        })
+        .or_else(|| match source_file.cmds.first() {
+            // Ignore prefixes for moved, aliased, or obsolete parts.
+            Some(Command::Comment(c)) => c
+                .text
+                .trim_start_matches(['~', '=', '_', '|'])
+                .split_whitespace()
+                .next(),
+            _ => None,
+        })
 }
 
-fn gaps_scale(dimensions: Vec3) -> Vec3 {
-    // TODO: Avoid applying this on chains, ropes, etc?
+fn gaps_scale(dimensions: Vec3, gap_distance: Vec3) -> Vec3 {
     // TODO: Weld ropes into a single piece?
     // Convert a distance between parts to a scale factor.
     // This gap is in LDUs since we haven't scaled the part yet.
-    let gap_distance = 0.1;
     if dimensions.length_squared() > 0.0 {
         ((2.0 * gap_distance - dimensions) / dimensions).abs()
     } else {
@@ -204,28 +768,50 @@ fn gaps_scale(dimensions: Vec3) -> Vec3 {
     }
 }
 
-fn edge_indices(edges: &[[Vec3; 2]], vertex_map: &VertexMap) -> Vec<[u32; 2]> {
+fn edge_indices(
+    edges: &[([Vec3; 2], ColorCode)],
+    vertex_map: &VertexMap,
+) -> Vec<([u32; 2], ColorCode)> {
     // Find the edges marked as edges in the LDraw geometry.
     // These edges can be split by consuming applications later.
     let mut edge_indices = Vec::new();
-    for [v0, v1] in edges.iter() {
+    for ([v0, v1], color) in edges.iter() {
         // TODO: Why is get_nearest not enough to find some indices?
         let i0 = vertex_map.get_nearest(v0.to_array());
         let i1 = vertex_map.get_nearest(v1.to_array());
         if let (Some(i0), Some(i1)) = (i0, i1) {
-            edge_indices.push([i0, i1]);
+            edge_indices.push(([i0, i1], *color));
         }
     }
 
     edge_indices
 }
 
+fn optional_edge_indices(
+    edges: &[([Vec3; 2], [Vec3; 2])],
+    vertex_map: &VertexMap,
+) -> Vec<([u32; 2], [u32; 2])> {
+    // Control points don't always lie on faces,
+    // so they also use the nearest vertex in the geometry.
+    edges
+        .iter()
+        .filter_map(|(vertices, control_points)| {
+            let [i0, i1] = vertices.map(|v| vertex_map.get_nearest(v.to_array()));
+            let [c0, c1] = control_points.map(|v| vertex_map.get_nearest(v.to_array()));
+            Some(([i0?, i1?], [c0?, c1?]))
+        })
+        .collect()
+}
+
 // TODO: simplify the parameters on these functions.
+#[allow(clippy::too_many_arguments)]
 fn append_geometry(
     geometry: &mut LDrawGeometry,
-    hard_edges: &mut Vec<[Vec3; 2]>,
+    hard_edges: &mut Vec<([Vec3; 2], ColorCode)>,
+    optional_edges: &mut Vec<([Vec3; 2], [Vec3; 2])>,
     vertex_map: &mut VertexMap,
     source_file: &crate::ldraw::SourceFile,
+    file_name: &str,
     source_map: &crate::ldraw::SourceMap,
     mut ctx: GeometryContext,
     recursive: bool,
@@ -234,8 +820,10 @@ fn append_geometry(
     // BFC Extension: https://www.ldraw.org/article/415.html
     // The default winding can be assumed to be CCW.
     // Winding can be changed within a file.
-    // Winding only impacts the current file commands.
+    // Winding and certification only impact the current file commands,
+    // so a subfile's BFC state never leaks back into the file referencing it.
     let mut current_winding = Winding::Ccw;
+    let mut is_certified = false;
 
     let mut current_inverted = ctx.inverted;
     // Invert if the current transform is "inverted".
@@ -245,8 +833,13 @@ fn append_geometry(
 
     let mut invert_next = false;
 
+    // Faces use the parent's culling only after certifying this file.
+    let parent_double_sided = ctx.is_double_sided;
+    ctx.is_double_sided = settings.double_side_uncertified;
+
     let mut tex_path_index = 0;
-    let mut current_tex_path: &[i32] = &[];
+    let mut current_tex_path = Vec::new();
+    let mut file_texture_index = 0;
 
     let (mut active_textures, pending_textures) = ctx
         .studio_textures
@@ -255,28 +848,94 @@ fn append_geometry(
 
     ctx.studio_textures = pending_textures;
 
-    if active_textures.len() > 1 {
-        // TODO: at least narrow it down to one that intersects with the face being operated on
-        println!("warning: multiple active textures. ignoring all but one");
-    }
+    // The number of active textures before applying a !TEXMAP texture.
+    let mut texmap_start_len = None;
+    let mut texmap_next_len = None;
+    let mut is_texmap_fallback = false;
+    let mut previous_is_line = false;
 
     for cmd in &source_file.cmds {
+        // !TEXMAP NEXT only applies to the following line.
+        if previous_is_line {
+            if let Some(len) = texmap_next_len.take() {
+                active_textures.truncate(len);
+            }
+        }
+
+        // Lines starting with "0 !:" before the fallback are only geometry for applications with !TEXMAP support.
+        let texmap_geometry =
+            if (texmap_start_len.is_some() && !is_texmap_fallback) || texmap_next_len.is_some() {
+                texmap_geometry(cmd)
+            } else {
+                None
+            };
+        let cmd = texmap_geometry.as_ref().unwrap_or(cmd);
+
+        let is_line = matches!(
+            cmd,
+            Command::SubFileRef(_)
+                | Command::Line(_)
+                | Command::Triangle(_)
+                | Command::Quad(_)
+                | Command::OptLine(_)
+        );
+        previous_is_line = is_line;
+
+        // Skip the lines for applications without !TEXMAP support if the texture was applied.
+        if is_texmap_fallback && is_line {
+            // Texture paths count every subfile reference even if the file isn't loaded.
+            if matches!(cmd, Command::SubFileRef(_)) {
+                tex_path_index += 1;
+            }
+            continue;
+        }
+
         match cmd {
+            Command::TexMap(texmap) => match texmap {
+                TexMapCommand::Start(texmap_cmd) | TexMapCommand::Next(texmap_cmd) => {
+                    let len = active_textures.len();
+                    if let Some(texture) = PendingStudioTexture::from_texmap(
+                        texmap_cmd,
+                        ctx.transform,
+                        source_map,
+                        geometry,
+                    ) {
+                        active_textures.push(texture);
+                        if matches!(texmap, TexMapCommand::Start(_)) {
+                            texmap_start_len = Some(len);
+                        } else {
+                            texmap_next_len = Some(len);
+                        }
+                    }
+                }
+                TexMapCommand::Fallback => is_texmap_fallback = texmap_start_len.is_some(),
+                TexMapCommand::End => {
+                    if let Some(len) = texmap_start_len.take() {
+                        active_textures.truncate(len);
+                    }
+                    is_texmap_fallback = false;
+                }
+            },
             Command::PeTexPath(pe_tex_path) => {
-                current_tex_path = &pe_tex_path.paths;
+                current_tex_path.clone_from(&pe_tex_path.paths);
             }
             Command::PeTexInfo(pe_tex_info) => {
-                if let Some(mut tex_info) =
-                    PendingStudioTexture::from_cmd(pe_tex_info, current_tex_path, geometry)
-                {
-                    if tex_info.path == [-1] {
-                        tex_info.path.clear()
+                // Name textures by their index in this file to be consistent for all geometry.
+                let texture_name = texture_name(file_name, file_texture_index, &pe_tex_info.data);
+                file_texture_index += 1;
+
+                if let Some(mut tex_info) = PendingStudioTexture::from_cmd(
+                    pe_tex_info,
+                    &current_tex_path,
+                    texture_name,
+                    geometry,
+                ) {
+                    // A trailing -1 refers to the file at the preceding path like [0, -1].
+                    while tex_info.path.last() == Some(&-1) {
+                        tex_info.path.pop();
                     }
 
                     if tex_info.path.is_empty() {
-                        if active_textures.len() > 1 {
-                            println!("warning: multiple active textures. ignoring all but one");
-                        }
                         active_textures.push(tex_info);
                     } else {
                         ctx.studio_textures.push(tex_info);
@@ -284,20 +943,31 @@ fn append_geometry(
                 }
             }
             Command::Bfc(bfc_cmd) => {
-                // Ignore clip and certify since we only need to set winding.
+                // Uncertified faces and faces after NOCLIP can't be culled.
+                // These use the same double sided setting since their winding isn't reliable.
                 match bfc_cmd {
-                    BfcCommand::NoCertify => (),
+                    BfcCommand::NoCertify => {
+                        is_certified = false;
+                        ctx.is_double_sided = settings.double_side_uncertified;
+                    }
                     BfcCommand::Certify(winding) => {
                         current_winding = winding.unwrap_or(Winding::Ccw);
+                        is_certified = true;
+                        ctx.is_double_sided = parent_double_sided;
                     }
                     BfcCommand::Winding(winding) => {
                         current_winding = *winding;
                     }
-                    BfcCommand::NoClip => (),
+                    BfcCommand::NoClip => {
+                        ctx.is_double_sided = settings.double_side_uncertified;
+                    }
                     BfcCommand::Clip(winding) => {
                         if let Some(winding) = winding {
                             current_winding = *winding;
                         }
+                        if is_certified {
+                            ctx.is_double_sided = parent_double_sided;
+                        }
                     }
                     BfcCommand::InvertNext => invert_next = true,
                 }
@@ -313,37 +983,41 @@ fn append_geometry(
                     vertex_map,
                     color,
                     settings.weld_vertices,
-                    active_textures.first(),
+                    settings.flip_texture_v,
+                    settings.record_face_windings,
+                    &active_textures,
                 );
             }
             Command::Quad(q) => {
                 let color = replace_color(q.color, ctx.current_color);
 
-                // TODO: Avoid repetition
-                if settings.triangulate {
-                    // TODO: How to properly triangulate a quad?
-                    add_triangle_face(
-                        geometry,
-                        &ctx,
-                        [q.vertices[0], q.vertices[1], q.vertices[2]],
-                        q.uvs.map(|[a, b, c, _d]| [a, b, c]),
-                        invert_winding(current_winding, current_inverted),
-                        vertex_map,
-                        color,
-                        settings.weld_vertices,
-                        active_textures.first(),
-                    );
-                    add_triangle_face(
-                        geometry,
-                        &ctx,
-                        [q.vertices[0], q.vertices[2], q.vertices[3]],
-                        q.uvs.map(|[a, _b, c, d]| [a, c, d]),
-                        invert_winding(current_winding, current_inverted),
-                        vertex_map,
-                        color,
-                        settings.weld_vertices,
-                        active_textures.first(),
-                    );
+                // Quads should be planar, but bent quads would have incorrect normals.
+                let is_planar = is_planar_quad(q.vertices);
+                if !is_planar {
+                    log::trace!("Splitting non-planar quad in {file_name:?}");
+                }
+
+                if settings.triangulate || !is_planar {
+                    let triangles = if is_planar {
+                        [[0, 1, 2], [0, 2, 3]]
+                    } else {
+                        quad_triangles(q.vertices)
+                    };
+                    for [a, b, c] in triangles {
+                        add_triangle_face(
+                            geometry,
+                            &ctx,
+                            [q.vertices[a], q.vertices[b], q.vertices[c]],
+                            q.uvs.map(|uvs| [uvs[a], uvs[b], uvs[c]]),
+                            invert_winding(current_winding, current_inverted),
+                            vertex_map,
+                            color,
+                            settings.weld_vertices,
+                            settings.flip_texture_v,
+                            settings.record_face_windings,
+                            &active_textures,
+                        );
+                    }
                 } else {
                     add_face(
                         geometry,
@@ -353,30 +1027,53 @@ fn append_geometry(
                         invert_winding(current_winding, current_inverted),
                         vertex_map,
                         settings.weld_vertices,
-                        active_textures.first(),
+                        settings.flip_texture_v,
+                        settings.record_face_windings,
+                        &active_textures,
                     );
 
-                    let face_color = replace_color(q.color, ctx.current_color);
-                    geometry.face_colors.push(face_color);
+                    geometry.face_colors.push(color);
                     geometry.is_face_stud.push(ctx.is_stud);
+                    geometry.is_face_double_sided.push(ctx.is_double_sided);
                 }
             }
             Command::Line(line_cmd) => {
                 let edge = line_cmd.vertices.map(|v| ctx.transform.transform_point3(v));
-                hard_edges.push(edge);
+                let edge_color = replace_color(line_cmd.color, ctx.current_color);
+                hard_edges.push((edge, edge_color));
+            }
+            Command::OptLine(opt_line_cmd) => {
+                let edge = opt_line_cmd
+                    .vertices
+                    .map(|v| ctx.transform.transform_point3(v));
+                let control_points = opt_line_cmd
+                    .control_points
+                    .map(|v| ctx.transform.transform_point3(v));
+                optional_edges.push((edge, control_points));
             }
             Command::SubFileRef(subfile_cmd) => {
+                // Texture paths count every subfile reference even if the file isn't loaded.
+                let subfile_index = tex_path_index;
+                tex_path_index += 1;
+
                 if !recursive {
                     continue;
                 }
-                let subfilename = replace_studs(subfile_cmd, settings.stud_type);
+                let subfilename = substitute_part(
+                    replace_studs(subfile_cmd, source_map, settings),
+                    source_map,
+                    settings,
+                );
+                if crate::exceeds_max_depth(subfilename, ctx.depth, settings) {
+                    continue;
+                }
                 let Some(subfile) = source_map.get(subfilename) else {
                     continue;
                 };
 
                 // Subfiles of slopes or studs are still slopes or studs.
-                let is_stud = ctx.is_stud || is_stud(subfilename);
-                let is_slope = ctx.is_slope || is_slope_piece(subfilename);
+                let is_stud = ctx.is_stud || is_stud(subfilename, Some(subfile), settings);
+                let is_slope = ctx.is_slope || is_slope(subfilename, subfile);
 
                 // Set the walls of high contrast studs to black.
                 // TODO: Create custom stud files for better accuracy.
@@ -391,7 +1088,7 @@ fn append_geometry(
 
                 let mut child_textures = active_textures.clone();
                 for texture in &ctx.studio_textures {
-                    if texture.path.first() == Some(&tex_path_index) {
+                    if texture.path.first() == Some(&subfile_index) {
                         let mut texture = texture.clone();
                         texture.path.remove(0);
                         child_textures.push(texture);
@@ -410,31 +1107,45 @@ fn append_geometry(
                     },
                     is_stud,
                     is_slope,
+                    is_double_sided: ctx.is_double_sided,
                     studio_textures: child_textures,
+                    depth: ctx.depth + 1,
                 };
 
                 // Don't invert additional subfile reference commands.
                 invert_next = false;
 
                 // TODO: Cache the processed geometry for studs?
-                // TODO: Will studs ever need to be welded to other geometry?
+                // Studs share the vertex map to weld the stud base to the surface of the part.
+                // Normals are still split along the edge lines at the base of the stud.
                 append_geometry(
-                    geometry, hard_edges, vertex_map, subfile, source_map, child_ctx, recursive,
+                    geometry,
+                    hard_edges,
+                    optional_edges,
+                    vertex_map,
+                    subfile,
+                    subfilename,
+                    source_map,
+                    child_ctx,
+                    recursive,
                     settings,
                 );
-
-                tex_path_index += 1;
             }
             _ => {}
         }
     }
 }
 
-fn replace_studs(subfile_cmd: &crate::ldraw::SubFileRefCmd, stud_type: StudType) -> &str {
+fn replace_studs<'a>(
+    subfile_cmd: &'a crate::ldraw::SubFileRefCmd,
+    source_map: &crate::ldraw::SourceMap,
+    settings: &GeometrySettings,
+) -> &'a str {
     // https://wiki.ldraw.org/wiki/Studs_with_Logos
-    match stud_type {
+    match settings.stud_type {
         StudType::Disabled => {
-            if is_stud(&subfile_cmd.file) {
+            let file = &subfile_cmd.file;
+            if is_stud(file, source_map.get(file), settings) {
                 // TODO: is there a better way to empty out files?
                 ""
             } else {
@@ -442,16 +1153,28 @@ fn replace_studs(subfile_cmd: &crate::ldraw::SubFileRefCmd, stud_type: StudType)
             }
         }
         StudType::Normal => &subfile_cmd.file,
-        StudType::Logo4 => match subfile_cmd.file.as_str() {
-            "stud.dat" => "stud-logo4.dat",
-            "stud2.dat" => "stud2-logo4.dat",
-            "stud20.dat" => "stud20-logo4.dat",
-            _ => subfile_cmd.file.as_str(),
-        },
+        StudType::Logo4 => {
+            let logo_stud = match subfile_cmd.file.as_str() {
+                "stud.dat" => "stud-logo4.dat",
+                "stud2.dat" => "stud2-logo4.dat",
+                "stud20.dat" => "stud20-logo4.dat",
+                _ => return &subfile_cmd.file,
+            };
+            // Use the normal stud if the library doesn't have the logo stud.
+            if source_map
+                .get(logo_stud)
+                .is_some_and(|f| !f.cmds.is_empty())
+            {
+                logo_stud
+            } else {
+                &subfile_cmd.file
+            }
+        }
         StudType::HighContrast => &subfile_cmd.file,
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn add_triangle_face(
     geometry: &mut LDrawGeometry,
     ctx: &GeometryContext,
@@ -461,7 +1184,9 @@ fn add_triangle_face(
     vertex_map: &mut VertexMap,
     color: u32,
     weld_vertices: bool,
-    texture: Option<&PendingStudioTexture>,
+    flip_texture_v: bool,
+    record_winding: bool,
+    textures: &[PendingStudioTexture],
 ) {
     add_face(
         geometry,
@@ -471,11 +1196,50 @@ fn add_triangle_face(
         winding,
         vertex_map,
         weld_vertices,
-        texture,
+        flip_texture_v,
+        record_winding,
+        textures,
     );
 
     geometry.face_colors.push(color);
     geometry.is_face_stud.push(ctx.is_stud);
+    geometry.is_face_double_sided.push(ctx.is_double_sided);
+}
+
+// The maximum angle in degrees between the normals of the triangles in a quad.
+// This allows for small rounding errors in the vertex coordinates.
+const PLANAR_QUAD_ANGLE_THRESHOLD: f32 = 5.0;
+
+// Parse the line after the "0 !:" prefix like "0 !: 3 16 0 0 0 1 0 0 0 1 0".
+fn texmap_geometry(cmd: &Command) -> Option<Command> {
+    let Command::Comment(comment) = cmd else {
+        return None;
+    };
+    let line = comment.text.strip_prefix("!:")?;
+    crate::ldraw::parse_raw(line.as_bytes())
+        .ok()?
+        .into_iter()
+        .next()
+}
+
+fn is_planar_quad(vertices: [Vec3; 4]) -> bool {
+    let [v0, v1, v2, v3] = vertices;
+    let n1 = (v1 - v0).cross(v2 - v0).normalize_or_zero();
+    let n2 = (v2 - v0).cross(v3 - v0).normalize_or_zero();
+    // Degenerate triangles don't have a meaningful normal to compare.
+    n1 == Vec3::ZERO
+        || n2 == Vec3::ZERO
+        || n1.dot(n2) >= PLANAR_QUAD_ANGLE_THRESHOLD.to_radians().cos()
+}
+
+/// Split a quad into two triangles along the shorter diagonal.
+fn quad_triangles(vertices: [Vec3; 4]) -> [[usize; 3]; 2] {
+    let [v0, v1, v2, v3] = vertices;
+    if v0.distance_squared(v2) <= v1.distance_squared(v3) {
+        [[0, 1, 2], [0, 2, 3]]
+    } else {
+        [[0, 1, 3], [1, 2, 3]]
+    }
 }
 
 fn invert_winding(winding: Winding, invert: bool) -> Winding {
@@ -487,6 +1251,7 @@ fn invert_winding(winding: Winding, invert: bool) -> Winding {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn add_face<const N: usize>(
     geometry: &mut LDrawGeometry,
     transform: Mat4,
@@ -495,14 +1260,23 @@ fn add_face<const N: usize>(
     winding: Winding,
     vertex_map: &mut VertexMap,
     weld_vertices: bool,
-    texture: Option<&PendingStudioTexture>,
+    flip_texture_v: bool,
+    record_winding: bool,
+    textures: &[PendingStudioTexture],
 ) {
     let mut vertices = vertices;
     if winding == Winding::Cw {
         vertices.reverse();
     }
+    if record_winding {
+        geometry.face_windings.push(winding);
+    }
 
-    let texmap = texture.and_then(|t| project_texture(t, transform, vertices, uvs));
+    // Overlapping textures prefer the most recently defined texture.
+    let texmap = textures
+        .iter()
+        .rev()
+        .find_map(|t| project_texture(t, transform, vertices, uvs));
 
     let starting_index = geometry.vertex_indices.len() as u32;
     let indices =
@@ -511,12 +1285,19 @@ fn add_face<const N: usize>(
     geometry.vertex_indices.extend_from_slice(&indices);
     geometry.face_start_indices.push(starting_index);
     geometry.face_sizes.push(N as u32);
+    geometry.face_normals.push(face_normal(transform, vertices));
 
     if let Some(texmap) = texmap {
         // Lazily initialize the texture info, because we have actual data to insert.
         let texture_info = geometry.texture_info();
         texture_info.indices.push(texmap.texture_index);
-        texture_info.uvs.extend(texmap.uvs);
+        if flip_texture_v {
+            texture_info
+                .uvs
+                .extend(texmap.uvs.map(|uv| Vec2::new(uv.x, 1.0 - uv.y)));
+        } else {
+            texture_info.uvs.extend(texmap.uvs);
+        }
     } else {
         // Avoid initializing the texture info,
         // as we only need to add placeholder data if the buffers are already there.
@@ -527,6 +1308,110 @@ fn add_face<const N: usize>(
     }
 }
 
+/// Pad or truncate the per face and per vertex texture data to match the mesh.
+/// Mismatched lengths would otherwise assign UVs to the wrong vertices.
+fn repair_texture_info(
+    texture_info: &mut LDrawTextureInfo,
+    face_count: usize,
+    vertex_count: usize,
+    name: &str,
+) {
+    if texture_info.indices.len() != face_count || texture_info.uvs.len() != vertex_count {
+        log::warn!(
+            "Texture data for {name:?} has {} face indices and {} UVs but expected {face_count} and {vertex_count}",
+            texture_info.indices.len(),
+            texture_info.uvs.len()
+        );
+        texture_info.indices.resize(face_count, u8::MAX);
+        texture_info.uvs.resize(vertex_count, Vec2::ZERO);
+    }
+}
+
+/// Remove faces with the same vertices in the same winding order as an earlier face.
+/// Faces with the opposite winding are kept since they may be intentionally double sided.
+fn remove_duplicate_faces(geometry: &mut LDrawGeometry) {
+    // Compare positions instead of indices since vertices may not be welded.
+    // Use the same tolerance in LDUs as welding to ignore small differences.
+    let position = |i: &u32| {
+        let v = (geometry.vertices[*i as usize] / 0.01).round();
+        [v.x as i32, v.y as i32, v.z as i32]
+    };
+
+    let mut faces = HashSet::new();
+    let keep: Vec<bool> = (0..geometry.face_sizes.len())
+        .map(|i| {
+            // Rotate to start from the smallest position to ignore the starting vertex.
+            let positions: Vec<_> = geometry.vertex_indices[geometry.face_range(i)]
+                .iter()
+                .map(position)
+                .collect();
+            let start = (0..positions.len())
+                .min_by_key(|i| positions[*i])
+                .unwrap_or(0);
+            let key: Vec<_> = positions[start..]
+                .iter()
+                .chain(&positions[..start])
+                .copied()
+                .collect();
+            faces.insert(key)
+        })
+        .collect();
+
+    if keep.iter().all(|k| *k) {
+        return;
+    }
+
+    let mut vertex_indices = Vec::new();
+    let mut face_start_indices = Vec::new();
+    let mut uvs = Vec::new();
+    for (i, _) in keep.iter().enumerate().filter(|(_, k)| **k) {
+        let range = geometry.face_range(i);
+        face_start_indices.push(vertex_indices.len() as u32);
+        vertex_indices.extend_from_slice(&geometry.vertex_indices[range.clone()]);
+        if let Some(texture_info) = &geometry.texture_info {
+            uvs.extend_from_slice(&texture_info.uvs[range]);
+        }
+    }
+    geometry.vertex_indices = vertex_indices;
+    geometry.face_start_indices = face_start_indices;
+
+    retain_faces(&mut geometry.face_sizes, &keep);
+    retain_faces(&mut geometry.face_normals, &keep);
+    if geometry.face_colors.len() == keep.len() {
+        retain_faces(&mut geometry.face_colors, &keep);
+    }
+    retain_faces(&mut geometry.is_face_stud, &keep);
+    retain_faces(&mut geometry.is_face_double_sided, &keep);
+    if !geometry.face_windings.is_empty() {
+        retain_faces(&mut geometry.face_windings, &keep);
+    }
+    if let Some(texture_info) = &mut geometry.texture_info {
+        retain_faces(&mut texture_info.indices, &keep);
+        texture_info.uvs = uvs;
+    }
+}
+
+fn retain_faces<T>(values: &mut Vec<T>, keep: &[bool]) {
+    let mut i = 0;
+    values.retain(|_| {
+        i += 1;
+        keep[i - 1]
+    });
+}
+
+fn face_normal<const N: usize>(transform: Mat4, vertices: [Vec3; N]) -> Vec3 {
+    // The winding is already reversed for mirrored transforms.
+    // Undo this and transform the normal to avoid relying on the vertex order.
+    let normal = (vertices[1] - vertices[0]).cross(vertices[2] - vertices[0]);
+    let normal = if transform.determinant() < 0.0 {
+        -normal
+    } else {
+        normal
+    };
+    let normal_matrix = Mat3::from_mat4(transform).inverse().transpose();
+    (normal_matrix * normal).normalize_or_zero()
+}
+
 fn insert_vertex(
     geometry: &mut LDrawGeometry,
     transform: Mat4,
@@ -541,6 +1426,7 @@ fn insert_vertex(
         geometry.vertices.push(new_vertex);
         new_index
     } else if let Some(index) = vertex_map.insert(new_index, new_vertex.to_array()) {
+        geometry.welded_vertex_count += 1;
         index
     } else {
         geometry.vertices.push(new_vertex);
@@ -585,6 +1471,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn create_geometry_mpd() {
         let mut source_map = crate::ldraw::SourceMap::new();
 
@@ -641,6 +1528,121 @@ mod tests {
     }
 
     #[test]
+    fn create_geometry_weld_quads() {
+        let mut source_map = crate::ldraw::SourceMap::new();
+
+        // A cube with every edge split should still have quad faces.
+        let document = indoc! {"
+            4 16 0 0 0 1 0 0 1 1 0 0 1 0
+            4 16 0 0 1 0 1 1 1 1 1 1 0 1
+            4 16 0 0 0 0 0 1 1 0 1 1 0 0
+            4 16 0 1 0 1 1 0 1 1 1 0 1 1
+            4 16 0 0 0 0 1 0 0 1 1 0 0 1
+            4 16 1 0 0 1 0 1 1 1 1 1 1 0
+            2 24 0 0 0 1 0 0
+            2 24 1 0 0 1 1 0
+            2 24 1 1 0 0 1 0
+            2 24 0 1 0 0 0 0
+            2 24 0 0 1 1 0 1
+            2 24 1 0 1 1 1 1
+            2 24 1 1 1 0 1 1
+            2 24 0 1 1 0 0 1
+            2 24 0 0 0 0 0 1
+            2 24 1 0 0 1 0 1
+            2 24 1 1 0 1 1 1
+            2 24 0 1 0 0 1 1
+        "};
+
+        let mut resolver = DummyResolver::new();
+        resolver.files.insert("root", document.as_bytes().to_vec());
+
+        let main_model_name = crate::ldraw::parse("root", &resolver, &mut source_map).unwrap();
+        let source_file = source_map.get(&main_model_name).unwrap();
+
+        let geometry = create_geometry(
+            source_file,
+            &source_map,
+            "",
+            16,
+            true,
+            &GeometrySettings {
+                weld_vertices: true,
+                triangulate: false,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(vec![4; 6], geometry.face_sizes);
+        assert_eq!(vec![0, 4, 8, 12, 16, 20], geometry.face_start_indices);
+        assert_eq!(12, geometry.edge_line_indices.len());
+
+        // Each face has its own vertices after splitting every edge.
+        assert_eq!(24, geometry.vertices.len());
+        let mut indices = geometry.vertex_indices.clone();
+        indices.sort();
+        assert_eq!((0..24).collect::<Vec<_>>(), indices);
+    }
+
+    #[test]
+    fn create_geometry_weld_smooth_quads() {
+        // A curved strip of quads with smooth edges between faces.
+        let document = indoc! {"
+            4 16 0 0 0 10 0 0 10 10 0 0 10 0
+            4 16 10 0 0 20 0 3 20 10 3 10 10 0
+            4 16 20 0 3 30 0 9 30 10 9 20 10 3
+        "};
+
+        let create = |document: &str| {
+            let mut source_map = crate::ldraw::SourceMap::new();
+            let mut resolver = DummyResolver::new();
+            resolver.files.insert("root", document.as_bytes().to_vec());
+            let main_model_name = crate::ldraw::parse("root", &resolver, &mut source_map).unwrap();
+            create_geometry(
+                source_map.get(&main_model_name).unwrap(),
+                &source_map,
+                "",
+                16,
+                true,
+                &GeometrySettings {
+                    weld_vertices: true,
+                    triangulate: false,
+                    ..Default::default()
+                },
+            )
+        };
+
+        // Smooth edges stay welded without triangulating the quads.
+        let geometry = create(document);
+        assert_eq!(vec![4; 3], geometry.face_sizes);
+        assert_eq!(8, geometry.vertices.len());
+        assert_eq!(&[0, 1, 2, 3], geometry.face_vertices(0));
+        assert_eq!(&[1, 4, 5, 2], geometry.face_vertices(1));
+        assert_eq!(&[4, 6, 7, 5], geometry.face_vertices(2));
+
+        // Only the edge line between the last two faces is split.
+        let geometry = create(&format!("{document}2 24 20 0 3 20 10 3\n"));
+        assert_eq!(vec![4; 3], geometry.face_sizes);
+        assert_eq!(10, geometry.vertices.len());
+        assert_eq!(&[0, 1, 2, 3], geometry.face_vertices(0));
+        assert_eq!(&[1, 4, 5, 2], geometry.face_vertices(1));
+        let last_face = geometry.face_vertices(2);
+        assert!(!last_face.contains(&4) && !last_face.contains(&5));
+        assert_eq!(
+            geometry.vertices[4],
+            geometry.vertices[last_face[0] as usize]
+        );
+        assert_eq!(
+            geometry.vertices[5],
+            geometry.vertices[last_face[3] as usize]
+        );
+        assert!(geometry
+            .vertex_indices
+            .iter()
+            .all(|i| (*i as usize) < geometry.vertices.len()));
+    }
+
+    #[test]
+    #[allow(clippy::needless_borrow)]
     fn create_geometry_ccw() {
         let mut source_map = crate::ldraw::SourceMap::new();
 
@@ -673,6 +1675,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn create_geometry_cw() {
         let mut source_map = crate::ldraw::SourceMap::new();
 
@@ -705,6 +1708,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn create_geometry_invert_next_determinant() {
         let mut source_map = crate::ldraw::SourceMap::new();
 
@@ -750,7 +1754,1079 @@ mod tests {
         assert_eq!(vec![3, 3, 3, 3], geometry.face_sizes);
     }
 
-    // TODO: Test create geometry with and without welding and triangulate options
-
-    // TODO: Add tests for BFC certified superfiles.
+    #[test]
+    fn create_geometry_record_face_windings() {
+        let mut source_map = crate::ldraw::SourceMap::new();
+
+        let document = indoc! {"
+            0 FILE main.ldr
+            0 BFC CERTIFY CCW
+            3 16 0 0 0 1 0 0 0 1 0
+            0 BFC INVERTNEXT
+            1 16 0 0 0 1 0 0 0 1 0 0 0 1 a.ldr
+            0 BFC CW
+            4 16 0 0 0 1 0 0 1 1 0 0 1 0
+
+            0 FILE a.ldr
+            0 BFC CERTIFY CCW
+            3 16 0 0 0 1 0 0 0 1 0
+        "};
+
+        let mut resolver = DummyResolver::new();
+        resolver.files.insert("root", document.as_bytes().to_vec());
+
+        let main_model_name = crate::ldraw::parse("root", &resolver, &mut source_map).unwrap();
+        let source_file = source_map.get(&main_model_name).unwrap();
+
+        let create = |record_face_windings| {
+            create_geometry(
+                source_file,
+                &source_map,
+                "",
+                16,
+                true,
+                &GeometrySettings {
+                    triangulate: true,
+                    record_face_windings,
+                    ..Default::default()
+                },
+            )
+        };
+
+        // Both triangles of the quad use its winding.
+        let geometry = create(true);
+        assert_eq!(
+            vec![Winding::Ccw, Winding::Cw, Winding::Cw, Winding::Cw],
+            geometry.face_windings
+        );
+        assert!(create(false).face_windings.is_empty());
+    }
+
+    #[test]
+    fn create_geometry_texmap_data_sticker() {
+        let mut source_map = crate::ldraw::SourceMap::new();
+        let cmds = crate::ldraw::parse_raw(crate::ldraw::STICKER_MPD).unwrap();
+        source_map.insert("sticker.mpd", crate::ldraw::SourceFile { cmds });
+
+        let source_file = source_map.get("sticker.ldr").unwrap();
+        let geometry = create_geometry(
+            source_file,
+            &source_map,
+            "sticker.ldr",
+            4,
+            true,
+            &GeometrySettings::default(),
+        );
+
+        // The missing box5.dat is skipped and the quad uses the embedded texture.
+        assert_eq!(vec![4], geometry.face_sizes);
+        let texture_info = geometry.texture_info.unwrap();
+        assert_eq!(vec!["sticker.png".to_string()], texture_info.texture_names);
+        assert_eq!(vec![(80, 120)], texture_info.texture_sizes);
+        assert_eq!(
+            Some(texture_info.textures[0].as_slice()),
+            source_map.data("sticker.png")
+        );
+        assert_eq!(vec![0], texture_info.indices);
+        assert_eq!(
+            vec![
+                Vec2::new(0.0, 1.0),
+                Vec2::new(0.0, 0.0),
+                Vec2::new(1.0, 0.0),
+                Vec2::new(1.0, 1.0)
+            ],
+            texture_info.uvs
+        );
+    }
+
+    #[test]
+    fn create_geometry_texmap_next_fallback() {
+        let mut source_map = crate::ldraw::SourceMap::new();
+
+        let document = indoc! {"
+            0 FILE main.ldr
+            0 !TEXMAP NEXT PLANAR 0 0 0 2 0 0 0 2 0 a.png
+            3 16 0 0 0 2 0 0 0 2 0
+            3 16 0 0 0 2 0 0 0 2 0
+            0 !TEXMAP START PLANAR 0 0 0 2 0 0 0 2 0 a.png
+            4 16 0 0 0 1 0 0 1 1 0 0 1 0
+            0 !TEXMAP FALLBACK
+            4 16 0 0 0 1 0 0 1 1 0 0 1 0
+            0 !TEXMAP END
+            0 !TEXMAP START PLANAR 0 0 0 2 0 0 0 2 0 missing.png
+            3 16 0 0 0 2 0 0 0 2 0
+            0 !TEXMAP FALLBACK
+            3 16 0 0 0 2 0 0 0 2 0
+            0 !TEXMAP END
+
+            0 !DATA a.png
+            0 !: iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR4nGP4z8DwHwAFAAH/iZk9HQAAAABJRU5ErkJggg==
+        "};
+
+        let mut resolver = DummyResolver::new();
+        resolver.files.insert("root", document.as_bytes().to_vec());
+
+        let main_model_name = crate::ldraw::parse("root", &resolver, &mut source_map).unwrap();
+        let source_file = source_map.get(&main_model_name).unwrap();
+
+        let geometry = create_geometry(
+            source_file,
+            &source_map,
+            "",
+            16,
+            true,
+            &GeometrySettings::default(),
+        );
+
+        // Fallback faces are only skipped if the texture could be applied.
+        assert_eq!(vec![3, 3, 4, 3, 3], geometry.face_sizes);
+        let texture_info = geometry.texture_info.unwrap();
+        assert_eq!(vec!["a.png".to_string()], texture_info.texture_names);
+        assert_eq!(vec![0, u8::MAX, 0, u8::MAX, u8::MAX], texture_info.indices);
+        assert_eq!(
+            &[
+                Vec2::new(0.0, 1.0),
+                Vec2::new(1.0, 1.0),
+                Vec2::new(0.0, 0.0)
+            ],
+            &texture_info.uvs[..3]
+        );
+    }
+
+    #[test]
+    fn create_geometry_texmap_geometry() {
+        let mut source_map = crate::ldraw::SourceMap::new();
+
+        let document = indoc! {"
+            0 FILE main.ldr
+            0 !TEXMAP START PLANAR 0 0 0 2 0 0 0 2 0 a.png
+            0 !: 3 16 0 0 0 2 0 0 0 2 0
+            0 !TEXMAP FALLBACK
+            4 16 0 0 0 1 0 0 1 1 0 0 1 0
+            0 !TEXMAP END
+            0 !TEXMAP START PLANAR 0 0 0 2 0 0 0 2 0 missing.png
+            0 !: 3 16 0 0 0 2 0 0 0 2 0
+            0 !TEXMAP FALLBACK
+            4 16 0 0 0 1 0 0 1 1 0 0 1 0
+            0 !TEXMAP END
+            0 !TEXMAP NEXT PLANAR 0 0 0 2 0 0 0 2 0 a.png
+            0 !: 3 16 0 0 0 2 0 0 0 2 0
+            3 16 0 0 0 2 0 0 0 2 0
+            0 !: 3 16 0 0 0 2 0 0 0 2 0
+
+            0 !DATA a.png
+            0 !: iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR4nGP4z8DwHwAFAAH/iZk9HQAAAABJRU5ErkJggg==
+        "};
+
+        let mut resolver = DummyResolver::new();
+        resolver.files.insert("root", document.as_bytes().to_vec());
+
+        let main_model_name = crate::ldraw::parse("root", &resolver, &mut source_map).unwrap();
+        let source_file = source_map.get(&main_model_name).unwrap();
+
+        let geometry = create_geometry(
+            source_file,
+            &source_map,
+            "",
+            16,
+            true,
+            &GeometrySettings::default(),
+        );
+
+        // Textured geometry replaces the fallback only if the texture could be applied.
+        // Lines with "0 !:" outside a !TEXMAP block are not geometry.
+        assert_eq!(vec![3, 4, 3, 3], geometry.face_sizes);
+        let texture_info = geometry.texture_info.unwrap();
+        assert_eq!(vec![0, u8::MAX, 0, u8::MAX], texture_info.indices);
+    }
+
+    #[test]
+    fn create_geometry_flip_texture_v() {
+        let mut source_map = crate::ldraw::SourceMap::new();
+
+        let document = indoc! {"
+            0 PE_TEX_PATH -1
+            0 PE_TEX_INFO YWJj
+            3 16 1 0 0 0 1 0 0 0 1 0 0.25 1 0.25 0 1
+        "};
+
+        let mut resolver = DummyResolver::new();
+        resolver.files.insert("root", document.as_bytes().to_vec());
+
+        let main_model_name = crate::ldraw::parse("root", &resolver, &mut source_map).unwrap();
+        let source_file = source_map.get(&main_model_name).unwrap();
+
+        let geometry = create_geometry(
+            source_file,
+            &source_map,
+            "",
+            16,
+            true,
+            &GeometrySettings {
+                flip_texture_v: true,
+                ..Default::default()
+            },
+        );
+
+        let texture_info = geometry.texture_info.unwrap();
+        assert_eq!(vec![0], texture_info.indices);
+        assert_eq!(
+            vec![
+                Vec2::new(0.0, 0.75),
+                Vec2::new(1.0, 0.75),
+                Vec2::new(0.0, 0.0)
+            ],
+            texture_info.uvs
+        );
+    }
+
+    #[test]
+    fn create_geometry_overlapping_textures() {
+        let mut source_map = crate::ldraw::SourceMap::new();
+
+        // Faces use the most recent texture that intersects the face.
+        let document = indoc! {"
+            0 PE_TEX_PATH -1
+            0 PE_TEX_INFO 0 0 0 30 0 0 0 30 0 0 0 30 -1 -1 1 1 YWJj
+            0 PE_TEX_PATH -1
+            0 PE_TEX_INFO 10 0 0 2 0 0 0 2 0 0 0 2 -1 -1 1 1 YWJj
+            0 PE_TEX_PATH -1
+            0 PE_TEX_INFO 0 0 0 2 0 0 0 2 0 0 0 2 -1 -1 1 1 YWJj
+            3 16 -0.5 0 -0.5 0.5 0 -0.5 0 0 0.5
+            3 16 9.5 0 -0.5 10.5 0 -0.5 10 0 0.5
+            3 16 11.5 0 -0.5 12.5 0 -0.5 12 0 0.5
+            3 16 49.5 0 -0.5 50.5 0 -0.5 50 0 0.5
+        "};
+
+        let mut resolver = DummyResolver::new();
+        resolver.files.insert("root", document.as_bytes().to_vec());
+
+        let main_model_name = crate::ldraw::parse("root", &resolver, &mut source_map).unwrap();
+        let source_file = source_map.get(&main_model_name).unwrap();
+
+        let geometry = create_geometry(
+            source_file,
+            &source_map,
+            "",
+            16,
+            true,
+            &GeometrySettings::default(),
+        );
+
+        let texture_info = geometry.texture_info.unwrap();
+        assert_eq!(3, texture_info.textures.len());
+        assert_eq!(vec![None; 3], texture_info.glossmaps);
+        assert_eq!(vec![2, 1, 0, u8::MAX], texture_info.indices);
+        assert_eq!(12, texture_info.uvs.len());
+    }
+
+    #[test]
+    fn create_geometry_texture_paths() {
+        let mut source_map = crate::ldraw::SourceMap::new();
+
+        // The texture should only apply to the second subfile of the first subfile.
+        let document = indoc! {"
+            0 FILE main.ldr
+            0 PE_TEX_PATH 0 1
+            0 PE_TEX_INFO YWJj
+            1 16 0 0 0 1 0 0 0 1 0 0 0 1 a.ldr
+            1 16 0 0 0 1 0 0 0 1 0 0 0 1 b.ldr
+
+            0 FILE a.ldr
+            1 16 0 0 0 1 0 0 0 1 0 0 0 1 c.ldr
+            1 16 0 0 0 1 0 0 0 1 0 0 0 1 b.ldr
+            3 16 1 0 0 0 1 0 0 0 1 0 0 1 0 0 1
+
+            0 FILE c.ldr
+            0 PE_TEX_PATH -1
+            0 PE_TEX_INFO YWJj
+
+            0 FILE b.ldr
+            3 16 1 0 0 0 1 0 0 0 1 0 0 1 0 0 1
+        "};
+
+        let mut resolver = DummyResolver::new();
+        resolver.files.insert("root", document.as_bytes().to_vec());
+
+        let main_model_name = crate::ldraw::parse("root", &resolver, &mut source_map).unwrap();
+        let source_file = source_map.get(&main_model_name).unwrap();
+
+        let geometry = create_geometry(
+            source_file,
+            &source_map,
+            "main.ldr",
+            16,
+            true,
+            &GeometrySettings::default(),
+        );
+
+        let texture_info = geometry.texture_info.unwrap();
+        assert_eq!(vec![0, u8::MAX, u8::MAX], texture_info.indices);
+        assert_eq!(
+            vec!["main.ldr.0.png".to_string(), "c.ldr.0.png".to_string()],
+            texture_info.texture_names
+        );
+    }
+
+    #[test]
+    fn create_geometry_texture_path_current_file() {
+        let mut source_map = crate::ldraw::SourceMap::new();
+
+        let document = indoc! {"
+            0 FILE main.ldr
+            0 PE_TEX_PATH 0 -1
+            0 PE_TEX_INFO YWJj
+            0 PE_TEX_PATH -1
+            0 PE_TEX_INFO YWJj
+            1 16 0 0 0 1 0 0 0 1 0 0 0 1 a.ldr
+            3 16 1 0 0 0 1 0 0 0 1 0 0 1 0 0 1
+
+            0 FILE a.ldr
+            3 16 1 0 0 0 1 0 0 0 1 0 0 1 0 0 1
+        "};
+
+        let mut resolver = DummyResolver::new();
+        resolver.files.insert("root", document.as_bytes().to_vec());
+
+        let main_model_name = crate::ldraw::parse("root", &resolver, &mut source_map).unwrap();
+        let source_file = source_map.get(&main_model_name).unwrap();
+
+        let geometry = create_geometry(
+            source_file,
+            &source_map,
+            "",
+            16,
+            true,
+            &GeometrySettings::default(),
+        );
+
+        // The subfile prefers its own texture over the inherited texture.
+        let texture_info = geometry.texture_info.unwrap();
+        assert_eq!(vec![0, 1], texture_info.indices);
+    }
+
+    #[test]
+    fn create_geometry_triangulate_face_flags() {
+        let mut source_map = crate::ldraw::SourceMap::new();
+
+        // Each quad becomes two triangles with the same color and stud flag.
+        let document = indoc! {"
+            1 4 0 0 0 1 0 0 0 1 0 0 0 1 stud.dat
+            4 1 0 0 0 1 0 0 1 0 1 0 0 1
+        "};
+        let stud = indoc! {"
+            4 16 0 0 0 1 0 0 1 1 0 0 1 0
+        "};
+
+        let mut resolver = DummyResolver::new();
+        resolver.files.insert("root", document.as_bytes().to_vec());
+        resolver.files.insert("stud.dat", stud.as_bytes().to_vec());
+
+        let main_model_name = crate::ldraw::parse("root", &resolver, &mut source_map).unwrap();
+        let source_file = source_map.get(&main_model_name).unwrap();
+
+        for weld_vertices in [false, true] {
+            let geometry = create_geometry(
+                source_file,
+                &source_map,
+                "",
+                16,
+                true,
+                &GeometrySettings {
+                    triangulate: true,
+                    weld_vertices,
+                    ..Default::default()
+                },
+            );
+
+            assert_eq!(vec![3; 4], geometry.face_sizes);
+            assert_eq!(vec![4, 4, 1, 1], geometry.face_colors);
+            assert_eq!(vec![true, true, false, false], geometry.is_face_stud);
+            assert_eq!(vec![false; 4], geometry.is_face_double_sided);
+            assert_eq!(4, geometry.face_normals.len());
+        }
+    }
+
+    #[test]
+    fn create_geometry_non_planar_quad() {
+        let mut source_map = crate::ldraw::SourceMap::new();
+
+        // The second quad has one corner raised and is split along the shorter diagonal.
+        let document = indoc! {"
+            4 16 0 0 0 1 0 0 1 0 1 0 0 1
+            4 16 0 0 0 1 0 0 2 0 2 0 1 1
+        "};
+
+        let mut resolver = DummyResolver::new();
+        resolver.files.insert("root", document.as_bytes().to_vec());
+
+        let main_model_name = crate::ldraw::parse("root", &resolver, &mut source_map).unwrap();
+        let source_file = source_map.get(&main_model_name).unwrap();
+
+        let geometry = create_geometry(
+            source_file,
+            &source_map,
+            "",
+            16,
+            true,
+            &GeometrySettings::default(),
+        );
+
+        assert_eq!(vec![4, 3, 3], geometry.face_sizes);
+        assert_eq!(vec![0, 4, 7], geometry.face_start_indices);
+        assert_eq!(
+            vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 1.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(2.0, 0.0, 2.0),
+                Vec3::new(0.0, 1.0, 1.0),
+            ],
+            geometry.vertex_indices[4..]
+                .iter()
+                .map(|i| geometry.vertices[*i as usize])
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(vec![false; 3], geometry.is_face_stud);
+    }
+
+    #[test]
+    fn create_geometry_double_side_uncertified() {
+        let mut source_map = crate::ldraw::SourceMap::new();
+
+        // Only faces from uncertified files or subfiles of uncertified files are double sided.
+        let document = indoc! {"
+            0 FILE main.ldr
+            0 BFC CERTIFY CCW
+            3 16 1 0 0 0 1 0 0 0 1
+            1 16 0 0 0 1 0 0 0 1 0 0 0 1 a.ldr
+            1 16 0 0 0 1 0 0 0 1 0 0 0 1 b.ldr
+
+            0 FILE a.ldr
+            0 BFC CERTIFY CCW
+            3 16 1 0 0 0 1 0 0 0 1
+
+            0 FILE b.ldr
+            3 16 1 0 0 0 1 0 0 0 1
+            1 16 0 0 0 1 0 0 0 1 0 0 0 1 a.ldr
+        "};
+
+        let mut resolver = DummyResolver::new();
+        resolver.files.insert("root", document.as_bytes().to_vec());
+
+        let main_model_name = crate::ldraw::parse("root", &resolver, &mut source_map).unwrap();
+        let source_file = source_map.get(&main_model_name).unwrap();
+
+        let geometry = create_geometry(
+            source_file,
+            &source_map,
+            "",
+            16,
+            true,
+            &GeometrySettings {
+                double_side_uncertified: true,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            vec![false, false, true, true],
+            geometry.is_face_double_sided
+        );
+    }
+
+    #[test]
+    fn is_stud_names() {
+        let settings = GeometrySettings::default();
+        let file = |document: &str| crate::ldraw::SourceFile {
+            cmds: crate::ldraw::parse_raw(document.as_bytes()).unwrap(),
+        };
+        let primitive = file("0 !LDRAW_ORG Primitive UPDATE 2012-01\n");
+        let part = file("0 !LDRAW_ORG Part UPDATE 2004-01\n");
+
+        assert!(is_stud("stud.dat", Some(&primitive), &settings));
+        assert!(is_stud("48\\Stud4a.dat", None, &settings));
+        assert!(is_stud("stud2-logo4.dat", None, &settings));
+        assert!(!is_stud("stud.dat", Some(&part), &settings));
+        assert!(!is_stud("3068bstu.dat", None, &settings));
+        assert!(!is_stud("stud-box.ldr", None, &settings));
+
+        let settings = GeometrySettings {
+            stud_substring_fallback: true,
+            ..Default::default()
+        };
+        assert!(is_stud("3068bstu.dat", None, &settings));
+    }
+
+    #[test]
+    fn create_geometry_gap_excludes_flexible() {
+        let source_map = crate::ldraw::SourceMap::new();
+        let settings = GeometrySettings {
+            add_gap_between_parts: true,
+            ..Default::default()
+        };
+        let dimensions = |document: &str| {
+            let source_file = crate::ldraw::SourceFile {
+                cmds: crate::ldraw::parse_raw(document.as_bytes()).unwrap(),
+            };
+            let geometry = create_geometry(&source_file, &source_map, "", 16, false, &settings);
+            let min = geometry.vertices.iter().copied().reduce(Vec3::min).unwrap();
+            let max = geometry.vertices.iter().copied().reduce(Vec3::max).unwrap();
+            max - min
+        };
+
+        let rope = dimensions(indoc! {"
+            0 Rope String  2L
+            0 !CATEGORY String
+            3 16 0 0 0 40 0 0 0 0 40
+        "});
+        assert_eq!(Vec3::new(40.0, 0.0, 40.0), rope);
+
+        let brick = dimensions(indoc! {"
+            0 Brick  2 x  4
+            3 16 0 0 0 40 0 0 0 0 40
+        "});
+        assert!(brick.x < 40.0 && brick.z < 40.0);
+    }
+
+    #[test]
+    fn create_geometry_slope_category() {
+        let source_map = crate::ldraw::SourceMap::new();
+        let has_grainy_slopes = |name: &str, document: &str| {
+            let source_file = crate::ldraw::SourceFile {
+                cmds: crate::ldraw::parse_raw(document.as_bytes()).unwrap(),
+            };
+            create_geometry(
+                &source_file,
+                &source_map,
+                name,
+                16,
+                false,
+                &GeometrySettings::default(),
+            )
+            .has_grainy_slopes
+        };
+
+        // Parts not in the list are slopes based on their category.
+        let slope = indoc! {"
+            0 Slope Brick 33  3 x  1
+            0 !CATEGORY Slope
+            3 16 0 0 0 40 0 0 0 0 40
+        "};
+        assert!(has_grainy_slopes("99999.dat", slope));
+        assert!(!has_grainy_slopes("model.ldr", slope));
+        assert!(has_grainy_slopes("3039.dat", "3 16 0 0 0 40 0 0 0 0 40"));
+        assert!(!has_grainy_slopes(
+            "3001.dat",
+            "0 Brick  2 x  4\n3 16 0 0 0 40 0 0 0 0 40"
+        ));
+    }
+
+    #[test]
+    fn create_geometry_weld_excluded_parts() {
+        let source_file = crate::ldraw::SourceFile {
+            cmds: crate::ldraw::parse_raw(indoc! {b"
+                0 Hose Flexible  8.5L
+                3 16 0 0 0 1 0 0 0 0 1
+                3 16 1 0 0 0 0 1 1 0 1
+            "})
+            .unwrap(),
+        };
+        let vertex_count = |name: &str, settings: &GeometrySettings| {
+            create_geometry(
+                &source_file,
+                &crate::ldraw::SourceMap::new(),
+                name,
+                16,
+                false,
+                settings,
+            )
+            .vertices
+            .len()
+        };
+
+        let weld = GeometrySettings {
+            weld_vertices: true,
+            ..Default::default()
+        };
+        assert_eq!(4, vertex_count("73590a.dat", &weld));
+
+        let exclude_parts = GeometrySettings {
+            weld_excluded_parts: HashSet::from(["73590a.dat".to_string()]),
+            ..weld
+        };
+        assert_eq!(6, vertex_count("73590A.dat", &exclude_parts));
+        assert_eq!(4, vertex_count("3001.dat", &exclude_parts));
+
+        let exclude_flexible = GeometrySettings {
+            weld_excludes_flexible: true,
+            ..exclude_parts
+        };
+        assert_eq!(6, vertex_count("3001.dat", &exclude_flexible));
+    }
+
+    #[test]
+    fn create_geometry_gap_distance() {
+        let source_file = crate::ldraw::SourceFile {
+            cmds: crate::ldraw::parse_raw(b"3 16 0 0 0 40 -24 0 0 0 40").unwrap(),
+        };
+        let settings = GeometrySettings {
+            add_gap_between_parts: true,
+            gap_distance: Vec3::new(1.0, 0.0, 2.0),
+            ..Default::default()
+        };
+        let geometry = create_geometry(
+            &source_file,
+            &crate::ldraw::SourceMap::new(),
+            "",
+            16,
+            false,
+            &settings,
+        );
+
+        assert_eq!(Vec3::new(38.0, -24.0, 0.0), geometry.vertices[1]);
+        assert_eq!(Vec3::new(0.0, 0.0, 36.0), geometry.vertices[2]);
+    }
+
+    #[test]
+    fn create_geometry_remove_duplicate_faces() {
+        let document = indoc! {"
+            3 16 0 0 0 1 0 0 0 1 0
+            3 16 1 0 0 0 1 0 0 0 0
+            3 16 0 0 0 0 1 0 1 0 0
+        "};
+        let source_file = crate::ldraw::SourceFile {
+            cmds: crate::ldraw::parse_raw(document.as_bytes()).unwrap(),
+        };
+
+        let create = |remove_duplicate_faces| {
+            create_geometry(
+                &source_file,
+                &crate::ldraw::SourceMap::new(),
+                "",
+                16,
+                false,
+                &GeometrySettings {
+                    remove_duplicate_faces,
+                    ..Default::default()
+                },
+            )
+        };
+
+        assert_eq!(3, create(false).face_sizes.len());
+
+        // The reversed triangle is kept.
+        let geometry = create(true);
+        assert_eq!(vec![3, 3], geometry.face_sizes);
+        assert_eq!(vec![0, 3], geometry.face_start_indices);
+        assert_eq!(vec![0, 1, 2, 6, 7, 8], geometry.vertex_indices);
+        assert_eq!(2, geometry.face_normals.len());
+        assert_eq!(vec![false, false], geometry.is_face_stud);
+    }
+
+    #[test]
+    fn create_geometry_welded_vertex_count() {
+        let document = indoc! {"
+            3 16 0 0 0 1 0 0 0 1 0
+            3 16 1 0 0 1 1 0 0 1 0
+        "};
+        let source_file = crate::ldraw::SourceFile {
+            cmds: crate::ldraw::parse_raw(document.as_bytes()).unwrap(),
+        };
+
+        let create = |weld_vertices| {
+            create_geometry(
+                &source_file,
+                &crate::ldraw::SourceMap::new(),
+                "",
+                16,
+                true,
+                &GeometrySettings {
+                    weld_vertices,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let geometry = create(true);
+        assert_eq!(4, geometry.vertices.len());
+        assert_eq!(2, geometry.welded_vertex_count);
+
+        let geometry = create(false);
+        assert_eq!(6, geometry.vertices.len());
+        assert_eq!(0, geometry.welded_vertex_count);
+    }
+
+    #[test]
+    fn create_geometry_weld_stud_base() {
+        let mut source_map = crate::ldraw::SourceMap::new();
+
+        // The base of the stud side touches the corners of the top face.
+        let document = indoc! {"
+            4 16 -10 0 -10 10 0 -10 10 0 10 -10 0 10
+            1 16 0 0 0 1 0 0 0 1 0 0 0 1 stud.dat
+        "};
+        let stud = indoc! {"
+            4 16 -10 0 -10 10 0 -10 10 -4 -10 -10 -4 -10
+        "};
+
+        let mut resolver = DummyResolver::new();
+        resolver.files.insert("root", document.as_bytes().to_vec());
+        resolver.files.insert("stud.dat", stud.as_bytes().to_vec());
+
+        let main_model_name = crate::ldraw::parse("root", &resolver, &mut source_map).unwrap();
+        let source_file = source_map.get(&main_model_name).unwrap();
+
+        let create = |weld_vertices| {
+            create_geometry(
+                source_file,
+                &source_map,
+                "",
+                16,
+                true,
+                &GeometrySettings {
+                    weld_vertices,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let geometry = create(true);
+        assert_eq!(vec![false, true], geometry.is_face_stud);
+        assert_eq!(6, geometry.vertices.len());
+        assert_eq!(&[0, 1, 2, 3], geometry.face_vertices(0));
+        assert_eq!(&[0, 1, 4, 5], geometry.face_vertices(1));
+
+        let geometry = create(false);
+        assert_eq!(8, geometry.vertices.len());
+        assert_eq!(&[4, 5, 6, 7], geometry.face_vertices(1));
+    }
+
+    #[test]
+    fn geometry_faces() {
+        let document = indoc! {"
+            3 4 0 0 0 1 0 0 0 1 0
+            4 16 0 0 0 1 0 0 1 1 0 0 1 0
+        "};
+        let source_file = crate::ldraw::SourceFile {
+            cmds: crate::ldraw::parse_raw(document.as_bytes()).unwrap(),
+        };
+
+        let geometry = create_geometry(
+            &source_file,
+            &crate::ldraw::SourceMap::new(),
+            "",
+            1,
+            true,
+            &GeometrySettings::default(),
+        );
+
+        let faces: Vec<_> = geometry.faces().collect();
+        assert_eq!(2, faces.len());
+        assert_eq!(&[0, 1, 2], faces[0].vertex_indices);
+        assert_eq!(4, faces[0].color);
+        assert_eq!(&[3, 4, 5, 6], faces[1].vertex_indices);
+        assert_eq!(1, faces[1].color);
+        assert!(!faces[1].is_stud);
+        assert_eq!(None, faces[1].texture_index);
+        assert_eq!(None, faces[1].uvs);
+        assert_eq!(&[3, 4, 5, 6], geometry.face_vertices(1));
+    }
+
+    #[test]
+    fn create_geometry_edge_colors() {
+        let document = indoc! {"
+            3 16 0 0 0 1 0 0 0 1 0
+            2 24 0 0 0 1 0 0
+            2 16 1 0 0 0 1 0
+            2 0 0 1 0 0 0 0
+        "};
+        let source_file = crate::ldraw::SourceFile {
+            cmds: crate::ldraw::parse_raw(document.as_bytes()).unwrap(),
+        };
+
+        let geometry = create_geometry(
+            &source_file,
+            &crate::ldraw::SourceMap::new(),
+            "",
+            4,
+            true,
+            &GeometrySettings {
+                weld_vertices: true,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(vec![24, 4, 0], geometry.edge_line_colors);
+        assert_eq!(
+            geometry.edge_line_indices.len(),
+            geometry.edge_line_colors.len()
+        );
+    }
+
+    #[test]
+    fn create_geometry_optional_lines() {
+        let document = indoc! {"
+            3 16 0 0 0 1 0 0 0 1 0
+            3 16 1 0 0 1 1 1 0 1 0
+            5 24 1 0 0 0 1 0 0 0 0 1 1 1
+        "};
+        let source_file = crate::ldraw::SourceFile {
+            cmds: crate::ldraw::parse_raw(document.as_bytes()).unwrap(),
+        };
+
+        let geometry = create_geometry(
+            &source_file,
+            &crate::ldraw::SourceMap::new(),
+            "",
+            16,
+            true,
+            &GeometrySettings {
+                weld_vertices: true,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(vec![([1, 2], [0, 3])], geometry.optional_line_edges);
+    }
+
+    #[test]
+    fn create_geometry_mirrored_normals() {
+        let mut source_map = crate::ldraw::SourceMap::new();
+
+        // The mirrored triangle should face the same direction.
+        let document = indoc! {"
+            0 FILE main.ldr
+            0 BFC CERTIFY CCW
+            1 16 0 0 0 1 0 0 0 1 0 0 0 1 a.ldr
+            1 16 0 0 0 -1 0 0 0 1 0 0 0 1 a.ldr
+
+            0 FILE a.ldr
+            0 BFC CERTIFY CCW
+            3 16 0 0 0 1 0 0 0 1 0
+        "};
+
+        let mut resolver = DummyResolver::new();
+        resolver.files.insert("root", document.as_bytes().to_vec());
+
+        let main_model_name = crate::ldraw::parse("root", &resolver, &mut source_map).unwrap();
+        let source_file = source_map.get(&main_model_name).unwrap();
+
+        let geometry = create_geometry(
+            source_file,
+            &source_map,
+            "",
+            16,
+            true,
+            &GeometrySettings::default(),
+        );
+
+        assert_eq!(vec![Vec3::Z, Vec3::Z], geometry.face_normals);
+        assert_eq!(
+            geometry.face_normals,
+            crate::normal::face_normals(
+                &geometry.vertices,
+                &geometry.vertex_indices,
+                &geometry.face_start_indices,
+                &geometry.face_sizes
+            )
+        );
+    }
+
+    // TODO: Test create geometry with and without welding and triangulate options
+
+    #[test]
+    fn create_geometry_bfc_inline_faces() {
+        let mut source_map = crate::ldraw::SourceMap::new();
+
+        // The CW winding of the subfile should not apply to faces after the reference.
+        // Faces in the uncertified model and after NOCLIP can't be culled.
+        let document = indoc! {"
+            0 FILE certified.ldr
+            0 BFC CERTIFY CCW
+            1 16 0 0 0 1 0 0 0 1 0 0 0 1 cw.ldr
+            3 16 0 0 0 1 0 0 0 1 0
+            0 BFC NOCLIP
+            3 16 0 0 0 1 0 0 0 1 0
+            0 BFC CLIP
+            3 16 0 0 0 1 0 0 0 1 0
+
+            0 FILE uncertified.ldr
+            1 16 0 0 0 1 0 0 0 1 0 0 0 1 cw.ldr
+            3 16 0 0 0 1 0 0 0 1 0
+            0 BFC CLIP
+            3 16 0 0 0 1 0 0 0 1 0
+
+            0 FILE cw.ldr
+            0 BFC CERTIFY CW
+            3 16 0 0 0 1 0 0 0 1 0
+        "};
+
+        let mut resolver = DummyResolver::new();
+        resolver.files.insert("root", document.as_bytes().to_vec());
+        crate::ldraw::parse("root", &resolver, &mut source_map).unwrap();
+
+        let settings = GeometrySettings {
+            double_side_uncertified: true,
+            ..Default::default()
+        };
+        let create = |name| {
+            let source_file = source_map.get(name).unwrap();
+            create_geometry(source_file, &source_map, name, 16, true, &settings)
+        };
+
+        let certified = create("certified.ldr");
+        assert_eq!(
+            vec![Vec3::NEG_Z, Vec3::Z, Vec3::Z, Vec3::Z],
+            certified.face_normals
+        );
+        assert_eq!(
+            vec![false, false, true, false],
+            certified.is_face_double_sided
+        );
+
+        // Certified subfiles of uncertified files are also double sided.
+        // CLIP does not certify the file.
+        let uncertified = create("uncertified.ldr");
+        assert_eq!(
+            vec![Vec3::NEG_Z, Vec3::Z, Vec3::Z],
+            uncertified.face_normals
+        );
+        assert_eq!(vec![true; 3], uncertified.is_face_double_sided);
+    }
+
+    #[test]
+    fn repair_texture_info_lengths() {
+        let mut texture_info = LDrawTextureInfo::new(1, 3);
+        texture_info.indices.push(0);
+        texture_info.uvs.push(Vec2::ONE);
+
+        repair_texture_info(&mut texture_info, 3, 9, "");
+        assert_eq!(vec![u8::MAX, 0, u8::MAX], texture_info.indices);
+        assert_eq!(9, texture_info.uvs.len());
+        assert_eq!(Vec2::ONE, texture_info.uvs[3]);
+        assert_eq!(Vec2::ZERO, texture_info.uvs[8]);
+
+        repair_texture_info(&mut texture_info, 2, 6, "");
+        assert_eq!(vec![u8::MAX, 0], texture_info.indices);
+        assert_eq!(6, texture_info.uvs.len());
+    }
+
+    fn triangle(color: ColorCode) -> LDrawGeometry {
+        let source_file = crate::ldraw::SourceFile {
+            cmds: crate::ldraw::parse_raw(b"3 16 0 0 0 1 0 0 0 1 0\n2 24 0 0 0 1 0 0").unwrap(),
+        };
+        create_geometry(
+            &source_file,
+            &crate::ldraw::SourceMap::new(),
+            "",
+            color,
+            true,
+            &GeometrySettings {
+                weld_vertices: true,
+                calculate_mesh_edges: true,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn create_geometry_mesh_edges_disabled() {
+        let source_file = crate::ldraw::SourceFile {
+            cmds: crate::ldraw::parse_raw(b"3 16 0 0 0 1 0 0 0 1 0\n2 24 0 0 0 1 0 0").unwrap(),
+        };
+        let geometry = create_geometry(
+            &source_file,
+            &crate::ldraw::SourceMap::new(),
+            "",
+            4,
+            true,
+            &GeometrySettings {
+                weld_vertices: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(vec![[0, 1]], geometry.edge_line_indices);
+        assert!(geometry.mesh_edge_indices.is_empty());
+        assert!(geometry.is_mesh_edge_sharp.is_empty());
+    }
+
+    #[test]
+    fn geometry_merge_same_color() {
+        let mut geometry = triangle(4);
+        geometry.merge(&triangle(4), Mat4::from_translation(Vec3::X));
+
+        assert_eq!(6, geometry.vertices.len());
+        assert_eq!(Vec3::new(2.0, 0.0, 0.0), geometry.vertices[4]);
+        assert_eq!(vec![0, 1, 2, 3, 4, 5], geometry.vertex_indices);
+        assert_eq!(vec![0, 3], geometry.face_start_indices);
+        assert_eq!(vec![4], geometry.face_colors);
+        assert_eq!(vec![[0, 1], [3, 4]], geometry.edge_line_indices);
+        assert_eq!(6, geometry.mesh_edge_indices.len());
+    }
+
+    #[test]
+    fn geometry_merge_mirrored() {
+        let mut geometry = triangle(4);
+        geometry.merge(&triangle(1), Mat4::from_scale(Vec3::new(-1.0, 1.0, 1.0)));
+
+        assert_eq!(vec![4, 1], geometry.face_colors);
+        assert_eq!(&[5, 4, 3], geometry.face_vertices(1));
+        assert_eq!(geometry.face_normals[0], geometry.face_normals[1]);
+    }
+
+    #[test]
+    fn geometry_merge_textures() {
+        let textured = |name: &str| {
+            let mut geometry = triangle(4);
+            let texture_info = geometry.texture_info();
+            texture_info.textures.push(name.as_bytes().to_vec());
+            texture_info.texture_sizes.push((1, 1));
+            texture_info.texture_names.push(name.to_string());
+            texture_info.glossmaps.push(None);
+            texture_info.indices = vec![0];
+            geometry
+        };
+
+        // Instances of the same part reuse the same texture.
+        let mut geometry = textured("a.png");
+        geometry.merge(&textured("a.png"), Mat4::IDENTITY);
+        geometry.merge(&textured("b.png"), Mat4::IDENTITY);
+        let texture_info = geometry.texture_info.as_ref().unwrap();
+        assert_eq!(vec!["a.png", "b.png"], texture_info.texture_names);
+        assert_eq!(vec![0, 0, 1], texture_info.indices);
+
+        // Faces don't use the wrong texture when there are too many textures.
+        let mut geometry = triangle(4);
+        for i in 0..256 {
+            geometry.merge(&textured(&format!("{i}.png")), Mat4::IDENTITY);
+        }
+        let texture_info = geometry.texture_info.as_ref().unwrap();
+        assert_eq!(255, texture_info.textures.len());
+        assert_eq!(254, texture_info.indices[255]);
+        assert_eq!(u8::MAX, texture_info.indices[256]);
+    }
+
+    #[test]
+    fn geometry_transform() {
+        let mut geometry = triangle(4);
+        let normal = geometry.face_normals[0];
+        let rotation = Mat4::from_rotation_x(std::f32::consts::FRAC_PI_2);
+        geometry.transform(rotation);
+
+        assert!(geometry.vertices[2].abs_diff_eq(Vec3::Z, 1e-6));
+        assert!(geometry.face_normals[0].abs_diff_eq(rotation.transform_vector3(normal), 1e-6));
+        assert_eq!(&[0, 1, 2], geometry.face_vertices(0));
+    }
+
+    #[test]
+    fn geometry_sharp_edges() {
+        let mut geometry = triangle(4);
+        assert_eq!(vec![[0, 1]], geometry.sharp_edges());
+
+        geometry.mesh_edge_indices = vec![[2, 1], [1, 0], [0, 2], [1, 2]];
+        geometry.is_mesh_edge_sharp = vec![true, true, false, true];
+        assert_eq!(vec![[0, 1], [1, 2]], geometry.sharp_edges());
+    }
 }
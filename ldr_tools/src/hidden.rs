@@ -0,0 +1,218 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use glam::{Mat4, Vec3};
+use rstar::{
+    primitives::{GeomWithData, Rectangle},
+    Envelope, RTree, RTreeObject, AABB,
+};
+
+use crate::{ColorCode, Face, LDrawGeometry};
+
+/// Appended to geometry names for copies of geometry with stud faces removed.
+pub const HIDDEN_STUDS_SUFFIX: &str = "#hidden_studs";
+
+// The gap between parts may leave studs slightly outside the covering part.
+const TOLERANCE_LDU: f32 = 1.0;
+
+/// Replace instances with studs enclosed by the bounds of another part
+/// with a copy of the geometry without stud faces.
+///
+/// This uses bounding boxes and may remove studs under parts with openings like arches.
+/// Parts whose bounds are entirely enclosed by another part are never changed
+/// to avoid removing studs from duplicate parts in the same location.
+pub fn remove_hidden_studs(
+    geometry_world_transforms: &mut BTreeMap<(String, ColorCode), Vec<Mat4>>,
+    geometry_cache: &mut BTreeMap<String, LDrawGeometry>,
+    scene_scale: f32,
+) {
+    let tolerance = TOLERANCE_LDU * scene_scale;
+
+    let bounds: HashMap<_, _> = geometry_cache
+        .iter()
+        .filter_map(|(name, geometry)| {
+            let part = bounds(geometry, |_| true)?;
+            let studs = bounds(geometry, |f| f.is_stud);
+            Some((name.clone(), (part, studs)))
+        })
+        .collect();
+
+    // Find the bounds of every instance in world space.
+    let mut instances = Vec::new();
+    for ((name, color), transforms) in geometry_world_transforms.iter() {
+        if let Some((part, studs)) = bounds.get(name) {
+            for (i, transform) in transforms.iter().enumerate() {
+                let part = world_bounds(part, transform);
+                let studs = studs.map(|s| world_bounds(&s, transform));
+                instances.push(((name.clone(), *color), i, part, studs));
+            }
+        }
+    }
+
+    let tree = RTree::bulk_load(
+        instances
+            .iter()
+            .enumerate()
+            .map(|(id, (_, _, [min, max], _))| {
+                let min = (*min - tolerance).to_array();
+                let max = (*max + tolerance).to_array();
+                GeomWithData::new(Rectangle::from_corners(min, max), id)
+            })
+            .collect(),
+    );
+
+    let mut hidden: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for (id, (key, i, [min, max], studs)) in instances.iter().enumerate() {
+        let Some([stud_min, stud_max]) = studs else {
+            continue;
+        };
+        let part = AABB::from_corners(min.to_array(), max.to_array());
+        let studs = AABB::from_corners(stud_min.to_array(), stud_max.to_array());
+
+        let is_hidden = tree.locate_in_envelope_intersecting(&studs).any(|other| {
+            let envelope = other.geom().envelope();
+            other.data != id
+                && envelope.contains_envelope(&studs)
+                && !envelope.contains_envelope(&part)
+        });
+        if is_hidden {
+            hidden.entry(key.clone()).or_default().push(*i);
+        }
+    }
+
+    for ((name, color), indices) in hidden {
+        let indices: HashSet<_> = indices.into_iter().collect();
+        let hidden_name = format!("{name}{HIDDEN_STUDS_SUFFIX}");
+        if !geometry_cache.contains_key(&hidden_name) {
+            let geometry = geometry_cache[&name].filter_faces(|f| !f.is_stud);
+            geometry_cache.insert(hidden_name.clone(), geometry);
+        }
+
+        // Preserve the order of the remaining transforms.
+        let transforms = geometry_world_transforms
+            .get_mut(&(name.clone(), color))
+            .unwrap();
+        let mut hidden_transforms = Vec::new();
+        let mut index = 0;
+        transforms.retain(|t| {
+            let is_hidden = indices.contains(&index);
+            if is_hidden {
+                hidden_transforms.push(*t);
+            }
+            index += 1;
+            !is_hidden
+        });
+        if transforms.is_empty() {
+            geometry_world_transforms.remove(&(name, color));
+        }
+
+        geometry_world_transforms.insert((hidden_name, color), hidden_transforms);
+    }
+
+    // Geometry no longer used by any instance doesn't need to be loaded by applications.
+    let used: HashSet<_> = geometry_world_transforms.keys().map(|(n, _)| n).collect();
+    geometry_cache.retain(|name, _| !bounds.contains_key(name) || used.contains(name));
+}
+
+fn bounds(geometry: &LDrawGeometry, include_face: impl Fn(&Face) -> bool) -> Option<[Vec3; 2]> {
+    geometry
+        .faces()
+        .filter(include_face)
+        .flat_map(|face| face.vertex_indices)
+        .map(|i| geometry.vertices[*i as usize])
+        .fold(None, |result, v| match result {
+            Some([min, max]) => Some([v.min(min), v.max(max)]),
+            None => Some([v, v]),
+        })
+}
+
+fn world_bounds([min, max]: &[Vec3; 2], transform: &Mat4) -> [Vec3; 2] {
+    let mut result = [Vec3::INFINITY, Vec3::NEG_INFINITY];
+    for i in 0..8 {
+        let corner = Vec3::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        );
+        let v = transform.transform_point3(corner);
+        result = [result[0].min(v), result[1].max(v)];
+    }
+    result
+}
+
+/// A 1x1 brick with a flat quad as the body and a smaller quad as the stud.
+#[cfg(test)]
+pub(crate) fn brick() -> LDrawGeometry {
+    LDrawGeometry {
+        vertices: vec![
+            Vec3::new(-10.0, 0.0, -10.0),
+            Vec3::new(10.0, -24.0, 10.0),
+            Vec3::new(-10.0, -24.0, 10.0),
+            Vec3::new(-6.0, -28.0, -6.0),
+            Vec3::new(6.0, -24.0, -6.0),
+            Vec3::new(6.0, -28.0, 6.0),
+        ],
+        vertex_indices: vec![0, 1, 2, 3, 4, 5],
+        face_start_indices: vec![0, 3],
+        face_sizes: vec![3, 3],
+        face_normals: vec![Vec3::Y; 2],
+        face_colors: vec![16],
+        is_face_stud: vec![false, true],
+        is_face_double_sided: vec![false, false],
+        face_windings: Vec::new(),
+        edge_line_indices: vec![[0, 1], [3, 4]],
+        edge_line_colors: vec![24, 24],
+        optional_line_edges: Vec::new(),
+        mesh_edge_indices: vec![[0, 1], [1, 2], [2, 0], [3, 4], [4, 5], [5, 3]],
+        is_mesh_edge_sharp: vec![false; 6],
+        has_grainy_slopes: false,
+        texture_info: None,
+        welded_vertex_count: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remove_hidden_studs_stacked() {
+        let mut geometry_cache = BTreeMap::from([("3005.dat".to_string(), brick())]);
+        let bottom = Mat4::IDENTITY;
+        let top = Mat4::from_translation(Vec3::new(0.0, -24.0, 0.0));
+        let side = Mat4::from_translation(Vec3::new(40.0, 0.0, 0.0));
+        let mut geometry_world_transforms =
+            BTreeMap::from([(("3005.dat".to_string(), 4), vec![bottom, top, side])]);
+
+        remove_hidden_studs(&mut geometry_world_transforms, &mut geometry_cache, 1.0);
+
+        assert_eq!(
+            Some(&vec![top, side]),
+            geometry_world_transforms.get(&("3005.dat".to_string(), 4))
+        );
+        assert_eq!(
+            Some(&vec![bottom]),
+            geometry_world_transforms.get(&("3005.dat#hidden_studs".to_string(), 4))
+        );
+
+        let hidden = &geometry_cache["3005.dat#hidden_studs"];
+        assert_eq!(3, hidden.vertices.len());
+        assert_eq!(vec![0, 1, 2], hidden.vertex_indices);
+        assert_eq!(vec![false], hidden.is_face_stud);
+        assert_eq!(vec![[0, 1]], hidden.edge_line_indices);
+        assert_eq!(vec![[0, 1], [1, 2], [2, 0]], hidden.mesh_edge_indices);
+    }
+
+    #[test]
+    fn remove_hidden_studs_duplicate() {
+        let mut geometry_cache = BTreeMap::from([("3005.dat".to_string(), brick())]);
+        let mut geometry_world_transforms = BTreeMap::from([(
+            ("3005.dat".to_string(), 4),
+            vec![Mat4::IDENTITY, Mat4::IDENTITY],
+        )]);
+
+        remove_hidden_studs(&mut geometry_world_transforms, &mut geometry_cache, 1.0);
+
+        assert_eq!(1, geometry_world_transforms.len());
+        assert_eq!(1, geometry_cache.len());
+    }
+}
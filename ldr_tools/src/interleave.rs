@@ -0,0 +1,244 @@
+use glam::{Vec2, Vec3, Vec4};
+
+use crate::ColorCode;
+
+/// The number of floats for each vertex in [interleave] for `[position, normal, uv]`.
+pub const INTERLEAVED_STRIDE: usize = 8;
+
+/// Create an interleaved `[position, normal, uv]` vertex buffer and a triangle index buffer.
+///
+/// Each face corner is a separate vertex with the face's normal for flat shading.
+/// Quads are triangulated as triangle fans,
+/// and faces with more than 4 vertices are triangulated with [triangulate_polygon](crate::triangulate_polygon).
+/// UVs default to zero if `uvs` is `None`.
+pub fn interleave(
+    vertices: &[Vec3],
+    vertex_indices: &[u32],
+    face_start_indices: &[u32],
+    face_sizes: &[u32],
+    face_normals: &[Vec3],
+    uvs: Option<&[Vec2]>,
+) -> (Vec<f32>, Vec<u32>) {
+    let mut buffer = Vec::with_capacity(vertex_indices.len() * INTERLEAVED_STRIDE);
+    let mut indices = Vec::new();
+
+    for (face, (start, size)) in face_start_indices.iter().zip(face_sizes).enumerate() {
+        let start = *start as usize;
+        let size = *size as usize;
+        let normal = face_normals.get(face).copied().unwrap_or_default();
+
+        for i in start..start + size {
+            let position = vertices[vertex_indices[i] as usize];
+            let uv = uvs.map(|uvs| uvs[i]).unwrap_or_default();
+            buffer.extend_from_slice(&position.to_array());
+            buffer.extend_from_slice(&normal.to_array());
+            buffer.extend_from_slice(&uv.to_array());
+        }
+
+        if size > 4 {
+            let points: Vec<_> = vertex_indices[start..start + size]
+                .iter()
+                .map(|i| vertices[*i as usize])
+                .collect();
+            for triangle in crate::triangulate_polygon(&points, normal) {
+                indices.extend(triangle.map(|i| (start + i) as u32));
+            }
+        } else {
+            for i in 1..size.saturating_sub(1) {
+                indices.extend([start, start + i, start + i + 1].map(|i| i as u32));
+            }
+        }
+    }
+
+    (buffer, indices)
+}
+
+/// Tangents for normal mapping with the handedness in W for each face corner.
+///
+/// This matches the vertex order for [interleave] and the layout of `uvs`.
+/// Tangents are calculated for each face with Lengyel's method
+/// and orthogonalized against the smooth `vertex_normals` from [vertex_normals](crate::vertex_normals).
+pub fn tangents(
+    vertices: &[Vec3],
+    vertex_indices: &[u32],
+    face_start_indices: &[u32],
+    face_sizes: &[u32],
+    vertex_normals: &[Vec3],
+    uvs: &[Vec2],
+) -> Vec<Vec4> {
+    let mut tangents = Vec::with_capacity(vertex_indices.len());
+
+    for (start, size) in face_start_indices.iter().zip(face_sizes) {
+        let start = *start as usize;
+        let size = *size as usize;
+
+        // Sum the contributions of each triangle in the triangle fan.
+        let mut tangent = Vec3::ZERO;
+        let mut bitangent = Vec3::ZERO;
+        for i in start + 1..(start + size).saturating_sub(1) {
+            let [p0, p1, p2] = [start, i, i + 1].map(|i| vertices[vertex_indices[i] as usize]);
+            let [uv0, uv1, uv2] = [start, i, i + 1].map(|i| uvs[i]);
+
+            let (e1, e2) = (p1 - p0, p2 - p0);
+            let (d1, d2) = (uv1 - uv0, uv2 - uv0);
+            let det = d1.x * d2.y - d2.x * d1.y;
+            if det.abs() > f32::EPSILON {
+                tangent += (e1 * d2.y - e2 * d1.y) / det;
+                bitangent += (e2 * d1.x - e1 * d2.x) / det;
+            }
+        }
+
+        for i in &vertex_indices[start..start + size] {
+            let normal = vertex_normals.get(*i as usize).copied().unwrap_or_default();
+
+            // Faces without valid UVs still need a tangent perpendicular to the normal.
+            let tangent = (tangent - normal * normal.dot(tangent))
+                .try_normalize()
+                .unwrap_or_else(|| normal.any_orthonormal_vector());
+            let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            tangents.push(tangent.extend(handedness));
+        }
+    }
+
+    tangents
+}
+
+/// The color of each triangle in the index buffer from [interleave].
+/// `face_colors` can have a single element to use the same color for all faces.
+pub fn triangle_colors(face_sizes: &[u32], face_colors: &[ColorCode]) -> Vec<ColorCode> {
+    face_sizes
+        .iter()
+        .enumerate()
+        .flat_map(|(i, size)| {
+            let color = face_colors.get(i).or(face_colors.first()).copied();
+            std::iter::repeat_n(color.unwrap_or_default(), size.saturating_sub(2) as usize)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use glam::{vec2, vec3};
+
+    #[test]
+    fn interleave_triangle_quad() {
+        let (buffer, indices) = interleave(
+            &[
+                vec3(0.0, 0.0, 0.0),
+                vec3(1.0, 0.0, 0.0),
+                vec3(1.0, 1.0, 0.0),
+                vec3(0.0, 1.0, 0.0),
+            ],
+            &[0, 1, 2, 0, 1, 2, 3],
+            &[0, 3],
+            &[3, 4],
+            &[Vec3::Z, Vec3::NEG_Z],
+            Some(&[
+                vec2(0.0, 0.0),
+                vec2(1.0, 0.0),
+                vec2(1.0, 1.0),
+                vec2(0.0, 0.0),
+                vec2(1.0, 0.0),
+                vec2(1.0, 1.0),
+                vec2(0.0, 1.0),
+            ]),
+        );
+
+        assert_eq!(7 * INTERLEAVED_STRIDE, buffer.len());
+        assert_eq!(
+            &[1.0, 1.0, 0.0, 0.0, 0.0, -1.0, 1.0, 1.0],
+            &buffer[5 * INTERLEAVED_STRIDE..6 * INTERLEAVED_STRIDE]
+        );
+        assert_eq!(vec![0, 1, 2, 3, 4, 5, 3, 5, 6], indices);
+    }
+
+    #[test]
+    fn tangents_quad_flipped_uvs() {
+        let uvs = [
+            vec2(0.0, 0.0),
+            vec2(1.0, 0.0),
+            vec2(1.0, 1.0),
+            vec2(0.0, 1.0),
+        ];
+        let flipped = uvs.map(|uv| vec2(1.0 - uv.x, uv.y));
+        let vertices = [
+            vec3(0.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+        ];
+        let uvs = [uvs, flipped].concat();
+
+        let tangents = tangents(
+            &vertices,
+            &[0, 1, 2, 3, 0, 1, 2, 3],
+            &[0, 4],
+            &[4, 4],
+            &[Vec3::Z; 4],
+            &uvs,
+        );
+
+        assert_eq!(
+            [
+                vec![Vec4::new(1.0, 0.0, 0.0, 1.0); 4],
+                vec![Vec4::new(-1.0, 0.0, 0.0, -1.0); 4]
+            ]
+            .concat(),
+            tangents
+        );
+    }
+
+    #[test]
+    fn tangents_smooth_normals() {
+        // Two quads bent along the shared edge from 1 to 2.
+        let vertices = [
+            vec3(0.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            vec3(2.0, 0.0, 1.0),
+            vec3(2.0, 1.0, 1.0),
+        ];
+        let vertex_indices = [0, 1, 2, 3, 1, 4, 5, 2];
+        let face_normals = [Vec3::Z, vec3(-1.0, 0.0, 1.0).normalize()];
+        let normals = crate::vertex_normals(6, &vertex_indices, &[0, 4], &[4, 4], &face_normals);
+        let uvs = [
+            vec2(0.0, 0.0),
+            vec2(1.0, 0.0),
+            vec2(1.0, 1.0),
+            vec2(0.0, 1.0),
+            vec2(1.0, 0.0),
+            vec2(2.0, 0.0),
+            vec2(2.0, 1.0),
+            vec2(1.0, 1.0),
+        ];
+
+        let tangents = tangents(&vertices, &vertex_indices, &[0, 4], &[4, 4], &normals, &uvs);
+
+        // Tangents on the shared edge are perpendicular to the smooth normal.
+        for (i, tangent) in vertex_indices.iter().zip(&tangents) {
+            assert!(tangent.truncate().dot(normals[*i as usize]).abs() < 1e-6);
+            assert_eq!(1.0, tangent.w);
+        }
+        assert_eq!(Vec4::new(1.0, 0.0, 0.0, 1.0), tangents[0]);
+        assert!(tangents[1].abs_diff_eq(tangents[4], 1e-6));
+        assert!(tangents[1].abs_diff_eq(
+            vec3(1.0 + std::f32::consts::SQRT_2, 0.0, 1.0)
+                .normalize()
+                .extend(1.0),
+            1e-6
+        ));
+    }
+
+    #[test]
+    fn triangle_colors_single_color() {
+        assert_eq!(vec![4, 4, 4], triangle_colors(&[3, 4], &[4]));
+        assert_eq!(vec![1, 2, 2], triangle_colors(&[3, 4], &[1, 2]));
+    }
+}
@@ -1,7 +1,12 @@
 //! LDraw file format and parser.
 
 // The LDraw representation and parser are based on work done for [weldr](https://github.com/djeedai/weldr).
-use std::{collections::HashMap, path::Path, str};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    str,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
 
 pub use glam::{Mat4, Vec2, Vec3, Vec4};
 
@@ -11,6 +16,8 @@ mod parse;
 
 pub use error::{Error, ResolveError};
 use log::{debug, trace};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
 /// RGB color in sRGB color space.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -49,10 +56,39 @@ impl Color {
 /// });
 /// assert_eq!(parse_raw(b"0 this is a comment\n2 16 0 0 0 1 1 1").unwrap(), vec![cmd0, cmd1]);
 /// ```
+///
+/// Returns an [Error::Parse] with the line number starting from 1 for the first invalid line.
 pub fn parse_raw(ldr_content: &[u8]) -> Result<Vec<Command>, Error> {
     parse::parse_raw(ldr_content)
 }
 
+/// Parse each line separately and collect the lines that failed to parse.
+///
+/// Unlike [parse_raw], parsing continues after invalid lines.
+/// Errors contain the line text and the error with the line number starting from 1.
+pub fn parse_raw_with_errors(
+    ldr_content: &[u8],
+) -> (Vec<Command>, Vec<(String, error::ParseError)>) {
+    parse::parse_raw_with_errors(ldr_content)
+}
+
+/// Lazily parse commands one line at a time.
+///
+/// This avoids allocating all commands when only some are needed,
+/// like finding the first `0 FILE` command in a large MPD file.
+/// Unlike [parse_raw], parsing stops without an error at the first line that can't be parsed.
+///
+/// ```rust
+/// use ldr_tools::ldraw::{parse_raw_iter, Command};
+///
+/// let content = b"0 comment\n3 16 0 0 0 1 0 0 0 1 0\n4 16 0 0 0 1 0 0 1 1 0 0 1 0";
+/// let has_geometry = parse_raw_iter(content).any(|c| matches!(c, Command::Triangle(_)));
+/// assert!(has_geometry);
+/// ```
+pub fn parse_raw_iter(ldr_content: &[u8]) -> impl Iterator<Item = Command> + '_ {
+    parse::parse_raw_iter(ldr_content)
+}
+
 struct FileRef {
     /// Filename of unresolved source file.
     filename: String,
@@ -62,8 +98,14 @@ fn load_and_parse_single_file<P: AsRef<Path>, R: FileRefResolver>(
     filename: P,
     resolver: &R,
 ) -> Result<SourceFile, Error> {
-    let raw_content = resolver.resolve(filename)?;
-    let cmds = parse::parse_raw(&raw_content)?;
+    let raw_content = resolver.resolve(&filename)?;
+    let cmds = parse::parse_raw(&raw_content).map_err(|e| match e {
+        Error::Parse(e) => Error::Parse(error::ParseError {
+            filename: filename.as_ref().to_string_lossy().into_owned(),
+            ..e
+        }),
+        e => e,
+    })?;
     Ok(SourceFile { cmds })
 }
 
@@ -93,57 +135,141 @@ fn load_and_parse_single_file<P: AsRef<Path>, R: FileRefResolver>(
 ///   Ok(())
 /// }
 /// ```
-pub fn parse<P: AsRef<Path>, R: FileRefResolver>(
+///
+/// Sub-files are resolved and parsed in parallel,
+/// so the `resolver` must be safe to share between threads.
+pub fn parse<P: AsRef<Path>, R: FileRefResolver + Sync>(
     path: P,
     resolver: &R,
     source_map: &mut SourceMap,
 ) -> Result<String, Error> {
-    // Use a stack to avoid function recursion in load_file.
-    let mut stack: Vec<FileRef> = Vec::new();
+    parse_with_options(path, resolver, source_map, ParseOptions::default())
+}
 
-    debug!("Processing root file '{:?}'", path.as_ref());
-    // The provided path should refer to a file from the resolver.
-    // Use the path directly without any normalization.
-    let filename = path.as_ref().to_string_lossy().to_string();
-    let actual_root = load_file(path, &filename, resolver, source_map, &mut stack)?;
-
-    // Recursively load files referenced by the root file.
-    while let Some(file) = stack.pop() {
-        let filename = &file.filename;
-        debug!("Processing sub-file: '{}'", filename);
-        match source_map.get(filename) {
-            Some(_) => trace!("Already parsed; reusing sub-file: {}", filename),
-            None => {
-                trace!("Not yet parsed; parsing sub-file: {}", filename);
-                // Normalize file references to subfiles.
-                let subfile_ref = SubFileRef::new(filename);
-                load_subfile(subfile_ref, resolver, source_map, &mut stack)?;
-            }
-        }
-    }
+/// Options for controlling [`parse_with_options()`] while it is running.
+#[derive(Default, Clone, Copy)]
+pub struct ParseOptions<'a> {
+    /// Return [`Error::Cancelled`] as soon as possible once this is set to `true`.
+    pub cancel: Option<&'a AtomicBool>,
+    /// Called with the total number of parsed files after parsing each file.
+    /// This may be called from multiple threads.
+    pub on_file_parsed: Option<&'a (dyn Fn(usize) + Sync)>,
+}
 
-    Ok(actual_root)
+impl std::fmt::Debug for ParseOptions<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParseOptions")
+            .field("cancel", &self.cancel)
+            .field("on_file_parsed", &self.on_file_parsed.is_some())
+            .finish()
+    }
 }
 
-fn load_file<P: AsRef<Path>, R: FileRefResolver>(
+/// Same as [`parse()`] but with additional `options` for cancellation and progress.
+///
+/// Files added to `source_map` by this call are removed on cancellation or errors
+/// so that `source_map` only contains files with all sub-file references resolved.
+/// Files replaced by this call like MPD blocks with the same name as an existing file are restored.
+pub fn parse_with_options<P: AsRef<Path>, R: FileRefResolver + Sync>(
     path: P,
-    filename: &str,
     resolver: &R,
     source_map: &mut SourceMap,
-    stack: &mut Vec<FileRef>,
+    options: ParseOptions,
 ) -> Result<String, Error> {
-    let source_file = load_and_parse_single_file(path, resolver)?;
-    source_map.queue_subfiles(&source_file, stack);
-    Ok(source_map.insert(filename, source_file))
+    let mut inserted = Vec::new();
+    let result = parse_files(path, resolver, source_map, options, &mut inserted);
+    if result.is_err() {
+        // Don't leave behind files with sub-file references that were never loaded.
+        // Undo changes in reverse order in case the same file was replaced more than once.
+        for (key, previous) in inserted.into_iter().rev() {
+            match previous {
+                Some(previous) => source_map.source_files.insert(key, previous),
+                None => source_map.source_files.remove(&key),
+            };
+        }
+    }
+    result
 }
 
-fn load_subfile<R: FileRefResolver>(
-    filename: SubFileRef,
+fn parse_files<P: AsRef<Path>, R: FileRefResolver + Sync>(
+    path: P,
     resolver: &R,
     source_map: &mut SourceMap,
-    stack: &mut Vec<FileRef>,
+    options: ParseOptions,
+    inserted: &mut Vec<(SubFileRef, Option<SourceFile>)>,
 ) -> Result<String, Error> {
-    load_file(&filename.0, &filename.0, resolver, source_map, stack)
+    let is_cancelled = || options.cancel.is_some_and(|c| c.load(Ordering::Relaxed));
+
+    let parsed_count = AtomicUsize::new(0);
+    let file_parsed = || {
+        let count = parsed_count.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(on_file_parsed) = options.on_file_parsed {
+            on_file_parsed(count);
+        }
+    };
+
+    // Use a queue to avoid function recursion in load_file.
+    let mut queue: Vec<FileRef> = Vec::new();
+
+    debug!("Processing root file '{:?}'", path.as_ref());
+    // The provided path should refer to a file from the resolver.
+    // Use the path directly without any normalization.
+    let filename = path.as_ref().to_string_lossy().to_string();
+    let source_file = load_and_parse_single_file(path, resolver)?;
+    file_parsed();
+    source_map.queue_subfiles(&source_file, &mut queue);
+    let actual_root = source_map.insert_files(&filename, source_file, inserted);
+
+    // Load files referenced by the root file one level of the file hierarchy at a time.
+    // Resolving and parsing are independent for each file and can run in parallel.
+    while !queue.is_empty() {
+        if is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
+        let mut subfile_refs: Vec<SubFileRef> = Vec::new();
+        let mut queued = HashSet::new();
+        for file in queue.drain(..) {
+            let filename = &file.filename;
+            debug!("Processing sub-file: '{}'", filename);
+            if source_map.get(filename).is_some() {
+                trace!("Already parsed; reusing sub-file: {}", filename);
+            } else {
+                // Normalize file references to subfiles.
+                let subfile_ref = SubFileRef::new(filename);
+                if queued.insert(subfile_ref.0.clone()) {
+                    trace!("Not yet parsed; parsing sub-file: {}", filename);
+                    subfile_refs.push(subfile_ref);
+                }
+            }
+        }
+
+        #[cfg(feature = "parallel")]
+        let subfile_refs = subfile_refs.into_par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let subfile_refs = subfile_refs.into_iter();
+
+        let source_files = subfile_refs
+            .map(|subfile_ref| {
+                if is_cancelled() {
+                    return Err(Error::Cancelled);
+                }
+                let source_file = load_and_parse_single_file(&subfile_ref.0, resolver)?;
+                file_parsed();
+                Ok((subfile_ref, source_file))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (subfile_ref, source_file) in source_files {
+            // Files may have already been added from an MPD file in the same level.
+            if source_map.get(&subfile_ref.0).is_none() {
+                source_map.queue_subfiles(&source_file, &mut queue);
+                source_map.insert_files(&subfile_ref.0, source_file, inserted);
+            }
+        }
+    }
+
+    Ok(actual_root)
 }
 
 /// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
@@ -162,6 +288,83 @@ pub struct KeywordsCmd {
     pub keywords: Vec<String>,
 }
 
+/// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+/// [Name: header](https://www.ldraw.org/article/398.html#name).
+#[derive(Debug, PartialEq, Clone)]
+pub struct NameCmd {
+    /// File name like `3001.dat`.
+    pub name: String,
+}
+
+/// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+/// [Author: header](https://www.ldraw.org/article/398.html#author).
+#[derive(Debug, PartialEq, Clone)]
+pub struct AuthorCmd {
+    /// Real name of the author.
+    pub real_name: String,
+    /// LDraw.org username without the square brackets, if any.
+    pub username: Option<String>,
+}
+
+/// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+/// [!LICENSE header](https://www.ldraw.org/article/398.html#license).
+#[derive(Debug, PartialEq, Clone)]
+pub struct LicenseCmd {
+    /// License text.
+    pub license: String,
+}
+
+/// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+/// [!HELP header](https://www.ldraw.org/article/398.html#help).
+#[derive(Debug, PartialEq, Clone)]
+pub struct HelpCmd {
+    /// Help text for a single line.
+    pub text: String,
+}
+
+/// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+/// [LPub3D](https://trevorsandy.github.io/lpub3d/) instruction meta commands.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LPubCmd {
+    /// The directive text after `!LPUB` like `"PLI BEGIN IGN"`.
+    pub directive: String,
+}
+
+/// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+/// [LeoCAD](https://www.leocad.org/docs/meta.html) `!LEOCAD MODEL` properties of the current model.
+#[derive(Debug, PartialEq, Clone)]
+pub enum LeoCadModelCmd {
+    /// The model name from `NAME`.
+    Name(String),
+    /// The model author from `AUTHOR`.
+    Author(String),
+    /// The model description from `DESCRIPTION`.
+    Description(String),
+}
+
+/// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+/// [LeoCAD](https://www.leocad.org/docs/meta.html) `!LEOCAD PIECE` properties
+/// for the next [Command::SubFileRef].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LeoCadPieceCmd {
+    /// The step from `STEP_HIDE` where the piece is removed from the model.
+    StepHide(u32),
+    /// The piece is hidden in the editor from `HIDDEN`.
+    Hidden,
+}
+
+/// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+/// [!HISTORY header](https://www.ldraw.org/article/398.html#history).
+#[derive(Debug, PartialEq, Clone)]
+pub struct HistoryCmd {
+    /// Date in `YYYY-MM-DD` format.
+    pub date: String,
+    /// Username from `[Username]` or real name from `{Real Name}`.
+    pub author: String,
+    /// Description of the change.
+    pub comment: String,
+}
+
 /// Finish for color definitions ([!COLOUR language extension](https://www.ldraw.org/article/299.html)).
 #[derive(Debug, PartialEq, Clone)]
 pub enum ColorFinish {
@@ -290,6 +493,94 @@ pub struct SourceFile {
     pub cmds: Vec<Command>,
 }
 
+impl SourceFile {
+    /// The line type 1 subfile reference commands in order.
+    pub fn subfile_refs(&self) -> Vec<&SubFileRefCmd> {
+        self.cmds
+            .iter()
+            .filter_map(|c| match c {
+                Command::SubFileRef(cmd) => Some(cmd),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Find the commands between each [BufExchgCommand::Retrieve] and the most recent
+    /// [BufExchgCommand::Store] for the same buffer in order of the retrieve commands.
+    ///
+    /// Parts between the two commands appear in the steps before the retrieve
+    /// and are removed after it.
+    /// Retrieving a buffer that was never stored is ignored.
+    pub fn buffer_exchange_regions(&self) -> Vec<BufExchgRegion> {
+        let mut stored = HashMap::new();
+        let mut regions = Vec::new();
+        for (i, cmd) in self.cmds.iter().enumerate() {
+            match cmd {
+                Command::BufExchg(BufExchgCommand::Store(buffer)) => {
+                    stored.insert(*buffer, i);
+                }
+                Command::BufExchg(BufExchgCommand::Retrieve(buffer)) => {
+                    if let Some(store) = stored.get(buffer) {
+                        regions.push(BufExchgRegion {
+                            buffer: *buffer,
+                            store: *store,
+                            retrieve: i,
+                        });
+                    }
+                }
+                _ => (),
+            }
+        }
+        regions
+    }
+
+    /// The view rotation for each building step or `None` for the default view.
+    ///
+    /// Steps end with [Command::Step] or [Command::RotStep]
+    /// with an additional step for any commands after the last step command.
+    /// Rotations remain active for later steps until [RotStepCommand::End].
+    /// [RotStepMode::Add] is combined with the previous rotation,
+    /// so returned rotations never use [RotStepMode::Add].
+    pub fn step_rotations(&self) -> Vec<Option<StepRotation>> {
+        let mut rotations = Vec::new();
+        let mut rotation: Option<StepRotation> = None;
+        let mut has_commands = false;
+        for cmd in &self.cmds {
+            match cmd {
+                Command::Step => {
+                    rotations.push(rotation);
+                    has_commands = false;
+                }
+                Command::RotStep(RotStepCommand::Rotation(r)) => {
+                    rotation = Some(match (r.mode, rotation) {
+                        (RotStepMode::Add, Some(previous)) => StepRotation {
+                            angles: previous.angles + r.angles,
+                            mode: previous.mode,
+                        },
+                        (RotStepMode::Add, None) => StepRotation {
+                            angles: r.angles,
+                            mode: RotStepMode::Rel,
+                        },
+                        _ => *r,
+                    });
+                    rotations.push(rotation);
+                    has_commands = false;
+                }
+                Command::RotStep(RotStepCommand::End) => {
+                    rotation = None;
+                    rotations.push(rotation);
+                    has_commands = false;
+                }
+                _ => has_commands = true,
+            }
+        }
+        if has_commands {
+            rotations.push(rotation);
+        }
+        rotations
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash)]
 struct SubFileRef(String);
 
@@ -312,6 +603,10 @@ fn normalize_subfile_reference(s: &str) -> String {
 pub struct SourceMap {
     /// Map of filenames to source files.
     source_files: HashMap<SubFileRef, SourceFile>,
+    /// Map of filenames to the decoded contents of embedded `!DATA` files.
+    data_files: HashMap<SubFileRef, Vec<u8>>,
+    /// Files local to the most recently loaded model that shouldn't be reused for other models.
+    model_files: HashSet<SubFileRef>,
 }
 
 impl SourceMap {
@@ -319,6 +614,8 @@ impl SourceMap {
     pub fn new() -> Self {
         Self {
             source_files: HashMap::new(),
+            data_files: HashMap::new(),
+            model_files: HashSet::new(),
         }
     }
 
@@ -328,46 +625,150 @@ impl SourceMap {
         self.source_files.get(&SubFileRef::new(filename))
     }
 
+    /// Returns the decoded contents of the `0 !DATA` block named `filename`
+    /// from a multi-part document (MPD) like an embedded `sticker.png` texture.
+    pub fn data(&self, filename: &str) -> Option<&[u8]> {
+        self.data_files
+            .get(&SubFileRef::new(filename))
+            .map(|d| d.as_slice())
+    }
+
+    /// Returns the number of source files including files from multi-part documents (MPD).
+    pub fn len(&self) -> usize {
+        self.source_files.len()
+    }
+
+    /// Returns `true` if there are no source files.
+    pub fn is_empty(&self) -> bool {
+        self.source_files.is_empty()
+    }
+
     /// Returns a mutable reference to the source file corresponding to `filename`.
     pub fn get_mut(&mut self, filename: &str) -> Option<&mut SourceFile> {
         self.source_files.get_mut(&SubFileRef::new(filename))
     }
 
+    /// The names of all files referenced directly or indirectly by `filename`
+    /// in the order they are first referenced.
+    /// This includes subfiles from multi-part documents (MPD) and files missing from the map.
+    pub fn all_dependencies(&self, filename: &str) -> Vec<String> {
+        let mut dependencies = Vec::new();
+        let mut visited = HashSet::from([SubFileRef::new(filename)]);
+        self.add_dependencies(filename, &mut visited, &mut dependencies);
+        dependencies
+    }
+
+    fn add_dependencies(
+        &self,
+        filename: &str,
+        visited: &mut HashSet<SubFileRef>,
+        dependencies: &mut Vec<String>,
+    ) {
+        let Some(source_file) = self.get(filename) else {
+            return;
+        };
+        for cmd in source_file.subfile_refs() {
+            if visited.insert(SubFileRef::new(&cmd.file)) {
+                dependencies.push(cmd.file.clone());
+                self.add_dependencies(&cmd.file, visited, dependencies);
+            }
+        }
+    }
+
     /// Inserts a new source file into the collection.
     /// Returns a copy of the filename of `source_file`
     /// or the filename of the main file for multi-part documents (MPD).
+    ///
+    /// The main file is the first `0 FILE` block not referenced by another block in the document.
+    /// This is usually the first block but allows documents that place the main model later.
     pub fn insert(&mut self, filename: &str, source_file: SourceFile) -> String {
+        self.insert_files(filename, source_file, &mut Vec::new())
+    }
+
+    /// Same as [`Self::insert`] but also records the keys of added files
+    /// and any files they replaced in `inserted`.
+    fn insert_files(
+        &mut self,
+        filename: &str,
+        source_file: SourceFile,
+        inserted: &mut Vec<(SubFileRef, Option<SourceFile>)>,
+    ) -> String {
         // The MPD extension allows .ldr or .mpd files to contain multiple files.
         // Add each of these so that they can be resolved by subfile commands later.
         let files = split_mpd_file(&source_file.cmds);
+        for (name, data) in split_mpd_data(&source_file.cmds) {
+            self.data_files.insert(SubFileRef::new(&name), data);
+        }
 
         // Some files are referenced in their entirety even if they have multiple models.
-        self.source_files
-            .insert(SubFileRef::new(filename), source_file);
+        self.insert_file(SubFileRef::new(filename), source_file, inserted);
 
         // TODO: More cleanly handle the fact that not all files have 0 FILE commands.
         if files.is_empty() {
             filename.to_string()
         } else {
-            // The first block is the "main model" of the file.
-            let main_model_name = files[0].0.clone();
+            let main_model_name = files[main_model_index(&files)].0.clone();
             for (name, file) in files {
-                self.source_files.insert(SubFileRef::new(&name), file);
+                self.insert_file(SubFileRef::new(&name), file, inserted);
             }
             main_model_name
         }
     }
 
+    fn insert_file(
+        &mut self,
+        key: SubFileRef,
+        source_file: SourceFile,
+        inserted: &mut Vec<(SubFileRef, Option<SourceFile>)>,
+    ) {
+        let previous = self
+            .source_files
+            .insert(SubFileRef(key.0.clone()), source_file);
+        inserted.push((key, previous));
+    }
+
+    /// Mark `filename`, its multi-part document (MPD) blocks,
+    /// and files matching `is_model_file` as local to the model at `filename`.
+    pub(crate) fn mark_model_files(
+        &mut self,
+        filename: &str,
+        is_model_file: impl Fn(&str) -> bool,
+    ) {
+        let mut model_files = HashSet::from([SubFileRef::new(filename)]);
+        if let Some(source_file) = self.get(filename) {
+            model_files.extend(source_file.cmds.iter().filter_map(|c| match c {
+                Command::File(cmd) => Some(SubFileRef::new(&cmd.file)),
+                Command::Data(cmd) => Some(SubFileRef::new(&cmd.file)),
+                _ => None,
+            }));
+        }
+        model_files.extend(
+            self.source_files
+                .keys()
+                .filter(|key| is_model_file(&key.0))
+                .map(|key| SubFileRef(key.0.clone())),
+        );
+        self.model_files = model_files;
+    }
+
+    /// Remove the files marked by [`Self::mark_model_files`] and files matching `is_model_file`.
+    /// Models may use the same names for different files like MPD blocks named `main.ldr`.
+    pub(crate) fn remove_model_files(&mut self, is_model_file: impl Fn(&str) -> bool) {
+        for key in self.model_files.drain() {
+            self.source_files.remove(&key);
+            self.data_files.remove(&key);
+        }
+        self.source_files.retain(|key, _| !is_model_file(&key.0));
+    }
+
     fn queue_subfiles(&self, source_file: &SourceFile, stack: &mut Vec<FileRef>) {
-        for cmd in &source_file.cmds {
-            if let Command::SubFileRef(sfr_cmd) = cmd {
-                // Queue this file for loading if we haven't already.
-                if self.get(&sfr_cmd.file).is_none() {
-                    trace!("Queuing unresolved subfile ref {}", sfr_cmd.file);
-                    stack.push(FileRef {
-                        filename: sfr_cmd.file.clone(),
-                    });
-                }
+        for sfr_cmd in source_file.subfile_refs() {
+            // Queue this file for loading if we haven't already.
+            if self.get(&sfr_cmd.file).is_none() {
+                trace!("Queuing unresolved subfile ref {}", sfr_cmd.file);
+                stack.push(FileRef {
+                    filename: sfr_cmd.file.clone(),
+                });
             }
         }
     }
@@ -382,14 +783,14 @@ fn split_mpd_file(cmds: &[Command]) -> Vec<(String, SourceFile)> {
         })
         .map(|(file_start, file_cmd)| {
             // Each file block starts with a FILE command.
-            // The block continues until the next NOFILE or FILE command.
+            // The block continues until the next NOFILE, FILE, or !DATA command.
             // TODO: Is there a cleaner way of expressing this?
             let subfile = &cmds[file_start..];
             // Start from 1 to ignore the current file command.
             let subfile_end = subfile
                 .iter()
                 .skip(1)
-                .position(|c| matches!(c, Command::File(_) | Command::NoFile));
+                .position(|c| matches!(c, Command::File(_) | Command::NoFile | Command::Data(_)));
             let subfile_cmds = if let Some(subfile_end) = subfile_end {
                 // Add one here since we skip the first FILE command.
                 subfile[..subfile_end + 1].to_vec()
@@ -401,6 +802,42 @@ fn split_mpd_file(cmds: &[Command]) -> Vec<(String, SourceFile)> {
         .collect()
 }
 
+fn main_model_index(files: &[(String, SourceFile)]) -> usize {
+    // The first block is usually the "main model" of the file.
+    // Some applications place submodels before the models that use them.
+    let referenced: HashSet<_> = files
+        .iter()
+        .flat_map(|(_, file)| file.subfile_refs())
+        .map(|cmd| SubFileRef::new(&cmd.file))
+        .collect();
+    files
+        .iter()
+        .position(|(name, _)| !referenced.contains(&SubFileRef::new(name)))
+        .unwrap_or(0)
+}
+
+fn split_mpd_data(cmds: &[Command]) -> Vec<(String, Vec<u8>)> {
+    cmds.iter()
+        .enumerate()
+        .filter_map(|(i, c)| match c {
+            Command::Data(data_cmd) => Some((i, data_cmd)),
+            _ => None,
+        })
+        .map(|(data_start, data_cmd)| {
+            // The base64 encoded lines of a data block are decoded separately.
+            let data = cmds[data_start + 1..]
+                .iter()
+                .map_while(|c| match c {
+                    Command::Base64Data(chunk) => Some(chunk.data.as_slice()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .concat();
+            (data_cmd.file.clone(), data)
+        })
+        .collect()
+}
+
 impl Default for SourceMap {
     fn default() -> Self {
         Self::new()
@@ -498,8 +935,100 @@ pub enum BfcCommand {
     InvertNext,
 }
 
+/// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+/// MLCad BUFEXCHG extension for temporary parts in building instructions.
+///
+/// Buffers are identified by a single uppercase letter from `'A'` to `'Z'`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BufExchgCommand {
+    /// Save the current state of the model in the buffer.
+    Store(char),
+    /// Restore the model state saved in the buffer
+    /// and remove anything added after the matching [BufExchgCommand::Store].
+    Retrieve(char),
+}
+
+/// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+/// MLCad ROTSTEP extension for the view rotation of building instruction steps.
+/// This also ends the current step like [Command::Step].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RotStepCommand {
+    /// Rotate the view for this step.
+    Rotation(StepRotation),
+    /// Reset to the default view.
+    End,
+}
+
+/// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+/// [!TEXMAP language extension](https://www.ldraw.org/texmap-spec.html) for projected textures.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TexMapCommand {
+    /// Apply the texture to the following lines until [TexMapCommand::End].
+    Start(TexMapCmd),
+    /// Apply the texture to only the next line.
+    Next(TexMapCmd),
+    /// Begin the lines used by applications without texture support.
+    Fallback,
+    /// End the texture started with [TexMapCommand::Start].
+    End,
+}
+
+/// The texture and projection for [TexMapCommand::Start] or [TexMapCommand::Next].
+#[derive(Debug, PartialEq, Clone)]
+pub struct TexMapCmd {
+    pub method: TexMapMethod,
+    /// The PNG image file name relative to the `textures` folder.
+    pub texture: String,
+    /// The optional PNG image file name for the glossiness of the texture.
+    pub glossmap: Option<String>,
+}
+
+/// The projection from vertex positions to UVs for [TexMapCmd].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TexMapMethod {
+    /// Project along the plane normal with the U and V directions
+    /// from the first point to the second and third points.
+    Planar { points: [Vec3; 3] },
+    /// Project around the cylinder axis from the first to second point
+    /// with the third point at the center of the texture and `angle` in degrees.
+    Cylindrical { points: [Vec3; 3], angle: f32 },
+    /// Project around a sphere centered at the first point
+    /// with the horizontal and vertical extent `angles` in degrees.
+    Spherical { points: [Vec3; 3], angles: [f32; 2] },
+}
+
+/// The view rotation for a step from [RotStepCommand] or [SourceFile::step_rotations].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct StepRotation {
+    /// The rotation in degrees around the X, Y, and Z axes.
+    pub angles: Vec3,
+    pub mode: RotStepMode,
+}
+
+/// How to apply [StepRotation::angles].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RotStepMode {
+    /// Relative to the application's default view.
+    Rel,
+    /// Added to the rotation of the previous step.
+    Add,
+    /// Absolute rotation ignoring the default view.
+    Abs,
+}
+
+/// The commands hidden by a [BufExchgCommand::Retrieve] from [SourceFile::buffer_exchange_regions].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BufExchgRegion {
+    /// The buffer identifier.
+    pub buffer: char,
+    /// The index of the [BufExchgCommand::Store] command in [SourceFile::cmds].
+    pub store: usize,
+    /// The index of the [BufExchgCommand::Retrieve] command in [SourceFile::cmds].
+    pub retrieve: usize,
+}
+
 /// The ordering of vertices in a face.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Winding {
     /// Countr-clockwise winding
     Ccw,
@@ -541,6 +1070,30 @@ pub enum Command {
     /// [!KEYWORDS language extension](https://www.ldraw.org/article/340.html#keywords).
     Keywords(KeywordsCmd),
     /// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+    /// [Name: header](https://www.ldraw.org/article/398.html#name).
+    Name(NameCmd),
+    /// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+    /// [Author: header](https://www.ldraw.org/article/398.html#author).
+    Author(AuthorCmd),
+    /// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+    /// [!LICENSE header](https://www.ldraw.org/article/398.html#license).
+    License(LicenseCmd),
+    /// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+    /// [!HELP header](https://www.ldraw.org/article/398.html#help).
+    Help(HelpCmd),
+    /// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+    /// [!HISTORY header](https://www.ldraw.org/article/398.html#history).
+    History(HistoryCmd),
+    /// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+    /// [LPub3D](https://trevorsandy.github.io/lpub3d/) `!LPUB` or `LPUB` instruction meta commands.
+    LPub(LPubCmd),
+    /// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+    /// [LeoCAD](https://www.leocad.org/docs/meta.html) `!LEOCAD MODEL` properties.
+    LeoCadModel(LeoCadModelCmd),
+    /// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+    /// [LeoCAD](https://www.leocad.org/docs/meta.html) `!LEOCAD PIECE` properties.
+    LeoCadPiece(LeoCadPieceCmd),
+    /// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
     /// [!COLOUR language extension](https://www.ldraw.org/article/299.html).
     Colour(ColourCmd),
     /// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
@@ -577,6 +1130,18 @@ pub enum Command {
     /// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
     /// Bricklink Studio texture extension
     PeTexInfo(PeTexInfoCmd),
+    /// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+    /// MLCad BUFEXCHG extension
+    BufExchg(BufExchgCommand),
+    /// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+    /// [STEP](https://www.ldraw.org/article/218.html#lt0) ends the current building step.
+    Step,
+    /// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+    /// MLCad ROTSTEP extension
+    RotStep(RotStepCommand),
+    /// [Line Type 0](https://www.ldraw.org/article/218.html#lt0) META command:
+    /// [!TEXMAP language extension](https://www.ldraw.org/texmap-spec.html)
+    TexMap(TexMapCommand),
 }
 
 /// Resolver trait for sub-file references ([Line Type 1](https://www.ldraw.org/article/218.html#lt1) LDraw command).
@@ -613,6 +1178,42 @@ impl Transform {
     }
 }
 
+/// The example from the [MPD specification](https://www.ldraw.org/article/47.html)
+/// with a sticker texture in an embedded `!DATA` file.
+#[cfg(test)]
+pub(crate) const STICKER_MPD: &[u8] = b"0 FILE main.ldr
+        1 7 0 0 0 1 0 0 0 1 0 0 0 1 819.dat
+        1 4 80 -8 70 1 0 0 0 1 0 0 0 1 house.ldr
+        1 4 -70 -8 20 0 0 -1 0 1 0 1 0 0 house.ldr
+        1 4 50 -8 -20 0 0 -1 0 1 0 1 0 0 house.ldr
+        1 4 0 -8 -30 1 0 0 0 1 0 0 0 1 house.ldr
+        1 4 -20 -8 70 1 0 0 0 1 0 0 0 1 house.ldr
+        
+        0 FILE house.ldr
+        1 16 0 0 0 1 0 0 0 1 0 0 0 1 3023.dat
+        1 16 0 -24 0 1 0 0 0 1 0 0 0 1 3065.dat
+        1 16 0 -48 0 1 0 0 0 1 0 0 0 1 3065.dat
+        1 16 0 -72 0 0 0 -1 0 1 0 1 0 0 3044b.dat
+        1 4 0 -22 -10 1 0 0 0 0 -1 0 1 0 sticker.ldr
+        
+        0 FILE sticker.ldr
+        0 UNOFFICIAL PART
+        0 BFC CERTIFY CCW
+        1 16   0 -0.25 0   20 0 0   0 0.25 0   0 0 30   box5.dat
+        0 !TEXMAP START PLANAR   -20 -0.25 30   20 -0.25 30   -20 -0.25 -30   sticker.png
+        4 16   -20 -0.25 30   -20 -0.25 -30   20 -0.25 -30   20 -0.25 30
+        0 !TEXMAP END
+        
+        0 !DATA sticker.png
+        0 !: iVBORw0KGgoAAAANSUhEUgAAAFAAAAB4CAIAAADqjOKhAAAAAXNSR0IArs4c6QAAAARnQU1BAACx
+        0 !: jwv8YQUAAAAJcEhZcwAADsMAAA7DAcdvqGQAAAEUSURBVHhe7du9DcIwFABhk5WgQLSsQM0UjMEU
+        0 !: 1BQsQIsoYAt6NkAYxQV/JQ7WvfuKkFTR6UmOFJzR9bJLkXTlNwyD6QymM5ju5Tl8m67KGUt3XJcz
+        0 !: J/yY8HZ/6C8BFvNZPoaesMF0BtMZTGcwncF0BtMZTGcwncF0BtMZTGcwnf8t0bmLh85gOoPpDKYz
+        0 !: mM5gOoPpDKYzmM5gunDBf3tN+/zqNKt367cbOeGUTstxf1nJZHPOx68T/u3XB5/7/zMXLTqD6Qym
+        0 !: M5jOYDqD6QymM5jOYDqD6QymM5jOYDqD6QymM5jOYLpwwW3t8ajBXTxtTHgwLlp0BtMZTGcwncF0
+        0 !: BtMZTNfKZzyDiT3hCFy06IIFp3QH/CBMh66aBy4AAAAASUVORK5CYII=
+        ";
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -723,41 +1324,7 @@ mod tests {
     #[test]
     fn test_parse_raw_mpd() {
         // Test various language extensions.
-        // Example taken from https://www.ldraw.org/article/47.html
-        let ldr_contents = b"0 FILE main.ldr
-        1 7 0 0 0 1 0 0 0 1 0 0 0 1 819.dat
-        1 4 80 -8 70 1 0 0 0 1 0 0 0 1 house.ldr
-        1 4 -70 -8 20 0 0 -1 0 1 0 1 0 0 house.ldr
-        1 4 50 -8 -20 0 0 -1 0 1 0 1 0 0 house.ldr
-        1 4 0 -8 -30 1 0 0 0 1 0 0 0 1 house.ldr
-        1 4 -20 -8 70 1 0 0 0 1 0 0 0 1 house.ldr
-        
-        0 FILE house.ldr
-        1 16 0 0 0 1 0 0 0 1 0 0 0 1 3023.dat
-        1 16 0 -24 0 1 0 0 0 1 0 0 0 1 3065.dat
-        1 16 0 -48 0 1 0 0 0 1 0 0 0 1 3065.dat
-        1 16 0 -72 0 0 0 -1 0 1 0 1 0 0 3044b.dat
-        1 4 0 -22 -10 1 0 0 0 0 -1 0 1 0 sticker.ldr
-        
-        0 FILE sticker.ldr
-        0 UNOFFICIAL PART
-        0 BFC CERTIFY CCW
-        1 16   0 -0.25 0   20 0 0   0 0.25 0   0 0 30   box5.dat
-        0 !TEXMAP START PLANAR   -20 -0.25 30   20 -0.25 30   -20 -0.25 -30   sticker.png
-        4 16   -20 -0.25 30   -20 -0.25 -30   20 -0.25 -30   20 -0.25 30
-        0 !TEXMAP END
-        
-        0 !DATA sticker.png
-        0 !: iVBORw0KGgoAAAANSUhEUgAAAFAAAAB4CAIAAADqjOKhAAAAAXNSR0IArs4c6QAAAARnQU1BAACx
-        0 !: jwv8YQUAAAAJcEhZcwAADsMAAA7DAcdvqGQAAAEUSURBVHhe7du9DcIwFABhk5WgQLSsQM0UjMEU
-        0 !: 1BQsQIsoYAt6NkAYxQV/JQ7WvfuKkFTR6UmOFJzR9bJLkXTlNwyD6QymM5ju5Tl8m67KGUt3XJcz
-        0 !: J/yY8HZ/6C8BFvNZPoaesMF0BtMZTGcwncF0BtMZTGcwncF0BtMZTGcwnf8t0bmLh85gOoPpDKYz
-        0 !: mM5gOoPpDKYzmM5gunDBf3tN+/zqNKt367cbOeGUTstxf1nJZHPOx68T/u3XB5/7/zMXLTqD6Qym
-        0 !: M5jOYDqD6QymM5jOYDqD6QymM5jOYDqD6QymM5jOYLpwwW3t8ajBXTxtTHgwLlp0BtMZTGcwncF0
-        0 !: BtMZTNfKZzyDiT3hCFy06IIFp3QH/CBMh66aBy4AAAAASUVORK5CYII=
-        ";
-
-        let commands = parse_raw(ldr_contents).unwrap();
+        let commands = parse_raw(STICKER_MPD).unwrap();
         // TODO: Check the actual commands.
         assert_eq!(28, commands.len());
     }
@@ -839,6 +1406,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_source_map_data() {
+        let mut source_map = SourceMap::new();
+        let cmds = parse_raw(STICKER_MPD).unwrap();
+        source_map.insert("sticker.mpd", SourceFile { cmds });
+
+        // The data block isn't part of the preceding file.
+        let sticker = source_map.get("sticker.ldr").unwrap();
+        assert_eq!(7, sticker.cmds.len());
+
+        let data = source_map.data("STICKER.PNG").unwrap();
+        assert!(data.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert!(data.ends_with(b"IEND\xaeB`\x82"));
+        assert_eq!(None, source_map.data("sticker.ldr"));
+    }
+
+    #[test]
+    fn test_source_map_main_model_not_first() {
+        let mut source_map = SourceMap::new();
+        let cmds = parse_raw(
+            b"0 FILE sub.ldr\n1 16 0 0 0 1 0 0 0 1 0 0 0 1 3001.dat\n0 FILE main.ldr\n1 4 0 0 0 1 0 0 0 1 0 0 0 1 SUB.LDR\n",
+        )
+        .unwrap();
+
+        // The first block is a submodel of a later block.
+        assert_eq!(
+            "main.ldr",
+            source_map.insert("model.mpd", SourceFile { cmds })
+        );
+        assert!(source_map.get("sub.ldr").is_some());
+    }
+
     #[test]
     fn test_source_map_normalization() {
         let mut source_map = SourceMap::new();
@@ -851,4 +1450,223 @@ mod tests {
         source_map.insert("a//b\\\\c//d.dat", SourceFile { cmds: Vec::new() });
         assert!(source_map.get("a/b/c/d.dat").is_some());
     }
+
+    struct DummyResolver;
+
+    impl FileRefResolver for DummyResolver {
+        fn resolve<P: AsRef<Path>>(&self, filename: P) -> Result<Vec<u8>, ResolveError> {
+            match filename.as_ref().to_str().unwrap() {
+                "root.ldr" => Ok(b"1 16 0 0 0 1 0 0 0 1 0 0 0 1 a.dat".to_vec()),
+                "a.dat" => Ok(b"3 16 1 0 0 0 1 0 0 0 1".to_vec()),
+                name => Err(ResolveError::new_raw(name)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_cancelled() {
+        let mut source_map = SourceMap::new();
+        let cancel = AtomicBool::new(true);
+
+        let options = ParseOptions {
+            cancel: Some(&cancel),
+            ..Default::default()
+        };
+        let result = parse_with_options("root.ldr", &DummyResolver, &mut source_map, options);
+        assert!(matches!(result, Err(Error::Cancelled)));
+
+        // Files with unresolved references should be removed.
+        assert!(source_map.get("root.ldr").is_none());
+        assert!(source_map.get("a.dat").is_none());
+
+        cancel.store(false, Ordering::Relaxed);
+        let result = parse_with_options("root.ldr", &DummyResolver, &mut source_map, options);
+        assert_eq!("root.ldr", result.unwrap());
+        assert!(source_map.get("a.dat").is_some());
+    }
+
+    #[test]
+    fn test_parse_progress() {
+        let mut source_map = SourceMap::new();
+        let counts = std::sync::Mutex::new(Vec::new());
+
+        let on_file_parsed = |count| counts.lock().unwrap().push(count);
+        let options = ParseOptions {
+            on_file_parsed: Some(&on_file_parsed),
+            ..Default::default()
+        };
+        parse_with_options("root.ldr", &DummyResolver, &mut source_map, options).unwrap();
+
+        assert_eq!(vec![1, 2], counts.into_inner().unwrap());
+    }
+
+    #[test]
+    fn test_parse_reuse_source_map() {
+        struct CountingResolver(std::sync::Mutex<Vec<String>>);
+
+        impl FileRefResolver for CountingResolver {
+            fn resolve<P: AsRef<Path>>(&self, filename: P) -> Result<Vec<u8>, ResolveError> {
+                let filename = filename.as_ref().to_str().unwrap();
+                self.0.lock().unwrap().push(filename.to_string());
+                match filename {
+                    "a.dat" => Ok(b"3 16 1 0 0 0 1 0 0 0 1".to_vec()),
+                    _ => Ok(b"1 16 0 0 0 1 0 0 0 1 0 0 0 1 a.dat".to_vec()),
+                }
+            }
+        }
+
+        let resolver = CountingResolver(Default::default());
+        let mut source_map = SourceMap::new();
+        parse("root1.ldr", &resolver, &mut source_map).unwrap();
+        parse("root2.ldr", &resolver, &mut source_map).unwrap();
+
+        assert_eq!(
+            vec!["root1.ldr", "a.dat", "root2.ldr"],
+            resolver.0.into_inner().unwrap()
+        );
+        assert_eq!(3, source_map.len());
+    }
+
+    #[test]
+    fn test_parse_error_restores_replaced_files() {
+        struct MpdResolver;
+
+        impl FileRefResolver for MpdResolver {
+            fn resolve<P: AsRef<Path>>(&self, filename: P) -> Result<Vec<u8>, ResolveError> {
+                match filename.as_ref().to_str().unwrap() {
+                    "model.mpd" => {
+                        Ok(b"0 FILE a.dat\n1 16 0 0 0 1 0 0 0 1 0 0 0 1 missing.dat".to_vec())
+                    }
+                    "a.dat" => Ok(b"3 16 1 0 0 0 1 0 0 0 1".to_vec()),
+                    name => Err(ResolveError::new_raw(name)),
+                }
+            }
+        }
+
+        let mut source_map = SourceMap::new();
+        parse("a.dat", &MpdResolver, &mut source_map).unwrap();
+
+        // The MPD block replaces the existing file before failing to resolve its subfile.
+        assert!(parse("model.mpd", &MpdResolver, &mut source_map).is_err());
+        assert!(source_map.get("model.mpd").is_none());
+        assert_eq!(
+            parse_raw(b"3 16 1 0 0 0 1 0 0 0 1").unwrap(),
+            source_map.get("a.dat").unwrap().cmds
+        );
+    }
+
+    #[test]
+    fn test_parse_error_line_number() {
+        struct InvalidResolver;
+
+        impl FileRefResolver for InvalidResolver {
+            fn resolve<P: AsRef<Path>>(&self, _filename: P) -> Result<Vec<u8>, ResolveError> {
+                Ok(b"0 comment\n\n3 16 1 0 0 0 1 0 0 0 1\n6 16\n".to_vec())
+            }
+        }
+
+        let error = parse("a.dat", &InvalidResolver, &mut SourceMap::new()).unwrap_err();
+        match error {
+            Error::Parse(e) => {
+                assert_eq!("a.dat", e.filename);
+                assert_eq!(4, e.line_number);
+            }
+            e => panic!("unexpected error {e}"),
+        }
+    }
+
+    #[test]
+    fn test_step_rotations() {
+        let source_file = SourceFile {
+            cmds: parse_raw(
+                b"0 STEP
+                0 ROTSTEP 10 20 0 ABS
+                0 STEP
+                0 ROTSTEP 5 0 0 ADD
+                0 ROTSTEP END
+                0 ROTSTEP 0 45 0
+                3 16 1 0 0 0 1 0 0 0 1
+                ",
+            )
+            .unwrap(),
+        };
+        let rotation = |x, y, mode| {
+            Some(StepRotation {
+                angles: Vec3::new(x, y, 0.0),
+                mode,
+            })
+        };
+        assert_eq!(
+            vec![
+                None,
+                rotation(10.0, 20.0, RotStepMode::Abs),
+                rotation(10.0, 20.0, RotStepMode::Abs),
+                rotation(15.0, 20.0, RotStepMode::Abs),
+                None,
+                rotation(0.0, 45.0, RotStepMode::Rel),
+                rotation(0.0, 45.0, RotStepMode::Rel),
+            ],
+            source_file.step_rotations()
+        );
+    }
+
+    #[test]
+    fn test_buffer_exchange_regions() {
+        let source_file = SourceFile {
+            cmds: parse_raw(
+                b"0 BUFEXCHG A STORE
+                0 STEP
+                0 BUFEXCHG B RETRIEVE
+                0 BUFEXCHG A RETRIEVE
+                0 BUFEXCHG A STORE
+                0 BUFEXCHG A RETRIEVE
+                ",
+            )
+            .unwrap(),
+        };
+        assert_eq!(
+            vec![
+                BufExchgRegion {
+                    buffer: 'A',
+                    store: 0,
+                    retrieve: 3
+                },
+                BufExchgRegion {
+                    buffer: 'A',
+                    store: 4,
+                    retrieve: 5
+                }
+            ],
+            source_file.buffer_exchange_regions()
+        );
+    }
+
+    #[test]
+    fn test_all_dependencies() {
+        let mut source_map = SourceMap::new();
+        let main = source_map.insert(
+            "model.mpd",
+            SourceFile {
+                cmds: parse_raw(
+                    b"0 FILE main.ldr
+                    1 16 0 0 0 1 0 0 0 1 0 0 0 1 a.dat
+                    1 16 0 0 0 1 0 0 0 1 0 0 0 1 B.dat
+                    0 FILE a.dat
+                    1 16 0 0 0 1 0 0 0 1 0 0 0 1 s\\x.dat
+                    1 16 0 0 0 1 0 0 0 1 0 0 0 1 b.dat
+                    0 FILE b.dat
+                    1 16 0 0 0 1 0 0 0 1 0 0 0 1 main.ldr
+                    1 16 0 0 0 1 0 0 0 1 0 0 0 1 c.dat
+                    ",
+                )
+                .unwrap(),
+            },
+        );
+
+        assert_eq!(2, source_map.get(&main).unwrap().subfile_refs().len());
+        assert_eq!(
+            vec!["a.dat", "s\\x.dat", "b.dat", "c.dat"],
+            source_map.all_dependencies(&main)
+        );
+    }
 }
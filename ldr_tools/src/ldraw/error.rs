@@ -11,6 +11,9 @@ pub enum Error {
 
     /// An error encountered while resolving a sub-file reference.
     Resolve(ResolveError),
+
+    /// Loading was cancelled before all files were parsed.
+    Cancelled,
 }
 
 /// Error related to parsing the content of an LDraw file.
@@ -19,8 +22,11 @@ pub struct ParseError {
     /// Filename of the sub-file reference, generally relative to some canonical catalog path(s).
     pub filename: String,
 
+    /// The line number starting from 1 or 0 if the error is not for a single line.
+    pub line_number: usize,
+
     /// Optional underlying error raised by the internal parser.
-    pub parse_error: Option<Box<dyn std::error::Error>>,
+    pub parse_error: Option<Box<dyn std::error::Error + Send + Sync>>,
 }
 
 /// Error related to resolving a sub-file reference of a source file.
@@ -30,14 +36,15 @@ pub struct ResolveError {
     pub filename: String,
 
     /// Optional underlying error raised by the resolver implementation.
-    pub resolve_error: Option<Box<dyn std::error::Error>>,
+    pub resolve_error: Option<Box<dyn std::error::Error + Send + Sync>>,
 }
 
 impl ParseError {
     /// Create a [`ParseError`] that stems from an arbitrary error of an underlying parser.
-    pub fn new(filename: &str, err: impl Into<Box<dyn std::error::Error>>) -> Self {
+    pub fn new(filename: &str, err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
         Self {
             filename: filename.to_string(),
+            line_number: 0,
             parse_error: Some(err.into()),
         }
     }
@@ -47,6 +54,7 @@ impl ParseError {
     pub fn new_from_nom(filename: &str, err: &nom::Err<nom::error::Error<&[u8]>>) -> Self {
         Self {
             filename: filename.to_string(),
+            line_number: 0,
             parse_error: match err {
                 nom::Err::Incomplete(_) => None,
                 nom::Err::Error(e) => {
@@ -64,7 +72,7 @@ impl ParseError {
 
 impl ResolveError {
     /// Create a [`ResolveError`] that stems from an arbitrary error of an underlying resolution error.
-    pub fn new(filename: String, err: impl Into<Box<dyn std::error::Error>>) -> Self {
+    pub fn new(filename: String, err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
         Self {
             filename,
             resolve_error: Some(err.into()),
@@ -85,8 +93,18 @@ impl fmt::Display for Error {
         match self {
             Error::Parse(ParseError {
                 filename,
+                line_number: 0,
                 parse_error,
             }) => write!(f, "parse error in file '{}': {:?}", filename, parse_error),
+            Error::Parse(ParseError {
+                filename,
+                line_number,
+                parse_error,
+            }) => write!(
+                f,
+                "parse error in file '{}' at line {}: {:?}",
+                filename, line_number, parse_error
+            ),
             Error::Resolve(ResolveError {
                 filename,
                 resolve_error,
@@ -95,6 +113,7 @@ impl fmt::Display for Error {
                 "resolve error for filename '{}': {:?}",
                 filename, resolve_error
             ),
+            Error::Cancelled => write!(f, "loading was cancelled"),
         }
     }
 }
@@ -135,6 +154,7 @@ mod tests {
     fn get_error() -> Result<u32, Error> {
         let underlying = Error::Parse(ParseError {
             filename: "low_level.ldr".to_string(),
+            line_number: 3,
             parse_error: None,
         });
         Err(Error::Resolve(ResolveError::new(
@@ -144,6 +164,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::single_match)]
     fn test_error() {
         match get_error() {
             Err(e) => eprintln!("Error: {}", e),
@@ -151,6 +172,25 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_display_line_number() {
+        let error = Error::Parse(ParseError {
+            filename: "file.ldr".to_string(),
+            line_number: 3,
+            parse_error: None,
+        });
+        assert_eq!(
+            "parse error in file 'file.ldr' at line 3: None",
+            error.to_string()
+        );
+
+        let error = Error::Parse(ParseError::new("file.ldr", "invalid"));
+        assert_eq!(
+            "parse error in file 'file.ldr': Some(\"invalid\")",
+            error.to_string()
+        );
+    }
+
     #[test]
     fn test_new_from_nom() {
         let nom_error = nom::Err::Error(nom::error::Error::new(
@@ -9,28 +9,90 @@ use nom::{
     character::complete::digit1,
     combinator::{complete, map, map_res, opt},
     error::ErrorKind,
-    multi::{many0, separated_list1},
+    multi::separated_list1,
     number::complete::float,
     AsChar, IResult, Input, Parser,
 };
-use std::str;
+use std::{borrow::Cow, str};
 
 use crate::ldraw::PeTexInfoTransform;
 
 use super::{
-    error::ParseError, Base64DataCmd, BfcCommand, CategoryCmd, Color, ColorFinish, ColourCmd,
-    Command, CommentCmd, DataCmd, Error, FileCmd, GlitterMaterial, GrainSize, KeywordsCmd, LineCmd,
-    MaterialFinish, OptLineCmd, PeTexInfoCmd, PeTexPathCmd, QuadCmd, SpeckleMaterial,
-    SubFileRefCmd, Transform, TriangleCmd, Winding,
+    error::ParseError, AuthorCmd, Base64DataCmd, BfcCommand, BufExchgCommand, CategoryCmd, Color,
+    ColorFinish, ColourCmd, Command, CommentCmd, DataCmd, Error, FileCmd, GlitterMaterial,
+    GrainSize, HelpCmd, HistoryCmd, KeywordsCmd, LPubCmd, LeoCadModelCmd, LeoCadPieceCmd,
+    LicenseCmd, LineCmd, MaterialFinish, NameCmd, OptLineCmd, PeTexInfoCmd, PeTexPathCmd, QuadCmd,
+    RotStepCommand, RotStepMode, SpeckleMaterial, StepRotation, SubFileRefCmd, TexMapCmd,
+    TexMapCommand, TexMapMethod, Transform, TriangleCmd, Winding,
 };
 
 pub fn parse_raw(ldr_content: &[u8]) -> Result<Vec<Command>, Error> {
     // "An LDraw file consists of one command per line."
-    // TODO: What to set for the error message here?
-    many0(read_line).parse(ldr_content).map_or_else(
-        |e| Err(Error::Parse(ParseError::new_from_nom("", &e))),
-        |(_, cmds)| Ok(cmds),
-    )
+    let mut cmds = Vec::new();
+    let mut input = ldr_content;
+    loop {
+        match read_line(input) {
+            Ok((remaining, cmd)) => {
+                cmds.push(cmd);
+                input = remaining;
+            }
+            Err(e) => {
+                // Only trailing whitespace is left at the end of the file.
+                let Some(offset) = input.iter().position(|c| !is_space(*c) && !is_cr_or_lf(*c))
+                else {
+                    return Ok(cmds);
+                };
+
+                let start = ldr_content.len() - input.len() + offset;
+                let mut error = ParseError::new_from_nom("", &e);
+                // Line numbers start from 1 like in text editors.
+                error.line_number =
+                    ldr_content[..start].iter().filter(|c| **c == b'\n').count() + 1;
+                return Err(Error::Parse(error));
+            }
+        }
+    }
+}
+
+pub fn parse_raw_iter(ldr_content: &[u8]) -> impl Iterator<Item = Command> + '_ {
+    // "An LDraw file consists of one command per line."
+    // Stop at the first line that can't be parsed.
+    let mut input = ldr_content;
+    std::iter::from_fn(move || {
+        let (remaining, cmd) = read_line(input).ok()?;
+        input = remaining;
+        Some(cmd)
+    })
+}
+
+pub fn parse_raw_with_errors(ldr_content: &[u8]) -> (Vec<Command>, Vec<(String, ParseError)>) {
+    let mut cmds = Vec::new();
+    let mut errors = Vec::new();
+    for (i, line) in ldr_content.split(|c| *c == b'\n').enumerate() {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.iter().all(|c| is_space(*c)) {
+            continue;
+        }
+
+        let mut error = match read_line(line) {
+            Ok((remaining, cmd)) if remaining.iter().all(|c| is_space(*c)) => {
+                cmds.push(cmd);
+                continue;
+            }
+            Ok((remaining, _)) => ParseError::new(
+                "",
+                format!(
+                    "unexpected trailing content {:?}",
+                    String::from_utf8_lossy(remaining).trim()
+                ),
+            ),
+            Err(e) => ParseError::new_from_nom("", &e),
+        };
+        // Line numbers start from 1 like in text editors.
+        error.line_number = i + 1;
+        errors.push((String::from_utf8_lossy(line).into_owned(), error));
+    }
+    (cmds, errors)
 }
 
 fn nom_error(i: &[u8], kind: ErrorKind) -> nom::Err<nom::error::Error<&[u8]>> {
@@ -89,18 +151,18 @@ fn read_cmd_id_str(i: &[u8]) -> IResult<&[u8], &[u8]> {
 fn category(i: &[u8]) -> IResult<&[u8], Command> {
     let (i, _) = tag(&b"!CATEGORY"[..]).parse(i)?;
     let (i, _) = sp(i)?;
-    let (i, content) = map_res(take_not_cr_or_lf, str::from_utf8).parse(i)?;
+    let (i, content) = take_not_cr_or_lf(i)?;
 
     Ok((
         i,
         Command::Category(CategoryCmd {
-            category: content.to_string(),
+            category: decode_text(content).into_owned(),
         }),
     ))
 }
 
-fn keywords_list(i: &[u8]) -> IResult<&[u8], Vec<&str>> {
-    separated_list1(single_comma, map_res(take_not_comma_or_eol, str::from_utf8)).parse(i)
+fn keywords_list(i: &[u8]) -> IResult<&[u8], Vec<Cow<'_, str>>> {
+    separated_list1(single_comma, map(take_not_comma_or_eol, decode_text)).parse(i)
 }
 
 fn keywords(i: &[u8]) -> IResult<&[u8], Command> {
@@ -113,6 +175,154 @@ fn keywords(i: &[u8]) -> IResult<&[u8], Command> {
     ))
 }
 
+// Decode text as UTF-8 or as Windows-1252 for older files with accented characters.
+// Geometry is always ASCII, so this is only needed for comments and metadata.
+fn decode_text(i: &[u8]) -> Cow<'_, str> {
+    match str::from_utf8(i) {
+        Ok(s) => Cow::Borrowed(s),
+        Err(_) => Cow::Owned(i.iter().map(|b| windows_1252_char(*b)).collect()),
+    }
+}
+
+fn windows_1252_char(b: u8) -> char {
+    // Windows-1252 differs from Latin-1 only for 0x80 to 0x9F.
+    const CHARS_80_9F: [char; 32] = [
+        '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž',
+        '\u{8F}', '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}',
+        'ž', 'Ÿ',
+    ];
+    match b {
+        0x80..=0x9F => CHARS_80_9F[(b - 0x80) as usize],
+        _ => b as char,
+    }
+}
+
+// Parse the rest of the line as trimmed text.
+fn text(i: &[u8]) -> IResult<&[u8], String> {
+    map(take_not_cr_or_lf, |t| decode_text(t).trim().to_string()).parse(i)
+}
+
+fn name(i: &[u8]) -> IResult<&[u8], Command> {
+    let (i, (_, _, name)) = (tag(&b"Name:"[..]), space0, text).parse(i)?;
+    Ok((i, Command::Name(NameCmd { name })))
+}
+
+fn author(i: &[u8]) -> IResult<&[u8], Command> {
+    let (i, (_, _, author)) = (tag(&b"Author:"[..]), space0, text).parse(i)?;
+
+    // "0 Author: <Realname> [<Username>]"
+    let (real_name, username) = match author.strip_suffix(']').and_then(|a| a.rsplit_once('[')) {
+        Some((real_name, username)) => (real_name.trim(), Some(username.trim().to_string())),
+        None => (author.as_str(), None),
+    };
+
+    Ok((
+        i,
+        Command::Author(AuthorCmd {
+            real_name: real_name.to_string(),
+            username,
+        }),
+    ))
+}
+
+fn license(i: &[u8]) -> IResult<&[u8], Command> {
+    let (i, (_, _, license)) = (tag(&b"!LICENSE"[..]), space0, text).parse(i)?;
+    Ok((i, Command::License(LicenseCmd { license })))
+}
+
+fn help(i: &[u8]) -> IResult<&[u8], Command> {
+    let (i, (_, _, text)) = (tag(&b"!HELP"[..]), space0, text).parse(i)?;
+    Ok((i, Command::Help(HelpCmd { text })))
+}
+
+// Older files may omit the "!" from "0 !LPUB".
+fn lpub(i: &[u8]) -> IResult<&[u8], Command> {
+    let (i, (_, _, directive)) =
+        (alt((tag(&b"!LPUB"[..]), tag(&b"LPUB"[..]))), sp, text).parse(i)?;
+    Ok((i, Command::LPub(LPubCmd { directive })))
+}
+
+// "0 !LEOCAD MODEL <property> <value>"
+fn leocad_model(i: &[u8]) -> IResult<&[u8], Command> {
+    let (i, (_, _, _, _, property, _, value)) = (
+        tag(&b"!LEOCAD"[..]),
+        sp,
+        tag(&b"MODEL"[..]),
+        sp,
+        alt((
+            tag(&b"NAME"[..]),
+            tag(&b"AUTHOR"[..]),
+            tag(&b"DESCRIPTION"[..]),
+        )),
+        sp,
+        text,
+    )
+        .parse(i)?;
+    let cmd = match property {
+        b"NAME" => LeoCadModelCmd::Name(value),
+        b"AUTHOR" => LeoCadModelCmd::Author(value),
+        _ => LeoCadModelCmd::Description(value),
+    };
+    Ok((i, Command::LeoCadModel(cmd)))
+}
+
+// "0 !LEOCAD PIECE STEP_HIDE <step>" or "0 !LEOCAD PIECE HIDDEN"
+fn leocad_piece(i: &[u8]) -> IResult<&[u8], Command> {
+    let (i, _) = (tag(&b"!LEOCAD"[..]), sp, tag(&b"PIECE"[..]), sp).parse(i)?;
+    let (i, cmd) = alt((
+        map(
+            (
+                tag(&b"STEP_HIDE"[..]),
+                sp,
+                map_res(map_res(digit1, str::from_utf8), str::parse::<u32>),
+            ),
+            |(_, _, step)| LeoCadPieceCmd::StepHide(step),
+        ),
+        map(tag(&b"HIDDEN"[..]), |_| LeoCadPieceCmd::Hidden),
+    ))
+    .parse(i)?;
+    let (i, _) = end_of_line(i)?;
+    Ok((i, Command::LeoCadPiece(cmd)))
+}
+
+fn history_author(i: &[u8]) -> IResult<&[u8], Cow<'_, str>> {
+    let username = (
+        tag(&b"["[..]),
+        take_while1(|c| c != b']' && !is_cr_or_lf(c)),
+        tag(&b"]"[..]),
+    );
+    let real_name = (
+        tag(&b"{"[..]),
+        take_while1(|c| c != b'}' && !is_cr_or_lf(c)),
+        tag(&b"}"[..]),
+    );
+    map(alt((username, real_name)), |(_, author, _)| {
+        decode_text(author)
+    })
+    .parse(i)
+}
+
+fn history(i: &[u8]) -> IResult<&[u8], Command> {
+    let (i, (_, _, date, _, author, _, comment)) = (
+        tag(&b"!HISTORY"[..]),
+        sp,
+        map_res(take_not_space, str::from_utf8),
+        sp,
+        history_author,
+        space0,
+        text,
+    )
+        .parse(i)?;
+    Ok((
+        i,
+        Command::History(HistoryCmd {
+            date: date.to_string(),
+            author: author.trim().to_string(),
+            comment,
+        }),
+    ))
+}
+
 fn from_hex(i: &[u8]) -> Result<u8, nom::error::ErrorKind> {
     match std::str::from_utf8(i) {
         Ok(s) => match u8::from_str_radix(s, 16) {
@@ -141,10 +351,6 @@ fn digit1_as_u8(i: &[u8]) -> IResult<&[u8], u8> {
     map_res(map_res(digit1, str::from_utf8), str::parse::<u8>).parse(i)
 }
 
-fn digit1_as_i32(i: &[u8]) -> IResult<&[u8], i32> {
-    map_res(map_res(digit1, str::from_utf8), str::parse::<i32>).parse(i)
-}
-
 // ALPHA part of !COLOUR
 fn colour_alpha(i: &[u8]) -> IResult<&[u8], Option<u8>> {
     opt(complete(|i| {
@@ -317,8 +523,8 @@ fn meta_colour(i: &[u8]) -> IResult<&[u8], Command> {
 }
 
 fn comment(i: &[u8]) -> IResult<&[u8], Command> {
-    let (i, comment) = map_res(take_not_cr_or_lf, str::from_utf8).parse(i)?;
-    Ok((i, Command::Comment(CommentCmd::new(comment))))
+    let (i, comment) = take_not_cr_or_lf(i)?;
+    Ok((i, Command::Comment(CommentCmd::new(&decode_text(comment)))))
 }
 
 fn meta_file(i: &[u8]) -> IResult<&[u8], Command> {
@@ -369,6 +575,17 @@ fn meta_cmd(i: &[u8]) -> IResult<&[u8], Command> {
     alt((
         complete(category),
         complete(keywords),
+        complete(name),
+        complete(author),
+        complete(license),
+        complete(help),
+        complete(history),
+        // Nest application extensions to stay within the maximum number of alt parsers.
+        alt((
+            complete(lpub),
+            complete(leocad_model),
+            complete(leocad_piece),
+        )),
         complete(meta_colour),
         complete(meta_file),
         complete(meta_nofile),
@@ -377,6 +594,10 @@ fn meta_cmd(i: &[u8]) -> IResult<&[u8], Command> {
         complete(bfc),
         complete(pe_tex_path),
         complete(pe_tex_info),
+        complete(buf_exchg),
+        complete(step),
+        complete(rot_step),
+        complete(tex_map),
         comment,
     ))
     .parse(i)
@@ -512,7 +733,8 @@ fn opt_line_cmd(i: &[u8]) -> IResult<&[u8], Command> {
 fn pe_tex_path(i: &[u8]) -> IResult<&[u8], Command> {
     let (i, _) = tag(&b"PE_TEX_PATH"[..])(i)?;
     let (i, _) = sp(i)?;
-    let (i, paths) = separated_list1(sp, digit1_as_i32).parse(i)?;
+    // Negative indices like -1 refer to the current file.
+    let (i, paths) = separated_list1(sp, nom::character::complete::i32).parse(i)?;
 
     Ok((i, Command::PeTexPath(PeTexPathCmd { paths })))
 }
@@ -594,6 +816,138 @@ fn bfc_invertnext(i: &[u8]) -> IResult<&[u8], BfcCommand> {
     Ok((i, BfcCommand::InvertNext))
 }
 
+// "0 BUFEXCHG <buffer> STORE" or "0 BUFEXCHG <buffer> RETRIEVE"
+// Invalid buffers or actions are parsed as comments instead.
+fn buf_exchg(i: &[u8]) -> IResult<&[u8], Command> {
+    let (i, _) = tag(&b"BUFEXCHG"[..])(i)?;
+    let (i, _) = sp(i)?;
+    let (i, buffer) = take_not_space(i)?;
+    let (i, _) = sp(i)?;
+    let (remaining, action) = text(i)?;
+
+    let buffer = match buffer {
+        [b] if b.is_ascii_alphabetic() => b.to_ascii_uppercase() as char,
+        _ => return Err(nom_error(i, ErrorKind::Verify)),
+    };
+    let cmd = if action.eq_ignore_ascii_case("STORE") {
+        BufExchgCommand::Store(buffer)
+    } else if action.eq_ignore_ascii_case("RETRIEVE") {
+        BufExchgCommand::Retrieve(buffer)
+    } else {
+        return Err(nom_error(i, ErrorKind::Verify));
+    };
+
+    Ok((remaining, Command::BufExchg(cmd)))
+}
+
+fn step(i: &[u8]) -> IResult<&[u8], Command> {
+    let (i, _) = tag(&b"STEP"[..])(i)?;
+    let (i, _) = end_of_line(i)?;
+    Ok((i, Command::Step))
+}
+
+// "0 ROTSTEP <x> <y> <z> [REL|ADD|ABS]" or "0 ROTSTEP END"
+fn rot_step(i: &[u8]) -> IResult<&[u8], Command> {
+    let (i, _) = tag(&b"ROTSTEP"[..])(i)?;
+    let (i, _) = sp(i)?;
+    let (i, cmd) = alt((
+        map(tag(&b"END"[..]), |_| RotStepCommand::End),
+        map((v3, rot_step_mode), |(angles, mode)| {
+            RotStepCommand::Rotation(StepRotation { angles, mode })
+        }),
+    ))
+    .parse(i)?;
+    let (i, _) = end_of_line(i)?;
+    Ok((i, Command::RotStep(cmd)))
+}
+
+// "0 !TEXMAP (START | NEXT) <method> <parameters> <pngfile> [GLOSSMAP pngfile]"
+fn tex_map(i: &[u8]) -> IResult<&[u8], Command> {
+    let (i, _) = tag(&b"!TEXMAP"[..])(i)?;
+    let (i, _) = sp(i)?;
+    let (i, cmd) = alt((
+        map((tag(&b"START"[..]), sp, tex_map_cmd), |(_, _, c)| {
+            TexMapCommand::Start(c)
+        }),
+        map((tag(&b"NEXT"[..]), sp, tex_map_cmd), |(_, _, c)| {
+            TexMapCommand::Next(c)
+        }),
+        map((tag(&b"FALLBACK"[..]), end_of_line), |_| {
+            TexMapCommand::Fallback
+        }),
+        map((tag(&b"END"[..]), end_of_line), |_| TexMapCommand::End),
+    ))
+    .parse(i)?;
+    Ok((i, Command::TexMap(cmd)))
+}
+
+fn tex_map_cmd(i: &[u8]) -> IResult<&[u8], TexMapCmd> {
+    let points = |i| {
+        let (i, (p1, _, p2, _, p3)) = (v3, sp, v3, sp, v3).parse(i)?;
+        Ok((i, [p1, p2, p3]))
+    };
+    let (i, method) = alt((
+        map((tag(&b"PLANAR"[..]), sp, points), |(_, _, points)| {
+            TexMapMethod::Planar { points }
+        }),
+        map(
+            (tag(&b"CYLINDRICAL"[..]), sp, points, sp, float),
+            |(_, _, points, _, angle)| TexMapMethod::Cylindrical { points, angle },
+        ),
+        map(
+            (tag(&b"SPHERICAL"[..]), sp, points, sp, float, sp, float),
+            |(_, _, points, _, a, _, b)| TexMapMethod::Spherical {
+                points,
+                angles: [a, b],
+            },
+        ),
+    ))
+    .parse(i)?;
+    let (i, _) = sp(i)?;
+    let (i, files) = filename(i)?;
+
+    // File names may be quoted to allow spaces.
+    let unquote = |f: &str| f.trim().trim_matches('"').to_string();
+    let (texture, glossmap) = match files.split_once(" GLOSSMAP ") {
+        Some((texture, glossmap)) => (unquote(texture), Some(unquote(glossmap))),
+        None => (unquote(files), None),
+    };
+
+    Ok((
+        i,
+        TexMapCmd {
+            method,
+            texture,
+            glossmap,
+        },
+    ))
+}
+
+fn rot_step_mode(i: &[u8]) -> IResult<&[u8], RotStepMode> {
+    // The mode is relative if not specified.
+    let (i, mode) = opt(complete(|i| {
+        let (i, _) = sp(i)?;
+        alt((
+            map(tag_no_case(&b"REL"[..]), |_| RotStepMode::Rel),
+            map(tag_no_case(&b"ADD"[..]), |_| RotStepMode::Add),
+            map(tag_no_case(&b"ABS"[..]), |_| RotStepMode::Abs),
+        ))
+        .parse(i)
+    }))
+    .parse(i)?;
+    Ok((i, mode.unwrap_or(RotStepMode::Rel)))
+}
+
+// Check for only trailing whitespace to avoid parsing a prefix of a longer command.
+fn end_of_line(i: &[u8]) -> IResult<&[u8], ()> {
+    let (i, _) = space0(i)?;
+    match i.first() {
+        None => Ok((i, ())),
+        Some(c) if is_cr_or_lf(*c) => Ok((i, ())),
+        Some(_) => Err(nom_error(i, ErrorKind::Eof)),
+    }
+}
+
 fn opt_winding(i: &[u8]) -> IResult<&[u8], Option<Winding>> {
     opt(complete(|i| {
         let (i, _) = sp(i)?;
@@ -1190,8 +1544,14 @@ mod tests {
             keywords_list(b""),
             Err(nom_error(&b""[..], ErrorKind::TakeWhile1))
         );
-        assert_eq!(keywords_list(b"a"), Ok((&b""[..], vec!["a"])));
-        assert_eq!(keywords_list(b"a,b,c"), Ok((&b""[..], vec!["a", "b", "c"])));
+        assert_eq!(keywords_list(b"a"), Ok((&b""[..], vec![Cow::from("a")])));
+        assert_eq!(
+            keywords_list(b"a,b,c"),
+            Ok((
+                &b""[..],
+                vec![Cow::from("a"), Cow::from("b"), Cow::from("c")]
+            ))
+        );
     }
 
     #[test]
@@ -1249,6 +1609,287 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_raw_iter() {
+        let mut cmds = parse_raw_iter(b"0 FILE a.ldr\n0 comment\n9 invalid\n0 FILE b.ldr");
+        assert!(matches!(cmds.next(), Some(Command::File(_))));
+        assert!(matches!(cmds.next(), Some(Command::Comment(_))));
+        assert_eq!(None, cmds.next());
+    }
+
+    #[test]
+    fn test_parse_raw_with_errors() {
+        let (cmds, errors) = parse_raw_with_errors(
+            b"0 comment\r\n\
+              garbage\r\n\
+              \r\n\
+              3 16 0 0 0 1 0 0 0 1 0\n\
+              2 16 0 0 0 1 0\n\
+              2 24 0 0 0 1 0 0 extra\n\
+              4 16 0 0 0 1 0 0 1 1 0 0 1 0",
+        );
+
+        assert_eq!(3, cmds.len());
+        assert!(matches!(cmds[0], Command::Comment(_)));
+        assert!(matches!(cmds[1], Command::Triangle(_)));
+        assert!(matches!(cmds[2], Command::Quad(_)));
+
+        let lines: Vec<_> = errors
+            .iter()
+            .map(|(line, e)| (e.line_number, line.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                (2, "garbage"),
+                (5, "2 16 0 0 0 1 0"),
+                (6, "2 24 0 0 0 1 0 0 extra")
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn test_name_cmd() {
+        assert_eq!(
+            meta_cmd(b"Name: 3001.dat"),
+            Ok((
+                &b""[..],
+                Command::Name(NameCmd {
+                    name: "3001.dat".to_string(),
+                })
+            ))
+        );
+    }
+
+    #[test]
+    fn test_author_cmd() {
+        assert_eq!(
+            meta_cmd(b"Author: James Jessiman"),
+            Ok((
+                &b""[..],
+                Command::Author(AuthorCmd {
+                    real_name: "James Jessiman".to_string(),
+                    username: None,
+                })
+            ))
+        );
+        assert_eq!(
+            meta_cmd(b"Author: Chris Dee [cwdee]\r\n"),
+            Ok((
+                &b"\r\n"[..],
+                Command::Author(AuthorCmd {
+                    real_name: "Chris Dee".to_string(),
+                    username: Some("cwdee".to_string()),
+                })
+            ))
+        );
+    }
+
+    #[test]
+    fn test_author_cmd_latin1() {
+        assert_eq!(
+            meta_cmd(b"Author: Bj\xF6rn [bjorn]"),
+            Ok((
+                &b""[..],
+                Command::Author(AuthorCmd {
+                    real_name: "Bj\u{f6}rn".to_string(),
+                    username: Some("bjorn".to_string()),
+                })
+            ))
+        );
+    }
+
+    #[test]
+    fn test_comment_cmd_windows_1252() {
+        assert_eq!(
+            meta_cmd(b"\x93quoted\x94 \x80 \xE9"),
+            Ok((
+                &b""[..],
+                Command::Comment(CommentCmd::new("\u{201c}quoted\u{201d} \u{20ac} \u{e9}"))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_raw_error_line_number() {
+        assert_eq!(1, parse_raw(b"0 comment\n  \n").unwrap().len());
+
+        let Err(Error::Parse(error)) = parse_raw(b"0 comment\r\n\r\n  2 16 0 0 0 1 1\n") else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(3, error.line_number);
+    }
+
+    #[test]
+    fn test_parse_raw_latin1() {
+        // Invalid UTF-8 should not stop parsing the rest of the file.
+        let cmds =
+            parse_raw(b"0 Author: Bj\xF6rn\n0 !KEYWORDS caf\xE9\n3 16 0 0 0 1 0 0 0 1 0").unwrap();
+        assert_eq!(3, cmds.len());
+        assert_eq!(
+            Command::Keywords(KeywordsCmd {
+                keywords: vec!["caf\u{e9}".to_string()]
+            }),
+            cmds[1]
+        );
+        assert!(matches!(cmds[2], Command::Triangle(_)));
+    }
+
+    #[test]
+    fn test_license_cmd() {
+        assert_eq!(
+            meta_cmd(b"!LICENSE Licensed under CC BY 4.0 : see CAreadme.txt"),
+            Ok((
+                &b""[..],
+                Command::License(LicenseCmd {
+                    license: "Licensed under CC BY 4.0 : see CAreadme.txt".to_string(),
+                })
+            ))
+        );
+    }
+
+    #[test]
+    fn test_help_cmd() {
+        assert_eq!(
+            meta_cmd(b"!HELP Use with 3002.dat"),
+            Ok((
+                &b""[..],
+                Command::Help(HelpCmd {
+                    text: "Use with 3002.dat".to_string(),
+                })
+            ))
+        );
+    }
+
+    #[test]
+    fn test_lpub_cmd() {
+        assert_eq!(
+            meta_cmd(b"!LPUB PLI BEGIN IGN"),
+            Ok((
+                &b""[..],
+                Command::LPub(LPubCmd {
+                    directive: "PLI BEGIN IGN".to_string(),
+                })
+            ))
+        );
+        assert_eq!(
+            meta_cmd(b"LPUB CALLOUT BEGIN"),
+            Ok((
+                &b""[..],
+                Command::LPub(LPubCmd {
+                    directive: "CALLOUT BEGIN".to_string(),
+                })
+            ))
+        );
+        assert_eq!(
+            meta_cmd(b"!LPUBLISHED"),
+            Ok((&b""[..], Command::Comment(CommentCmd::new("!LPUBLISHED"))))
+        );
+    }
+
+    #[test]
+    fn test_leocad_cmd() {
+        assert_eq!(
+            meta_cmd(b"!LEOCAD MODEL NAME Town House"),
+            Ok((
+                &b""[..],
+                Command::LeoCadModel(LeoCadModelCmd::Name("Town House".to_string()))
+            ))
+        );
+        assert_eq!(
+            meta_cmd(b"!LEOCAD MODEL AUTHOR Jane Doe"),
+            Ok((
+                &b""[..],
+                Command::LeoCadModel(LeoCadModelCmd::Author("Jane Doe".to_string()))
+            ))
+        );
+        assert_eq!(
+            meta_cmd(b"!LEOCAD MODEL DESCRIPTION A small house"),
+            Ok((
+                &b""[..],
+                Command::LeoCadModel(LeoCadModelCmd::Description("A small house".to_string()))
+            ))
+        );
+        assert_eq!(
+            meta_cmd(b"!LEOCAD PIECE STEP_HIDE 5\r\n"),
+            Ok((
+                &b"\r\n"[..],
+                Command::LeoCadPiece(LeoCadPieceCmd::StepHide(5))
+            ))
+        );
+        assert_eq!(
+            meta_cmd(b"!LEOCAD PIECE HIDDEN"),
+            Ok((&b""[..], Command::LeoCadPiece(LeoCadPieceCmd::Hidden)))
+        );
+
+        // Unsupported properties are still comments.
+        assert_eq!(
+            meta_cmd(b"!LEOCAD MODEL BACKGROUND COLOR 1 1 1"),
+            Ok((
+                &b""[..],
+                Command::Comment(CommentCmd::new("!LEOCAD MODEL BACKGROUND COLOR 1 1 1"))
+            ))
+        );
+        assert_eq!(
+            meta_cmd(b"!LEOCAD PIECE PIVOT 1 0 0 0 1 0 0 0 1 0 0 0"),
+            Ok((
+                &b""[..],
+                Command::Comment(CommentCmd::new(
+                    "!LEOCAD PIECE PIVOT 1 0 0 0 1 0 0 0 1 0 0 0"
+                ))
+            ))
+        );
+        assert_eq!(
+            meta_cmd(b"!LEOCAD PIECE HIDDEN_TOO"),
+            Ok((
+                &b""[..],
+                Command::Comment(CommentCmd::new("!LEOCAD PIECE HIDDEN_TOO"))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_history_cmd() {
+        assert_eq!(
+            meta_cmd(b"!HISTORY 2002-05-07 [unknown] BFC Certification"),
+            Ok((
+                &b""[..],
+                Command::History(HistoryCmd {
+                    date: "2002-05-07".to_string(),
+                    author: "unknown".to_string(),
+                    comment: "BFC Certification".to_string(),
+                })
+            ))
+        );
+        assert_eq!(
+            meta_cmd(b"!HISTORY 1999-12-10 {James Jessiman} Made from old file"),
+            Ok((
+                &b""[..],
+                Command::History(HistoryCmd {
+                    date: "1999-12-10".to_string(),
+                    author: "James Jessiman".to_string(),
+                    comment: "Made from old file".to_string(),
+                })
+            ))
+        );
+    }
+
+    #[test]
+    fn test_unknown_meta_cmd() {
+        assert_eq!(
+            meta_cmd(b"!FOO bar"),
+            Ok((&b""[..], Command::Comment(CommentCmd::new("!FOO bar"))))
+        );
+        // Malformed history lines are still preserved as comments.
+        assert_eq!(
+            meta_cmd(b"!HISTORY 2002-05-07"),
+            Ok((
+                &b""[..],
+                Command::Comment(CommentCmd::new("!HISTORY 2002-05-07"))
+            ))
+        );
+    }
+
     #[test]
     fn test_comment_cmd() {
         let comment = b"test of comment, with \"weird\" characters";
@@ -1543,6 +2184,58 @@ mod tests {
                 Command::PeTexPath(PeTexPathCmd { paths: vec![0, 1] })
             ))
         );
+        assert_eq!(
+            read_line(b"0 PE_TEX_PATH -1"),
+            Ok((
+                &b""[..],
+                Command::PeTexPath(PeTexPathCmd { paths: vec![-1] })
+            ))
+        );
+    }
+
+    #[test]
+    fn test_tex_map_cmd() {
+        assert_eq!(
+            read_line(b"0 !TEXMAP START PLANAR -20 -0.25 30 20 -0.25 30 -20 -0.25 -30 sticker.png"),
+            Ok((
+                &b""[..],
+                Command::TexMap(TexMapCommand::Start(TexMapCmd {
+                    method: TexMapMethod::Planar {
+                        points: [
+                            Vec3::new(-20.0, -0.25, 30.0),
+                            Vec3::new(20.0, -0.25, 30.0),
+                            Vec3::new(-20.0, -0.25, -30.0)
+                        ]
+                    },
+                    texture: "sticker.png".to_string(),
+                    glossmap: None
+                }))
+            ))
+        );
+        assert_eq!(
+            read_line(
+                b"0 !TEXMAP NEXT SPHERICAL 0 0 0 1 0 0 0 1 0 90 45 \"a b.png\" GLOSSMAP gloss.png"
+            ),
+            Ok((
+                &b""[..],
+                Command::TexMap(TexMapCommand::Next(TexMapCmd {
+                    method: TexMapMethod::Spherical {
+                        points: [Vec3::ZERO, Vec3::X, Vec3::Y],
+                        angles: [90.0, 45.0]
+                    },
+                    texture: "a b.png".to_string(),
+                    glossmap: Some("gloss.png".to_string())
+                }))
+            ))
+        );
+        assert_eq!(
+            read_line(b"0 !TEXMAP FALLBACK"),
+            Ok((&b""[..], Command::TexMap(TexMapCommand::Fallback)))
+        );
+        assert_eq!(
+            read_line(b"0 !TEXMAP END"),
+            Ok((&b""[..], Command::TexMap(TexMapCommand::End)))
+        );
     }
 
     #[test]
@@ -1617,4 +2310,54 @@ mod tests {
             parse_raw(ldr_content).unwrap()
         );
     }
+
+    #[test]
+    fn test_step_cmd() {
+        let ldr_content = b"0 STEP
+        0 ROTSTEP 10 -20.5 0 ABS
+        0 ROTSTEP 1 2 3
+        0 ROTSTEP 1 2 3 add
+        0 ROTSTEP END
+        0 STEPS
+        0 ROTSTEP 1 2
+        ";
+        let rotation = |x, y, z, mode| {
+            Command::RotStep(RotStepCommand::Rotation(StepRotation {
+                angles: Vec3::new(x, y, z),
+                mode,
+            }))
+        };
+        assert_eq!(
+            vec![
+                Command::Step,
+                rotation(10.0, -20.5, 0.0, RotStepMode::Abs),
+                rotation(1.0, 2.0, 3.0, RotStepMode::Rel),
+                rotation(1.0, 2.0, 3.0, RotStepMode::Add),
+                Command::RotStep(RotStepCommand::End),
+                Command::Comment(CommentCmd::new("STEPS")),
+                Command::Comment(CommentCmd::new("ROTSTEP 1 2")),
+            ],
+            parse_raw(ldr_content).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_buf_exchg_cmd() {
+        let ldr_content = b"0 BUFEXCHG A STORE
+        0 BUFEXCHG b RETRIEVE
+        0 BUFEXCHG AB STORE
+        0 BUFEXCHG 1 STORE
+        0 BUFEXCHG A SWAP
+        ";
+        assert_eq!(
+            vec![
+                Command::BufExchg(BufExchgCommand::Store('A')),
+                Command::BufExchg(BufExchgCommand::Retrieve('B')),
+                Command::Comment(CommentCmd::new("BUFEXCHG AB STORE")),
+                Command::Comment(CommentCmd::new("BUFEXCHG 1 STORE")),
+                Command::Comment(CommentCmd::new("BUFEXCHG A SWAP")),
+            ],
+            parse_raw(ldr_content).unwrap()
+        );
+    }
 }
@@ -1,37 +1,102 @@
 use std::{
-    collections::HashMap,
-    fs::File,
-    io::{BufReader, Read},
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+#[cfg(feature = "fs")]
+use std::{
+    io::Read,
     path::{Path, PathBuf},
 };
 
 use geometry::create_geometry;
 use glam::{vec4, Mat4, Vec3};
-use ldraw::{Command, FileRefResolver, ResolveError};
+#[cfg(feature = "fs")]
+use ldraw::ResolveError;
+use ldraw::{Command, FileRefResolver};
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
+use substitute::substitute_part;
+#[cfg(feature = "io")]
 use zip::ZipArchive;
 
-pub use color::{load_color_table, LDrawColor};
-pub use geometry::LDrawGeometry;
+#[cfg(feature = "async")]
+pub use async_load::load_file_async;
+pub use bfc::{check_bfc, BfcIssue, BfcIssueKind};
+#[cfg(feature = "io")]
+pub use color::load_io_color_table;
+pub use color::{conflicting_colors, edge_color_rgba_linear, resolve_node_color, LDrawColor};
+#[cfg(feature = "fs")]
+pub use color::{
+    load_alt_color_table, load_alt_color_table_for_file, load_color_table,
+    load_color_table_for_file,
+};
+#[cfg(feature = "network")]
+pub use download::{DownloadResolver, LDRAW_LIBRARY_URL};
+pub use footprint::{stud_footprint, StudFootprint, PLATE_HEIGHT_LDU, STUD_SIZE_LDU};
+pub use geometry::{Face, LDrawGeometry};
 pub use glam;
+pub use hidden::HIDDEN_STUDS_SUFFIX;
+pub use interleave::{interleave, tangents, triangle_colors, INTERLEAVED_STRIDE};
 pub use ldraw::Color;
+pub use manifold::{manifold_report, ManifoldReport};
+pub use normal::vertex_normals;
+#[cfg(feature = "fs")]
+pub use part_index::{part_index, search_part_index, PartInfo};
+#[cfg(feature = "image")]
+pub use pe_tex_info::DecodedTexture;
 pub use pe_tex_info::LDrawTextureInfo;
+pub use separate::STUDS_SUFFIX;
+#[cfg(feature = "fs")]
+pub use substitute::load_part_substitutions;
+pub use substitute::parse_part_substitutions;
+pub use triangulate::triangulate_polygon;
+#[cfg(feature = "fs")]
+pub use validate::{validate_file, ValidationReport};
 
 pub type ColorCode = u32;
 
-// Special color code that "inherits" the existing color.
-const CURRENT_COLOR: ColorCode = 16;
+/// Special color code that "inherits" the existing color.
+pub const CURRENT_COLOR: ColorCode = 16;
+
+/// Special color code for the edge color of the current color.
+pub const EDGE_COLOR: ColorCode = 24;
 
+#[cfg(feature = "async")]
+mod async_load;
+mod bfc;
 mod color;
-mod edge_split;
+mod dedup;
+#[cfg(feature = "network")]
+mod download;
+pub mod edge_split;
+mod footprint;
 mod geometry;
+mod hidden;
+mod interleave;
 pub mod ldraw;
+mod manifold;
 mod normal;
+#[cfg(feature = "fs")]
+mod part_index;
 mod pe_tex_info;
+mod separate;
 mod slope;
+mod substitute;
+mod triangulate;
+#[cfg(feature = "fs")]
+mod validate;
 
 pub struct LDrawNode {
+    /// The file name of the part or submodel for this node.
     pub name: String,
+    /// A name that is unique within the scene for creating objects or collections.
+    ///
+    /// The first node for each [name](#structfield.name) uses the name unchanged.
+    /// Later nodes with the same name append a 3 digit number starting from `.001`
+    /// in depth first order like `"submodel.ldr.002"`.
+    /// This is stable across loads of the same file with the same settings.
+    pub unique_name: String,
     pub transform: Mat4,
     /// The name of the geometry in [geometry_cache](struct.LDrawScene.html#structfield.geometry_cache)
     /// or `None` for internal nodes.
@@ -39,72 +104,168 @@ pub struct LDrawNode {
     /// The current color set for this node.
     /// Overrides colors in the geometry if present.
     pub current_color: ColorCode,
+    /// `true` if the world transform of this node has a negative determinant.
+    /// This accounts for the transforms of all parent nodes.
+    pub is_mirrored: bool,
+    /// The linear RGBA color for [current_color](#structfield.current_color)
+    /// if [GeometrySettings::resolve_node_colors] is enabled and the color is defined.
+    pub rgba_linear: Option<[f32; 4]>,
+    /// Properties from [Command::LeoCadPiece](ldraw::Command::LeoCadPiece) commands
+    /// before the subfile reference for this node.
+    pub piece_metadata: PieceMetadata,
     pub children: Vec<LDrawNode>,
 }
 
+/// LeoCAD or Studio properties for a single piece in a model.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct PieceMetadata {
+    /// The step where the piece is removed from the model if set.
+    pub step_hide: Option<u32>,
+    /// `true` if the piece is hidden in the editor.
+    pub is_hidden: bool,
+}
+
+#[cfg(feature = "fs")]
+#[derive(Clone)]
 struct DiskResolver {
     base_paths: Vec<PathBuf>,
+    /// The primitive folders for the default resolution in `base_paths` if any.
+    primitive_paths: Vec<PathBuf>,
+    /// Lowercase name patterns and their primitive folders checked before `base_paths`.
+    primitive_overrides: Vec<(String, Vec<PathBuf>)>,
 }
 
+#[cfg(feature = "fs")]
 impl DiskResolver {
+    /// Search each of the LDraw `library_paths` in order followed by `additional_paths`.
     fn new_from_library<P: AsRef<Path>>(
-        catalog_path: P,
+        library_paths: &[P],
         additional_paths: impl IntoIterator<Item = P>,
         resolution: PrimitiveResolution,
+        resolution_overrides: &[(String, PrimitiveResolution)],
     ) -> Self {
-        let catalog_path = catalog_path.as_ref().to_owned();
-        let mut base_paths = vec![
-            catalog_path.join("p"),
-            catalog_path.join("parts"),
-            catalog_path.join("parts").join("s"),
-            // Studio unoffical part folders.
-            catalog_path.join("UnOfficial").join("p"),
-            catalog_path.join("UnOfficial").join("parts"),
-            catalog_path.join("UnOfficial").join("parts").join("s"),
-        ];
-        // Insert at the front since earlier elements take priority.
-        match resolution {
-            PrimitiveResolution::Low => base_paths.insert(0, catalog_path.join("p").join("8")),
-            PrimitiveResolution::Normal => (),
-            PrimitiveResolution::High => base_paths.insert(0, catalog_path.join("p").join("48")),
+        let library_paths: Vec<_> = library_paths.iter().map(|p| p.as_ref()).collect();
+        let primitive_paths = |resolution| -> Vec<PathBuf> {
+            library_paths
+                .iter()
+                .filter_map(|library| match resolution {
+                    PrimitiveResolution::Low => Some(library.join("p").join("8")),
+                    PrimitiveResolution::Normal => None,
+                    PrimitiveResolution::High => Some(library.join("p").join("48")),
+                })
+                .collect()
+        };
+
+        // Earlier elements take priority.
+        let default_primitive_paths = primitive_paths(resolution);
+        let mut base_paths = default_primitive_paths.clone();
+        for library in &library_paths {
+            base_paths.extend([
+                library.join("p"),
+                library.join("parts"),
+                library.join("parts").join("s"),
+                // Studio unoffical part folders.
+                library.join("UnOfficial").join("p"),
+                library.join("UnOfficial").join("parts"),
+                library.join("UnOfficial").join("parts").join("s"),
+            ]);
         }
+        let primitive_overrides = resolution_overrides
+            .iter()
+            .map(|(pattern, resolution)| (pattern.to_lowercase(), primitive_paths(*resolution)))
+            .collect();
 
         // Users may want to specify additional folders for parts.
         for path in additional_paths {
             base_paths.push(path.as_ref().to_owned());
         }
 
-        Self { base_paths }
+        Self {
+            base_paths,
+            primitive_paths: default_primitive_paths,
+            primitive_overrides,
+        }
+    }
+
+    /// The folders to search for `filename` in order of priority.
+    fn paths(&self, filename: &Path) -> Vec<&PathBuf> {
+        let name = filename.to_string_lossy().to_lowercase();
+        match self
+            .primitive_overrides
+            .iter()
+            .find(|(pattern, _)| name.contains(pattern.as_str()))
+        {
+            // Replace the default primitive folders with the override folders.
+            Some((_, paths)) => paths
+                .iter()
+                .chain(
+                    self.base_paths
+                        .iter()
+                        .filter(|p| !self.primitive_paths.contains(p)),
+                )
+                .collect(),
+            None => self.base_paths.iter().collect(),
+        }
     }
 }
 
+#[cfg(feature = "fs")]
 impl FileRefResolver for DiskResolver {
     fn resolve<P: AsRef<Path>>(&self, filename: P) -> Result<Vec<u8>, ResolveError> {
         let filename = filename.as_ref();
 
         // Find the first folder that contains the given file.
-        let contents = self
-            .base_paths
-            .iter()
-            .find_map(|prefix| std::fs::read(prefix.join(filename)).ok());
+        // Files may also be stored compressed with an additional ".gz" extension.
+        let contents = self.paths(filename).into_iter().find_map(|prefix| {
+            let path = prefix.join(filename);
+            std::fs::read(&path)
+                .or_else(|_| std::fs::read(gz_path(&path)))
+                .ok()
+        });
 
         match contents {
-            Some(contents) => Ok(contents),
+            Some(contents) => Ok(decompress_gzip(filename, contents)),
             None => {
                 // TODO: Is there a better way to allow partial imports with resolve errors?
-                println!("Error resolving {filename:?}");
+                log::warn!("Error resolving {filename:?}");
                 Ok(Vec::new())
             }
         }
     }
 }
 
+#[cfg(feature = "fs")]
+fn gz_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".gz");
+    path.into()
+}
+
+#[cfg(feature = "fs")]
+fn decompress_gzip(filename: &Path, contents: Vec<u8>) -> Vec<u8> {
+    // Check the magic bytes since compressed files don't always use the ".gz" extension.
+    if contents.starts_with(&[0x1f, 0x8b]) {
+        let mut decompressed = Vec::new();
+        match flate2::read::GzDecoder::new(contents.as_slice()).read_to_end(&mut decompressed) {
+            Ok(_) => decompressed,
+            Err(e) => {
+                log::warn!("Error decompressing {filename:?}: {e}");
+                Vec::new()
+            }
+        }
+    } else {
+        contents
+    }
+}
+
+#[cfg(feature = "io")]
 struct IoFileResolver {
     io_path: String,
     model_ldr: Vec<u8>,
     resolver: DiskResolver,
 }
 
+#[cfg(feature = "io")]
 impl FileRefResolver for IoFileResolver {
     fn resolve<P: AsRef<Path>>(&self, filename: P) -> Result<Vec<u8>, ResolveError> {
         if filename.as_ref() == Path::new(&self.io_path) {
@@ -115,21 +276,10 @@ impl FileRefResolver for IoFileResolver {
     }
 }
 
+#[cfg(feature = "io")]
 impl IoFileResolver {
     fn new(io_path: String, resolver: DiskResolver) -> Result<Self, Box<dyn std::error::Error>> {
-        let zip_file = File::open(&io_path)?;
-        let mut archive = ZipArchive::new(BufReader::new(zip_file))?;
-        let mut ldr_file = archive.by_name("model.ldr")?;
-
-        let mut buffer = Vec::with_capacity(ldr_file.size() as usize);
-
-        // skip a BOM, if present
-        ldr_file.by_ref().take(3).read_to_end(&mut buffer)?;
-        if buffer == "\u{FEFF}".as_bytes() {
-            buffer.clear();
-        }
-
-        ldr_file.read_to_end(&mut buffer)?;
+        let buffer = read_io_entry(&io_path, "model.ldr")?;
 
         // TODO: read custom parts from the file?
 
@@ -141,22 +291,331 @@ impl IoFileResolver {
     }
 }
 
+/// Read the file `name` from the Studio `.io` archive at `io_path`.
+/// Files in subfolders are also checked if there is no exact match.
+#[cfg(feature = "io")]
+fn read_io_entry(io_path: &str, name: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let zip_file = std::fs::File::open(io_path)?;
+    let mut archive = ZipArchive::new(std::io::BufReader::new(zip_file))?;
+
+    let suffix = format!("/{}", name.to_lowercase());
+    let entry_name = archive
+        .file_names()
+        .find(|n| n.eq_ignore_ascii_case(name))
+        .or_else(|| {
+            archive
+                .file_names()
+                .find(|n| n.to_lowercase().ends_with(&suffix))
+        })
+        .unwrap_or(name)
+        .to_string();
+    let mut file = archive.by_name(&entry_name)?;
+
+    let mut buffer = Vec::with_capacity(file.size() as usize);
+
+    // skip a BOM, if present
+    file.by_ref().take(3).read_to_end(&mut buffer)?;
+    if buffer == "\u{FEFF}".as_bytes() {
+        buffer.clear();
+    }
+
+    file.read_to_end(&mut buffer)?;
+
+    Ok(buffer)
+}
+
 pub struct LDrawScene {
+    /// The name of the main model like the first `FILE` in an MPD file.
+    pub main_model_name: String,
     pub root_node: LDrawNode,
-    pub geometry_cache: HashMap<String, LDrawGeometry>,
+    pub geometry_cache: BTreeMap<String, LDrawGeometry>,
+    /// Colors defined with `!COLOUR` commands in the model's files.
+    /// These replace colors with the same code from the color table.
+    pub custom_colors: HashMap<ColorCode, LDrawColor>,
+    pub stats: LoadStats,
+}
+
+impl LDrawScene {
+    /// Combine the geometry for all nodes into a single geometry in world space.
+    /// Faces and edges with the current color 16 use the color of their node.
+    /// Edges with the edge color 24 are unchanged.
+    ///
+    /// This copies the geometry for every instance of a part and defeats instancing.
+    /// Prefer using the nodes directly for large models and use this for smaller models or final export.
+    pub fn flatten(&self) -> LDrawGeometry {
+        let mut geometry = LDrawGeometry::default();
+        flatten_node(
+            &self.root_node,
+            Mat4::IDENTITY,
+            &self.geometry_cache,
+            &mut geometry,
+        );
+
+        // Use a single color if possible like other geometry.
+        if let Some(color) = geometry.face_colors.first() {
+            if geometry.face_colors.iter().all(|c| c == color) {
+                geometry.face_colors = vec![*color];
+            }
+        }
+
+        geometry
+    }
+
+    /// The color codes used by the faces of all nodes with geometry.
+    /// Faces with the current color 16 use the color of their node.
+    pub fn used_colors(&self) -> HashSet<ColorCode> {
+        let mut colors = HashSet::new();
+        used_node_colors(&self.root_node, &self.geometry_cache, &mut colors);
+        colors
+    }
+
+    /// The minimum and maximum world space vertex positions for all nodes with geometry
+    /// or `None` if there is no geometry.
+    pub fn bounds(&self) -> Option<[Vec3; 2]> {
+        let mut bounds = None;
+        node_bounds(
+            &self.root_node,
+            Mat4::IDENTITY,
+            &self.geometry_cache,
+            &mut bounds,
+        );
+        bounds
+    }
+
+    /// The geometry name, world transform, and color code for each node with geometry
+    /// in the order the nodes appear in the scene hierarchy.
+    /// Unlike [load_file_instanced], instances with the same geometry and color are not grouped.
+    /// Transforms already account for [GeometrySettings::scene_scale].
+    pub fn flat_instances(&self) -> Vec<(String, Mat4, ColorCode)> {
+        let mut instances = Vec::new();
+        node_instances(&self.root_node, Mat4::IDENTITY, &mut instances);
+        instances
+    }
+
+    /// The [ground_transform] for the [bounds](Self::bounds) of the scene
+    /// or the identity if there is no geometry.
+    pub fn ground_transform(&self, center: bool) -> Mat4 {
+        self.bounds()
+            .map(|[min, max]| ground_transform(min, max, center))
+            .unwrap_or(Mat4::IDENTITY)
+    }
+}
+
+/// A translation that moves the lowest point of the bounds from `min` to `max` to Y=0.
+/// LDraw uses -Y as the up axis, so the lowest point is the maximum Y coordinate.
+/// If `center` is `true`, the bounds are also centered on X=0 and Z=0.
+pub fn ground_transform(min: Vec3, max: Vec3, center: bool) -> Mat4 {
+    let center_xz = if center {
+        (min + max) / 2.0
+    } else {
+        Vec3::ZERO
+    };
+    Mat4::from_translation(Vec3::new(-center_xz.x, -max.y, -center_xz.z))
+}
+
+fn node_instances(
+    node: &LDrawNode,
+    parent_transform: Mat4,
+    instances: &mut Vec<(String, Mat4, ColorCode)>,
+) {
+    let transform = parent_transform * node.transform;
+    if let Some(name) = &node.geometry_name {
+        instances.push((name.clone(), transform, node.current_color));
+    }
+    for child in &node.children {
+        node_instances(child, transform, instances);
+    }
+}
+
+fn node_bounds(
+    node: &LDrawNode,
+    parent_transform: Mat4,
+    geometry_cache: &BTreeMap<String, LDrawGeometry>,
+    bounds: &mut Option<[Vec3; 2]>,
+) {
+    let transform = parent_transform * node.transform;
+
+    if let Some(geometry) = node
+        .geometry_name
+        .as_ref()
+        .and_then(|name| geometry_cache.get(name))
+    {
+        for v in &geometry.vertices {
+            let v = transform.transform_point3(*v);
+            *bounds = match *bounds {
+                Some([min, max]) => Some([v.min(min), v.max(max)]),
+                None => Some([v, v]),
+            };
+        }
+    }
+
+    for child in &node.children {
+        node_bounds(child, transform, geometry_cache, bounds);
+    }
+}
+
+fn used_node_colors(
+    node: &LDrawNode,
+    geometry_cache: &BTreeMap<String, LDrawGeometry>,
+    colors: &mut HashSet<ColorCode>,
+) {
+    if let Some(geometry) = node
+        .geometry_name
+        .as_ref()
+        .and_then(|name| geometry_cache.get(name))
+    {
+        colors.extend(
+            geometry
+                .face_colors
+                .iter()
+                .map(|c| replace_color(*c, node.current_color)),
+        );
+    }
+    for child in &node.children {
+        used_node_colors(child, geometry_cache, colors);
+    }
+}
+
+fn flatten_node(
+    node: &LDrawNode,
+    parent_transform: Mat4,
+    geometry_cache: &BTreeMap<String, LDrawGeometry>,
+    geometry: &mut LDrawGeometry,
+) {
+    let transform = parent_transform * node.transform;
+
+    if let Some(node_geometry) = node
+        .geometry_name
+        .as_ref()
+        .and_then(|name| geometry_cache.get(name))
+    {
+        let face_start = geometry.face_sizes.len();
+        let edge_start = geometry.edge_line_colors.len();
+        geometry.merge(node_geometry, transform);
+
+        // Resolve colors for the new faces since nodes can share geometry.
+        if geometry.face_colors.len() == 1 {
+            geometry.face_colors = vec![geometry.face_colors[0]; geometry.face_sizes.len()];
+        }
+        for color in &mut geometry.face_colors[face_start..] {
+            *color = replace_color(*color, node.current_color);
+        }
+        for color in &mut geometry.edge_line_colors[edge_start..] {
+            *color = replace_color(*color, node.current_color);
+        }
+    }
+
+    for child in &node.children {
+        flatten_node(child, transform, geometry_cache, geometry);
+    }
 }
 
 pub struct LDrawSceneInstanced {
     pub main_model_name: String,
-    pub geometry_world_transforms: HashMap<(String, ColorCode), Vec<Mat4>>,
-    pub geometry_cache: HashMap<String, LDrawGeometry>,
+    /// World transforms for each unique part and color in the order they appear in the file.
+    pub geometry_world_transforms: BTreeMap<(String, ColorCode), Vec<Mat4>>,
+    pub geometry_cache: BTreeMap<String, LDrawGeometry>,
+    /// Colors defined with `!COLOUR` commands in the model's files.
+    /// These replace colors with the same code from the color table.
+    pub custom_colors: HashMap<ColorCode, LDrawColor>,
+    pub stats: LoadStats,
 }
 
 pub struct LDrawSceneInstancedPoints {
     pub main_model_name: String,
     /// Decomposed instance transforms for unique part and color.
-    pub geometry_point_instances: HashMap<(String, ColorCode), PointInstances>,
-    pub geometry_cache: HashMap<String, LDrawGeometry>,
+    pub geometry_point_instances: BTreeMap<(String, ColorCode), PointInstances>,
+    pub geometry_cache: BTreeMap<String, LDrawGeometry>,
+    /// Colors defined with `!COLOUR` commands in the model's files.
+    /// These replace colors with the same code from the color table.
+    pub custom_colors: HashMap<ColorCode, LDrawColor>,
+    pub stats: LoadStats,
+}
+
+/// Statistics for a single call to a load function.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LoadStats {
+    /// The number of files parsed and added to the source map.
+    /// Files already in a reused source map are not included.
+    pub files_parsed: usize,
+    /// The number of unique geometries in the geometry cache.
+    pub geometry_count: usize,
+    /// The total number of faces for all unique geometries.
+    pub face_count: usize,
+    /// The time spent resolving and parsing files.
+    /// Times are always zero on `wasm32` targets without a system clock.
+    pub parse_time: Duration,
+    /// The time spent creating the scene and geometry after parsing.
+    pub geometry_time: Duration,
+}
+
+impl LoadStats {
+    fn new(
+        files_parsed: usize,
+        geometry_cache: &BTreeMap<String, LDrawGeometry>,
+        parse_time: Duration,
+        geometry_time: Duration,
+    ) -> Self {
+        Self {
+            files_parsed,
+            geometry_count: geometry_cache.len(),
+            face_count: geometry_cache.values().map(|g| g.face_sizes.len()).sum(),
+            parse_time,
+            geometry_time,
+        }
+    }
+}
+
+/// Measures the times for [LoadStats].
+/// [std::time::Instant] panics on `wasm32-unknown-unknown`, so no time elapses on `wasm32` targets.
+#[derive(Clone, Copy)]
+struct Instant(#[cfg(not(target_arch = "wasm32"))] std::time::Instant);
+
+impl Instant {
+    fn now() -> Self {
+        Self(
+            #[cfg(not(target_arch = "wasm32"))]
+            std::time::Instant::now(),
+        )
+    }
+
+    fn elapsed(&self) -> Duration {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.0.elapsed();
+        #[cfg(target_arch = "wasm32")]
+        return Duration::ZERO;
+    }
+}
+
+/// The size of a single geometry from [geometry_stats].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeometryStats {
+    /// The key in the geometry cache.
+    pub name: String,
+    pub vertex_count: usize,
+    /// The number of triangles after splitting faces with more than 3 vertices.
+    pub triangle_count: usize,
+}
+
+/// The vertex and triangle counts for each geometry in `geometry_cache`
+/// sorted from the most to the fewest triangles to find the most expensive parts.
+/// Counts are for a single instance and don't include how often a geometry is used.
+pub fn geometry_stats(geometry_cache: &BTreeMap<String, LDrawGeometry>) -> Vec<GeometryStats> {
+    let mut stats: Vec<_> = geometry_cache
+        .iter()
+        .map(|(name, geometry)| GeometryStats {
+            name: name.clone(),
+            vertex_count: geometry.vertices.len(),
+            triangle_count: geometry
+                .face_sizes
+                .iter()
+                .map(|s| s.saturating_sub(2) as usize)
+                .sum(),
+        })
+        .collect();
+    // The sort is stable, so geometries with the same counts remain sorted by name.
+    stats.sort_by_key(|s| std::cmp::Reverse((s.triangle_count, s.vertex_count)));
+    stats
 }
 
 #[derive(Debug, PartialEq)]
@@ -166,13 +625,17 @@ pub struct PointInstances {
     /// The angle of the rotation in radians.
     pub rotations_angle: Vec<f32>,
     pub scales: Vec<Vec3>,
+    /// The world transform for each instance before decomposing.
+    /// This is useful for validating transforms with negative scaling.
+    pub transforms: Vec<Mat4>,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
 pub enum StudType {
     /// Removes all visible and internal studs.
     Disabled,
     /// The default stud model and quality.
+    #[default]
     Normal,
     /// A higher quality modeled logo suitable for realistic rendering.
     Logo4,
@@ -180,37 +643,119 @@ pub enum StudType {
     HighContrast,
 }
 
-impl Default for StudType {
-    fn default() -> Self {
-        Self::Normal
-    }
-}
-
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
 pub enum PrimitiveResolution {
     /// Primitives in the `p/8` folder.
     Low,
     /// The standard primitive resolution
+    #[default]
     Normal,
     /// Primitives in the `p/48` folder.
     High,
 }
 
-impl Default for PrimitiveResolution {
-    fn default() -> Self {
-        Self::Normal
-    }
-}
-
 // TODO: Come up with a better name.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GeometrySettings {
+    /// Split quads into triangles
+    /// and split larger polygons with [triangulate_polygon].
+    /// Welding and splitting sharp edges preserve quads when this is `false`.
     pub triangulate: bool,
     pub add_gap_between_parts: bool,
+    /// Skip [add_gap_between_parts](#structfield.add_gap_between_parts)
+    /// for flexible parts like ropes, chains, and hoses that would be visibly distorted.
+    /// Parts are detected using `!CATEGORY` or the first word of the part description.
+    pub gap_excludes_flexible: bool,
+    /// The gap in LDUs on each side of the part along each axis
+    /// for [add_gap_between_parts](#structfield.add_gap_between_parts).
+    /// LDraw uses -Y as the up axis.
+    pub gap_distance: Vec3,
     pub stud_type: StudType,
     pub weld_vertices: bool, // TODO: default to true?
+    /// Skip [weld_vertices](#structfield.weld_vertices) for these lowercase part file names
+    /// like `"73590a.dat"` that are distorted by welding.
+    pub weld_excluded_parts: HashSet<String>,
+    /// Skip [weld_vertices](#structfield.weld_vertices) for flexible parts
+    /// detected like [gap_excludes_flexible](#structfield.gap_excludes_flexible).
+    pub weld_excludes_flexible: bool,
     pub primitive_resolution: PrimitiveResolution,
+    /// Use a different [PrimitiveResolution] for files with names containing a pattern
+    /// like `("cyli".to_string(), PrimitiveResolution::High)` for smoother visible curves.
+    /// Patterns are not case sensitive and the first matching pattern is used.
+    /// Files without a matching pattern use [primitive_resolution](#structfield.primitive_resolution).
+    pub primitive_resolution_overrides: Vec<(String, PrimitiveResolution)>,
+    /// Additional LDraw library folders like an unofficial or personal parts library.
+    /// Each folder is searched like the main LDraw library with its `p`, `parts`, and `parts/s` subfolders.
+    /// The main library takes priority followed by these folders in order and then any additional paths.
+    pub additional_library_paths: Vec<String>,
     pub scene_scale: f32,
+    /// Flip the V coordinate of texture UVs with `v -> 1.0 - v`
+    /// for applications that place the UV origin in the top left corner.
+    pub flip_texture_v: bool,
+    /// The number of threads for loading or `None` to use all available cores.
+    pub thread_count: Option<usize>,
+    /// Create geometry for each part sequentially
+    /// to keep log output in order when debugging.
+    pub single_threaded: bool,
+    /// Mark faces from files without `0 BFC CERTIFY` as double sided
+    /// in [LDrawGeometry::is_face_double_sided].
+    pub double_side_uncertified: bool,
+    /// The angle in degrees between adjacent faces above which edges are sharp.
+    /// This applies to splitting edges with [weld_vertices](#structfield.weld_vertices)
+    /// and to [LDrawGeometry::is_mesh_edge_sharp].
+    pub sharp_angle_threshold: f32,
+    /// Find the edges of the faces for [LDrawGeometry::mesh_edge_indices]
+    /// and [LDrawGeometry::is_mesh_edge_sharp].
+    /// This is disabled by default since finding adjacent faces is slow for large parts.
+    pub calculate_mesh_edges: bool,
+    /// Replacements for subfile references like higher detail LGEO parts.
+    /// Keys are lowercase file names like `"3001.dat"`.
+    /// See [load_part_substitutions] for loading this from a file.
+    pub part_substitutions: HashMap<String, String>,
+    /// Set [LDrawNode::rgba_linear] for each node from the color table when loading.
+    pub resolve_node_colors: bool,
+    /// Use geometry without stud faces for parts with studs covered by another part
+    /// to reduce face counts for large models.
+    /// The geometry name has [HIDDEN_STUDS_SUFFIX] appended.
+    /// This only applies to [load_file_instanced] and [load_file_instanced_points].
+    pub remove_hidden_studs: bool,
+    /// The maximum nesting depth of subfile references.
+    /// Deeper references are skipped with a warning
+    /// to avoid overflowing the stack for pathological files.
+    pub max_recursion_depth: usize,
+    /// Remove geometry identical to another part with a different name
+    /// and use the remaining geometry name for nodes and instances instead.
+    /// This can reduce memory usage for aliased or moved parts.
+    pub deduplicate_geometry: bool,
+    /// Move stud faces to separate geometry with [STUDS_SUFFIX] appended to the name
+    /// to allow changing stud materials or styles independently of the part.
+    /// Nodes get a child node with the stud geometry,
+    /// and instanced scenes use the same transforms for the studs as the part.
+    pub separate_studs: bool,
+    /// Download files missing from the local library from the official LDraw library
+    /// and cache them in this folder when loading from disk with [load_file] and related functions.
+    /// Cached files are never downloaded again.
+    /// This requires the `network` feature and is ignored with a warning otherwise.
+    pub download_cache_path: Option<String>,
+    /// Remove faces with the same vertices and winding order as another face in the same part
+    /// to avoid z-fighting from accidentally duplicated faces.
+    /// Faces with the opposite winding are kept since they may be intentionally double sided.
+    pub remove_duplicate_faces: bool,
+    /// Also treat any file containing `"stu"` as a stud
+    /// for unofficial files that don't use the stud primitive names.
+    /// This may incorrectly mark faces of unrelated parts as studs.
+    pub stud_substring_fallback: bool,
+    /// Record the winding of each face in [LDrawGeometry::face_windings].
+    /// This is disabled by default to avoid storing an additional value per face.
+    pub record_face_windings: bool,
+    /// The color code for the root node replacing the current color 16 in the loaded file
+    /// like displaying a single part in a chosen color.
+    /// Explicit colors in the file are unchanged.
+    pub root_color: ColorCode,
+    /// Load pieces marked as hidden with `0 !LEOCAD PIECE HIDDEN`
+    /// instead of skipping them and any of their children.
+    /// See [PieceMetadata::is_hidden].
+    pub import_hidden: bool,
 }
 
 impl Default for GeometrySettings {
@@ -218,10 +763,34 @@ impl Default for GeometrySettings {
         Self {
             triangulate: Default::default(),
             add_gap_between_parts: Default::default(),
+            gap_excludes_flexible: true,
+            gap_distance: Vec3::splat(0.1),
             stud_type: Default::default(),
             weld_vertices: Default::default(),
+            weld_excluded_parts: HashSet::new(),
+            weld_excludes_flexible: false,
             primitive_resolution: Default::default(),
+            primitive_resolution_overrides: Vec::new(),
+            additional_library_paths: Vec::new(),
             scene_scale: 1.0,
+            flip_texture_v: false,
+            thread_count: None,
+            single_threaded: false,
+            double_side_uncertified: false,
+            sharp_angle_threshold: 89.0,
+            calculate_mesh_edges: false,
+            part_substitutions: HashMap::new(),
+            resolve_node_colors: false,
+            remove_hidden_studs: false,
+            max_recursion_depth: 256,
+            deduplicate_geometry: false,
+            separate_studs: false,
+            download_cache_path: None,
+            remove_duplicate_faces: false,
+            stud_substring_fallback: false,
+            record_face_windings: false,
+            root_color: CURRENT_COLOR,
+            import_hidden: false,
         }
     }
 }
@@ -241,7 +810,90 @@ struct GeometryInitDescriptor<'a> {
     recursive: bool,
 }
 
+/// Options for controlling a load while it is running.
+#[derive(Default, Clone, Copy)]
+pub struct LoadOptions<'a> {
+    /// Stop loading as soon as possible once this is set to `true`.
+    pub cancel: Option<&'a AtomicBool>,
+    /// Called to report progress while loading.
+    /// This may be called from multiple threads.
+    pub on_progress: Option<&'a (dyn Fn(ProgressEvent) + Sync)>,
+    /// The name of the `0 FILE` block to load from a multi-part document (MPD)
+    /// instead of the detected main model.
+    /// See [SourceMap::insert](ldraw::SourceMap::insert) for how the main model is detected.
+    pub main_model: Option<&'a str>,
+}
+
+impl std::fmt::Debug for LoadOptions<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoadOptions")
+            .field("cancel", &self.cancel)
+            .field("on_progress", &self.on_progress.is_some())
+            .field("main_model", &self.main_model)
+            .finish()
+    }
+}
+
+impl LoadOptions<'_> {
+    fn is_cancelled(&self) -> bool {
+        self.cancel.is_some_and(|c| c.load(Ordering::Relaxed))
+    }
+
+    fn progress(&self, event: ProgressEvent) {
+        if let Some(on_progress) = self.on_progress {
+            on_progress(event);
+        }
+    }
+}
+
+/// A step completed while loading for [LoadOptions::on_progress].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ProgressEvent<'a> {
+    /// Resolved and parsed `count` files so far.
+    FileParsed { count: usize },
+    /// Created geometry for the part `name`.
+    /// This is the `index`-th completed part out of `total` parts starting from 1.
+    GeometryCreated {
+        name: &'a str,
+        index: usize,
+        total: usize,
+    },
+}
+
+/// Errors while loading with one of the `try_load_file` functions.
+#[derive(Debug)]
+pub enum LoadError {
+    /// Loading was stopped early using [LoadOptions::cancel].
+    Cancelled,
+    /// Loading `.io` files requires the `io` feature.
+    IoUnsupported,
+    /// A file could not be resolved or parsed.
+    Parse(ldraw::Error),
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Cancelled => write!(f, "loading was cancelled"),
+            LoadError::IoUnsupported => {
+                write!(f, "loading .io files requires the \"io\" feature")
+            }
+            LoadError::Parse(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 // TODO: Add tests for this using files from models?
+#[cfg(feature = "fs")]
 #[tracing::instrument]
 pub fn load_file(
     path: &str,
@@ -249,86 +901,430 @@ pub fn load_file(
     additional_paths: &[String],
     settings: &GeometrySettings,
 ) -> LDrawScene {
-    let (source_map, main_model_name) = parse_file(path, ldraw_path, additional_paths, settings);
-    let source_file = source_map.get(&main_model_name).unwrap();
+    try_load_file(
+        path,
+        ldraw_path,
+        additional_paths,
+        settings,
+        &mut ldraw::SourceMap::new(),
+        LoadOptions::default(),
+    )
+    .unwrap()
+}
+
+/// Same as [load_file] but with additional `options` for controlling the load.
+///
+/// Parsed files are added to `source_map` and reused without resolving them again.
+/// Files local to the model like MPD blocks or files in the model's folder are parsed again for each call.
+/// Only reuse `source_map` for calls with the same `ldraw_path`, `additional_paths`, and settings.
+#[cfg(feature = "fs")]
+#[tracing::instrument(skip(source_map))]
+pub fn try_load_file(
+    path: &str,
+    ldraw_path: &str,
+    additional_paths: &[String],
+    settings: &GeometrySettings,
+    source_map: &mut ldraw::SourceMap,
+    options: LoadOptions,
+) -> Result<LDrawScene, LoadError> {
+    with_thread_pool(settings, || {
+        let start = Instant::now();
+        let (main_model_name, files_parsed) = parse_file(
+            path,
+            ldraw_path,
+            additional_paths,
+            settings,
+            source_map,
+            options,
+        )?;
+        let parse_time = start.elapsed();
+
+        let color_table = if !settings.resolve_node_colors {
+            None
+        } else if Path::new(ldraw_path).join("LDConfig.ldr").exists() {
+            Some(load_color_table_for_file(ldraw_path, path))
+        } else {
+            log::warn!("Unable to resolve node colors without LDConfig.ldr in {ldraw_path:?}");
+            None
+        };
+
+        load_scene(
+            &main_model_name,
+            source_map,
+            settings,
+            options,
+            color_table,
+            files_parsed,
+            parse_time,
+        )
+    })
+}
+
+/// Same as [try_load_file] but resolves all files including `path` using `resolver`.
+///
+/// This does not access the file system and is available without the `fs` feature.
+/// Only `path` and its MPD blocks are parsed again when reusing `source_map`.
+/// Node colors are resolved using the `LDConfig.ldr` file from `resolver`.
+#[tracing::instrument(skip(resolver, source_map))]
+pub fn try_load_with_resolver<R: FileRefResolver + Sync>(
+    path: &str,
+    resolver: &R,
+    settings: &GeometrySettings,
+    source_map: &mut ldraw::SourceMap,
+    options: LoadOptions,
+) -> Result<LDrawScene, LoadError> {
+    with_thread_pool(settings, || {
+        let start = Instant::now();
+        let (main_model_name, files_parsed) =
+            parse_with_resolver(path, resolver, settings, source_map, options, |_| false)?;
+        let parse_time = start.elapsed();
+
+        let color_table = if settings.resolve_node_colors {
+            let color_table = resolver
+                .resolve("LDConfig.ldr")
+                .ok()
+                .and_then(|bytes| ldraw::parse_raw(&bytes).ok())
+                .map(color::color_table)
+                .filter(|table| !table.is_empty());
+            if color_table.is_none() {
+                log::warn!("Unable to resolve node colors without LDConfig.ldr");
+            }
+            color_table
+        } else {
+            None
+        };
+
+        load_scene(
+            &main_model_name,
+            source_map,
+            settings,
+            options,
+            color_table,
+            files_parsed,
+            parse_time,
+        )
+    })
+}
+
+/// Resolve and parse the model at `path` like [load_file]
+/// to find the names of all files it references directly or indirectly.
+/// See [SourceMap::all_dependencies](ldraw::SourceMap::all_dependencies).
+#[cfg(feature = "fs")]
+pub fn file_dependencies(
+    path: &str,
+    ldraw_path: &str,
+    additional_paths: &[String],
+) -> Result<Vec<String>, LoadError> {
+    let settings = GeometrySettings::default();
+    let mut source_map = ldraw::SourceMap::new();
+    let (main_model_name, _) = parse_file(
+        path,
+        ldraw_path,
+        additional_paths,
+        &settings,
+        &mut source_map,
+        LoadOptions::default(),
+    )?;
+    Ok(source_map.all_dependencies(&main_model_name))
+}
+
+fn load_scene(
+    main_model_name: &str,
+    source_map: &ldraw::SourceMap,
+    settings: &GeometrySettings,
+    options: LoadOptions,
+    color_table: Option<HashMap<u32, LDrawColor>>,
+    files_parsed: usize,
+    parse_time: Duration,
+) -> Result<LDrawScene, LoadError> {
+    let start = Instant::now();
+    let empty = ldraw::SourceFile { cmds: Vec::new() };
+    let source_file = main_model(source_map, main_model_name, &empty);
 
     // Collect the scene hierarchy and geometry descriptors.
     let mut geometry_descriptors = HashMap::new();
-    let root_node = load_node(
+    let mut root_node = load_node(
         source_file,
-        &main_model_name,
+        main_model_name,
         &Mat4::IDENTITY,
-        &source_map,
+        false,
+        source_map,
         &mut geometry_descriptors,
-        CURRENT_COLOR,
+        settings.root_color,
+        0,
         settings,
     );
 
-    let geometry_cache = create_geometry_cache(geometry_descriptors, &source_map, settings);
+    let custom_colors = color::model_colors(source_map, main_model_name);
+    if let Some(mut color_table) = color_table {
+        color_table.extend(custom_colors.iter().map(|(k, v)| (*k, v.clone())));
+        color::resolve_node_colors(&mut root_node, settings.root_color, &color_table);
+    }
+
+    let mut geometry_cache =
+        create_geometry_cache(geometry_descriptors, source_map, settings, options)?;
+
+    if settings.deduplicate_geometry {
+        let renamed = dedup::deduplicate_geometry(&mut geometry_cache);
+        dedup::rename_node_geometry(&mut root_node, &renamed);
+    }
+
+    if settings.separate_studs {
+        let separated = separate::separate_studs(&mut geometry_cache);
+        separate::add_stud_nodes(&mut root_node, &separated);
+    }
+
+    assign_unique_names(&mut root_node, &mut HashMap::new());
 
-    LDrawScene {
+    let stats = LoadStats::new(files_parsed, &geometry_cache, parse_time, start.elapsed());
+
+    Ok(LDrawScene {
+        main_model_name: main_model_name.to_string(),
         root_node,
         geometry_cache,
-    }
+        custom_colors,
+        stats,
+    })
 }
 
-#[tracing::instrument]
+#[cfg(feature = "fs")]
+#[tracing::instrument(skip(source_map))]
 fn parse_file(
     path: &str,
     ldraw_path: &str,
     additional_paths: &[String],
     settings: &GeometrySettings,
-) -> (ldraw::SourceMap, String) {
+    source_map: &mut ldraw::SourceMap,
+    options: LoadOptions,
+) -> Result<(String, usize), LoadError> {
+    let resolver = file_resolver(path, ldraw_path, additional_paths, settings)?;
+    let model_folder = ModelFolder::new(path);
+    parse_with_resolver(path, &resolver, settings, source_map, options, |name| {
+        model_folder.contains(name)
+    })
+}
+
+/// The files in the folder of a model on disk.
+/// These are resolved before library files with the same name.
+#[cfg(feature = "fs")]
+struct ModelFolder {
+    path: PathBuf,
+    /// Lowercase names of the files directly in `path` without any ".gz" extension.
+    names: HashSet<String>,
+}
+
+#[cfg(feature = "fs")]
+impl ModelFolder {
+    fn new(model_path: &str) -> Self {
+        let path = Path::new(model_path)
+            .parent()
+            .map(|p| p.to_owned())
+            .unwrap_or_default();
+        // Avoid checking the disk for every file in the source map.
+        let dir = if path.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            &path
+        };
+        let names = std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+            .map(|e| {
+                let name = e.file_name().to_string_lossy().to_lowercase();
+                name.strip_suffix(".gz")
+                    .map(|n| n.to_string())
+                    .unwrap_or(name)
+            })
+            .collect();
+        Self { path, names }
+    }
+
+    /// Returns `true` if the normalized sub-file reference `name` resolves to a file in this folder.
+    fn contains(&self, name: &str) -> bool {
+        if name.contains('/') {
+            let path = self.path.join(name);
+            path.is_file() || gz_path(&path).is_file()
+        } else {
+            self.names.contains(name)
+        }
+    }
+}
+
+/// The resolver for a model file at `path` on disk and its library files.
+#[cfg(feature = "fs")]
+enum FileResolver {
+    Disk(DiskResolver),
+    #[cfg(feature = "io")]
+    Io(IoFileResolver),
+    #[cfg(feature = "network")]
+    Download(Box<DownloadResolver<FileResolver>>),
+}
+
+#[cfg(feature = "fs")]
+impl FileRefResolver for FileResolver {
+    fn resolve<P: AsRef<Path>>(&self, filename: P) -> Result<Vec<u8>, ResolveError> {
+        match self {
+            FileResolver::Disk(resolver) => resolver.resolve(filename),
+            #[cfg(feature = "io")]
+            FileResolver::Io(resolver) => resolver.resolve(filename),
+            #[cfg(feature = "network")]
+            FileResolver::Download(resolver) => resolver.resolve(filename),
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+fn file_resolver(
+    path: &str,
+    ldraw_path: &str,
+    additional_paths: &[String],
+    settings: &GeometrySettings,
+) -> Result<FileResolver, LoadError> {
+    let library_paths: Vec<_> = std::iter::once(ldraw_path)
+        .chain(settings.additional_library_paths.iter().map(|s| s.as_str()))
+        .collect();
     let mut resolver = DiskResolver::new_from_library(
-        ldraw_path,
+        &library_paths,
         additional_paths.iter().map(|s| s.as_str()),
         settings.primitive_resolution,
+        &settings.primitive_resolution_overrides,
     );
     // Resolve paths relative to the current file.
     if let Some(parent) = Path::new(path).parent() {
         resolver.base_paths.insert(0, parent.to_owned());
     }
 
-    let mut source_map = ldraw::SourceMap::new();
-    ensure_studs(settings, &resolver, &mut source_map);
-
     let is_io = Path::new(path).extension() == Some("io".as_ref());
-
-    let main_model_name = if is_io {
-        let io_resolver = IoFileResolver::new(path.to_owned(), resolver).unwrap();
-        ldraw::parse(path, &io_resolver, &mut source_map).unwrap()
+    let resolver = if is_io {
+        #[cfg(feature = "io")]
+        {
+            FileResolver::Io(IoFileResolver::new(path.to_owned(), resolver).unwrap())
+        }
+        #[cfg(not(feature = "io"))]
+        return Err(LoadError::IoUnsupported);
     } else {
-        ldraw::parse(path, &resolver, &mut source_map).unwrap()
+        FileResolver::Disk(resolver)
+    };
+
+    match &settings.download_cache_path {
+        #[cfg(feature = "network")]
+        Some(cache_path) => Ok(FileResolver::Download(Box::new(DownloadResolver::new(
+            resolver, cache_path,
+        )))),
+        #[cfg(not(feature = "network"))]
+        Some(_) => {
+            log::warn!("Downloading missing files requires the network feature");
+            Ok(resolver)
+        }
+        None => Ok(resolver),
+    }
+}
+
+/// Returns the main model name and the number of parsed files.
+/// Files matching `is_model_file` are only reused for the same model.
+fn parse_with_resolver<R: FileRefResolver + Sync>(
+    path: &str,
+    resolver: &R,
+    settings: &GeometrySettings,
+    source_map: &mut ldraw::SourceMap,
+    options: LoadOptions,
+    is_model_file: impl Fn(&str) -> bool,
+) -> Result<(String, usize), LoadError> {
+    // Only reuse library files from previous loads.
+    // Models may use the same names for different files like MPD blocks named "main.ldr".
+    source_map.remove_model_files(&is_model_file);
+    let file_count = source_map.len();
+
+    ensure_studs(settings, resolver, source_map);
+
+    let on_file_parsed = |count| options.progress(ProgressEvent::FileParsed { count });
+    let parse_options = ldraw::ParseOptions {
+        cancel: options.cancel,
+        on_file_parsed: options.on_progress.map(|_| &on_file_parsed as _),
     };
+    let main_model_name = match ldraw::parse_with_options(path, resolver, source_map, parse_options)
+    {
+        Ok(name) => name,
+        Err(ldraw::Error::Cancelled) => return Err(LoadError::Cancelled),
+        Err(e) => return Err(LoadError::Parse(e)),
+    };
+
+    ensure_substitutes(settings, resolver, source_map);
 
-    (source_map, main_model_name)
+    source_map.mark_model_files(path, &is_model_file);
+    let files_parsed = source_map.len() - file_count;
+
+    match options.main_model {
+        Some(name) if source_map.get(name).is_some() => Ok((name.to_string(), files_parsed)),
+        Some(name) => {
+            log::warn!("Unable to find main model {name:?} in {path:?}");
+            Ok((main_model_name, files_parsed))
+        }
+        None => Ok((main_model_name, files_parsed)),
+    }
 }
 
-fn ensure_studs(
+fn ensure_studs<R: FileRefResolver + Sync>(
     settings: &GeometrySettings,
-    resolver: &DiskResolver,
+    resolver: &R,
     source_map: &mut ldraw::SourceMap,
 ) {
     // The replaced studs likely won't be referenced by existing files.
     // Make sure the selected stud type is in the source map.
     if settings.stud_type == StudType::Logo4 {
-        ldraw::parse("stud-logo4.dat", resolver, source_map).unwrap();
-        ldraw::parse("stud2-logo4.dat", resolver, source_map).unwrap();
+        let missing: Vec<_> = ["stud-logo4.dat", "stud2-logo4.dat"]
+            .into_iter()
+            .filter(|name| {
+                // Resolvers may return an error or an empty file for missing files.
+                let _ = ldraw::parse(name, resolver, source_map);
+                source_map.get(name).is_none_or(|f| f.cmds.is_empty())
+            })
+            .collect();
+        if !missing.is_empty() {
+            log::warn!("Using normal studs since {missing:?} could not be found in the library");
+        }
     }
 }
 
+fn ensure_substitutes<R: FileRefResolver + Sync>(
+    settings: &GeometrySettings,
+    resolver: &R,
+    source_map: &mut ldraw::SourceMap,
+) {
+    // Only load substitutes for files that are actually referenced.
+    let substitutes: Vec<_> = settings
+        .part_substitutions
+        .iter()
+        .filter(|(original, _)| source_map.get(original).is_some())
+        .map(|(_, substitute)| substitute.clone())
+        .collect();
+    for substitute in substitutes {
+        if let Err(e) = ldraw::parse(&substitute, resolver, source_map) {
+            log::warn!("Error loading substitute {substitute:?}: {e}");
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn load_node<'a>(
     source_file: &'a ldraw::SourceFile,
     filename: &str,
     transform: &Mat4,
+    parent_mirrored: bool,
     source_map: &'a ldraw::SourceMap,
     geometry_descriptors: &mut HashMap<String, GeometryInitDescriptor<'a>>,
     current_color: ColorCode,
+    depth: usize,
     settings: &GeometrySettings,
 ) -> LDrawNode {
     let mut children = Vec::new();
     let mut geometry_name = None;
 
+    // Mirroring a mirrored node cancels out.
+    let is_mirrored = parent_mirrored != (transform.determinant() < 0.0);
+
     if is_part(source_file, filename) || has_geometry(source_file) {
         // Create geometry if the node is a part.
         // Use the special color code to reuse identical parts in different colors.
@@ -354,27 +1350,35 @@ fn load_node<'a>(
 
         geometry_name = Some(filename.to_lowercase());
     } else {
-        for cmd in &source_file.cmds {
-            if let Command::SubFileRef(sfr_cmd) = cmd {
-                if let Some(subfile) = source_map.get(&sfr_cmd.file) {
-                    // Don't apply node transforms to preserve the scene hierarchy.
-                    // Applications should handle combining the transforms.
-                    let child_transform = sfr_cmd.transform.to_matrix();
-
-                    // Handle replacing colors.
-                    let child_color = replace_color(sfr_cmd.color, current_color);
-
-                    let child_node = load_node(
-                        subfile,
-                        &sfr_cmd.file,
-                        &child_transform,
-                        source_map,
-                        geometry_descriptors,
-                        child_color,
-                        settings,
-                    );
-                    children.push(child_node);
-                }
+        for (sfr_cmd, piece_metadata) in piece_refs(source_file) {
+            if piece_metadata.is_hidden && !settings.import_hidden {
+                continue;
+            }
+            let subfilename = substitute_part(&sfr_cmd.file, source_map, settings);
+            if exceeds_max_depth(subfilename, depth, settings) {
+                continue;
+            }
+            if let Some(subfile) = source_map.get(subfilename) {
+                // Don't apply node transforms to preserve the scene hierarchy.
+                // Applications should handle combining the transforms.
+                let child_transform = sfr_cmd.transform.to_matrix();
+
+                // Handle replacing colors.
+                let child_color = replace_color(sfr_cmd.color, current_color);
+
+                let mut child_node = load_node(
+                    subfile,
+                    subfilename,
+                    &child_transform,
+                    is_mirrored,
+                    source_map,
+                    geometry_descriptors,
+                    child_color,
+                    depth + 1,
+                    settings,
+                );
+                child_node.piece_metadata = piece_metadata;
+                children.push(child_node);
             }
         }
     }
@@ -383,43 +1387,177 @@ fn load_node<'a>(
 
     LDrawNode {
         name: filename.to_string(),
+        unique_name: filename.to_string(),
         transform,
         geometry_name,
         current_color,
+        is_mirrored,
+        rgba_linear: None,
+        piece_metadata: PieceMetadata::default(),
         children,
     }
 }
 
+/// The subfile references in `source_file` with the properties
+/// from the [Command::LeoCadPiece] commands before each reference.
+fn piece_refs(
+    source_file: &ldraw::SourceFile,
+) -> impl Iterator<Item = (&ldraw::SubFileRefCmd, PieceMetadata)> {
+    let mut piece_metadata = PieceMetadata::default();
+    source_file.cmds.iter().filter_map(move |cmd| match cmd {
+        Command::LeoCadPiece(ldraw::LeoCadPieceCmd::StepHide(step)) => {
+            piece_metadata.step_hide = Some(*step);
+            None
+        }
+        Command::LeoCadPiece(ldraw::LeoCadPieceCmd::Hidden) => {
+            piece_metadata.is_hidden = true;
+            None
+        }
+        // Piece properties only apply to the next subfile reference.
+        Command::SubFileRef(sfr_cmd) => Some((sfr_cmd, std::mem::take(&mut piece_metadata))),
+        _ => None,
+    })
+}
+
+fn assign_unique_names(node: &mut LDrawNode, name_counts: &mut HashMap<String, usize>) {
+    let count = name_counts.entry(node.name.clone()).or_default();
+    if *count > 0 {
+        node.unique_name = format!("{}.{:03}", node.name, count);
+    }
+    *count += 1;
+
+    for child in &mut node.children {
+        assign_unique_names(child, name_counts);
+    }
+}
+
 #[tracing::instrument]
 fn create_geometry_cache(
     geometry_descriptors: HashMap<String, GeometryInitDescriptor>,
     source_map: &ldraw::SourceMap,
     settings: &GeometrySettings,
-) -> HashMap<String, LDrawGeometry> {
-    // Create the actual geometry in parallel to improve performance.
-    // TODO: The workload is incredibly uneven across threads.
-    geometry_descriptors
-        .into_par_iter()
+    options: LoadOptions,
+) -> Result<BTreeMap<String, LDrawGeometry>, LoadError> {
+    // A single large part can take much longer than many small parts.
+    // Start the most expensive parts first so smaller parts can fill in the gaps.
+    let mut costs = HashMap::new();
+    let mut descriptors: Vec<_> = geometry_descriptors
+        .into_iter()
         .map(|(name, descriptor)| {
-            let GeometryInitDescriptor {
-                source_file,
-                current_color,
-                recursive,
-            } = descriptor;
-
-            let geometry = create_geometry(
-                source_file,
+            let cost = estimated_cost(
+                descriptor.source_file,
+                descriptor.recursive,
                 source_map,
-                &name,
-                current_color,
-                recursive,
-                settings,
+                &mut costs,
             );
-
-            (name, geometry)
+            (cost, name, descriptor)
         })
-        .collect()
-}
+        .collect();
+    descriptors.sort_by(|(a, _, _), (b, _, _)| b.cmp(a));
+
+    let total = descriptors.len();
+    let created_count = AtomicUsize::new(0);
+
+    let create = |(_, name, descriptor): (usize, String, GeometryInitDescriptor)| {
+        // Check for cancellation between parts.
+        if options.is_cancelled() {
+            return Err(LoadError::Cancelled);
+        }
+
+        let GeometryInitDescriptor {
+            source_file,
+            current_color,
+            recursive,
+        } = descriptor;
+
+        let geometry = create_geometry(
+            source_file,
+            source_map,
+            &name,
+            current_color,
+            recursive,
+            settings,
+        );
+
+        if options.on_progress.is_some() {
+            let index = created_count.fetch_add(1, Ordering::Relaxed) + 1;
+            options.progress(ProgressEvent::GeometryCreated {
+                name: &name,
+                index,
+                total,
+            });
+        }
+
+        Ok((name, geometry))
+    };
+
+    #[cfg(feature = "parallel")]
+    if !settings.single_threaded {
+        // Create the actual geometry in parallel to improve performance.
+        // Each part is a separate job to keep expensive parts from being grouped together.
+        return descriptors
+            .into_par_iter()
+            .with_max_len(1)
+            .map(create)
+            .collect();
+    }
+
+    descriptors.into_iter().map(create).collect()
+}
+
+/// Estimate the work to create geometry as the total number of commands including subfiles.
+fn estimated_cost(
+    source_file: &ldraw::SourceFile,
+    recursive: bool,
+    source_map: &ldraw::SourceMap,
+    costs: &mut HashMap<String, usize>,
+) -> usize {
+    let mut cost = source_file.cmds.len();
+    if recursive {
+        for cmd in &source_file.cmds {
+            if let Command::SubFileRef(sfr_cmd) = cmd {
+                let key = sfr_cmd.file.to_lowercase();
+                cost += match costs.get(&key) {
+                    Some(subfile_cost) => *subfile_cost,
+                    None => match source_map.get(&sfr_cmd.file) {
+                        Some(subfile) => {
+                            // Insert a placeholder first to avoid infinite recursion.
+                            costs.insert(key.clone(), 0);
+                            let subfile_cost = estimated_cost(subfile, true, source_map, costs);
+                            costs.insert(key, subfile_cost);
+                            subfile_cost
+                        }
+                        None => 0,
+                    },
+                };
+            }
+        }
+    }
+    cost
+}
+
+/// Run `op` in a thread pool with the thread count from `settings`.
+#[cfg(feature = "parallel")]
+fn with_thread_pool<T: Send>(settings: &GeometrySettings, op: impl FnOnce() -> T + Send) -> T {
+    match settings.thread_count {
+        // Avoid creating a new pool when already running in a matching pool.
+        Some(count) if count != rayon::current_num_threads() => {
+            match rayon::ThreadPoolBuilder::new().num_threads(count).build() {
+                Ok(pool) => pool.install(op),
+                Err(e) => {
+                    log::warn!("Error creating thread pool: {e}");
+                    op()
+                }
+            }
+        }
+        _ => op(),
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+fn with_thread_pool<T: Send>(_settings: &GeometrySettings, op: impl FnOnce() -> T + Send) -> T {
+    op()
+}
 
 fn scaled_transform(transform: &Mat4, scale: f32) -> Mat4 {
     // Only scale the translation so that the scale doesn't accumulate.
@@ -429,6 +1567,7 @@ fn scaled_transform(transform: &Mat4, scale: f32) -> Mat4 {
     transform
 }
 
+#[cfg(feature = "fs")]
 #[tracing::instrument]
 pub fn load_file_instanced_points(
     path: &str,
@@ -436,24 +1575,69 @@ pub fn load_file_instanced_points(
     additional_paths: &[String],
     settings: &GeometrySettings,
 ) -> LDrawSceneInstancedPoints {
-    let scene = load_file_instanced(path, ldraw_path, additional_paths, settings);
-
-    let geometry_point_instances = scene
-        .geometry_world_transforms
-        .into_par_iter()
-        .map(|(k, transforms)| {
-            let instances = geometry_point_instances(transforms);
-            (k, instances)
-        })
-        .collect();
+    try_load_file_instanced_points(
+        path,
+        ldraw_path,
+        additional_paths,
+        settings,
+        &mut ldraw::SourceMap::new(),
+        LoadOptions::default(),
+    )
+    .unwrap()
+}
 
-    LDrawSceneInstancedPoints {
-        main_model_name: scene.main_model_name,
-        geometry_point_instances,
-        geometry_cache: scene.geometry_cache,
-    }
+/// Same as [load_file_instanced_points] but with additional `options` for controlling the load.
+///
+/// Parsed files are added to `source_map` and reused without resolving them again.
+/// Files local to the model like MPD blocks or files in the model's folder are parsed again for each call.
+/// Only reuse `source_map` for calls with the same `ldraw_path`, `additional_paths`, and settings.
+#[cfg(feature = "fs")]
+#[tracing::instrument(skip(source_map))]
+pub fn try_load_file_instanced_points(
+    path: &str,
+    ldraw_path: &str,
+    additional_paths: &[String],
+    settings: &GeometrySettings,
+    source_map: &mut ldraw::SourceMap,
+    options: LoadOptions,
+) -> Result<LDrawSceneInstancedPoints, LoadError> {
+    with_thread_pool(settings, || {
+        let scene = try_load_file_instanced(
+            path,
+            ldraw_path,
+            additional_paths,
+            settings,
+            source_map,
+            options,
+        )?;
+
+        let start = Instant::now();
+        #[cfg(feature = "parallel")]
+        let transforms = scene.geometry_world_transforms.into_par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let transforms = scene.geometry_world_transforms.into_iter();
+
+        let geometry_point_instances = transforms
+            .map(|(k, transforms)| {
+                let instances = geometry_point_instances(transforms);
+                (k, instances)
+            })
+            .collect();
+
+        let mut stats = scene.stats;
+        stats.geometry_time += start.elapsed();
+
+        Ok(LDrawSceneInstancedPoints {
+            main_model_name: scene.main_model_name,
+            geometry_point_instances,
+            geometry_cache: scene.geometry_cache,
+            custom_colors: scene.custom_colors,
+            stats,
+        })
+    })
 }
 
+#[cfg(feature = "fs")]
 #[tracing::instrument]
 fn geometry_point_instances(transforms: Vec<Mat4>) -> PointInstances {
     let mut translations = Vec::new();
@@ -461,7 +1645,7 @@ fn geometry_point_instances(transforms: Vec<Mat4>) -> PointInstances {
     let mut rotations_angle = Vec::new();
     let mut scales = Vec::new();
 
-    for transform in transforms {
+    for transform in &transforms {
         let (s, r, t) = transform.to_scale_rotation_translation();
 
         translations.push(t);
@@ -480,6 +1664,7 @@ fn geometry_point_instances(transforms: Vec<Mat4>) -> PointInstances {
         rotations_axis,
         rotations_angle,
         scales,
+        transforms,
     }
 }
 
@@ -487,6 +1672,7 @@ fn geometry_point_instances(transforms: Vec<Mat4>) -> PointInstances {
 /// Find the world transforms for each geometry.
 /// This allows applications to more easily use instancing.
 // TODO: Take AsRef<Path> instead?
+#[cfg(feature = "fs")]
 #[tracing::instrument]
 pub fn load_file_instanced(
     path: &str,
@@ -494,42 +1680,160 @@ pub fn load_file_instanced(
     additional_paths: &[String],
     settings: &GeometrySettings,
 ) -> LDrawSceneInstanced {
-    let (source_map, main_model_name) = parse_file(path, ldraw_path, additional_paths, settings);
-    let source_file = source_map.get(&main_model_name).unwrap();
+    try_load_file_instanced(
+        path,
+        ldraw_path,
+        additional_paths,
+        settings,
+        &mut ldraw::SourceMap::new(),
+        LoadOptions::default(),
+    )
+    .unwrap()
+}
+
+/// Same as [load_file_instanced] but with additional `options` for controlling the load.
+///
+/// Parsed files are added to `source_map` and reused without resolving them again.
+/// Files local to the model like MPD blocks or files in the model's folder are parsed again for each call.
+/// Only reuse `source_map` for calls with the same `ldraw_path`, `additional_paths`, and settings.
+#[cfg(feature = "fs")]
+#[tracing::instrument(skip(source_map))]
+pub fn try_load_file_instanced(
+    path: &str,
+    ldraw_path: &str,
+    additional_paths: &[String],
+    settings: &GeometrySettings,
+    source_map: &mut ldraw::SourceMap,
+    options: LoadOptions,
+) -> Result<LDrawSceneInstanced, LoadError> {
+    with_thread_pool(settings, || {
+        let start = Instant::now();
+        let (main_model_name, files_parsed) = parse_file(
+            path,
+            ldraw_path,
+            additional_paths,
+            settings,
+            source_map,
+            options,
+        )?;
+        let parse_time = start.elapsed();
+
+        load_scene_instanced(
+            main_model_name,
+            source_map,
+            settings,
+            options,
+            files_parsed,
+            parse_time,
+        )
+    })
+}
+
+/// Same as [try_load_file_instanced] but resolves all files including `path` using `resolver`.
+///
+/// This does not access the file system and is available without the `fs` feature.
+/// Only `path` and its MPD blocks are parsed again when reusing `source_map`.
+#[tracing::instrument(skip(resolver, source_map))]
+pub fn try_load_instanced_with_resolver<R: FileRefResolver + Sync>(
+    path: &str,
+    resolver: &R,
+    settings: &GeometrySettings,
+    source_map: &mut ldraw::SourceMap,
+    options: LoadOptions,
+) -> Result<LDrawSceneInstanced, LoadError> {
+    with_thread_pool(settings, || {
+        let start = Instant::now();
+        let (main_model_name, files_parsed) =
+            parse_with_resolver(path, resolver, settings, source_map, options, |_| false)?;
+        let parse_time = start.elapsed();
+
+        load_scene_instanced(
+            main_model_name,
+            source_map,
+            settings,
+            options,
+            files_parsed,
+            parse_time,
+        )
+    })
+}
+
+fn load_scene_instanced(
+    main_model_name: String,
+    source_map: &ldraw::SourceMap,
+    settings: &GeometrySettings,
+    options: LoadOptions,
+    files_parsed: usize,
+    parse_time: Duration,
+) -> Result<LDrawSceneInstanced, LoadError> {
+    let start = Instant::now();
+    let empty = ldraw::SourceFile { cmds: Vec::new() };
+    let source_file = main_model(source_map, &main_model_name, &empty);
 
     // Find the world transforms for each geometry.
     // This allows applications to more easily use instancing.
     let mut geometry_descriptors = HashMap::new();
-    let mut geometry_world_transforms = HashMap::new();
+    let mut instances = Vec::new();
     load_node_instanced(
         source_file,
         &main_model_name,
         &Mat4::IDENTITY,
-        &source_map,
+        source_map,
         &mut geometry_descriptors,
-        &mut geometry_world_transforms,
-        CURRENT_COLOR,
+        &mut instances,
+        settings.root_color,
+        0,
         settings,
     );
 
-    let geometry_cache = create_geometry_cache(geometry_descriptors, &source_map, settings);
+    let mut geometry_cache =
+        create_geometry_cache(geometry_descriptors, source_map, settings, options)?;
+
+    // Rename before grouping to keep the transforms for merged geometry in file order.
+    let renamed = if settings.deduplicate_geometry {
+        dedup::deduplicate_geometry(&mut geometry_cache)
+    } else {
+        HashMap::new()
+    };
+    let mut geometry_world_transforms = group_instances(instances, &renamed);
+
+    if settings.remove_hidden_studs {
+        hidden::remove_hidden_studs(
+            &mut geometry_world_transforms,
+            &mut geometry_cache,
+            settings.scene_scale,
+        );
+    }
+
+    if settings.separate_studs {
+        let separated = separate::separate_studs(&mut geometry_cache);
+        separate::add_stud_instances(&mut geometry_world_transforms, &separated);
+    }
 
-    LDrawSceneInstanced {
+    let custom_colors = color::model_colors(source_map, &main_model_name);
+
+    let stats = LoadStats::new(files_parsed, &geometry_cache, parse_time, start.elapsed());
+
+    Ok(LDrawSceneInstanced {
         main_model_name,
         geometry_world_transforms,
         geometry_cache,
-    }
+        custom_colors,
+        stats,
+    })
 }
 
 // TODO: Share code with the non instanced function?
+#[allow(clippy::too_many_arguments)]
 fn load_node_instanced<'a>(
     source_file: &'a ldraw::SourceFile,
     filename: &str,
     world_transform: &Mat4,
     source_map: &'a ldraw::SourceMap,
     geometry_descriptors: &mut HashMap<String, GeometryInitDescriptor<'a>>,
-    geometry_world_transforms: &mut HashMap<(String, ColorCode), Vec<Mat4>>,
+    instances: &mut Vec<((String, ColorCode), Mat4)>,
     current_color: ColorCode,
+    depth: usize,
     settings: &GeometrySettings,
 ) {
     // TODO: Find a way to avoid repetition.
@@ -547,10 +1851,10 @@ fn load_node_instanced<'a>(
 
         // Add another instance of the current geometry.
         // Also key by the color in case a part appears in multiple colors.
-        geometry_world_transforms
-            .entry((filename.to_lowercase(), current_color))
-            .or_default()
-            .push(scaled_transform(world_transform, settings.scene_scale));
+        instances.push((
+            (filename.to_lowercase(), current_color),
+            scaled_transform(world_transform, settings.scene_scale),
+        ));
     } else if has_geometry(source_file) {
         // Just add geometry for this node.
         // Use the current color at this node since this geometry might not be referenced elsewhere.
@@ -564,44 +1868,104 @@ fn load_node_instanced<'a>(
 
         // Add another instance of the current geometry.
         // Also key by the color in case a part appears in multiple colors.
-        geometry_world_transforms
-            .entry((filename.to_lowercase(), current_color))
-            .or_default()
-            .push(scaled_transform(world_transform, settings.scene_scale));
+        instances.push((
+            (filename.to_lowercase(), current_color),
+            scaled_transform(world_transform, settings.scene_scale),
+        ));
     }
 
     // Recursion is already handled for parts.
     if !is_part {
-        for cmd in &source_file.cmds {
-            if let Command::SubFileRef(sfr_cmd) = cmd {
-                if let Some(subfile) = source_map.get(&sfr_cmd.file) {
-                    // Accumulate transforms.
-                    let child_transform = *world_transform * sfr_cmd.transform.to_matrix();
-
-                    // Handle replacing colors.
-                    let child_color = replace_color(sfr_cmd.color, current_color);
-
-                    load_node_instanced(
-                        subfile,
-                        &sfr_cmd.file,
-                        &child_transform,
-                        source_map,
-                        geometry_descriptors,
-                        geometry_world_transforms,
-                        child_color,
-                        settings,
-                    );
-                }
+        for (sfr_cmd, piece_metadata) in piece_refs(source_file) {
+            if piece_metadata.is_hidden && !settings.import_hidden {
+                continue;
+            }
+            let subfilename = substitute_part(&sfr_cmd.file, source_map, settings);
+            if exceeds_max_depth(subfilename, depth, settings) {
+                continue;
+            }
+            if let Some(subfile) = source_map.get(subfilename) {
+                // Accumulate transforms.
+                let child_transform = *world_transform * sfr_cmd.transform.to_matrix();
+
+                // Handle replacing colors.
+                let child_color = replace_color(sfr_cmd.color, current_color);
+
+                load_node_instanced(
+                    subfile,
+                    subfilename,
+                    &child_transform,
+                    source_map,
+                    geometry_descriptors,
+                    instances,
+                    child_color,
+                    depth + 1,
+                    settings,
+                );
             }
         }
     }
 }
 
+/// Group the transforms for each geometry name and color in the order they appear in the file.
+/// Instances of `renamed` geometry use the remaining geometry name instead.
+fn group_instances(
+    instances: Vec<((String, ColorCode), Mat4)>,
+    renamed: &HashMap<String, String>,
+) -> BTreeMap<(String, ColorCode), Vec<Mat4>> {
+    let mut geometry_world_transforms: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for ((name, color), transform) in instances {
+        let name = renamed.get(&name).cloned().unwrap_or(name);
+        geometry_world_transforms
+            .entry((name, color))
+            .or_default()
+            .push(transform);
+    }
+    geometry_world_transforms
+}
+
+fn exceeds_max_depth(filename: &str, depth: usize, settings: &GeometrySettings) -> bool {
+    let exceeded = depth >= settings.max_recursion_depth;
+    if exceeded {
+        log::warn!(
+            "Skipping {filename:?} after exceeding the maximum recursion depth of {}",
+            settings.max_recursion_depth
+        );
+    }
+    exceeded
+}
+
 fn is_part(_source_file: &ldraw::SourceFile, filename: &str) -> bool {
     // TODO: Check the part type rather than the extension.
     filename.to_lowercase().ends_with(".dat")
 }
 
+/// Find the main model or `empty` if the file doesn't contain a usable model.
+fn main_model<'a>(
+    source_map: &'a ldraw::SourceMap,
+    main_model_name: &str,
+    empty: &'a ldraw::SourceFile,
+) -> &'a ldraw::SourceFile {
+    match source_map.get(main_model_name) {
+        Some(source_file) => {
+            let is_empty = !source_file.cmds.iter().any(|c| {
+                matches!(
+                    c,
+                    Command::SubFileRef(_) | Command::Triangle(_) | Command::Quad(_)
+                )
+            });
+            if is_empty {
+                log::warn!("No parts or geometry found in {main_model_name:?}");
+            }
+            source_file
+        }
+        None => {
+            log::warn!("Unable to find main model {main_model_name:?}");
+            empty
+        }
+    }
+}
+
 fn has_geometry(source_file: &ldraw::SourceFile) -> bool {
     // Some files have subfile ref commands but also define parts inline.
     // This includes tube segments on the Volkswagen Beetle.mpd
@@ -613,12 +1977,15 @@ fn has_geometry(source_file: &ldraw::SourceFile) -> bool {
 
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "fs")]
     use approx::assert_relative_eq;
+    #[cfg(feature = "fs")]
     use glam::vec3;
 
     use super::*;
 
     #[test]
+    #[cfg(feature = "fs")]
     fn geometry_point_instances_flip() {
         // Some LDraw models use negative scaling.
         // Test that decomposed transforms are correct.
@@ -641,8 +2008,9 @@ mod tests {
             .transpose(),
         ];
 
-        let instances = geometry_point_instances(transforms);
+        let instances = geometry_point_instances(transforms.clone());
 
+        assert_eq!(transforms, instances.transforms);
         assert_relative_eq!(instances.rotations_axis[0].to_array()[..], [0.0, 1.0, 0.0]);
         assert_relative_eq!(instances.rotations_axis[1].to_array()[..], [0.0, 1.0, 0.0]);
 
@@ -654,4 +2022,1212 @@ mod tests {
             vec![vec3(1.0, 1.0, 1.0), vec3(-1.0, 1.0, 1.0)]
         );
     }
+
+    #[test]
+    fn estimated_cost_subfiles() {
+        let mut source_map = ldraw::SourceMap::new();
+        let a = ldraw::SourceFile {
+            cmds: ldraw::parse_raw(
+                b"1 16 0 0 0 1 0 0 0 1 0 0 0 1 b.dat\n1 16 0 0 0 1 0 0 0 1 0 0 0 1 b.dat",
+            )
+            .unwrap(),
+        };
+        let b = ldraw::SourceFile {
+            cmds: ldraw::parse_raw(b"3 16 1 0 0 0 1 0 0 0 1\n3 16 1 0 0 0 1 0 0 0 1").unwrap(),
+        };
+        source_map.insert("b.dat", b);
+
+        let mut costs = HashMap::new();
+        assert_eq!(6, estimated_cost(&a, true, &source_map, &mut costs));
+        assert_eq!(2, estimated_cost(&a, false, &source_map, &mut costs));
+    }
+
+    #[test]
+    fn load_node_instanced_sorted_keys() {
+        let mut source_map = ldraw::SourceMap::new();
+        let main = ldraw::SourceFile {
+            cmds: ldraw::parse_raw(indoc::indoc! {b"
+                1 4 0 0 0 1 0 0 0 1 0 0 0 1 c.dat
+                1 2 1 0 0 1 0 0 0 1 0 0 0 1 a.dat
+                1 1 2 0 0 1 0 0 0 1 0 0 0 1 b.dat
+                1 1 3 0 0 1 0 0 0 1 0 0 0 1 a.dat
+                1 2 4 0 0 1 0 0 0 1 0 0 0 1 a.dat
+            "})
+            .unwrap(),
+        };
+        for name in ["a.dat", "b.dat", "c.dat"] {
+            let part = ldraw::SourceFile {
+                cmds: ldraw::parse_raw(b"3 16 1 0 0 0 1 0 0 0 1").unwrap(),
+            };
+            source_map.insert(name, part);
+        }
+
+        let settings = GeometrySettings::default();
+        let mut geometry_descriptors = HashMap::new();
+        let mut instances = Vec::new();
+        load_node_instanced(
+            &main,
+            "main.ldr",
+            &Mat4::IDENTITY,
+            &source_map,
+            &mut geometry_descriptors,
+            &mut instances,
+            CURRENT_COLOR,
+            0,
+            &settings,
+        );
+        let geometry_world_transforms = group_instances(instances, &HashMap::new());
+        let geometry_cache = create_geometry_cache(
+            geometry_descriptors,
+            &source_map,
+            &settings,
+            LoadOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec!["a.dat", "b.dat", "c.dat"],
+            geometry_cache.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                (("a.dat".to_string(), 1), vec![3.0]),
+                (("a.dat".to_string(), 2), vec![1.0, 4.0]),
+                (("b.dat".to_string(), 1), vec![2.0]),
+                (("c.dat".to_string(), 4), vec![0.0]),
+            ],
+            geometry_world_transforms
+                .into_iter()
+                .map(|(k, v)| (k, v.iter().map(|t| t.w_axis.x).collect::<Vec<_>>()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn flatten_scene() {
+        let mut source_map = ldraw::SourceMap::new();
+        let main = ldraw::SourceFile {
+            cmds: ldraw::parse_raw(indoc::indoc! {b"
+                1 4 0 0 0 1 0 0 0 1 0 0 0 1 a.dat
+                1 1 10 0 0 1 0 0 0 1 0 0 0 1 a.dat
+            "})
+            .unwrap(),
+        };
+        source_map.insert(
+            "a.dat",
+            ldraw::SourceFile {
+                cmds: ldraw::parse_raw(b"3 16 1 0 0 0 1 0 0 0 1\n2 24 1 0 0 0 1 0").unwrap(),
+            },
+        );
+
+        let settings = GeometrySettings {
+            weld_vertices: true,
+            ..Default::default()
+        };
+        let mut geometry_descriptors = HashMap::new();
+        let root_node = load_node(
+            &main,
+            "main.ldr",
+            &Mat4::IDENTITY,
+            false,
+            &source_map,
+            &mut geometry_descriptors,
+            CURRENT_COLOR,
+            0,
+            &settings,
+        );
+        let geometry_cache = create_geometry_cache(
+            geometry_descriptors,
+            &source_map,
+            &settings,
+            LoadOptions::default(),
+        )
+        .unwrap();
+        let scene = LDrawScene {
+            main_model_name: String::new(),
+            root_node,
+            geometry_cache,
+            custom_colors: HashMap::new(),
+            stats: LoadStats::default(),
+        };
+
+        let geometry = scene.flatten();
+        assert_eq!(6, geometry.vertices.len());
+        assert_eq!(Vec3::new(11.0, 0.0, 0.0), geometry.vertices[3]);
+        assert_eq!(vec![4, 1], geometry.face_colors);
+        assert_eq!(vec![24, 24], geometry.edge_line_colors);
+    }
+
+    #[test]
+    fn scene_flat_instances() {
+        let resolver = MemoryResolver(HashMap::from([
+            (
+                "main.ldr",
+                indoc::indoc! {"
+                    1 4 0 -8 0 1 0 0 0 1 0 0 0 1 a.dat
+                    1 4 0 -8 0 1 0 0 0 1 0 0 0 1 a.dat
+                    1 1 10 0 0 1 0 0 0 1 0 0 0 1 b.ldr
+                "},
+            ),
+            ("b.ldr", "1 16 0 0 20 -1 0 0 0 1 0 0 0 1 a.dat\n"),
+            ("a.dat", "3 16 0 0 0 10 0 0 0 -4 0\n"),
+        ]));
+        let scene = try_load_with_resolver(
+            "main.ldr",
+            &resolver,
+            &GeometrySettings {
+                scene_scale: 0.5,
+                ..Default::default()
+            },
+            &mut ldraw::SourceMap::new(),
+            LoadOptions::default(),
+        )
+        .unwrap();
+
+        // Each instance is listed separately with the scale only applied once.
+        assert_eq!(
+            vec![
+                (
+                    "a.dat".to_string(),
+                    Mat4::from_translation(Vec3::new(0.0, -4.0, 0.0)),
+                    4
+                ),
+                (
+                    "a.dat".to_string(),
+                    Mat4::from_translation(Vec3::new(0.0, -4.0, 0.0)),
+                    4
+                ),
+                (
+                    "a.dat".to_string(),
+                    Mat4::from_translation(Vec3::new(5.0, 0.0, 10.0))
+                        * Mat4::from_scale(Vec3::new(-1.0, 1.0, 1.0)),
+                    1
+                ),
+            ],
+            scene.flat_instances()
+        );
+    }
+
+    #[test]
+    fn scene_ground_transform() {
+        let resolver = MemoryResolver(HashMap::from([
+            (
+                "main.ldr",
+                indoc::indoc! {"
+                    1 4 0 -8 0 1 0 0 0 1 0 0 0 1 a.dat
+                    1 4 30 -2 0 1 0 0 0 1 0 0 0 1 a.dat
+                "},
+            ),
+            ("a.dat", "3 16 0 0 0 10 0 0 0 -4 0\n"),
+        ]));
+
+        let scene = try_load_with_resolver(
+            "main.ldr",
+            &resolver,
+            &GeometrySettings::default(),
+            &mut ldraw::SourceMap::new(),
+            LoadOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some([Vec3::new(0.0, -12.0, 0.0), Vec3::new(40.0, -2.0, 0.0)]),
+            scene.bounds()
+        );
+        assert_eq!(
+            Mat4::from_translation(Vec3::new(0.0, 2.0, 0.0)),
+            scene.ground_transform(false)
+        );
+        assert_eq!(
+            Mat4::from_translation(Vec3::new(-20.0, 2.0, 0.0)),
+            scene.ground_transform(true)
+        );
+    }
+
+    #[test]
+    fn load_node_max_recursion_depth() {
+        // Each submodel references the next one.
+        let mut source_map = ldraw::SourceMap::new();
+        for i in 0..10 {
+            let cmds = format!("1 16 0 0 0 1 0 0 0 1 0 0 0 1 {}.ldr", i + 1);
+            source_map.insert(
+                &format!("{i}.ldr"),
+                ldraw::SourceFile {
+                    cmds: ldraw::parse_raw(cmds.as_bytes()).unwrap(),
+                },
+            );
+        }
+        let settings = GeometrySettings {
+            max_recursion_depth: 4,
+            ..Default::default()
+        };
+
+        let mut node = load_node(
+            source_map.get("0.ldr").unwrap(),
+            "0.ldr",
+            &Mat4::IDENTITY,
+            false,
+            &source_map,
+            &mut HashMap::new(),
+            CURRENT_COLOR,
+            0,
+            &settings,
+        );
+
+        let mut depth = 0;
+        while let Some(child) = node.children.pop() {
+            node = child;
+            depth += 1;
+        }
+        assert_eq!(4, depth);
+        assert_eq!("4.ldr", node.name);
+    }
+
+    #[test]
+    fn load_node_instanced_recursive_reference() {
+        let mut source_map = ldraw::SourceMap::new();
+        source_map.insert(
+            "main.ldr",
+            ldraw::SourceFile {
+                cmds: ldraw::parse_raw(indoc::indoc! {b"
+                    1 16 0 0 0 1 0 0 0 1 0 0 0 1 a.dat
+                    1 16 0 0 0 1 0 0 0 1 0 0 0 1 main.ldr
+                "})
+                .unwrap(),
+            },
+        );
+        source_map.insert(
+            "a.dat",
+            ldraw::SourceFile {
+                cmds: ldraw::parse_raw(b"3 16 1 0 0 0 1 0 0 0 1").unwrap(),
+            },
+        );
+
+        let mut instances = Vec::new();
+        load_node_instanced(
+            source_map.get("main.ldr").unwrap(),
+            "main.ldr",
+            &Mat4::IDENTITY,
+            &source_map,
+            &mut HashMap::new(),
+            &mut instances,
+            CURRENT_COLOR,
+            0,
+            &GeometrySettings {
+                max_recursion_depth: 3,
+                ..Default::default()
+            },
+        );
+        let geometry_world_transforms = group_instances(instances, &HashMap::new());
+
+        // References at the maximum depth are skipped.
+        assert_eq!(
+            3,
+            geometry_world_transforms[&("a.dat".to_string(), 16)].len()
+        );
+    }
+
+    #[test]
+    fn load_node_is_mirrored() {
+        let mut source_map = ldraw::SourceMap::new();
+        let main = ldraw::SourceFile {
+            cmds: ldraw::parse_raw(indoc::indoc! {b"
+                1 16 0 0 0 -1 0 0 0 1 0 0 0 1 sub.ldr
+                1 16 0 0 0 1 0 0 0 1 0 0 0 1 a.dat
+            "})
+            .unwrap(),
+        };
+        source_map.insert(
+            "sub.ldr",
+            ldraw::SourceFile {
+                cmds: ldraw::parse_raw(indoc::indoc! {b"
+                    1 16 0 0 0 1 0 0 0 1 0 0 0 1 a.dat
+                    1 16 0 0 0 1 0 0 0 1 0 0 0 -1 a.dat
+                "})
+                .unwrap(),
+            },
+        );
+        source_map.insert(
+            "a.dat",
+            ldraw::SourceFile {
+                cmds: ldraw::parse_raw(b"3 16 1 0 0 0 1 0 0 0 1").unwrap(),
+            },
+        );
+
+        let node = load_node(
+            &main,
+            "main.ldr",
+            &Mat4::IDENTITY,
+            false,
+            &source_map,
+            &mut HashMap::new(),
+            CURRENT_COLOR,
+            0,
+            &GeometrySettings::default(),
+        );
+
+        assert!(!node.is_mirrored);
+        assert!(node.children[0].is_mirrored);
+        assert!(node.children[0].children[0].is_mirrored);
+        assert!(!node.children[0].children[1].is_mirrored);
+        assert!(!node.children[1].is_mirrored);
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn load_file_comments_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("comments_only.ldr");
+        std::fs::write(&path, "0 Untitled\n0 // comment\n").unwrap();
+
+        let scene = load_file(
+            path.to_str().unwrap(),
+            "",
+            &[],
+            &GeometrySettings::default(),
+        );
+
+        assert!(scene.root_node.children.is_empty());
+        assert_eq!(None, scene.root_node.geometry_name);
+        assert!(scene.geometry_cache.is_empty());
+    }
+
+    struct MemoryResolver(HashMap<&'static str, &'static str>);
+
+    impl FileRefResolver for MemoryResolver {
+        fn resolve<P: AsRef<std::path::Path>>(
+            &self,
+            filename: P,
+        ) -> Result<Vec<u8>, ldraw::ResolveError> {
+            let filename = filename.as_ref().to_str().unwrap();
+            Ok(self.0.get(filename).unwrap_or(&"").as_bytes().to_vec())
+        }
+    }
+
+    #[test]
+    fn try_load_with_resolver_missing_logo_studs() {
+        let files = HashMap::from([
+            ("main.ldr", "1 4 0 0 0 1 0 0 0 1 0 0 0 1 a.dat\n"),
+            (
+                "a.dat",
+                "1 16 0 0 0 1 0 0 0 1 0 0 0 1 stud.dat\n1 16 0 0 0 1 0 0 0 1 0 0 0 1 stud2.dat\n",
+            ),
+            ("stud.dat", "3 16 1 0 0 0 1 0 0 0 1\n"),
+            ("stud2.dat", "3 16 1 0 0 0 1 0 0 0 1\n"),
+        ]);
+        let settings = GeometrySettings {
+            stud_type: StudType::Logo4,
+            ..Default::default()
+        };
+        let load = |resolver: &MemoryResolver| {
+            try_load_with_resolver(
+                "main.ldr",
+                resolver,
+                &settings,
+                &mut ldraw::SourceMap::new(),
+                LoadOptions::default(),
+            )
+            .unwrap()
+        };
+
+        // Fall back to normal studs instead of empty geometry.
+        let scene = load(&MemoryResolver(files.clone()));
+        assert_eq!(vec![3, 3], scene.geometry_cache["a.dat"].face_sizes);
+
+        // Logo studs are only used for stud files in the library.
+        let mut files = files;
+        files.insert("stud-logo4.dat", "4 16 0 0 0 1 0 0 1 1 0 0 1 0\n");
+        let scene = load(&MemoryResolver(files));
+        assert_eq!(vec![4, 3], scene.geometry_cache["a.dat"].face_sizes);
+    }
+
+    #[test]
+    fn try_load_with_resolver_resolve_error() {
+        struct ErrorResolver;
+
+        impl FileRefResolver for ErrorResolver {
+            fn resolve<P: AsRef<std::path::Path>>(
+                &self,
+                filename: P,
+            ) -> Result<Vec<u8>, ldraw::ResolveError> {
+                match filename.as_ref().to_str().unwrap() {
+                    "main.ldr" => Ok(b"1 4 0 0 0 1 0 0 0 1 0 0 0 1 a.dat\n".to_vec()),
+                    name => Err(ldraw::ResolveError::new_raw(name)),
+                }
+            }
+        }
+
+        let result = try_load_with_resolver(
+            "main.ldr",
+            &ErrorResolver,
+            &GeometrySettings::default(),
+            &mut ldraw::SourceMap::new(),
+            LoadOptions::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(LoadError::Parse(ldraw::Error::Resolve(_)))
+        ));
+    }
+
+    #[test]
+    fn try_load_with_resolver_memory() {
+        let resolver = MemoryResolver(HashMap::from([
+            ("main.ldr", "1 4 0 0 0 1 0 0 0 1 0 0 0 1 a.dat\n"),
+            ("a.dat", "3 16 1 0 0 0 1 0 0 0 1\n"),
+        ]));
+
+        let scene = try_load_with_resolver(
+            "main.ldr",
+            &resolver,
+            &GeometrySettings::default(),
+            &mut ldraw::SourceMap::new(),
+            LoadOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(1, scene.root_node.children.len());
+        assert_eq!(4, scene.root_node.children[0].current_color);
+        assert_eq!(3, scene.geometry_cache["a.dat"].vertices.len());
+    }
+
+    #[test]
+    fn try_load_with_resolver_reuse_source_map() {
+        // Both models have a submodel named "sub.ldr" using the shared library part.
+        let resolver = MemoryResolver(HashMap::from([
+            (
+                "model1.mpd",
+                indoc::indoc! {"
+                    0 FILE main.ldr
+                    1 16 0 0 0 1 0 0 0 1 0 0 0 1 sub.ldr
+                    0 FILE sub.ldr
+                    1 4 0 0 0 1 0 0 0 1 0 0 0 1 a.dat
+                "},
+            ),
+            (
+                "model2.mpd",
+                indoc::indoc! {"
+                    0 FILE main.ldr
+                    1 16 0 0 0 1 0 0 0 1 0 0 0 1 sub.ldr
+                    0 FILE sub.ldr
+                    1 2 0 0 0 1 0 0 0 1 0 0 0 1 a.dat
+                "},
+            ),
+            ("a.dat", "3 16 1 0 0 0 1 0 0 0 1\n"),
+        ]));
+
+        let mut source_map = ldraw::SourceMap::new();
+        let mut load = |path| {
+            try_load_with_resolver(
+                path,
+                &resolver,
+                &GeometrySettings::default(),
+                &mut source_map,
+                LoadOptions::default(),
+            )
+            .unwrap()
+        };
+        let part_color = |scene: &LDrawScene| scene.root_node.children[0].children[0].current_color;
+
+        let scene1 = load("model1.mpd");
+        let scene2 = load("model2.mpd");
+        assert_eq!(4, part_color(&scene1));
+        assert_eq!(2, part_color(&scene2));
+
+        // Only the library part is reused.
+        assert_eq!(4, scene1.stats.files_parsed);
+        assert_eq!(3, scene2.stats.files_parsed);
+    }
+
+    #[test]
+    fn try_load_with_resolver_root_color() {
+        let resolver = MemoryResolver(HashMap::from([
+            (
+                "main.ldr",
+                "1 16 0 0 0 1 0 0 0 1 0 0 0 1 a.dat\n1 1 0 0 0 1 0 0 0 1 0 0 0 1 a.dat\n",
+            ),
+            ("a.dat", "3 16 1 0 0 0 1 0 0 0 1\n3 2 1 0 0 0 1 0 0 0 1\n"),
+        ]));
+        let settings = GeometrySettings {
+            root_color: 4,
+            ..Default::default()
+        };
+        let load = |path| {
+            try_load_with_resolver(
+                path,
+                &resolver,
+                &settings,
+                &mut ldraw::SourceMap::new(),
+                LoadOptions::default(),
+            )
+            .unwrap()
+        };
+
+        // Only faces and nodes using the current color inherit the root color.
+        let scene = load("main.ldr");
+        assert_eq!(4, scene.root_node.current_color);
+        assert_eq!(vec![4, 2, 1, 2], scene.flatten().face_colors);
+
+        // Standalone parts can be previewed in the root color.
+        let scene = load("a.dat");
+        assert_eq!(4, scene.root_node.current_color);
+        assert_eq!(vec![4, 2], scene.flatten().face_colors);
+    }
+
+    #[test]
+    fn try_load_with_resolver_main_model_name() {
+        let resolver = MemoryResolver(HashMap::from([(
+            "model.mpd",
+            indoc::indoc! {"
+                0 FILE main.ldr
+                1 4 0 0 0 1 0 0 0 1 0 0 0 1 a.dat
+
+                0 FILE a.dat
+                3 16 1 0 0 0 1 0 0 0 1
+            "},
+        )]));
+
+        let scene = try_load_with_resolver(
+            "model.mpd",
+            &resolver,
+            &GeometrySettings::default(),
+            &mut ldraw::SourceMap::new(),
+            LoadOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!("main.ldr", scene.main_model_name);
+    }
+
+    #[test]
+    fn try_load_with_resolver_main_model_option() {
+        let resolver = MemoryResolver(HashMap::from([(
+            "model.mpd",
+            indoc::indoc! {"
+                0 FILE a.ldr
+                1 16 0 0 0 1 0 0 0 1 0 0 0 1 b.dat
+
+                0 FILE main.ldr
+                1 4 0 0 0 1 0 0 0 1 0 0 0 1 a.ldr
+
+                0 FILE b.dat
+                3 16 1 0 0 0 1 0 0 0 1
+            "},
+        )]));
+        let load = |main_model| {
+            try_load_with_resolver(
+                "model.mpd",
+                &resolver,
+                &GeometrySettings::default(),
+                &mut ldraw::SourceMap::new(),
+                LoadOptions {
+                    main_model,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        // Detect the block not used by other blocks.
+        let scene = load(None);
+        assert_eq!("main.ldr", scene.main_model_name);
+        assert_eq!(vec![4], scene.flatten().face_colors);
+
+        let scene = load(Some("a.ldr"));
+        assert_eq!("a.ldr", scene.main_model_name);
+        assert_eq!(vec![16], scene.flatten().face_colors);
+
+        // Unknown names fall back to the detected main model.
+        let scene = load(Some("missing.ldr"));
+        assert_eq!("main.ldr", scene.main_model_name);
+    }
+
+    #[test]
+    fn try_load_with_resolver_piece_metadata() {
+        let resolver = MemoryResolver(HashMap::from([
+            (
+                "main.ldr",
+                indoc::indoc! {"
+                    0 !LEOCAD MODEL NAME House
+                    0 !LEOCAD PIECE STEP_HIDE 3
+                    0 !LEOCAD PIECE HIDDEN
+                    1 4 0 0 0 1 0 0 0 1 0 0 0 1 a.dat
+                    1 4 0 0 0 1 0 0 0 1 0 0 0 1 a.dat
+                "},
+            ),
+            ("a.dat", "3 16 1 0 0 0 1 0 0 0 1\n"),
+        ]));
+
+        let settings = GeometrySettings {
+            import_hidden: true,
+            ..Default::default()
+        };
+        let scene = try_load_with_resolver(
+            "main.ldr",
+            &resolver,
+            &settings,
+            &mut ldraw::SourceMap::new(),
+            LoadOptions::default(),
+        )
+        .unwrap();
+
+        // Properties only apply to the next piece.
+        let children = &scene.root_node.children;
+        assert_eq!(
+            PieceMetadata {
+                step_hide: Some(3),
+                is_hidden: true
+            },
+            children[0].piece_metadata
+        );
+        assert_eq!(PieceMetadata::default(), children[1].piece_metadata);
+    }
+
+    #[test]
+    fn try_load_with_resolver_import_hidden() {
+        let resolver = MemoryResolver(HashMap::from([
+            (
+                "main.ldr",
+                indoc::indoc! {"
+                    1 4 0 0 0 1 0 0 0 1 0 0 0 1 a.dat
+                    0 !LEOCAD PIECE HIDDEN
+                    1 4 1 0 0 1 0 0 0 1 0 0 0 1 sub.ldr
+                "},
+            ),
+            ("sub.ldr", "1 16 0 0 0 1 0 0 0 1 0 0 0 1 a.dat\n"),
+            ("a.dat", "3 16 1 0 0 0 1 0 0 0 1\n"),
+        ]));
+        let load = |import_hidden| {
+            let settings = GeometrySettings {
+                import_hidden,
+                ..Default::default()
+            };
+            let scene = try_load_with_resolver(
+                "main.ldr",
+                &resolver,
+                &settings,
+                &mut ldraw::SourceMap::new(),
+                LoadOptions::default(),
+            )
+            .unwrap();
+            let instanced = try_load_instanced_with_resolver(
+                "main.ldr",
+                &resolver,
+                &settings,
+                &mut ldraw::SourceMap::new(),
+                LoadOptions::default(),
+            )
+            .unwrap();
+            (scene, instanced)
+        };
+
+        // Hidden pieces and their children are skipped by default.
+        let (scene, instanced) = load(false);
+        assert_eq!(1, scene.root_node.children.len());
+        assert_eq!(
+            vec![1],
+            instanced
+                .geometry_world_transforms
+                .values()
+                .map(|t| t.len())
+                .collect::<Vec<_>>()
+        );
+
+        let (scene, instanced) = load(true);
+        let children = &scene.root_node.children;
+        assert_eq!(2, children.len());
+        assert!(!children[0].piece_metadata.is_hidden);
+        assert!(children[1].piece_metadata.is_hidden);
+        assert_eq!(
+            vec![2],
+            instanced
+                .geometry_world_transforms
+                .values()
+                .map(|t| t.len())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn try_load_with_resolver_unique_names() {
+        let resolver = MemoryResolver(HashMap::from([
+            (
+                "main.ldr",
+                indoc::indoc! {"
+                    1 4 0 0 0 1 0 0 0 1 0 0 0 1 sub.ldr
+                    1 4 0 0 0 1 0 0 0 1 0 0 0 1 sub.ldr
+                    1 4 0 0 0 1 0 0 0 1 0 0 0 1 a.dat
+                "},
+            ),
+            ("sub.ldr", "1 16 0 0 0 1 0 0 0 1 0 0 0 1 a.dat\n"),
+            ("a.dat", "3 16 1 0 0 0 1 0 0 0 1\n"),
+        ]));
+
+        let scene = try_load_with_resolver(
+            "main.ldr",
+            &resolver,
+            &GeometrySettings::default(),
+            &mut ldraw::SourceMap::new(),
+            LoadOptions::default(),
+        )
+        .unwrap();
+
+        let names: Vec<_> = scene
+            .root_node
+            .children
+            .iter()
+            .map(|c| (c.name.as_str(), c.unique_name.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("sub.ldr", "sub.ldr"),
+                ("sub.ldr", "sub.ldr.001"),
+                ("a.dat", "a.dat.002")
+            ],
+            names
+        );
+        assert_eq!("a.dat", scene.root_node.children[0].children[0].unique_name);
+        assert_eq!(
+            "a.dat.001",
+            scene.root_node.children[1].children[0].unique_name
+        );
+    }
+
+    #[test]
+    fn used_colors_current_color() {
+        let resolver = MemoryResolver(HashMap::from([
+            (
+                "main.ldr",
+                "1 4 0 0 0 1 0 0 0 1 0 0 0 1 a.dat\n1 2 0 0 0 1 0 0 0 1 0 0 0 1 b.ldr\n",
+            ),
+            (
+                "b.ldr",
+                "1 16 0 0 0 1 0 0 0 1 0 0 0 1 a.dat\n1 16 0 0 0 1 0 0 0 1 0 0 0 1 c.dat\n",
+            ),
+            ("a.dat", "3 16 1 0 0 0 1 0 0 0 1\n"),
+            ("c.dat", "3 14 1 0 0 0 1 0 0 0 1\n"),
+        ]));
+
+        let scene = try_load_with_resolver(
+            "main.ldr",
+            &resolver,
+            &GeometrySettings::default(),
+            &mut ldraw::SourceMap::new(),
+            LoadOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(HashSet::from([2, 4, 14]), scene.used_colors());
+    }
+
+    #[test]
+    fn try_load_with_resolver_custom_colors() {
+        let resolver = MemoryResolver(HashMap::from([
+            (
+                "main.mpd",
+                indoc::indoc! {"
+                    0 FILE main.ldr
+                    0 !COLOUR Custom_Red CODE 1000 VALUE #FF0000 EDGE #333333
+                    1 1000 0 0 0 1 0 0 0 1 0 0 0 1 a.dat
+                    1 4 0 0 0 1 0 0 0 1 0 0 0 1 a.dat
+                    0 FILE a.dat
+                    3 16 1 0 0 0 1 0 0 0 1
+                "},
+            ),
+            (
+                "LDConfig.ldr",
+                "0 !COLOUR Red CODE 4 VALUE #C91A09 EDGE #333333\n",
+            ),
+        ]));
+        let settings = GeometrySettings {
+            resolve_node_colors: true,
+            ..Default::default()
+        };
+
+        let scene = try_load_with_resolver(
+            "main.mpd",
+            &resolver,
+            &settings,
+            &mut ldraw::SourceMap::new(),
+            LoadOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(vec![&1000], scene.custom_colors.keys().collect::<Vec<_>>());
+        assert_eq!("Custom_Red", scene.custom_colors[&1000].name);
+        assert_eq!(
+            Some([1.0, 0.0, 0.0, 1.0]),
+            scene.root_node.children[0].rgba_linear
+        );
+        assert!(scene.root_node.children[1].rgba_linear.is_some());
+    }
+
+    #[test]
+    fn try_load_with_resolver_deduplicate_geometry() {
+        let resolver = MemoryResolver(HashMap::from([
+            (
+                "main.ldr",
+                indoc::indoc! {"
+                    1 4 0 0 0 1 0 0 0 1 0 0 0 1 a.dat
+                    1 4 1 0 0 1 0 0 0 1 0 0 0 1 b.dat
+                    1 4 2 0 0 1 0 0 0 1 0 0 0 1 a.dat
+                "},
+            ),
+            ("a.dat", "3 16 1 0 0 0 1 0 0 0 1\n"),
+            ("b.dat", "3 16 1 0 0 0 1 0 0 0 1\n"),
+        ]));
+        let settings = GeometrySettings {
+            deduplicate_geometry: true,
+            ..Default::default()
+        };
+
+        let scene = try_load_with_resolver(
+            "main.ldr",
+            &resolver,
+            &settings,
+            &mut ldraw::SourceMap::new(),
+            LoadOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            vec!["a.dat"],
+            scene.geometry_cache.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![Some("a.dat".to_string()); 3],
+            scene
+                .root_node
+                .children
+                .iter()
+                .map(|c| c.geometry_name.clone())
+                .collect::<Vec<_>>()
+        );
+
+        let scene = try_load_instanced_with_resolver(
+            "main.ldr",
+            &resolver,
+            &settings,
+            &mut ldraw::SourceMap::new(),
+            LoadOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            vec!["a.dat"],
+            scene.geometry_cache.keys().collect::<Vec<_>>()
+        );
+        // Transforms for merged geometry stay in the order they appear in the file.
+        assert_eq!(
+            vec![(("a.dat".to_string(), 4), vec![0.0, 1.0, 2.0])],
+            scene
+                .geometry_world_transforms
+                .into_iter()
+                .map(|(k, v)| (k, v.iter().map(|t| t.w_axis.x).collect::<Vec<_>>()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn load_file_gzip() {
+        let write_gz = |path: &Path, contents: &str| {
+            let mut encoder = flate2::write::GzEncoder::new(
+                std::fs::File::create(path).unwrap(),
+                flate2::Compression::default(),
+            );
+            std::io::Write::write_all(&mut encoder, contents.as_bytes()).unwrap();
+            encoder.finish().unwrap();
+        };
+
+        let main = "1 16 0 0 0 1 0 0 0 1 0 0 0 1 a.dat";
+        let part = "0 BFC CERTIFY CCW\n3 16 1 0 0 0 1 0 0 0 1\n4 16 0 0 0 1 0 0 1 1 0 0 1 0\n";
+
+        let dir = tempfile::tempdir().unwrap();
+        let plain_dir = dir.path().join("plain");
+        let gz_dir = dir.path().join("gz");
+        std::fs::create_dir_all(&plain_dir).unwrap();
+        std::fs::create_dir_all(&gz_dir).unwrap();
+        std::fs::write(plain_dir.join("main.ldr"), main).unwrap();
+        std::fs::write(plain_dir.join("a.dat"), part).unwrap();
+        write_gz(&gz_dir.join("main.ldr.gz"), main);
+        write_gz(&gz_dir.join("a.dat.gz"), part);
+
+        let settings = GeometrySettings::default();
+        let load = |path: PathBuf| load_file(path.to_str().unwrap(), "", &[], &settings);
+        let plain = load(plain_dir.join("main.ldr"));
+        let gz = load(gz_dir.join("main.ldr.gz"));
+
+        assert_eq!(1, plain.geometry_cache.len());
+        let plain_geometry = &plain.geometry_cache["a.dat"];
+        let gz_geometry = &gz.geometry_cache["a.dat"];
+        assert_eq!(plain_geometry.vertices, gz_geometry.vertices);
+        assert_eq!(plain_geometry.vertex_indices, gz_geometry.vertex_indices);
+        assert_eq!(plain_geometry.face_sizes, gz_geometry.face_sizes);
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn load_file_standalone_part() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = dir.path().join("ldraw");
+        std::fs::create_dir_all(library.join("parts").join("s")).unwrap();
+        std::fs::create_dir_all(library.join("p")).unwrap();
+        std::fs::write(
+            library.join("p").join("tri.dat"),
+            "3 16 1 0 0 0 1 0 0 0 1\n",
+        )
+        .unwrap();
+        std::fs::write(
+            library.join("parts").join("s").join("3001s01.dat"),
+            "1 16 0 0 0 1 0 0 0 1 0 0 0 1 tri.dat\n",
+        )
+        .unwrap();
+        let part = "0 BFC CERTIFY CCW\n1 16 0 0 0 1 0 0 0 1 0 0 0 1 s\\3001s01.dat\n4 16 0 0 0 1 0 0 1 1 0 0 1 0\n";
+        let path = dir.path().join("3040.dat");
+        std::fs::write(&path, part).unwrap();
+
+        let settings = GeometrySettings::default();
+        let path = path.to_str().unwrap();
+        let scene = load_file(path, library.to_str().unwrap(), &[], &settings);
+        let instanced = load_file_instanced(path, library.to_str().unwrap(), &[], &settings);
+
+        // The part itself is the root node with all of its geometry.
+        assert_eq!(path, scene.root_node.name);
+        assert!(scene.root_node.children.is_empty());
+        let geometry_name = scene.root_node.geometry_name.as_ref().unwrap();
+        let geometry = &scene.geometry_cache[geometry_name];
+        assert_eq!(vec![3, 4], geometry.face_sizes);
+        // Checks based on the part name should ignore the folder.
+        assert!(geometry.has_grainy_slopes);
+
+        assert_eq!(1, instanced.geometry_world_transforms.len());
+        let geometry = &instanced.geometry_cache[geometry_name];
+        assert_eq!(vec![3, 4], geometry.face_sizes);
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn try_load_file_reuse_source_map_model_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = dir.path().join("ldraw");
+        std::fs::create_dir_all(library.join("parts")).unwrap();
+        std::fs::write(
+            library.join("parts").join("a.dat"),
+            "3 16 1 0 0 0 1 0 0 0 1\n",
+        )
+        .unwrap();
+        // Each model folder has a different submodel with the same name.
+        for (folder, color) in [("model1", 4), ("model2", 2)] {
+            let folder = dir.path().join(folder);
+            std::fs::create_dir_all(&folder).unwrap();
+            std::fs::write(
+                folder.join("main.ldr"),
+                "1 16 0 0 0 1 0 0 0 1 0 0 0 1 Sub.ldr\n",
+            )
+            .unwrap();
+            std::fs::write(
+                folder.join("sub.ldr"),
+                format!("1 {color} 0 0 0 1 0 0 0 1 0 0 0 1 a.dat\n"),
+            )
+            .unwrap();
+        }
+
+        let mut source_map = ldraw::SourceMap::new();
+        let mut load = |folder: &str| {
+            let path = dir.path().join(folder).join("main.ldr");
+            try_load_file(
+                path.to_str().unwrap(),
+                library.to_str().unwrap(),
+                &[],
+                &GeometrySettings::default(),
+                &mut source_map,
+                LoadOptions::default(),
+            )
+            .unwrap()
+        };
+        let scene1 = load("model1");
+        let scene2 = load("model2");
+
+        let part_color = |scene: &LDrawScene| scene.root_node.children[0].children[0].current_color;
+        assert_eq!(4, part_color(&scene1));
+        assert_eq!(2, part_color(&scene2));
+        // Only the library part is reused.
+        assert_eq!(3, scene1.stats.files_parsed);
+        assert_eq!(2, scene2.stats.files_parsed);
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn disk_resolver_primitive_resolution_overrides() {
+        let dir = tempfile::tempdir().unwrap();
+        for (folder, contents) in [("p", "normal"), ("p/8", "low"), ("p/48", "high")] {
+            std::fs::create_dir_all(dir.path().join(folder)).unwrap();
+            std::fs::write(dir.path().join(folder).join("4-4cyli.dat"), contents).unwrap();
+            std::fs::write(dir.path().join(folder).join("4-4disc.dat"), contents).unwrap();
+        }
+
+        let resolve = |resolution, overrides: &[(String, PrimitiveResolution)], name| {
+            let resolver = DiskResolver::new_from_library(&[dir.path()], [], resolution, overrides);
+            String::from_utf8(resolver.resolve(name).unwrap()).unwrap()
+        };
+        let overrides = [
+            ("CYLI".to_string(), PrimitiveResolution::High),
+            ("disc".to_string(), PrimitiveResolution::Normal),
+        ];
+        let cyli = resolve(PrimitiveResolution::Low, &overrides, "4-4cyli.dat");
+        let disc = resolve(PrimitiveResolution::Low, &overrides, "4-4disc.dat");
+        let default = resolve(PrimitiveResolution::Low, &[], "4-4disc.dat");
+
+        assert_eq!("high", cyli);
+        assert_eq!("normal", disc);
+        assert_eq!("low", default);
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn disk_resolver_library_priority() {
+        let dir = tempfile::tempdir().unwrap();
+        for (folder, contents) in [
+            ("official/parts", "official"),
+            ("official/p/48", "official high"),
+            ("unofficial/parts", "unofficial"),
+            ("unofficial/p/48", "unofficial high"),
+        ] {
+            std::fs::create_dir_all(dir.path().join(folder)).unwrap();
+            std::fs::write(dir.path().join(folder).join("a.dat"), contents).unwrap();
+        }
+        std::fs::create_dir_all(dir.path().join("unofficial/parts")).unwrap();
+        std::fs::write(dir.path().join("unofficial/parts/b.dat"), "unofficial").unwrap();
+
+        let resolver = DiskResolver::new_from_library(
+            &[dir.path().join("official"), dir.path().join("unofficial")],
+            [],
+            PrimitiveResolution::Normal,
+            &[("a.dat".to_string(), PrimitiveResolution::High)],
+        );
+        let resolve = |name| String::from_utf8(resolver.resolve(name).unwrap()).unwrap();
+        let a = resolve("a.dat");
+        let b = resolve("b.dat");
+
+        assert_eq!("official high", a);
+        assert_eq!("unofficial", b);
+    }
+
+    #[test]
+    fn geometry_stats_sorted() {
+        let geometry = |vertex_count, face_sizes: Vec<u32>| LDrawGeometry {
+            vertices: vec![Vec3::ZERO; vertex_count],
+            face_sizes,
+            ..Default::default()
+        };
+        let geometry_cache = BTreeMap::from([
+            ("a.dat".to_string(), geometry(3, vec![3])),
+            ("b.dat".to_string(), geometry(8, vec![4, 4, 3])),
+            ("c.dat".to_string(), geometry(4, vec![4])),
+            ("d.dat".to_string(), geometry(3, vec![3])),
+        ]);
+
+        let stats = |name: &str, vertex_count, triangle_count| GeometryStats {
+            name: name.to_string(),
+            vertex_count,
+            triangle_count,
+        };
+        assert_eq!(
+            vec![
+                stats("b.dat", 8, 5),
+                stats("c.dat", 4, 2),
+                stats("a.dat", 3, 1),
+                stats("d.dat", 3, 1),
+            ],
+            geometry_stats(&geometry_cache)
+        );
+    }
+
+    #[test]
+    fn main_model_missing() {
+        let empty = ldraw::SourceFile { cmds: Vec::new() };
+        let source_map = ldraw::SourceMap::new();
+        assert!(std::ptr::eq(
+            &empty,
+            main_model(&source_map, "main.ldr", &empty)
+        ));
+    }
+
+    #[test]
+    fn create_geometry_cache_cancelled() {
+        let mut source_map = ldraw::SourceMap::new();
+        source_map.insert(
+            "a.dat",
+            ldraw::SourceFile {
+                cmds: ldraw::parse_raw(b"3 16 1 0 0 0 1 0 0 0 1").unwrap(),
+            },
+        );
+
+        let mut geometry_descriptors = HashMap::new();
+        geometry_descriptors.insert(
+            "a.dat".to_string(),
+            GeometryInitDescriptor {
+                source_file: source_map.get("a.dat").unwrap(),
+                current_color: CURRENT_COLOR,
+                recursive: true,
+            },
+        );
+
+        let cancel = AtomicBool::new(true);
+        let result = create_geometry_cache(
+            geometry_descriptors,
+            &source_map,
+            &GeometrySettings::default(),
+            LoadOptions {
+                cancel: Some(&cancel),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(result, Err(LoadError::Cancelled)));
+    }
+
+    #[test]
+    fn create_geometry_cache_progress() {
+        let mut source_map = ldraw::SourceMap::new();
+        source_map.insert(
+            "a.dat",
+            ldraw::SourceFile {
+                cmds: ldraw::parse_raw(b"3 16 1 0 0 0 1 0 0 0 1").unwrap(),
+            },
+        );
+
+        let mut geometry_descriptors = HashMap::new();
+        geometry_descriptors.insert(
+            "a.dat".to_string(),
+            GeometryInitDescriptor {
+                source_file: source_map.get("a.dat").unwrap(),
+                current_color: CURRENT_COLOR,
+                recursive: true,
+            },
+        );
+
+        let events = std::sync::Mutex::new(Vec::new());
+        let on_progress = |e: ProgressEvent| {
+            if let ProgressEvent::GeometryCreated { name, index, total } = e {
+                events
+                    .lock()
+                    .unwrap()
+                    .push((name.to_string(), index, total));
+            }
+        };
+        create_geometry_cache(
+            geometry_descriptors,
+            &source_map,
+            &GeometrySettings::default(),
+            LoadOptions {
+                on_progress: Some(&on_progress),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![("a.dat".to_string(), 1, 1)],
+            events.into_inner().unwrap()
+        );
+    }
 }
@@ -0,0 +1,152 @@
+use glam::Vec3;
+
+use crate::{edge_split::edge_face_counts, LDrawGeometry};
+
+/// The edges of a mesh that prevent it from being watertight.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ManifoldReport {
+    /// The end points of edges used by a single face like the open bottom of a brick.
+    pub boundary_edges: Vec<[Vec3; 2]>,
+    /// The end points of edges shared by more than two faces.
+    pub non_manifold_edges: Vec<[Vec3; 2]>,
+}
+
+impl ManifoldReport {
+    /// Returns `true` if every edge is shared by exactly two faces.
+    pub fn is_manifold(&self) -> bool {
+        self.boundary_edges.is_empty() && self.non_manifold_edges.is_empty()
+    }
+}
+
+/// Find the edges not shared by exactly two faces using [edge_face_counts].
+///
+/// Vertices closer than a small fraction of the size of the mesh are treated as the same position,
+/// so edges split for normals or faces from separate primitives still connect.
+/// Many LDraw parts are modeled as open surfaces and are not manifold.
+pub fn manifold_report(
+    vertices: &[Vec3],
+    vertex_indices: &[u32],
+    face_starts: &[u32],
+    face_sizes: &[u32],
+) -> ManifoldReport {
+    let (min, max) = vertex_indices
+        .iter()
+        .map(|i| vertices[*i as usize])
+        .fold((Vec3::MAX, Vec3::MIN), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+    let tolerance = (max - min).max_element().max(0.0) * 1e-5;
+
+    let mut report = ManifoldReport::default();
+    for ([v0, v1], count) in
+        edge_face_counts(vertices, vertex_indices, face_starts, face_sizes, tolerance)
+    {
+        let positions = [vertices[v0 as usize], vertices[v1 as usize]];
+        match count {
+            1 => report.boundary_edges.push(positions),
+            2 => (),
+            _ => report.non_manifold_edges.push(positions),
+        }
+    }
+    report
+}
+
+impl LDrawGeometry {
+    /// Returns `true` if every edge of the faces is shared by exactly two faces.
+    /// See [manifold_report](crate::manifold_report) for details.
+    pub fn is_manifold(&self) -> bool {
+        self.manifold_report().is_manifold()
+    }
+
+    /// Find the edges of the faces not shared by exactly two faces.
+    /// See [manifold_report](crate::manifold_report) for details.
+    pub fn manifold_report(&self) -> ManifoldReport {
+        crate::manifold_report(
+            &self.vertices,
+            &self.vertex_indices,
+            &self.face_start_indices,
+            &self.face_sizes,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use glam::vec3;
+
+    fn cube(vertex_indices: Vec<u32>, face_count: usize) -> LDrawGeometry {
+        LDrawGeometry {
+            vertices: vec![
+                vec3(0.0, 0.0, 0.0),
+                vec3(1.0, 0.0, 0.0),
+                vec3(1.0, 1.0, 0.0),
+                vec3(0.0, 1.0, 0.0),
+                vec3(0.0, 0.0, 1.0),
+                vec3(1.0, 0.0, 1.0),
+                vec3(1.0, 1.0, 1.0),
+                vec3(0.0, 1.0, 1.0),
+            ],
+            vertex_indices,
+            face_start_indices: (0..face_count as u32).map(|i| i * 4).collect(),
+            face_sizes: vec![4; face_count],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn manifold_report_cube() {
+        let sides = vec![
+            0, 3, 2, 1, 4, 5, 6, 7, 0, 1, 5, 4, 1, 2, 6, 5, 2, 3, 7, 6, 3, 0, 4, 7,
+        ];
+
+        let geometry = cube(sides.clone(), 6);
+        assert!(geometry.is_manifold());
+
+        // Removing the bottom leaves an open boundary.
+        let geometry = cube(sides[4..].to_vec(), 5);
+        assert!(!geometry.is_manifold());
+        assert_eq!(
+            ManifoldReport {
+                boundary_edges: vec![
+                    [vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0)],
+                    [vec3(1.0, 0.0, 0.0), vec3(1.0, 1.0, 0.0)],
+                    [vec3(1.0, 1.0, 0.0), vec3(0.0, 1.0, 0.0)],
+                    [vec3(0.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0)],
+                ],
+                non_manifold_edges: Vec::new(),
+            },
+            geometry.manifold_report()
+        );
+
+        // A duplicate face shares edges with three faces.
+        let geometry = cube([sides.clone(), vec![4, 5, 6, 7]].concat(), 7);
+        let report = geometry.manifold_report();
+        assert!(report.boundary_edges.is_empty());
+        assert_eq!(
+            vec![
+                [vec3(0.0, 0.0, 1.0), vec3(1.0, 0.0, 1.0)],
+                [vec3(1.0, 0.0, 1.0), vec3(1.0, 1.0, 1.0)],
+                [vec3(1.0, 1.0, 1.0), vec3(0.0, 1.0, 1.0)],
+                [vec3(0.0, 0.0, 1.0), vec3(0.0, 1.0, 1.0)],
+            ],
+            report.non_manifold_edges
+        );
+    }
+
+    #[test]
+    fn manifold_report_split_vertices() {
+        // Duplicate vertices from splitting sharp edges still connect faces.
+        let mut geometry = cube(
+            vec![
+                0, 3, 2, 1, 4, 5, 6, 7, 8, 9, 5, 4, 1, 2, 6, 5, 2, 3, 7, 6, 3, 0, 4, 7,
+            ],
+            6,
+        );
+        geometry
+            .vertices
+            .extend([vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0)]);
+        assert!(geometry.is_manifold());
+    }
+}
@@ -23,6 +23,29 @@ pub fn face_normals(
         .collect()
 }
 
+/// Smooth normals for each vertex from the average of the normals of the faces using that vertex.
+///
+/// Vertices are only shared by faces with smooth edges after welding vertices,
+/// so sharp edges split with [weld_vertices](crate::GeometrySettings::weld_vertices) stay sharp.
+pub fn vertex_normals(
+    vertex_count: usize,
+    vertex_indices: &[u32],
+    face_start_indices: &[u32],
+    face_sizes: &[u32],
+    face_normals: &[Vec3],
+) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::ZERO; vertex_count];
+    for ((start, size), normal) in face_start_indices.iter().zip(face_sizes).zip(face_normals) {
+        for i in &vertex_indices[*start as usize..*start as usize + *size as usize] {
+            normals[*i as usize] += *normal;
+        }
+    }
+    for normal in &mut normals {
+        *normal = normal.normalize_or_zero();
+    }
+    normals
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -59,4 +82,18 @@ mod tests {
         );
         assert_eq!(vec![vec3(0.0, 0.0, 1.0)], normals);
     }
+
+    #[test]
+    fn vertex_normals_shared_edge() {
+        // Two faces at a right angle sharing the edge from 0 to 1.
+        let normals = vertex_normals(
+            4,
+            &[0, 1, 2, 1, 0, 3],
+            &[0, 3],
+            &[3, 3],
+            &[Vec3::Z, Vec3::Y],
+        );
+        let diagonal = vec3(0.0, 1.0, 1.0).normalize();
+        assert_eq!(vec![diagonal, diagonal, Vec3::Z, Vec3::Y], normals);
+    }
 }
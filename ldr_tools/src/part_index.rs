@@ -0,0 +1,214 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::ldraw::{parse_raw_iter, Command};
+
+/// Header information for a part file in the LDraw library.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PartInfo {
+    /// The file name like `3001.dat` without the `.gz` extension for compressed files.
+    pub filename: String,
+    /// The text of the first comment line like `Brick  2 x  4`.
+    pub description: String,
+    /// The `!CATEGORY` or the first word of the description if not specified.
+    pub category: String,
+    /// The keywords from all `!KEYWORDS` lines in the header.
+    pub keywords: Vec<String>,
+}
+
+impl PartInfo {
+    /// Returns `true` if each whitespace separated term in `query` is contained in the
+    /// filename, description, category, or one of the keywords ignoring case.
+    pub fn matches(&self, query: &str) -> bool {
+        query.split_whitespace().all(|term| {
+            let term = term.to_lowercase();
+            [&self.filename, &self.description, &self.category]
+                .into_iter()
+                .chain(&self.keywords)
+                .any(|text| text.to_lowercase().contains(&term))
+        })
+    }
+}
+
+/// Read the header of each file in the `parts` folder of the LDraw library at `ldraw_path`
+/// and each of the `additional_library_paths` sorted by filename.
+///
+/// Parts in earlier libraries take priority like [GeometrySettings::additional_library_paths](crate::GeometrySettings::additional_library_paths).
+/// Subparts in `parts/s` are not included.
+/// Scanning the full library reads thousands of files,
+/// so applications should build the index once and reuse it for searches.
+pub fn part_index(ldraw_path: &str, additional_library_paths: &[String]) -> Vec<PartInfo> {
+    let mut paths: Vec<(String, PathBuf)> = Vec::new();
+    let mut lowercase_filenames = HashSet::new();
+    for library in
+        std::iter::once(ldraw_path).chain(additional_library_paths.iter().map(|p| p.as_str()))
+    {
+        let library = Path::new(library);
+        for folder in [
+            library.join("parts"),
+            library.join("UnOfficial").join("parts"),
+        ] {
+            for path in part_files(&folder) {
+                let filename = path.file_name().unwrap_or_default().to_string_lossy();
+                let filename = filename
+                    .strip_suffix(".gz")
+                    .unwrap_or(&filename)
+                    .to_string();
+                if lowercase_filenames.insert(filename.to_lowercase()) {
+                    paths.push((filename, path));
+                }
+            }
+        }
+    }
+    paths.sort_by_cached_key(|(f, _)| f.to_lowercase());
+
+    #[cfg(feature = "parallel")]
+    let paths = paths.into_par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let paths = paths.into_iter();
+
+    paths
+        .map(|(filename, path)| {
+            let contents = std::fs::read(&path).unwrap_or_default();
+            let contents = crate::decompress_gzip(&path, contents);
+            part_info(filename, &contents)
+        })
+        .collect()
+}
+
+/// Find the parts in `index` that match the search `query` with [PartInfo::matches].
+pub fn search_part_index<'a>(index: &'a [PartInfo], query: &str) -> Vec<&'a PartInfo> {
+    index.iter().filter(|p| p.matches(query)).collect()
+}
+
+fn part_files(folder: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(folder)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| {
+                    let name = p.to_string_lossy().to_lowercase();
+                    p.is_file() && (name.ends_with(".dat") || name.ends_with(".dat.gz"))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn part_info(filename: String, contents: &[u8]) -> PartInfo {
+    let mut cmds = parse_raw_iter(contents).peekable();
+    let description = match cmds.peek() {
+        Some(Command::Comment(c)) => {
+            let text = c.text.clone();
+            cmds.next();
+            text
+        }
+        _ => String::new(),
+    };
+
+    let mut category = None;
+    let mut keywords = Vec::new();
+
+    // The header is at the start of the file, so avoid parsing any geometry.
+    for cmd in cmds {
+        match cmd {
+            Command::Category(c) => category = Some(c.category),
+            Command::Keywords(k) => keywords.extend(k.keywords),
+            Command::SubFileRef(_)
+            | Command::Line(_)
+            | Command::Triangle(_)
+            | Command::Quad(_)
+            | Command::OptLine(_) => break,
+            _ => (),
+        }
+    }
+
+    // "If the !CATEGORY meta-command is not present, the category is the first word of the description."
+    // Ignore prefixes for moved, alias, and physical colour parts.
+    let category = category.unwrap_or_else(|| {
+        description
+            .trim_start_matches(['~', '_', '=', '|'])
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    });
+
+    PartInfo {
+        filename,
+        description,
+        category,
+        keywords,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn part_index_categories_keywords() {
+        let dir = tempfile::tempdir().unwrap();
+        for (folder, name, contents) in [
+            ("official/parts", "3001.dat", "0 Brick  2 x  4\n0 Name: 3001.dat\n0 !KEYWORDS bricks, classic\n0 !KEYWORDS 2x4\n1 16 0 0 0 1 0 0 0 1 0 0 0 1 s\\3001s01.dat\n0 // Not a keyword\n0 !KEYWORDS ignored\n"),
+            ("official/parts", "3626.dat", "0 ~Minifig Head\n0 !CATEGORY Minifig\n"),
+            ("official/parts/s", "3001s01.dat", "0 ~Brick  2 x  4 without Front Face\n"),
+            ("unofficial/parts", "3001.DAT", "0 Unofficial Brick  2 x  4\n"),
+            ("unofficial/UnOfficial/parts", "3003.dat", "0 ~Brick  2 x  2\n"),
+        ] {
+            std::fs::create_dir_all(dir.path().join(folder)).unwrap();
+            std::fs::write(dir.path().join(folder).join(name), contents).unwrap();
+        }
+
+        let index = part_index(
+            dir.path().join("official").to_str().unwrap(),
+            &[dir.path().join("unofficial").to_str().unwrap().to_string()],
+        );
+
+        assert_eq!(
+            vec![
+                PartInfo {
+                    filename: "3001.dat".to_string(),
+                    description: "Brick  2 x  4".to_string(),
+                    category: "Brick".to_string(),
+                    keywords: vec![
+                        "bricks".to_string(),
+                        "classic".to_string(),
+                        "2x4".to_string()
+                    ],
+                },
+                PartInfo {
+                    filename: "3003.dat".to_string(),
+                    description: "~Brick  2 x  2".to_string(),
+                    category: "Brick".to_string(),
+                    keywords: Vec::new(),
+                },
+                PartInfo {
+                    filename: "3626.dat".to_string(),
+                    description: "~Minifig Head".to_string(),
+                    category: "Minifig".to_string(),
+                    keywords: Vec::new(),
+                },
+            ],
+            index
+        );
+
+        let filenames = |query| -> Vec<_> {
+            search_part_index(&index, query)
+                .iter()
+                .map(|p| p.filename.as_str())
+                .collect()
+        };
+        assert_eq!(vec!["3001.dat", "3003.dat"], filenames("brick"));
+        assert_eq!(vec!["3001.dat"], filenames("CLASSIC brick"));
+        assert_eq!(vec!["3626.dat"], filenames("minifig"));
+        assert!(filenames("minifig brick").is_empty());
+        assert_eq!(3, filenames("").len());
+    }
+}
@@ -1,12 +1,27 @@
 // Reverse engineered from C# DLLs for the Unity app for Bricklink Studio.
 
-use crate::LDrawGeometry;
+use crate::{
+    ldraw::{SourceMap, TexMapCmd, TexMapMethod},
+    LDrawGeometry,
+};
 use glam::{Mat4, Vec2, Vec3, Vec3Swizzles};
 
 #[derive(Debug, PartialEq)]
 pub struct LDrawTextureInfo {
-    /// PNG-encoded images from PE_TEX_INFO commands.
+    /// Encoded PNG or JPEG images from PE_TEX_INFO commands
+    /// or embedded `!DATA` files used by `!TEXMAP` commands.
     pub textures: Vec<Vec<u8>>,
+    /// The `(width, height)` of each of the [textures](#structfield.textures)
+    /// or `(0, 0)` if the image header could not be read.
+    pub texture_sizes: Vec<(u32, u32)>,
+    /// A name for each of the [textures](#structfield.textures) from the file that defined it
+    /// and the texture's index in that file like `25269pb030.dat.0.png`
+    /// or the `!TEXMAP` texture file name like `sticker.png`.
+    pub texture_names: Vec<String>,
+    /// The encoded glossiness image for each of the [textures](#structfield.textures)
+    /// from the `GLOSSMAP` of a `!TEXMAP` command or `None` if there is no glossmap.
+    /// Studio textures from PE_TEX_INFO commands never have a glossmap.
+    pub glossmaps: Vec<Option<Vec<u8>>>,
     /// Per-face indices into `textures`. 0xFF indicates no texture for the face.
     /// Eight-bit indices save memory, especially for the untextured majority of parts.
     pub indices: Vec<u8>,
@@ -14,16 +29,62 @@ pub struct LDrawTextureInfo {
     pub uvs: Vec<Vec2>,
 }
 
+/// An image from [LDrawTextureInfo] decoded to RGBA8 pixels.
+#[cfg(feature = "image")]
+#[derive(Debug, PartialEq)]
+pub struct DecodedTexture {
+    pub width: u32,
+    pub height: u32,
+    /// Row-major RGBA8 pixels starting from the top left corner.
+    pub rgba: Vec<u8>,
+    /// `true` if any pixel is not fully opaque like the cutout regions of stickers.
+    /// Applications can use this to choose between alpha clipping and opaque materials.
+    pub has_alpha: bool,
+}
+
 impl LDrawTextureInfo {
     pub fn new(num_faces: usize, num_vertices: usize) -> Self {
         // "Catch up" with the mesh that we had optimistically assumed would have no textures
         // by filling in the arrays "up to this point" with sentinel/placeholder data.
         Self {
             textures: vec![],
+            texture_sizes: vec![],
+            texture_names: vec![],
+            glossmaps: vec![],
             indices: vec![u8::MAX; num_faces],
             uvs: vec![Vec2::ZERO; num_vertices],
         }
     }
+
+    /// Decode each of the [textures](#structfield.textures) to RGBA8 pixels.
+    ///
+    /// The image format is detected from the data, since Studio may also embed JPEG images.
+    /// Images that fail to decode are `None`
+    /// to preserve the ordering used by [indices](#structfield.indices).
+    #[cfg(feature = "image")]
+    pub fn decoded(&self) -> Vec<Option<DecodedTexture>> {
+        self.textures
+            .iter()
+            .map(|data| match image::load_from_memory(data) {
+                Ok(image) => {
+                    // Images without an alpha channel are always opaque.
+                    let may_have_alpha = image.color().has_alpha();
+                    let image = image.into_rgba8();
+                    let has_alpha = may_have_alpha && image.pixels().any(|p| p[3] < u8::MAX);
+                    Some(DecodedTexture {
+                        width: image.width(),
+                        height: image.height(),
+                        rgba: image.into_raw(),
+                        has_alpha,
+                    })
+                }
+                Err(e) => {
+                    log::warn!("Error decoding texture: {e}");
+                    None
+                }
+            })
+            .collect()
+    }
 }
 
 fn init_texture_transform(texture_matrix: Mat4, part_matrix: Mat4) -> (Mat4, Vec3) {
@@ -48,6 +109,11 @@ pub fn project_texture<const N: usize>(
         return Some(TextureMap { texture_index, uvs });
     }
 
+    if let Some(points) = texture.planar_points {
+        let uvs = vertices.map(|v| planar_uv(points, transform.transform_point3(v)));
+        return Some(TextureMap { texture_index, uvs });
+    }
+
     // if there are neither vertex UVs on the face
     // nor a projection matrix on the texture,
     // then the texture is not drawn on this face
@@ -57,7 +123,13 @@ pub fn project_texture<const N: usize>(
     let inverse = matrix.inverse();
     let vertices = vertices.map(|v| inverse.transform_point3(v));
 
-    if !intersect_poly_box(&vertices, box_extents) {
+    let intersects = intersect_poly_box(&vertices, box_extents);
+    // Log the projection for debugging incorrect UVs without storing it on the geometry.
+    log::trace!(
+        "Projecting texture {:?} with matrix {matrix:?} and box extents {box_extents:?}: intersects {intersects}",
+        texture.name
+    );
+    if !intersects {
         return None;
     }
 
@@ -68,10 +140,28 @@ pub fn project_texture<const N: usize>(
     Some(TextureMap { texture_index, uvs })
 }
 
+/// The UV for `position` with a `!TEXMAP PLANAR` projection through `points`.
+/// The texture's top left corner is at the first point
+/// with the top right and bottom left corners at the second and third points.
+fn planar_uv([p1, p2, p3]: [Vec3; 3], position: Vec3) -> Vec2 {
+    let u_axis = p2 - p1;
+    let v_axis = p3 - p1;
+    let offset = position - p1;
+    // Flip V to place the UV origin at the bottom left like Studio textures.
+    Vec2::new(
+        offset.dot(u_axis) / u_axis.length_squared(),
+        1.0 - offset.dot(v_axis) / v_axis.length_squared(),
+    )
+}
+
 #[derive(Clone)]
 pub struct PendingStudioTexture {
     pub index: u8,
+    /// The name from [texture_name] for logging.
+    pub name: String,
     pub location: Option<TextureLocation>,
+    /// The transformed points for a `!TEXMAP PLANAR` projection.
+    pub planar_points: Option<[Vec3; 3]>,
     pub path: Vec<i32>,
 }
 
@@ -89,10 +179,10 @@ pub struct TextureMap<const N: usize> {
 }
 
 impl PendingStudioTexture {
-    // TODO: the images probably need names based on their file of origin
     pub fn from_cmd(
         cmd: &crate::ldraw::PeTexInfoCmd,
         path: &[i32],
+        name: String,
         geometry: &mut LDrawGeometry,
     ) -> Option<Self> {
         let mut location = None::<TextureLocation>;
@@ -114,15 +204,139 @@ impl PendingStudioTexture {
             return None;
         }
 
-        let index = tex_info.textures.len() as u8;
-        tex_info.textures.push(image);
+        let index = add_texture(tex_info, image, name.clone(), None);
         let path = path.to_owned();
         Some(Self {
             index,
+            name,
             location,
+            planar_points: None,
             path,
         })
     }
+
+    /// Load the texture for a `!TEXMAP` command from an embedded `!DATA` file in `source_map`.
+    /// Only `PLANAR` projections are supported.
+    pub fn from_texmap(
+        cmd: &TexMapCmd,
+        transform: Mat4,
+        source_map: &SourceMap,
+        geometry: &mut LDrawGeometry,
+    ) -> Option<Self> {
+        let TexMapMethod::Planar { points } = cmd.method else {
+            log::warn!("Unsupported !TEXMAP projection for {:?}", cmd.texture);
+            return None;
+        };
+        let Some(image) = source_map.data(&cmd.texture) else {
+            log::warn!("Unable to find !DATA file for texture {:?}", cmd.texture);
+            return None;
+        };
+        let glossmap = cmd
+            .glossmap
+            .as_ref()
+            .and_then(|g| source_map.data(g))
+            .map(|g| g.to_vec());
+
+        let tex_info = geometry.texture_info();
+
+        // Textures may be referenced by more than one !TEXMAP command.
+        let index = match tex_info
+            .texture_names
+            .iter()
+            .position(|n| *n == cmd.texture)
+        {
+            Some(index) => index as u8,
+            None => {
+                if tex_info.textures.len() >= u8::MAX as usize {
+                    log::warn!("Texture limit exceeded for {:?}", cmd.texture);
+                    return None;
+                }
+                add_texture(tex_info, image.to_vec(), cmd.texture.clone(), glossmap)
+            }
+        };
+
+        Some(Self {
+            index,
+            name: cmd.texture.clone(),
+            location: None,
+            planar_points: Some(points.map(|p| transform.transform_point3(p))),
+            path: Vec::new(),
+        })
+    }
+}
+
+fn add_texture(
+    tex_info: &mut LDrawTextureInfo,
+    image: Vec<u8>,
+    name: String,
+    glossmap: Option<Vec<u8>>,
+) -> u8 {
+    let index = tex_info.textures.len() as u8;
+    let size = image_size(&image).unwrap_or_else(|| {
+        log::warn!("Unable to read texture dimensions from image header");
+        (0, 0)
+    });
+    tex_info.textures.push(image);
+    tex_info.texture_sizes.push(size);
+    tex_info.texture_names.push(name);
+    tex_info.glossmaps.push(glossmap);
+    index
+}
+
+/// The name for the texture at `index` in the file `file_name` with an extension based on `data`.
+pub fn texture_name(file_name: &str, index: usize, data: &[u8]) -> String {
+    let extension = if data.starts_with(&[0xFF, 0xD8]) {
+        "jpg"
+    } else {
+        "png"
+    };
+    format!("{file_name}.{index}.{extension}")
+}
+
+/// Read the dimensions from a PNG or JPEG header without decoding the image.
+fn image_size(data: &[u8]) -> Option<(u32, u32)> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // The IHDR chunk is always first and starts with the width and height.
+        let ihdr = data.get(12..24)?;
+        if &ihdr[..4] != b"IHDR" {
+            return None;
+        }
+        let width = u32::from_be_bytes(ihdr[4..8].try_into().unwrap());
+        let height = u32::from_be_bytes(ihdr[8..12].try_into().unwrap());
+        Some((width, height))
+    } else if data.starts_with(&[0xFF, 0xD8]) {
+        jpeg_size(data)
+    } else {
+        None
+    }
+}
+
+fn jpeg_size(data: &[u8]) -> Option<(u32, u32)> {
+    // Skip segments until the start of frame marker with the image dimensions.
+    let mut i = 2;
+    loop {
+        if *data.get(i)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(i + 1)?;
+        match marker {
+            // Fill bytes may precede a marker.
+            0xFF => i += 1,
+            // Markers without a length.
+            0x01 | 0xD0..=0xD7 => i += 2,
+            // SOF markers excluding DHT, JPG, and DAC.
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                let segment = data.get(i + 5..i + 9)?;
+                let height = u16::from_be_bytes([segment[0], segment[1]]);
+                let width = u16::from_be_bytes([segment[2], segment[3]]);
+                return Some((width as u32, height as u32));
+            }
+            _ => {
+                let length = u16::from_be_bytes([*data.get(i + 2)?, *data.get(i + 3)?]);
+                i += 2 + length as usize;
+            }
+        }
+    }
 }
 
 fn intersect_poly_box(polygon: &[Vec3], r: Vec3) -> bool {
@@ -177,3 +391,74 @@ fn min_max(values: &[f32]) -> (f32, f32) {
     }
     (min, max)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_size_png() {
+        use base64::{prelude::BASE64_STANDARD, Engine};
+
+        let png = BASE64_STANDARD
+            .decode("iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR4nGP4z8DwHwAFAAH/iZk9HQAAAABJRU5ErkJggg==")
+            .unwrap();
+        assert_eq!(Some((1, 1)), image_size(&png));
+    }
+
+    #[test]
+    fn image_size_jpeg() {
+        // SOI, an empty APP0 segment, and a baseline SOF0 segment for 3x2 pixels.
+        let jpeg = [
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x02, 0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x00, 0x02, 0x00,
+            0x03, 0x01, 0x01, 0x11, 0x00,
+        ];
+        assert_eq!(Some((3, 2)), image_size(&jpeg));
+    }
+
+    #[test]
+    fn image_size_invalid() {
+        assert_eq!(None, image_size(b"abc"));
+        assert_eq!(None, image_size(&[0xFF, 0xD8, 0xFF]));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn decode_png_and_invalid_data() {
+        use base64::{prelude::BASE64_STANDARD, Engine};
+
+        // 1x1 RGBA PNG with a single red pixel.
+        let png = BASE64_STANDARD
+            .decode("iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR4nGP4z8DwHwAFAAH/iZk9HQAAAABJRU5ErkJggg==")
+            .unwrap();
+
+        // 2x1 RGBA PNG with a red pixel and a transparent pixel.
+        let sticker = BASE64_STANDARD
+            .decode("iVBORw0KGgoAAAANSUhEUgAAAAIAAAABCAYAAAD0In+KAAAAD0lEQVR4nGP4z8AARAwMAAz8Af9c/RSVAAAAAElFTkSuQmCC")
+            .unwrap();
+
+        let mut info = LDrawTextureInfo::new(0, 0);
+        info.textures.push(png);
+        info.textures.push(sticker);
+        info.textures.push(b"abc".to_vec());
+
+        assert_eq!(
+            vec![
+                Some(DecodedTexture {
+                    width: 1,
+                    height: 1,
+                    rgba: vec![255, 0, 0, 255],
+                    has_alpha: false,
+                }),
+                Some(DecodedTexture {
+                    width: 2,
+                    height: 1,
+                    rgba: vec![255, 0, 0, 255, 0, 0, 0, 0],
+                    has_alpha: true,
+                }),
+                None
+            ],
+            info.decoded()
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::{BTreeMap, HashSet};
+
+use glam::Mat4;
+
+use crate::{ColorCode, LDrawGeometry, LDrawNode};
+
+/// Appended to geometry names for the stud faces split from geometry with studs.
+pub const STUDS_SUFFIX: &str = "#studs";
+
+/// Move stud faces for geometry with studs to a separate entry in `geometry_cache`
+/// with [STUDS_SUFFIX] appended to the name.
+/// Returns the names of the geometry that was split.
+pub fn separate_studs(geometry_cache: &mut BTreeMap<String, LDrawGeometry>) -> HashSet<String> {
+    let mut separated = HashSet::new();
+    let mut studs = Vec::new();
+    for (name, geometry) in geometry_cache.iter_mut() {
+        if !geometry.is_face_stud.contains(&true) {
+            continue;
+        }
+
+        studs.push((
+            format!("{name}{STUDS_SUFFIX}"),
+            geometry.filter_faces(|f| f.is_stud),
+        ));
+        *geometry = geometry.filter_faces(|f| !f.is_stud);
+        separated.insert(name.clone());
+    }
+
+    geometry_cache.extend(studs);
+    separated
+}
+
+/// Add a child node with the stud geometry to each node with separated geometry.
+pub fn add_stud_nodes(node: &mut LDrawNode, separated: &HashSet<String>) {
+    for child in &mut node.children {
+        add_stud_nodes(child, separated);
+    }
+
+    if let Some(name) = node
+        .geometry_name
+        .as_ref()
+        .filter(|n| separated.contains(*n))
+    {
+        // The child inherits the transform and color of the part.
+        node.children.push(LDrawNode {
+            name: format!("{}{STUDS_SUFFIX}", node.name),
+            unique_name: format!("{}{STUDS_SUFFIX}", node.name),
+            transform: Mat4::IDENTITY,
+            geometry_name: Some(format!("{name}{STUDS_SUFFIX}")),
+            current_color: node.current_color,
+            is_mirrored: node.is_mirrored,
+            rgba_linear: node.rgba_linear,
+            piece_metadata: node.piece_metadata,
+            children: Vec::new(),
+        });
+    }
+}
+
+/// Add instances of the stud geometry with the same transforms as each separated geometry.
+pub fn add_stud_instances(
+    geometry_world_transforms: &mut BTreeMap<(String, ColorCode), Vec<Mat4>>,
+    separated: &HashSet<String>,
+) {
+    let studs: Vec<_> = geometry_world_transforms
+        .iter()
+        .filter(|((name, _), _)| separated.contains(name))
+        .map(|((name, color), transforms)| {
+            (
+                (format!("{name}{STUDS_SUFFIX}"), *color),
+                transforms.clone(),
+            )
+        })
+        .collect();
+    geometry_world_transforms.extend(studs);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::hidden::brick;
+    use glam::Vec3;
+
+    #[test]
+    fn separate_studs_brick() {
+        let mut geometry_cache = BTreeMap::from([
+            ("3005.dat".to_string(), brick()),
+            (
+                "3070b.dat".to_string(),
+                brick().filter_faces(|f| !f.is_stud),
+            ),
+        ]);
+        let separated = separate_studs(&mut geometry_cache);
+        assert_eq!(HashSet::from(["3005.dat".to_string()]), separated);
+
+        let body = &geometry_cache["3005.dat"];
+        assert_eq!(vec![0, 1, 2], body.vertex_indices);
+        assert_eq!(vec![false], body.is_face_stud);
+        assert_eq!(vec![[0, 1]], body.edge_line_indices);
+
+        let studs = &geometry_cache["3005.dat#studs"];
+        assert_eq!(3, studs.vertices.len());
+        assert_eq!(vec![0, 1, 2], studs.vertex_indices);
+        assert_eq!(vec![true], studs.is_face_stud);
+        assert_eq!(vec![[0, 1]], studs.edge_line_indices);
+        assert_eq!(3, geometry_cache.len());
+
+        let transform = Mat4::from_translation(Vec3::X);
+        let mut geometry_world_transforms = BTreeMap::from([
+            (("3005.dat".to_string(), 4), vec![transform]),
+            (("3070b.dat".to_string(), 1), vec![transform]),
+        ]);
+        add_stud_instances(&mut geometry_world_transforms, &separated);
+        assert_eq!(
+            Some(&vec![transform]),
+            geometry_world_transforms.get(&("3005.dat#studs".to_string(), 4))
+        );
+        assert_eq!(3, geometry_world_transforms.len());
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use crate::{ldraw::SourceMap, GeometrySettings};
+
+/// Load a table of part substitutions for [GeometrySettings::part_substitutions].
+///
+/// Each line contains the original part name followed by the name of its replacement
+/// separated by whitespace like `3001.dat lg_3001.dat`.
+/// Empty lines and lines starting with `#` or `;` are ignored.
+#[cfg(feature = "fs")]
+pub fn load_part_substitutions<P: AsRef<Path>>(
+    path: P,
+) -> std::io::Result<HashMap<String, String>> {
+    let text = std::fs::read_to_string(path)?;
+    Ok(parse_part_substitutions(&text))
+}
+
+/// Parse the contents of a part substitutions file like [load_part_substitutions].
+pub fn parse_part_substitutions(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#') && !l.starts_with(';'))
+        .filter_map(|l| {
+            let mut parts = l.split_whitespace();
+            let original = parts.next()?;
+            let substitute = parts.next()?;
+            Some((original.to_lowercase(), substitute.to_string()))
+        })
+        .collect()
+}
+
+/// Find the replacement for `filename` from [GeometrySettings::part_substitutions].
+/// Substitutes that failed to load are ignored to avoid removing parts.
+pub fn substitute_part<'a>(
+    filename: &'a str,
+    source_map: &SourceMap,
+    settings: &'a GeometrySettings,
+) -> &'a str {
+    if settings.part_substitutions.is_empty() {
+        return filename;
+    }
+
+    match settings.part_substitutions.get(&filename.to_lowercase()) {
+        Some(substitute)
+            if source_map
+                .get(substitute)
+                .is_some_and(|f| !f.cmds.is_empty()) =>
+        {
+            substitute
+        }
+        _ => filename,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use indoc::indoc;
+
+    #[test]
+    fn parse_part_substitutions_comments() {
+        let text = indoc! {"
+            # LGEO replacements
+            3001.DAT lg_3001.dat
+
+            ; stud
+            stud.dat	lg_stud.dat
+            invalid
+        "};
+        assert_eq!(
+            HashMap::from([
+                ("3001.dat".to_string(), "lg_3001.dat".to_string()),
+                ("stud.dat".to_string(), "lg_stud.dat".to_string())
+            ]),
+            parse_part_substitutions(text)
+        );
+    }
+
+    #[test]
+    fn substitute_part_missing_substitute() {
+        let mut source_map = SourceMap::new();
+        source_map.insert(
+            "lg_3001.dat",
+            crate::ldraw::SourceFile {
+                cmds: crate::ldraw::parse_raw(b"3 16 1 0 0 0 1 0 0 0 1").unwrap(),
+            },
+        );
+        source_map.insert("lg_3002.dat", crate::ldraw::SourceFile { cmds: Vec::new() });
+
+        let settings = GeometrySettings {
+            part_substitutions: HashMap::from([
+                ("3001.dat".to_string(), "lg_3001.dat".to_string()),
+                ("3002.dat".to_string(), "lg_3002.dat".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            "lg_3001.dat",
+            substitute_part("3001.DAT", &source_map, &settings)
+        );
+        assert_eq!(
+            "3002.dat",
+            substitute_part("3002.dat", &source_map, &settings)
+        );
+        assert_eq!(
+            "3003.dat",
+            substitute_part("3003.dat", &source_map, &settings)
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use glam::{Vec2, Vec3};
+
+use crate::LDrawGeometry;
+
+/// Split a planar polygon with face normal `normal` into triangles using ear clipping.
+/// Returns indices into `points` with the same winding as the polygon.
+///
+/// Convex polygons produce a triangle fan from the first point.
+/// Concave polygons produce non overlapping triangles unlike a fan.
+/// Polygons with fewer than 3 points produce no triangles.
+pub fn triangulate_polygon(points: &[Vec3], normal: Vec3) -> Vec<[usize; 3]> {
+    let (u, v) = normal.any_orthonormal_pair();
+    let points: Vec<_> = points
+        .iter()
+        .map(|p| Vec2::new(p.dot(u), p.dot(v)))
+        .collect();
+
+    // The projection may reverse the winding, so use the sign of the area instead of the normal.
+    let orientation = signed_area(&points).signum();
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len().saturating_sub(2));
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let corner = |i: usize| {
+            [
+                remaining[(i + count - 1) % count],
+                remaining[i],
+                remaining[(i + 1) % count],
+            ]
+        };
+
+        // Degenerate polygons may not have any ears, so clip the first corner instead.
+        let i = (0..count)
+            .map(|i| (i + 1) % count)
+            .find(|i| is_ear(&points, &remaining, corner(*i), orientation))
+            .unwrap_or(1);
+        triangles.push(corner(i));
+        remaining.remove(i);
+    }
+    if let [a, b, c] = remaining[..] {
+        triangles.push([a, b, c]);
+    }
+
+    triangles
+}
+
+fn signed_area(points: &[Vec2]) -> f32 {
+    points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.perp_dot(*b))
+        .sum::<f32>()
+        * 0.5
+}
+
+fn is_ear(points: &[Vec2], remaining: &[usize], [a, b, c]: [usize; 3], orientation: f32) -> bool {
+    let [pa, pb, pc] = [a, b, c].map(|i| points[i]);
+
+    // Reflex and collinear corners would create flipped or degenerate triangles.
+    if (pb - pa).perp_dot(pc - pb) * orientation <= 0.0 {
+        return false;
+    }
+
+    !remaining
+        .iter()
+        .filter(|i| ![a, b, c].contains(i))
+        .any(|i| {
+            let p = points[*i];
+            [(pa, pb), (pb, pc), (pc, pa)]
+                .iter()
+                .all(|(start, end)| (*end - *start).perp_dot(p - *start) * orientation >= 0.0)
+        })
+}
+
+impl LDrawGeometry {
+    /// Split faces with more than 4 vertices into triangles with [triangulate_polygon].
+    /// Triangles and quads are unchanged.
+    pub fn triangulate_polygons(&mut self) {
+        if self.face_sizes.iter().all(|s| *s <= 4) {
+            return;
+        }
+
+        let is_single_color = self.face_colors.len() == 1;
+        let mut vertex_indices = Vec::new();
+        let mut face_start_indices = Vec::new();
+        let mut face_sizes = Vec::new();
+        let mut face_normals = Vec::new();
+        let mut face_colors = Vec::new();
+        let mut is_face_stud = Vec::new();
+        let mut is_face_double_sided = Vec::new();
+        let mut face_windings = Vec::new();
+        let mut texture_indices = Vec::new();
+        let mut uvs = Vec::new();
+
+        for i in 0..self.face_sizes.len() {
+            let start = self.face_start_indices[i] as usize;
+            let range = start..start + self.face_sizes[i] as usize;
+            let triangles = if range.len() > 4 {
+                let points: Vec<_> = self.vertex_indices[range.clone()]
+                    .iter()
+                    .map(|v| self.vertices[*v as usize])
+                    .collect();
+                triangulate_polygon(&points, self.face_normals[i])
+                    .into_iter()
+                    .map(|t| t.to_vec())
+                    .collect()
+            } else {
+                vec![(0..range.len()).collect::<Vec<_>>()]
+            };
+
+            for corners in triangles {
+                face_start_indices.push(vertex_indices.len() as u32);
+                face_sizes.push(corners.len() as u32);
+                vertex_indices.extend(corners.iter().map(|c| self.vertex_indices[range.start + c]));
+                face_normals.push(self.face_normals[i]);
+                if !is_single_color {
+                    face_colors.push(self.face_colors[i]);
+                }
+                is_face_stud.push(self.is_face_stud[i]);
+                is_face_double_sided.push(self.is_face_double_sided[i]);
+                if let Some(winding) = self.face_windings.get(i) {
+                    face_windings.push(*winding);
+                }
+                if let Some(texture_info) = &self.texture_info {
+                    texture_indices.push(texture_info.indices[i]);
+                    uvs.extend(corners.iter().map(|c| texture_info.uvs[range.start + c]));
+                }
+            }
+        }
+
+        self.vertex_indices = vertex_indices;
+        self.face_start_indices = face_start_indices;
+        self.face_sizes = face_sizes;
+        self.face_normals = face_normals;
+        if !is_single_color {
+            self.face_colors = face_colors;
+        }
+        self.is_face_stud = is_face_stud;
+        self.is_face_double_sided = is_face_double_sided;
+        self.face_windings = face_windings;
+        if let Some(texture_info) = &mut self.texture_info {
+            texture_info.indices = texture_indices;
+            texture_info.uvs = uvs;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use glam::vec3;
+
+    fn triangle_area(points: &[Vec3], [a, b, c]: [usize; 3]) -> f32 {
+        (points[b] - points[a])
+            .cross(points[c] - points[a])
+            .dot(Vec3::Z)
+            * 0.5
+    }
+
+    #[test]
+    fn triangulate_polygon_convex_pentagon() {
+        let points = [
+            vec3(0.0, 0.0, 0.0),
+            vec3(2.0, 0.0, 0.0),
+            vec3(3.0, 1.0, 0.0),
+            vec3(1.0, 2.0, 0.0),
+            vec3(-1.0, 1.0, 0.0),
+        ];
+        assert_eq!(
+            vec![[0, 1, 2], [0, 2, 3], [0, 3, 4]],
+            triangulate_polygon(&points, Vec3::Z)
+        );
+    }
+
+    #[test]
+    fn triangulate_polygon_concave_hexagon() {
+        // An L shape with a reflex corner at index 3.
+        // A fan from index 0 would create triangles outside the polygon.
+        let points = [
+            vec3(0.0, 0.0, 0.0),
+            vec3(2.0, 0.0, 0.0),
+            vec3(2.0, 1.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+            vec3(1.0, 2.0, 0.0),
+            vec3(0.0, 2.0, 0.0),
+        ];
+        let triangles = triangulate_polygon(&points, Vec3::Z);
+        assert_eq!(4, triangles.len());
+
+        // All triangles have the polygon's winding and cover its area exactly once.
+        for t in &triangles {
+            assert!(triangle_area(&points, *t) > 0.0, "{t:?}");
+        }
+        let area: f32 = triangles.iter().map(|t| triangle_area(&points, *t)).sum();
+        assert_eq!(3.0, area);
+
+        // Reversing the winding also reverses the triangles.
+        let reversed: Vec<_> = points.iter().rev().copied().collect();
+        for t in triangulate_polygon(&reversed, -Vec3::Z) {
+            assert!(triangle_area(&reversed, t) < 0.0, "{t:?}");
+        }
+    }
+
+    #[test]
+    fn triangulate_polygons_geometry() {
+        let mut geometry = LDrawGeometry {
+            vertices: vec![
+                vec3(0.0, 0.0, 0.0),
+                vec3(2.0, 0.0, 0.0),
+                vec3(2.0, 1.0, 0.0),
+                vec3(1.0, 1.0, 0.0),
+                vec3(1.0, 2.0, 0.0),
+                vec3(0.0, 2.0, 0.0),
+            ],
+            vertex_indices: vec![0, 1, 2, 3, 4, 5, 0, 1, 2],
+            face_start_indices: vec![0, 6],
+            face_sizes: vec![6, 3],
+            face_normals: vec![Vec3::Z, Vec3::Z],
+            face_colors: vec![4, 1],
+            is_face_stud: vec![false, true],
+            is_face_double_sided: vec![false, false],
+            ..Default::default()
+        };
+        geometry.triangulate_polygons();
+
+        assert_eq!(vec![3; 5], geometry.face_sizes);
+        assert_eq!(vec![0, 3, 6, 9, 12], geometry.face_start_indices);
+        assert_eq!(vec![4, 4, 4, 4, 1], geometry.face_colors);
+        assert_eq!(
+            vec![false, false, false, false, true],
+            geometry.is_face_stud
+        );
+        assert_eq!(&[0, 1, 2], geometry.face_vertices(4));
+    }
+}
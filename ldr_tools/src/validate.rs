@@ -0,0 +1,257 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    path::Path,
+    sync::Mutex,
+};
+
+use crate::{
+    color::{self, LDrawColor},
+    ldraw::{self, error::ParseError, Command, FileRefResolver, ResolveError, SourceMap},
+    load_color_table_for_file, ColorCode, GeometrySettings, CURRENT_COLOR, EDGE_COLOR,
+};
+
+/// Problems found by [validate_file] without creating any geometry.
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    /// Referenced files that could not be found or are empty.
+    pub missing_files: Vec<String>,
+    /// Lines that could not be parsed with the file name and line number.
+    /// These lines are skipped when checking the rest of the model.
+    pub parse_errors: Vec<ParseError>,
+    /// Color codes not defined in `LDConfig.ldr` or the model files.
+    /// This is empty if the LDraw library has no `LDConfig.ldr`.
+    pub unknown_colors: Vec<ColorCode>,
+    /// Color codes defined differently in `LDConfig.ldr`, custom Studio colors,
+    /// or `!COLOUR` commands in the model files.
+    /// The model colors take priority followed by the Studio colors.
+    pub conflicting_colors: Vec<ColorCode>,
+    /// The number of part instances in the model including all submodels.
+    pub part_count: usize,
+    /// The number of unique submodels referenced by the model.
+    pub submodel_count: usize,
+}
+
+/// Resolve and parse all files for the model at `path` like [crate::load_file]
+/// to find missing files, parse errors, and unknown or conflicting colors.
+/// This is much faster than loading since no geometry is created.
+pub fn validate_file(
+    path: &str,
+    ldraw_path: &str,
+    additional_paths: &[String],
+) -> ValidationReport {
+    let settings = GeometrySettings::default();
+    let resolver = match crate::file_resolver(path, ldraw_path, additional_paths, &settings) {
+        Ok(resolver) => resolver,
+        Err(e) => {
+            log::error!("Error validating {path:?}: {e}");
+            return ValidationReport::default();
+        }
+    };
+
+    let resolver = ValidatingResolver {
+        resolver,
+        missing_files: Mutex::new(BTreeSet::new()),
+        parse_errors: Mutex::new(Vec::new()),
+    };
+    let mut source_map = SourceMap::new();
+    let main_model_name = ldraw::parse(path, &resolver, &mut source_map);
+
+    let mut report = ValidationReport {
+        missing_files: resolver
+            .missing_files
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .collect(),
+        parse_errors: resolver.parse_errors.into_inner().unwrap(),
+        ..Default::default()
+    };
+    report
+        .parse_errors
+        .sort_by(|a, b| (&a.filename, a.line_number).cmp(&(&b.filename, b.line_number)));
+
+    let main_model_name = match main_model_name {
+        Ok(name) => name,
+        Err(e) => {
+            log::error!("Error validating {path:?}: {e}");
+            return report;
+        }
+    };
+
+    let color_table = if Path::new(ldraw_path).join("LDConfig.ldr").exists() {
+        let ldraw_colors = color::load_color_table(ldraw_path);
+        let mut color_table = load_color_table_for_file(ldraw_path, path);
+        let model_colors = color::model_colors(&source_map, &main_model_name);
+        report.conflicting_colors =
+            color::conflicting_colors(&[&ldraw_colors, &color_table, &model_colors]);
+        color_table.extend(model_colors);
+        Some(color_table)
+    } else {
+        None
+    };
+    report.unknown_colors = unknown_colors(&source_map, &main_model_name, color_table.as_ref());
+
+    let mut submodels = HashSet::new();
+    report.part_count = count_parts(&source_map, &main_model_name, &mut submodels, 0, &settings);
+    report.submodel_count = submodels.len();
+
+    report
+}
+
+struct ValidatingResolver<R> {
+    resolver: R,
+    missing_files: Mutex<BTreeSet<String>>,
+    parse_errors: Mutex<Vec<ParseError>>,
+}
+
+impl<R: FileRefResolver> FileRefResolver for ValidatingResolver<R> {
+    fn resolve<P: AsRef<Path>>(&self, filename: P) -> Result<Vec<u8>, ResolveError> {
+        let name = filename.as_ref().to_string_lossy().into_owned();
+        let bytes = match self.resolver.resolve(filename) {
+            Ok(bytes) if !bytes.is_empty() => bytes,
+            result => {
+                self.missing_files.lock().unwrap().insert(name);
+                return result;
+            }
+        };
+
+        let (_, errors) = ldraw::parse_raw_with_errors(&bytes);
+        if errors.is_empty() {
+            return Ok(bytes);
+        }
+
+        // Remove invalid lines to continue checking the rest of the model.
+        let error_lines: HashSet<_> = errors.iter().map(|(_, e)| e.line_number).collect();
+        let bytes = bytes
+            .split(|b| *b == b'\n')
+            .enumerate()
+            .filter(|(i, _)| !error_lines.contains(&(i + 1)))
+            .flat_map(|(_, line)| line.iter().copied().chain([b'\n']))
+            .collect();
+
+        self.parse_errors
+            .lock()
+            .unwrap()
+            .extend(errors.into_iter().map(|(_, mut e)| {
+                e.filename = name.clone();
+                e
+            }));
+
+        Ok(bytes)
+    }
+}
+
+fn unknown_colors(
+    source_map: &SourceMap,
+    main_model_name: &str,
+    color_table: Option<&HashMap<u32, LDrawColor>>,
+) -> Vec<ColorCode> {
+    let Some(color_table) = color_table else {
+        return Vec::new();
+    };
+
+    let mut unknown = BTreeSet::new();
+    let mut visited = HashSet::new();
+    let mut stack = vec![main_model_name.to_lowercase()];
+    while let Some(name) = stack.pop() {
+        let Some(source_file) = source_map.get(&name) else {
+            continue;
+        };
+        for cmd in &source_file.cmds {
+            let color = match cmd {
+                Command::SubFileRef(c) => {
+                    let name = c.file.to_lowercase();
+                    if visited.insert(name.clone()) {
+                        stack.push(name);
+                    }
+                    c.color
+                }
+                Command::Line(c) => c.color,
+                Command::Triangle(c) => c.color,
+                Command::Quad(c) => c.color,
+                Command::OptLine(c) => c.color,
+                _ => continue,
+            };
+            if color != CURRENT_COLOR && color != EDGE_COLOR && !color_table.contains_key(&color) {
+                unknown.insert(color);
+            }
+        }
+    }
+    unknown.into_iter().collect()
+}
+
+fn count_parts(
+    source_map: &SourceMap,
+    filename: &str,
+    submodels: &mut HashSet<String>,
+    depth: usize,
+    settings: &GeometrySettings,
+) -> usize {
+    if crate::exceeds_max_depth(filename, depth, settings) {
+        return 0;
+    }
+
+    let Some(source_file) = source_map.get(filename) else {
+        return 0;
+    };
+
+    let mut count = 0;
+    for cmd in &source_file.cmds {
+        if let Command::SubFileRef(sfr_cmd) = cmd {
+            // Missing files resolve to empty files and shouldn't count as parts.
+            if let Some(subfile) = source_map
+                .get(&sfr_cmd.file)
+                .filter(|subfile| !subfile.cmds.is_empty())
+            {
+                if crate::is_part(subfile, &sfr_cmd.file) {
+                    count += 1;
+                } else {
+                    submodels.insert(sfr_cmd.file.to_lowercase());
+                    count += count_parts(source_map, &sfr_cmd.file, submodels, depth + 1, settings);
+                }
+            }
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_file_problems() {
+        let dir = tempfile::tempdir().unwrap();
+        let parts = dir.path().join("parts");
+        std::fs::create_dir_all(&parts).unwrap();
+        std::fs::write(
+            dir.path().join("LDConfig.ldr"),
+            "0 !COLOUR Red CODE 4 VALUE #C91A09 EDGE #333333",
+        )
+        .unwrap();
+        std::fs::write(parts.join("a.dat"), "3 16 1 0 0 0 1 0 0 0 1\n").unwrap();
+        let main = indoc::indoc! {"
+            0 FILE main.ldr
+            1 4 0 0 0 1 0 0 0 1 0 0 0 1 sub.ldr
+            1 4 0 0 0 1 0 0 0 1 0 0 0 1 sub.ldr
+            1 4 0 0 0 1 0 0 0 1 0 0 0 1 missing.dat
+            0 !COLOUR Custom_Red CODE 4 VALUE #FF0000 EDGE #333333
+            0 FILE sub.ldr
+            1 4 0 0 0 1 0 0 0 1 0 0 0 1 a.dat
+            1 999 0 0 0 1 0 0 0 1 0 0 0 1 a.dat
+            3 16 invalid
+        "};
+        let path = dir.path().join("main.mpd");
+        std::fs::write(&path, main).unwrap();
+
+        let report = validate_file(path.to_str().unwrap(), dir.path().to_str().unwrap(), &[]);
+
+        assert_eq!(vec!["missing.dat".to_string()], report.missing_files);
+        assert_eq!(1, report.parse_errors.len());
+        assert_eq!(9, report.parse_errors[0].line_number);
+        assert_eq!(vec![999], report.unknown_colors);
+        assert_eq!(vec![4], report.conflicting_colors);
+        assert_eq!(4, report.part_count);
+        assert_eq!(1, report.submodel_count);
+    }
+}
@@ -2,7 +2,6 @@ import bpy
 import numpy as np
 import mathutils
 import math
-import struct
 import typing
 import itertools
 
@@ -39,7 +38,7 @@ def import_ldraw(
     instance_type: str,
     settings: GeometrySettings,
 ) -> None:
-    color_by_code = ldr_tools_py.load_color_table(ldraw_path)
+    color_by_code = ldr_tools_py.load_color_table(ldraw_path, filepath)
 
     # TODO: Add an option to make the lowest point have a height of 0 using obj.dimensions?
     if instance_type == "GeometryNodes":
@@ -66,6 +65,7 @@ def import_objects(
     settings.scene_scale = 1.0
 
     scene = ldr_tools_py.load_file(filepath, ldraw_path, additional_paths, settings)
+    color_by_code = {**color_by_code, **scene.custom_colors}
 
     root_obj = add_nodes(
         scene.root_node, scene.geometry_cache, blender_mesh_cache, color_by_code
@@ -96,13 +96,13 @@ def add_nodes(
             )
 
             blender_mesh_cache[mesh_key] = mesh
-            obj = bpy.data.objects.new(node.name, mesh)
+            obj = bpy.data.objects.new(node.unique_name, mesh)
         else:
             # Use an existing mesh data block like with linked duplicates (alt+d).
-            obj = bpy.data.objects.new(node.name, blender_mesh)
+            obj = bpy.data.objects.new(node.unique_name, blender_mesh)
     else:
         # Create an empty by setting the data to None.
-        obj = bpy.data.objects.new(node.name, None)
+        obj = bpy.data.objects.new(node.unique_name, None)
 
     # Each node is transformed relative to its parent.
     obj.matrix_local = mathutils.Matrix(node.transform).transposed()
@@ -130,6 +130,7 @@ def import_instanced(
     scene = ldr_tools_py.load_file_instanced_points(
         filepath, ldraw_path, additional_paths, settings
     )
+    color_by_code = {**color_by_code, **scene.custom_colors}
 
     # First create all the meshes and materials.
     blender_mesh_cache = {}
@@ -284,6 +285,8 @@ def create_colored_mesh_from_geometry(
     mesh.validate()
     mesh.update()
 
+    mark_sharp_edges(mesh, geometry)
+
     # Add attributes needed to render grainy slopes properly.
     if geometry.has_grainy_slopes:
         # Get custom normals now that everything has been initialized.
@@ -298,13 +301,14 @@ def create_colored_mesh_from_geometry(
     return mesh
 
 
-def load_png(data: bytes, name: str = "img") -> bpy.types.Image:
-    # TODO: pass image names up from the Rust side
-    w, h = struct.unpack(b">LL", data[16:24])
-    img = bpy.data.images.new(name, w, h)
+def load_image(rgba: np.ndarray, name: str) -> bpy.types.Image:
+    h, w, _ = rgba.shape
+    img = bpy.data.images.new(name, w, h, alpha=True)
     img.use_fake_user = True
-    img.pack(data=data, data_len=len(data))  # type: ignore[arg-type]
-    img.source = "FILE"  # ?
+    # Blender images start from the bottom left corner.
+    pixels = np.flipud(rgba).astype(np.float32) / 255.0
+    img.pixels.foreach_set(pixels.reshape(-1))  # type: ignore[arg-type]
+    img.pack()
     return img
 
 
@@ -325,7 +329,10 @@ def assign_materials(
         return
 
     if tex_info := geometry.texture_info:
-        images = [load_png(t) for t in tex_info.textures]
+        images = [
+            load_image(t, name) if t is not None else None
+            for t, name in zip(tex_info.decoded_textures, tex_info.texture_names)
+        ]
 
     if len(geometry.face_colors) > 1:
         assert len(geometry.face_colors) == len(mesh.polygons)
@@ -380,6 +387,29 @@ def create_mesh_from_geometry(name: str, geometry: LDrawGeometry) -> Mesh:
     return mesh
 
 
+def mark_sharp_edges(mesh: Mesh, geometry: LDrawGeometry) -> None:
+    if geometry.sharp_edges.shape[0] == 0:
+        return
+
+    # Blender edges are unordered, so compare sorted vertex pairs.
+    edges = np.zeros(len(mesh.edges) * 2, dtype=np.int32)
+    mesh.edges.foreach_get("vertices", edges)
+    edges = np.sort(edges.reshape(-1, 2), axis=1).astype(np.int64)
+
+    # Compare each pair as a single integer to avoid looping over edges in Python.
+    vertex_count = len(mesh.vertices)
+    sharp_edges = geometry.sharp_edges.astype(np.int64)
+    is_sharp = np.isin(
+        edges[:, 0] * vertex_count + edges[:, 1],
+        sharp_edges[:, 0] * vertex_count + sharp_edges[:, 1],
+    )
+
+    attr = mesh.attributes.get("sharp_edge") or mesh.attributes.new(
+        name="sharp_edge", type="BOOLEAN", domain="EDGE"
+    )
+    attr.data.foreach_set("value", is_sharp)  # type: ignore[union-attr]
+
+
 AttributeDomain: typing.TypeAlias = typing.Literal[
     "POINT",
     "EDGE",
@@ -96,6 +96,9 @@ def get_material(
     # Alpha is specified using transmission instead.
     base_color = (r, g, b, 1.0)
 
+    # Glow in the dark colors.
+    emission_strength = 0.0 if ldraw_color is None else ldraw_color.emission_strength
+
     # Normal opaque materials.
     metallicity = 0.0
     roughness = (0.075, 0.2)
@@ -215,6 +218,8 @@ def get_material(
             "Metallic": metallicity,
             "Transmission Weight": transmission,
             "IOR": refraction,
+            "Emission Color": (r, g, b, 1.0),
+            "Emission Strength": emission_strength,
         },
     )
     bsdf.node.location = (-240, 460)
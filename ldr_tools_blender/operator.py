@@ -164,7 +164,7 @@ class ImportOperator(bpy.types.Operator, ImportHelper):
         scene_scale: float
     else:
         filter_glob: StringProperty(
-            default="*.mpd;*.ldr;*.dat;*.io", options={"HIDDEN"}
+            default="*.mpd;*.ldr;*.dat;*.io;*.gz", options={"HIDDEN"}
         )
 
         ldraw_path: StringProperty(name="LDraw Library", default=preferences.ldraw_path)
@@ -264,18 +264,28 @@ class ImportOperator(bpy.types.Operator, ImportHelper):
 
         import time
 
+        # Collect warnings like unresolved files to show in Blender's reports.
+        warnings: list[str] = []
+        ldr_tools_py.set_log_callback(lambda level, message: warnings.append(message))
+
         start = time.time()
-        import_ldraw(
-            self,
-            self.filepath,  # type: ignore[attr-defined]
-            self.ldraw_path,
-            ImportOperator.preferences.additional_paths,
-            self.instance_type,
-            settings,
-        )
+        try:
+            import_ldraw(
+                self,
+                self.filepath,  # type: ignore[attr-defined]
+                self.ldraw_path,
+                ImportOperator.preferences.additional_paths,
+                self.instance_type,
+                settings,
+            )
+        finally:
+            ldr_tools_py.set_log_callback(None)
         end = time.time()
         print(f"Import: {end - start}")
 
+        for warning in warnings:
+            self.report({"WARNING"}, warning)
+
         # Save preferences to disk for loading next time.
         ImportOperator.preferences.save()
         return {"FINISHED"}
@@ -304,5 +314,7 @@ class ImportOperator(bpy.types.Operator, ImportHelper):
         settings.scene_scale = self.scene_scale
         # Required for calculated normals.
         settings.weld_vertices = True
+        # Required for marking sharp edges.
+        settings.calculate_mesh_edges = True
 
         return settings
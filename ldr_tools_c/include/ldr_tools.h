@@ -0,0 +1,73 @@
+/*
+ * C bindings for ldr_tools.
+ *
+ * Geometry returned by ldr_load_file is owned by ldr_tools.
+ * All pointers in LdrGeometry remain valid until the geometry is passed to ldr_geometry_free.
+ * Do not free the individual arrays or modify their contents.
+ */
+#ifndef LDR_TOOLS_H
+#define LDR_TOOLS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum LdrStudType {
+    LDR_STUD_TYPE_DISABLED,
+    LDR_STUD_TYPE_NORMAL,
+    LDR_STUD_TYPE_LOGO4,
+    LDR_STUD_TYPE_HIGH_CONTRAST,
+} LdrStudType;
+
+typedef struct LdrGeometrySettings {
+    bool triangulate;
+    bool add_gap_between_parts;
+    LdrStudType stud_type;
+    bool weld_vertices;
+    float scene_scale;
+} LdrGeometrySettings;
+
+/*
+ * Flattened geometry for an entire model.
+ * Pointers should not be read if the corresponding count is 0.
+ */
+typedef struct LdrGeometry {
+    /* XYZ positions with 3 floats for each vertex. */
+    const float *vertices;
+    size_t vertex_count;
+    /* Indices into vertices for all faces. */
+    const uint32_t *vertex_indices;
+    size_t vertex_index_count;
+    /* The start of each face in vertex_indices. */
+    const uint32_t *face_start_indices;
+    /* The number of vertices in each face. */
+    const uint32_t *face_sizes;
+    size_t face_count;
+    /* The LDraw color code for each face or a single color for all faces. */
+    const uint32_t *face_colors;
+    size_t face_color_count;
+} LdrGeometry;
+
+/* Default values for LdrGeometrySettings. */
+LdrGeometrySettings ldr_geometry_settings_default(void);
+
+/*
+ * Load the UTF-8 file path and combine all parts into a single geometry.
+ * Pass NULL for settings to use the defaults.
+ * Returns NULL if loading failed. Free the result with ldr_geometry_free.
+ */
+LdrGeometry *ldr_load_file(const char *path, const char *ldraw_path,
+                           const LdrGeometrySettings *settings);
+
+/* Free geometry returned by ldr_load_file. Passing NULL does nothing. */
+void ldr_geometry_free(LdrGeometry *geometry);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,240 @@
+//! C bindings to ldr_tools for applications without a Rust or Python dependency.
+//!
+//! Geometry returned by [ldr_load_file] is owned by this library.
+//! All pointers in [LdrGeometry] remain valid until the geometry is passed to [ldr_geometry_free].
+//! See `include/ldr_tools.h` for the matching C declarations.
+use std::{
+    ffi::{c_char, CStr},
+    panic::AssertUnwindSafe,
+};
+
+use ldr_tools::{ldraw::SourceMap, GeometrySettings, LDrawGeometry, LoadOptions, StudType};
+
+/// Settings for [ldr_load_file] with the same defaults as [ldr_geometry_settings_default].
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LdrGeometrySettings {
+    pub triangulate: bool,
+    pub add_gap_between_parts: bool,
+    pub stud_type: LdrStudType,
+    pub weld_vertices: bool,
+    pub scene_scale: f32,
+}
+
+/// The stud type for [LdrGeometrySettings] matching [StudType].
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub enum LdrStudType {
+    Disabled,
+    Normal,
+    Logo4,
+    HighContrast,
+}
+
+impl From<StudType> for LdrStudType {
+    fn from(value: StudType) -> Self {
+        match value {
+            StudType::Disabled => Self::Disabled,
+            StudType::Normal => Self::Normal,
+            StudType::Logo4 => Self::Logo4,
+            StudType::HighContrast => Self::HighContrast,
+        }
+    }
+}
+
+impl From<LdrStudType> for StudType {
+    fn from(value: LdrStudType) -> Self {
+        match value {
+            LdrStudType::Disabled => Self::Disabled,
+            LdrStudType::Normal => Self::Normal,
+            LdrStudType::Logo4 => Self::Logo4,
+            LdrStudType::HighContrast => Self::HighContrast,
+        }
+    }
+}
+
+/// Flattened geometry for an entire model with arrays in the same layout as [LDrawGeometry].
+///
+/// Each array is stored as a pointer and the number of elements.
+/// Pointers should not be read if the corresponding count is 0.
+#[repr(C)]
+#[derive(Debug)]
+pub struct LdrGeometry {
+    /// XYZ positions with 3 floats for each vertex.
+    pub vertices: *const f32,
+    pub vertex_count: usize,
+    /// Indices into [vertices](#structfield.vertices) for all faces.
+    pub vertex_indices: *const u32,
+    pub vertex_index_count: usize,
+    /// The start of each face in [vertex_indices](#structfield.vertex_indices).
+    pub face_start_indices: *const u32,
+    /// The number of vertices in each face.
+    pub face_sizes: *const u32,
+    pub face_count: usize,
+    /// The LDraw color code for each face.
+    /// This contains a single element if all faces have the same color.
+    pub face_colors: *const u32,
+    pub face_color_count: usize,
+}
+
+// Keep the geometry alive with the pointers into its arrays.
+// The C struct is the first field so the pointer can be cast back when freeing.
+#[repr(C)]
+struct OwnedGeometry {
+    ffi: LdrGeometry,
+    geometry: LDrawGeometry,
+}
+
+/// Default values for [LdrGeometrySettings].
+#[no_mangle]
+pub extern "C" fn ldr_geometry_settings_default() -> LdrGeometrySettings {
+    let settings = GeometrySettings::default();
+    LdrGeometrySettings {
+        triangulate: settings.triangulate,
+        add_gap_between_parts: settings.add_gap_between_parts,
+        stud_type: settings.stud_type.into(),
+        weld_vertices: settings.weld_vertices,
+        scene_scale: settings.scene_scale,
+    }
+}
+
+/// Load the file at `path` and combine all parts into a single geometry.
+///
+/// Returns null if loading failed. Free the result with [ldr_geometry_free].
+///
+/// # Safety
+/// `path` and `ldraw_path` must be valid null terminated UTF-8 strings.
+/// `settings` must be null to use the defaults or point to valid settings.
+#[no_mangle]
+pub unsafe extern "C" fn ldr_load_file(
+    path: *const c_char,
+    ldraw_path: *const c_char,
+    settings: *const LdrGeometrySettings,
+) -> *mut LdrGeometry {
+    let Some(path) = str_from_ptr(path) else {
+        return std::ptr::null_mut();
+    };
+    let Some(ldraw_path) = str_from_ptr(ldraw_path) else {
+        return std::ptr::null_mut();
+    };
+    let settings = geometry_settings(settings.as_ref());
+
+    // Unwinding across the FFI boundary is undefined behavior.
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        ldr_tools::try_load_file(
+            path,
+            ldraw_path,
+            &[],
+            &settings,
+            &mut SourceMap::new(),
+            LoadOptions::default(),
+        )
+        .map(|scene| scene.flatten())
+    }));
+
+    match result {
+        Ok(Ok(geometry)) => Box::into_raw(Box::new(OwnedGeometry {
+            ffi: ffi_geometry(&geometry),
+            geometry,
+        }))
+        .cast(),
+        Ok(Err(e)) => {
+            log::error!("Error loading {path:?}: {e}");
+            std::ptr::null_mut()
+        }
+        Err(_) => {
+            log::error!("Error loading {path:?}");
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Free geometry returned by [ldr_load_file].
+/// This invalidates all pointers in the geometry.
+///
+/// # Safety
+/// `geometry` must be null or returned from [ldr_load_file] and not already freed.
+#[no_mangle]
+pub unsafe extern "C" fn ldr_geometry_free(geometry: *mut LdrGeometry) {
+    if !geometry.is_null() {
+        drop(Box::from_raw(geometry.cast::<OwnedGeometry>()));
+    }
+}
+
+unsafe fn str_from_ptr<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        None
+    } else {
+        CStr::from_ptr(ptr).to_str().ok()
+    }
+}
+
+fn geometry_settings(settings: Option<&LdrGeometrySettings>) -> GeometrySettings {
+    let defaults = GeometrySettings::default();
+    match settings {
+        Some(s) => GeometrySettings {
+            triangulate: s.triangulate,
+            add_gap_between_parts: s.add_gap_between_parts,
+            stud_type: s.stud_type.into(),
+            weld_vertices: s.weld_vertices,
+            scene_scale: s.scene_scale,
+            ..defaults
+        },
+        None => defaults,
+    }
+}
+
+fn ffi_geometry(geometry: &LDrawGeometry) -> LdrGeometry {
+    // Moving the geometry into the box doesn't move the heap allocations for each array.
+    LdrGeometry {
+        vertices: geometry.vertices.as_ptr().cast(),
+        vertex_count: geometry.vertices.len(),
+        vertex_indices: geometry.vertex_indices.as_ptr(),
+        vertex_index_count: geometry.vertex_indices.len(),
+        face_start_indices: geometry.face_start_indices.as_ptr(),
+        face_sizes: geometry.face_sizes.as_ptr(),
+        face_count: geometry.face_sizes.len(),
+        face_colors: geometry.face_colors.as_ptr(),
+        face_color_count: geometry.face_colors.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ffi::CString;
+
+    #[test]
+    fn ldr_load_file_null_path() {
+        let geometry = unsafe { ldr_load_file(std::ptr::null(), c"".as_ptr(), std::ptr::null()) };
+        assert!(geometry.is_null());
+    }
+
+    #[test]
+    fn ldr_load_file_triangle() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("main.ldr"),
+            "1 4 0 0 0 1 0 0 0 1 0 0 0 1 a.dat\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("a.dat"), "3 16 1 0 0 0 1 0 0 0 1\n").unwrap();
+        let path = CString::new(dir.path().join("main.ldr").to_str().unwrap()).unwrap();
+
+        let settings = ldr_geometry_settings_default();
+        let geometry = unsafe { ldr_load_file(path.as_ptr(), c"".as_ptr(), &settings) };
+        assert!(!geometry.is_null());
+
+        let ffi = unsafe { &*geometry };
+        assert_eq!(3, ffi.vertex_index_count);
+        assert_eq!(1, ffi.face_count);
+        let face_sizes = unsafe { std::slice::from_raw_parts(ffi.face_sizes, ffi.face_count) };
+        assert_eq!(&[3], face_sizes);
+        let face_colors =
+            unsafe { std::slice::from_raw_parts(ffi.face_colors, ffi.face_color_count) };
+        assert_eq!(&[4], face_colors);
+
+        unsafe { ldr_geometry_free(geometry) };
+    }
+}
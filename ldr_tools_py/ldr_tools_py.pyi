@@ -1,38 +1,79 @@
-from typing import Final, ClassVar
+from typing import Callable, Final, ClassVar
 
 from .stub_helpers import (
     UByteArray,
+    RgbaImageArray,
     UIntArray,
     FloatArray,
     UVec2Array,
+    UVec2PairArray,
     Vec2Array,
     Vec3Array,
+    Vec3PairArray,
+    Vec4Array,
+    InterleavedVertexArray,
     Mat4Array,
     Vec2,
+    Vec3,
     Vec4,
     Mat4,
 )
 
 class LDrawNode:
     name: str
+    unique_name: str
     transform: Mat4
     geometry_name: str | None
     current_color: int
+    is_mirrored: bool
+    rgba_linear: Vec4 | None
+    piece_metadata: PieceMetadata
     children: list[LDrawNode]
 
+    def transform_flat(self) -> list[float]: ...
+
+class PieceMetadata:
+    step_hide: int | None
+    is_hidden: bool
+
 class LDrawGeometry:
     vertices: Vec3Array
     vertex_indices: UIntArray
     face_start_indices: UIntArray
     face_sizes: UIntArray
+    face_normals: Vec3Array
     face_colors: UIntArray
     is_face_stud: list[bool]
+    is_face_double_sided: list[bool]
+    face_windings: list[Winding]
     edge_line_indices: UVec2Array
+    edge_line_colors: UIntArray
+    optional_line_edges: UVec2PairArray
+    mesh_edge_indices: UVec2Array
+    is_mesh_edge_sharp: list[bool]
+    sharp_edges: UVec2Array
     has_grainy_slopes: bool
     texture_info: LDrawTextureInfo | None
+    welded_vertex_count: int
+
+    def interleaved(self) -> tuple[InterleavedVertexArray, UIntArray]: ...
+    def tangents(self) -> Vec4Array | None: ...
+    def triangle_colors(self) -> UIntArray: ...
+    def is_manifold(self) -> bool: ...
+    def manifold_report(self) -> ManifoldReport: ...
+
+class ManifoldReport:
+    is_manifold: bool
+    boundary_edges: Vec3PairArray
+    non_manifold_edges: Vec3PairArray
 
 class LDrawTextureInfo:
     textures: list[bytes]
+    texture_sizes: list[tuple[int, int]]
+    texture_names: list[str]
+    glossmaps: list[bytes | None]
+    decoded_textures: list[RgbaImageArray | None]
+    texture_has_alpha: list[bool]
     indices: UByteArray
     uvs: Vec2Array
 
@@ -40,15 +81,47 @@ class LDrawColor:
     name: str
     finish_name: str
     rgba_linear: Vec4
+    rgba_srgb: Vec4
+    edge_rgba_linear: Vec4
     speckle_rgba_linear: Vec4 | None
+    glitter_rgba_linear: Vec4 | None
+    grain_size: Vec2 | None
+    grain_surface_fraction: float | None
+    grain_volume_fraction: float | None
+    luminance: int | None
+    emission_strength: float
 
 class GeometrySettings:
     triangulate: bool
     add_gap_between_parts: bool
+    gap_excludes_flexible: bool
+    gap_distance: Vec3
     stud_type: StudType
     weld_vertices: bool
+    weld_excluded_parts: set[str]
+    weld_excludes_flexible: bool
     primitive_resolution: PrimitiveResolution
+    primitive_resolution_overrides: list[tuple[str, PrimitiveResolution]]
+    additional_library_paths: list[str]
     scene_scale: float
+    flip_texture_v: bool
+    thread_count: int | None
+    single_threaded: bool
+    double_side_uncertified: bool
+    sharp_angle_threshold: float
+    calculate_mesh_edges: bool
+    part_substitutions: dict[str, str]
+    resolve_node_colors: bool
+    remove_hidden_studs: bool
+    max_recursion_depth: int
+    deduplicate_geometry: bool
+    separate_studs: bool
+    download_cache_path: str | None
+    remove_duplicate_faces: bool
+    stud_substring_fallback: bool
+    record_face_windings: bool
+    root_color: int
+    import_hidden: bool
 
 class StudType:
     Disabled: Final[StudType]
@@ -56,6 +129,10 @@ class StudType:
     Logo4: Final[StudType]
     HighContrast: Final[StudType]
 
+class Winding:
+    Ccw: Final[Winding]
+    Cw: Final[Winding]
+
 class PrimitiveResolution:
     Low: Final[PrimitiveResolution]
     Normal: Final[PrimitiveResolution]
@@ -66,28 +143,187 @@ class PointInstances:
     rotations_axis: Vec3Array
     rotations_angle: FloatArray
     scales: Vec3Array
+    transforms: Mat4Array
+
+class ValidationReport:
+    missing_files: list[str]
+    parse_errors: list[tuple[str, int, str]]
+    unknown_colors: list[int]
+    conflicting_colors: list[int]
+    part_count: int
+    submodel_count: int
 
 class LDrawScene:
+    main_model_name: str
     root_node: LDrawNode
     geometry_cache: dict[str, LDrawGeometry]
+    custom_colors: dict[int, LDrawColor]
+    stats: LoadStats
+
+    def used_colors(self) -> list[int]: ...
+    def ground_transform(self, center: bool = False) -> Mat4: ...
+    def flat_instances(self) -> list[tuple[str, Mat4, int]]: ...
 
 class LDrawSceneInstanced:
     main_model_name: str
     geometry_world_transforms: dict[tuple[str, int], Mat4Array]
     geometry_cache: dict[str, LDrawGeometry]
+    custom_colors: dict[int, LDrawColor]
+    stats: LoadStats
 
 class LDrawSceneInstancedPoints:
     main_model_name: str
     geometry_point_instances: dict[tuple[str, int], PointInstances]
     geometry_cache: dict[str, LDrawGeometry]
+    custom_colors: dict[int, LDrawColor]
+    stats: LoadStats
+
+class GeometryStats:
+    name: str
+    vertex_count: int
+    triangle_count: int
+
+class LoadStats:
+    files_parsed: int
+    geometry_count: int
+    face_count: int
+    parse_time: float
+    geometry_time: float
+
+class Command:
+    class SubFileRef(Command):
+        color: int
+        transform: Mat4
+        file: str
+
+    class Line(Command):
+        color: int
+        vertices: tuple[Vec3, Vec3]
+
+    class Triangle(Command):
+        color: int
+        vertices: tuple[Vec3, Vec3, Vec3]
+
+    class Quad(Command):
+        color: int
+        vertices: tuple[Vec3, Vec3, Vec3, Vec3]
+
+    class Colour(Command):
+        name: str
+        code: int
+        value: tuple[int, int, int]
+        edge: tuple[int, int, int]
+        alpha: int | None
+        luminance: int | None
+
+    class Comment(Command):
+        text: str
+
+    class LPub(Command):
+        directive: str
+
+    class LeoCadModel(Command):
+        property: str
+        value: str
+
+    class LeoCadPieceStepHide(Command):
+        step: int
+
+    class LeoCadPieceHidden(Command): ...
+
+    class BufExchgStore(Command):
+        buffer: str
+
+    class BufExchgRetrieve(Command):
+        buffer: str
+
+    class Step(Command): ...
+
+    class RotStep(Command):
+        angles: Vec3
+        mode: str
+
+    class RotStepEnd(Command): ...
+
+    class Unhandled(Command):
+        name: str
+
+class LoadCancelledError(Exception): ...
+
+class CancelToken:
+    def __init__(self) -> None: ...
+    def cancel(self) -> None: ...
+    def is_cancelled(self) -> bool: ...
+
+class SourceMap:
+    def __init__(self) -> None: ...
+    def __len__(self) -> int: ...
 
 def load_file(
-    path: str, ldraw_path: str, additional_paths: list[str], settings: GeometrySettings
+    path: str,
+    ldraw_path: str,
+    additional_paths: list[str],
+    settings: GeometrySettings,
+    cancel: CancelToken | None = None,
+    progress: Callable[[float, str], None] | None = None,
+    source_map: SourceMap | None = None,
+    main_model: str | None = None,
 ) -> LDrawScene: ...
+def load_file_flattened(
+    path: str,
+    ldraw_path: str,
+    additional_paths: list[str],
+    settings: GeometrySettings,
+    cancel: CancelToken | None = None,
+    progress: Callable[[float, str], None] | None = None,
+    source_map: SourceMap | None = None,
+    main_model: str | None = None,
+) -> LDrawGeometry: ...
 def load_file_instanced(
-    path: str, ldraw_path: str, additional_paths: list[str], settings: GeometrySettings
+    path: str,
+    ldraw_path: str,
+    additional_paths: list[str],
+    settings: GeometrySettings,
+    cancel: CancelToken | None = None,
+    progress: Callable[[float, str], None] | None = None,
+    source_map: SourceMap | None = None,
+    main_model: str | None = None,
 ) -> LDrawSceneInstanced: ...
 def load_file_instanced_points(
-    path: str, ldraw_path: str, additional_paths: list[str], settings: GeometrySettings
+    path: str,
+    ldraw_path: str,
+    additional_paths: list[str],
+    settings: GeometrySettings,
+    cancel: CancelToken | None = None,
+    progress: Callable[[float, str], None] | None = None,
+    source_map: SourceMap | None = None,
+    main_model: str | None = None,
 ) -> LDrawSceneInstancedPoints: ...
-def load_color_table(ldraw_path: str) -> dict[int, LDrawColor]: ...
+def load_color_table(
+    ldraw_path: str, path: str | None = None, realistic: bool = False
+) -> dict[int, LDrawColor]: ...
+def load_io_color_table(io_path: str) -> dict[int, LDrawColor]: ...
+def parse_file_commands(bytes: bytes) -> list[Command]: ...
+def validate_file(
+    path: str, ldraw_path: str, additional_paths: list[str]
+) -> ValidationReport: ...
+def stud_footprint(
+    min: tuple[float, float, float], max: tuple[float, float, float]
+) -> tuple[int, int, int]: ...
+def geometry_stats(
+    geometry_cache: dict[str, LDrawGeometry],
+) -> list[GeometryStats]: ...
+def file_dependencies(
+    path: str, ldraw_path: str, additional_paths: list[str]
+) -> list[str]: ...
+def find_parse_errors(path: str) -> list[tuple[int, str, str]]: ...
+def build_part_index(
+    ldraw_path: str, additional_library_paths: list[str], rebuild: bool = False
+) -> list[tuple[str, str, str, list[str]]]: ...
+def search_parts(
+    query: str, ldraw_path: str, additional_library_paths: list[str]
+) -> list[tuple[str, str, str, list[str]]]: ...
+def load_part_substitutions(path: str) -> dict[str, str]: ...
+def set_log_callback(
+    callback: Callable[[int, str], None] | None, level: int = 30
+) -> None: ...
@@ -1,5 +1,9 @@
-use numpy::{IntoPyArray, PyArray2, PyArrayMethods};
-use pyo3::prelude::*;
+use std::sync::{Arc, Mutex};
+
+use numpy::{IntoPyArray, PyArray2, PyArray3, PyArrayMethods};
+use pyo3::{create_exception, exceptions::PyException, prelude::*};
+
+create_exception!(ldr_tools_py, LoadCancelledError, PyException);
 
 macro_rules! python_enum {
     ($py_ty:ident, $rust_ty:ty, $( $i:ident ),+) => {
@@ -45,11 +49,20 @@ python_enum!(
     High
 );
 
+python_enum!(Winding, ldr_tools::ldraw::Winding, Ccw, Cw);
+
 #[pymodule]
 mod ldr_tools_py {
     use super::*;
 
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap, HashSet};
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    #[pymodule_export]
+    use super::LoadCancelledError;
 
     use numpy::PyArray3;
     use numpy::{IntoPyArray, PyArray1, PyArray2, PyArrayMethods};
@@ -61,49 +74,216 @@ mod ldr_tools_py {
     #[pymodule_export]
     use super::PrimitiveResolution;
 
+    #[pymodule_export]
+    use super::Winding;
+
     #[pyclass(get_all)]
     #[derive(Debug, Clone)]
     pub struct LDrawNode {
         name: String,
+        unique_name: String,
         transform: [[f32; 4]; 4],
         geometry_name: Option<String>,
         current_color: u32,
+        is_mirrored: bool,
+        rgba_linear: Option<[f32; 4]>,
+        piece_metadata: PieceMetadata,
         children: Vec<LDrawNode>,
     }
 
+    #[pyclass(get_all)]
+    #[derive(Debug, Clone)]
+    pub struct PieceMetadata {
+        step_hide: Option<u32>,
+        is_hidden: bool,
+    }
+
+    impl From<ldr_tools::PieceMetadata> for PieceMetadata {
+        fn from(metadata: ldr_tools::PieceMetadata) -> Self {
+            Self {
+                step_hide: metadata.step_hide,
+                is_hidden: metadata.is_hidden,
+            }
+        }
+    }
+
     impl From<ldr_tools::LDrawNode> for LDrawNode {
         fn from(node: ldr_tools::LDrawNode) -> Self {
             Self {
                 name: node.name,
+                unique_name: node.unique_name,
                 transform: node.transform.to_cols_array_2d(),
                 geometry_name: node.geometry_name,
                 current_color: node.current_color,
+                is_mirrored: node.is_mirrored,
+                rgba_linear: node.rgba_linear,
+                piece_metadata: node.piece_metadata.into(),
                 children: node.children.into_iter().map(|c| c.into()).collect(),
             }
         }
     }
 
+    impl From<&PieceMetadata> for ldr_tools::PieceMetadata {
+        fn from(metadata: &PieceMetadata) -> Self {
+            Self {
+                step_hide: metadata.step_hide,
+                is_hidden: metadata.is_hidden,
+            }
+        }
+    }
+
+    impl From<&LDrawNode> for ldr_tools::LDrawNode {
+        fn from(node: &LDrawNode) -> Self {
+            Self {
+                name: node.name.clone(),
+                unique_name: node.unique_name.clone(),
+                transform: ldr_tools::glam::Mat4::from_cols_array_2d(&node.transform),
+                geometry_name: node.geometry_name.clone(),
+                current_color: node.current_color,
+                is_mirrored: node.is_mirrored,
+                rgba_linear: node.rgba_linear,
+                piece_metadata: (&node.piece_metadata).into(),
+                children: node.children.iter().map(|c| c.into()).collect(),
+            }
+        }
+    }
+
+    #[pymethods]
+    impl LDrawNode {
+        /// The column-major elements of `transform` as 16 floats
+        /// for applications that don't use nested lists.
+        fn transform_flat(&self) -> [f32; 16] {
+            ldr_tools::glam::Mat4::from_cols_array_2d(&self.transform).to_cols_array()
+        }
+    }
+
     #[pyclass(get_all)]
     #[derive(Debug, Clone)]
     pub struct LDrawScene {
+        pub main_model_name: String,
         pub root_node: LDrawNode,
-        pub geometry_cache: HashMap<String, LDrawGeometry>,
+        pub geometry_cache: BTreeMap<String, LDrawGeometry>,
+        pub custom_colors: HashMap<u32, LDrawColor>,
+        pub stats: LoadStats,
+    }
+
+    #[pymethods]
+    impl LDrawScene {
+        /// The sorted color codes used by the faces of all nodes with geometry.
+        /// Faces with the current color 16 use the color of their node.
+        fn used_colors(&self, py: Python) -> PyResult<Vec<u32>> {
+            let mut colors: Vec<_> = self.to_scene(py)?.used_colors().into_iter().collect();
+            colors.sort_unstable();
+            Ok(colors)
+        }
+
+        /// A translation that moves the lowest point of the scene to Y=0
+        /// and optionally centers the scene on X=0 and Z=0.
+        #[pyo3(signature = (center=false))]
+        fn ground_transform(&self, py: Python, center: bool) -> PyResult<[[f32; 4]; 4]> {
+            Ok(self
+                .to_scene(py)?
+                .ground_transform(center)
+                .to_cols_array_2d())
+        }
+
+        /// The `(geometry_name, world_transform, color)` for each node with geometry
+        /// without grouping instances with the same geometry and color.
+        fn flat_instances(&self) -> Vec<(String, [[f32; 4]; 4], u32)> {
+            // Instances only depend on the nodes, so the geometry doesn't need to be converted.
+            let scene = ldr_tools::LDrawScene {
+                main_model_name: self.main_model_name.clone(),
+                root_node: (&self.root_node).into(),
+                geometry_cache: BTreeMap::new(),
+                custom_colors: HashMap::new(),
+                stats: Default::default(),
+            };
+            scene
+                .flat_instances()
+                .into_iter()
+                .map(|(name, transform, color)| (name, transform.to_cols_array_2d(), color))
+                .collect()
+        }
+    }
+
+    impl LDrawScene {
+        // Convert back to the Rust scene to reuse its methods.
+        // Colors and stats aren't needed for querying the nodes and geometry.
+        fn to_scene(&self, py: Python) -> PyResult<ldr_tools::LDrawScene> {
+            Ok(ldr_tools::LDrawScene {
+                main_model_name: self.main_model_name.clone(),
+                root_node: (&self.root_node).into(),
+                geometry_cache: self
+                    .geometry_cache
+                    .iter()
+                    .map(|(name, geometry)| Ok((name.clone(), geometry.to_geometry(py)?)))
+                    .collect::<PyResult<_>>()?,
+                custom_colors: HashMap::new(),
+                stats: Default::default(),
+            })
+        }
     }
 
     #[pyclass(get_all)]
     #[derive(Debug, Clone)]
     pub struct LDrawSceneInstanced {
         pub main_model_name: String,
-        pub geometry_world_transforms: HashMap<(String, u32), Py<PyArray3<f32>>>,
-        pub geometry_cache: HashMap<String, LDrawGeometry>,
+        pub geometry_world_transforms: BTreeMap<(String, u32), Py<PyArray3<f32>>>,
+        pub geometry_cache: BTreeMap<String, LDrawGeometry>,
+        pub custom_colors: HashMap<u32, LDrawColor>,
+        pub stats: LoadStats,
     }
 
     #[pyclass(get_all)]
     #[derive(Debug, Clone)]
     pub struct LDrawSceneInstancedPoints {
         pub main_model_name: String,
-        pub geometry_point_instances: HashMap<(String, u32), PointInstances>,
-        pub geometry_cache: HashMap<String, LDrawGeometry>,
+        pub geometry_point_instances: BTreeMap<(String, u32), PointInstances>,
+        pub geometry_cache: BTreeMap<String, LDrawGeometry>,
+        pub custom_colors: HashMap<u32, LDrawColor>,
+        pub stats: LoadStats,
+    }
+
+    #[pyclass(get_all)]
+    #[derive(Debug, Clone)]
+    pub struct GeometryStats {
+        name: String,
+        vertex_count: usize,
+        triangle_count: usize,
+    }
+
+    impl From<ldr_tools::GeometryStats> for GeometryStats {
+        fn from(stats: ldr_tools::GeometryStats) -> Self {
+            Self {
+                name: stats.name,
+                vertex_count: stats.vertex_count,
+                triangle_count: stats.triangle_count,
+            }
+        }
+    }
+
+    #[pyclass(get_all)]
+    #[derive(Debug, Clone)]
+    pub struct LoadStats {
+        files_parsed: usize,
+        geometry_count: usize,
+        face_count: usize,
+        /// The parse time in seconds.
+        parse_time: f64,
+        /// The geometry time in seconds.
+        geometry_time: f64,
+    }
+
+    impl From<ldr_tools::LoadStats> for LoadStats {
+        fn from(stats: ldr_tools::LoadStats) -> Self {
+            Self {
+                files_parsed: stats.files_parsed,
+                geometry_count: stats.geometry_count,
+                face_count: stats.face_count,
+                parse_time: stats.parse_time.as_secs_f64(),
+                geometry_time: stats.geometry_time.as_secs_f64(),
+            }
+        }
     }
 
     // Use numpy arrays for reduced overhead.
@@ -114,16 +294,177 @@ mod ldr_tools_py {
         vertex_indices: Py<PyArray1<u32>>,
         face_start_indices: Py<PyArray1<u32>>,
         face_sizes: Py<PyArray1<u32>>,
+        face_normals: Py<PyArray2<f32>>,
         face_colors: Py<PyArray1<u32>>,
         is_face_stud: Vec<bool>,
+        is_face_double_sided: Vec<bool>,
+        face_windings: Vec<Winding>,
         edge_line_indices: Py<PyArray2<u32>>,
+        edge_line_colors: Py<PyArray1<u32>>,
+        optional_line_edges: Py<PyArray3<u32>>,
+        mesh_edge_indices: Py<PyArray2<u32>>,
+        is_mesh_edge_sharp: Vec<bool>,
+        sharp_edges: Py<PyArray2<u32>>,
         has_grainy_slopes: bool,
         texture_info: Option<LDrawTextureInfo>,
+        welded_vertex_count: usize,
+    }
+
+    /// Interleaved vertex data and triangle indices.
+    type InterleavedBuffers = (Py<PyArray2<f32>>, Py<PyArray1<u32>>);
+
+    #[pymethods]
+    impl LDrawGeometry {
+        /// Create an interleaved `[position, normal, uv]` vertex buffer and a triangle index buffer.
+        fn interleaved(&self, py: Python) -> PyResult<InterleavedBuffers> {
+            let vertices = vec3s(self.vertices.bind(py).readonly().as_slice()?);
+            let face_normals = vec3s(self.face_normals.bind(py).readonly().as_slice()?);
+            let uvs = match &self.texture_info {
+                Some(info) => Some(
+                    info.uvs
+                        .bind(py)
+                        .readonly()
+                        .as_slice()?
+                        .chunks_exact(2)
+                        .map(ldr_tools::glam::Vec2::from_slice)
+                        .collect::<Vec<_>>(),
+                ),
+                None => None,
+            };
+
+            let (buffer, indices) = ldr_tools::interleave(
+                &vertices,
+                self.vertex_indices.bind(py).readonly().as_slice()?,
+                self.face_start_indices.bind(py).readonly().as_slice()?,
+                self.face_sizes.bind(py).readonly().as_slice()?,
+                &face_normals,
+                uvs.as_deref(),
+            );
+
+            let vertex_count = buffer.len() / ldr_tools::INTERLEAVED_STRIDE;
+            Ok((
+                buffer
+                    .into_pyarray(py)
+                    .reshape((vertex_count, ldr_tools::INTERLEAVED_STRIDE))?
+                    .into(),
+                indices.into_pyarray(py).into(),
+            ))
+        }
+
+        /// Tangents with the handedness in W for each vertex in the buffer from `interleaved`
+        /// or `None` if the geometry has no UVs.
+        fn tangents(&self, py: Python) -> PyResult<Option<Py<PyArray2<f32>>>> {
+            let Some(info) = &self.texture_info else {
+                return Ok(None);
+            };
+            let vertices = vec3s(self.vertices.bind(py).readonly().as_slice()?);
+            let vertex_indices = self.vertex_indices.bind(py).readonly();
+            let face_start_indices = self.face_start_indices.bind(py).readonly();
+            let face_sizes = self.face_sizes.bind(py).readonly();
+            let face_normals = vec3s(self.face_normals.bind(py).readonly().as_slice()?);
+            let vertex_normals = ldr_tools::vertex_normals(
+                vertices.len(),
+                vertex_indices.as_slice()?,
+                face_start_indices.as_slice()?,
+                face_sizes.as_slice()?,
+                &face_normals,
+            );
+            let uvs: Vec<_> = info
+                .uvs
+                .bind(py)
+                .readonly()
+                .as_slice()?
+                .chunks_exact(2)
+                .map(ldr_tools::glam::Vec2::from_slice)
+                .collect();
+
+            let tangents = ldr_tools::tangents(
+                &vertices,
+                vertex_indices.as_slice()?,
+                face_start_indices.as_slice()?,
+                face_sizes.as_slice()?,
+                &vertex_normals,
+                &uvs,
+            );
+
+            let count = tangents.len();
+            Ok(Some(
+                tangents
+                    .iter()
+                    .flat_map(|t| t.to_array())
+                    .collect::<Vec<_>>()
+                    .into_pyarray(py)
+                    .reshape((count, 4))?
+                    .into(),
+            ))
+        }
+
+        /// The color of each triangle in the index buffer from `interleaved`.
+        fn triangle_colors(&self, py: Python) -> PyResult<Py<PyArray1<u32>>> {
+            let colors = ldr_tools::triangle_colors(
+                self.face_sizes.bind(py).readonly().as_slice()?,
+                self.face_colors.bind(py).readonly().as_slice()?,
+            );
+            Ok(colors.into_pyarray(py).into())
+        }
+
+        /// Returns `True` if every edge of the faces is shared by exactly two faces.
+        fn is_manifold(&self, py: Python) -> PyResult<bool> {
+            Ok(self.manifold_report(py)?.is_manifold)
+        }
+
+        /// Find the edges of the faces not shared by exactly two faces.
+        fn manifold_report(&self, py: Python) -> PyResult<ManifoldReport> {
+            let vertices = vec3s(self.vertices.bind(py).readonly().as_slice()?);
+            let report = ldr_tools::manifold_report(
+                &vertices,
+                self.vertex_indices.bind(py).readonly().as_slice()?,
+                self.face_start_indices.bind(py).readonly().as_slice()?,
+                self.face_sizes.bind(py).readonly().as_slice()?,
+            );
+            Ok(ManifoldReport {
+                is_manifold: report.is_manifold(),
+                boundary_edges: pyarray_vec3_pairs(py, report.boundary_edges),
+                non_manifold_edges: pyarray_vec3_pairs(py, report.non_manifold_edges),
+            })
+        }
+    }
+
+    #[pyclass(get_all)]
+    #[derive(Debug, Clone)]
+    pub struct ManifoldReport {
+        is_manifold: bool,
+        /// The end points of edges used by a single face.
+        boundary_edges: Py<PyArray3<f32>>,
+        /// The end points of edges shared by more than two faces.
+        non_manifold_edges: Py<PyArray3<f32>>,
     }
 
     impl LDrawGeometry {
+        // Edges and textures aren't needed for querying the faces.
+        fn to_geometry(&self, py: Python) -> PyResult<ldr_tools::LDrawGeometry> {
+            Ok(ldr_tools::LDrawGeometry {
+                vertices: vec3s(self.vertices.bind(py).readonly().as_slice()?),
+                vertex_indices: self.vertex_indices.bind(py).to_vec()?,
+                face_start_indices: self.face_start_indices.bind(py).to_vec()?,
+                face_sizes: self.face_sizes.bind(py).to_vec()?,
+                face_normals: vec3s(self.face_normals.bind(py).readonly().as_slice()?),
+                face_colors: self.face_colors.bind(py).to_vec()?,
+                is_face_stud: self.is_face_stud.clone(),
+                is_face_double_sided: self.is_face_double_sided.clone(),
+                face_windings: self.face_windings.iter().map(|w| (*w).into()).collect(),
+                has_grainy_slopes: self.has_grainy_slopes,
+                welded_vertex_count: self.welded_vertex_count,
+                ..Default::default()
+            })
+        }
+
         fn from_geometry(py: Python, geometry: ldr_tools::LDrawGeometry) -> Self {
-            let sharp_edge_count = geometry.edge_line_indices.len();
+            let edge_line_count = geometry.edge_line_indices.len();
+            let optional_edge_count = geometry.optional_line_edges.len();
+            let mesh_edge_count = geometry.mesh_edge_indices.len();
+            let sharp_edges = geometry.sharp_edges();
+            let sharp_edge_count = sharp_edges.len();
 
             // This flatten will be optimized in Release mode.
             // This avoids needing unsafe code.
@@ -132,10 +473,43 @@ mod ldr_tools_py {
                 vertex_indices: geometry.vertex_indices.into_pyarray(py).into(),
                 face_start_indices: geometry.face_start_indices.into_pyarray(py).into(),
                 face_sizes: geometry.face_sizes.into_pyarray(py).into(),
+                face_normals: pyarray_vec3(py, geometry.face_normals),
                 face_colors: geometry.face_colors.into_pyarray(py).into(),
                 is_face_stud: geometry.is_face_stud,
+                is_face_double_sided: geometry.is_face_double_sided,
+                face_windings: geometry.face_windings.into_iter().map(Into::into).collect(),
                 edge_line_indices: geometry
                     .edge_line_indices
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u32>>()
+                    .into_pyarray(py)
+                    .reshape((edge_line_count, 2))
+                    .unwrap()
+                    .into(),
+                edge_line_colors: geometry.edge_line_colors.into_pyarray(py).into(),
+                optional_line_edges: geometry
+                    .optional_line_edges
+                    .into_iter()
+                    .flat_map(|(vertices, control_points)| {
+                        vertices.into_iter().chain(control_points)
+                    })
+                    .collect::<Vec<u32>>()
+                    .into_pyarray(py)
+                    .reshape((optional_edge_count, 2, 2))
+                    .unwrap()
+                    .into(),
+                mesh_edge_indices: geometry
+                    .mesh_edge_indices
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<u32>>()
+                    .into_pyarray(py)
+                    .reshape((mesh_edge_count, 2))
+                    .unwrap()
+                    .into(),
+                is_mesh_edge_sharp: geometry.is_mesh_edge_sharp,
+                sharp_edges: sharp_edges
                     .into_iter()
                     .flatten()
                     .collect::<Vec<u32>>()
@@ -147,6 +521,7 @@ mod ldr_tools_py {
                 texture_info: geometry
                     .texture_info
                     .map(|ti| LDrawTextureInfo::from_texture_info(py, ti)),
+                welded_vertex_count: geometry.welded_vertex_count,
             }
         }
     }
@@ -155,6 +530,11 @@ mod ldr_tools_py {
     #[derive(Debug, Clone)]
     pub struct LDrawTextureInfo {
         textures: Vec<Py<PyBytes>>,
+        texture_sizes: Vec<(u32, u32)>,
+        texture_names: Vec<String>,
+        glossmaps: Vec<Option<Py<PyBytes>>>,
+        decoded_textures: Vec<Option<Py<PyArray3<u8>>>>,
+        texture_has_alpha: Vec<bool>,
         indices: Py<PyArray1<u8>>,
         uvs: Py<PyArray2<f32>>,
     }
@@ -163,12 +543,39 @@ mod ldr_tools_py {
         fn from_texture_info(py: Python, tex_info: ldr_tools::LDrawTextureInfo) -> Self {
             let uv_count = tex_info.uvs.len();
 
+            let decoded = tex_info.decoded();
+            let texture_has_alpha = decoded
+                .iter()
+                .map(|t| t.as_ref().is_some_and(|t| t.has_alpha))
+                .collect();
+            let decoded_textures = decoded
+                .into_iter()
+                .map(|t| {
+                    t.map(|t| {
+                        t.rgba
+                            .into_pyarray(py)
+                            .reshape((t.height as usize, t.width as usize, 4))
+                            .unwrap()
+                            .into()
+                    })
+                })
+                .collect();
+
             Self {
+                texture_sizes: tex_info.texture_sizes,
+                texture_names: tex_info.texture_names,
+                decoded_textures,
+                texture_has_alpha,
                 textures: tex_info
                     .textures
                     .into_iter()
                     .map(|bytes| PyBytes::new(py, &bytes).into())
                     .collect(),
+                glossmaps: tex_info
+                    .glossmaps
+                    .into_iter()
+                    .map(|bytes| bytes.map(|bytes| PyBytes::new(py, &bytes).into()))
+                    .collect(),
                 indices: tex_info.indices.into_pyarray(py).into(),
                 uvs: tex_info
                     .uvs
@@ -189,7 +596,15 @@ mod ldr_tools_py {
         name: String,
         finish_name: String,
         rgba_linear: [f32; 4],
+        rgba_srgb: [f32; 4],
+        edge_rgba_linear: [f32; 4],
         speckle_rgba_linear: Option<[f32; 4]>,
+        glitter_rgba_linear: Option<[f32; 4]>,
+        grain_size: Option<[f32; 2]>,
+        grain_surface_fraction: Option<f32>,
+        grain_volume_fraction: Option<f32>,
+        luminance: Option<u8>,
+        emission_strength: f32,
     }
 
     impl From<ldr_tools::LDrawColor> for LDrawColor {
@@ -197,8 +612,16 @@ mod ldr_tools_py {
             Self {
                 name: c.name,
                 rgba_linear: c.rgba_linear,
+                rgba_srgb: c.rgba_srgb,
+                edge_rgba_linear: c.edge_rgba_linear,
                 finish_name: c.finish_name,
                 speckle_rgba_linear: c.speckle_rgba_linear,
+                glitter_rgba_linear: c.glitter_rgba_linear,
+                grain_size: c.grain_size,
+                grain_surface_fraction: c.grain_surface_fraction,
+                grain_volume_fraction: c.grain_volume_fraction,
+                luminance: c.luminance,
+                emission_strength: c.emission_strength,
             }
         }
     }
@@ -208,10 +631,34 @@ mod ldr_tools_py {
     pub struct GeometrySettings {
         triangulate: bool,
         add_gap_between_parts: bool,
+        gap_excludes_flexible: bool,
+        gap_distance: [f32; 3],
         stud_type: StudType,
         weld_vertices: bool,
+        weld_excluded_parts: HashSet<String>,
+        weld_excludes_flexible: bool,
         primitive_resolution: PrimitiveResolution,
+        primitive_resolution_overrides: Vec<(String, PrimitiveResolution)>,
+        additional_library_paths: Vec<String>,
         scene_scale: f32,
+        flip_texture_v: bool,
+        thread_count: Option<usize>,
+        single_threaded: bool,
+        double_side_uncertified: bool,
+        sharp_angle_threshold: f32,
+        calculate_mesh_edges: bool,
+        part_substitutions: HashMap<String, String>,
+        resolve_node_colors: bool,
+        remove_hidden_studs: bool,
+        max_recursion_depth: usize,
+        deduplicate_geometry: bool,
+        separate_studs: bool,
+        download_cache_path: Option<String>,
+        remove_duplicate_faces: bool,
+        stud_substring_fallback: bool,
+        record_face_windings: bool,
+        root_color: u32,
+        import_hidden: bool,
     }
 
     #[pymethods]
@@ -227,10 +674,38 @@ mod ldr_tools_py {
             Self {
                 triangulate: value.triangulate,
                 add_gap_between_parts: value.add_gap_between_parts,
+                gap_excludes_flexible: value.gap_excludes_flexible,
+                gap_distance: value.gap_distance.to_array(),
                 stud_type: value.stud_type.into(),
                 weld_vertices: value.weld_vertices,
+                weld_excluded_parts: value.weld_excluded_parts,
+                weld_excludes_flexible: value.weld_excludes_flexible,
                 primitive_resolution: value.primitive_resolution.into(),
+                primitive_resolution_overrides: value
+                    .primitive_resolution_overrides
+                    .iter()
+                    .map(|(pattern, resolution)| (pattern.clone(), (*resolution).into()))
+                    .collect(),
+                additional_library_paths: value.additional_library_paths,
                 scene_scale: value.scene_scale,
+                flip_texture_v: value.flip_texture_v,
+                thread_count: value.thread_count,
+                single_threaded: value.single_threaded,
+                double_side_uncertified: value.double_side_uncertified,
+                sharp_angle_threshold: value.sharp_angle_threshold,
+                calculate_mesh_edges: value.calculate_mesh_edges,
+                part_substitutions: value.part_substitutions,
+                resolve_node_colors: value.resolve_node_colors,
+                remove_hidden_studs: value.remove_hidden_studs,
+                max_recursion_depth: value.max_recursion_depth,
+                deduplicate_geometry: value.deduplicate_geometry,
+                separate_studs: value.separate_studs,
+                download_cache_path: value.download_cache_path,
+                remove_duplicate_faces: value.remove_duplicate_faces,
+                stud_substring_fallback: value.stud_substring_fallback,
+                record_face_windings: value.record_face_windings,
+                root_color: value.root_color,
+                import_hidden: value.import_hidden,
             }
         }
     }
@@ -240,10 +715,38 @@ mod ldr_tools_py {
             Self {
                 triangulate: value.triangulate,
                 add_gap_between_parts: value.add_gap_between_parts,
+                gap_excludes_flexible: value.gap_excludes_flexible,
+                gap_distance: value.gap_distance.into(),
                 stud_type: value.stud_type.into(),
                 weld_vertices: value.weld_vertices,
+                weld_excluded_parts: value.weld_excluded_parts.clone(),
+                weld_excludes_flexible: value.weld_excludes_flexible,
                 primitive_resolution: value.primitive_resolution.into(),
+                primitive_resolution_overrides: value
+                    .primitive_resolution_overrides
+                    .iter()
+                    .map(|(pattern, resolution)| (pattern.clone(), (*resolution).into()))
+                    .collect(),
+                additional_library_paths: value.additional_library_paths.clone(),
                 scene_scale: value.scene_scale,
+                flip_texture_v: value.flip_texture_v,
+                thread_count: value.thread_count,
+                single_threaded: value.single_threaded,
+                double_side_uncertified: value.double_side_uncertified,
+                sharp_angle_threshold: value.sharp_angle_threshold,
+                calculate_mesh_edges: value.calculate_mesh_edges,
+                part_substitutions: value.part_substitutions.clone(),
+                resolve_node_colors: value.resolve_node_colors,
+                remove_hidden_studs: value.remove_hidden_studs,
+                max_recursion_depth: value.max_recursion_depth,
+                deduplicate_geometry: value.deduplicate_geometry,
+                separate_studs: value.separate_studs,
+                download_cache_path: value.download_cache_path.clone(),
+                remove_duplicate_faces: value.remove_duplicate_faces,
+                stud_substring_fallback: value.stud_substring_fallback,
+                record_face_windings: value.record_face_windings,
+                root_color: value.root_color,
+                import_hidden: value.import_hidden,
             }
         }
     }
@@ -255,6 +758,7 @@ mod ldr_tools_py {
         rotations_axis: Py<PyArray2<f32>>,
         rotations_angle: Py<PyArray1<f32>>,
         scales: Py<PyArray2<f32>>,
+        transforms: Py<PyArray3<f32>>,
     }
 
     impl PointInstances {
@@ -264,46 +768,408 @@ mod ldr_tools_py {
                 rotations_axis: pyarray_vec3(py, instances.rotations_axis),
                 rotations_angle: instances.rotations_angle.into_pyarray(py).into(),
                 scales: pyarray_vec3(py, instances.scales),
+                transforms: pyarray_mat4(py, instances.transforms),
             }
         }
     }
 
+    /// A flag for stopping a load from another thread.
+    #[pyclass]
+    #[derive(Debug, Clone, Default)]
+    pub struct CancelToken {
+        cancelled: Arc<AtomicBool>,
+    }
+
+    #[pymethods]
+    impl CancelToken {
+        #[new]
+        fn new() -> Self {
+            Self::default()
+        }
+
+        fn cancel(&self) {
+            self.cancelled.store(true, Ordering::Relaxed);
+        }
+
+        fn is_cancelled(&self) -> bool {
+            self.cancelled.load(Ordering::Relaxed)
+        }
+    }
+
+    /// Parsed files that can be reused for loading multiple models.
+    #[pyclass]
+    #[derive(Debug)]
+    pub struct SourceMap {
+        source_map: ldr_tools::ldraw::SourceMap,
+    }
+
+    #[pymethods]
+    impl SourceMap {
+        #[new]
+        fn new() -> Self {
+            Self {
+                source_map: ldr_tools::ldraw::SourceMap::new(),
+            }
+        }
+
+        fn __len__(&self) -> usize {
+            self.source_map.len()
+        }
+    }
+
+    fn progress_callback(progress: &PyObject) -> impl Fn(ldr_tools::ProgressEvent) + Sync + '_ {
+        |e| {
+            let (fraction, message) = match e {
+                // The total number of files isn't known until parsing is finished.
+                ldr_tools::ProgressEvent::FileParsed { count } => {
+                    (0.0, format!("Parsed {count} files"))
+                }
+                ldr_tools::ProgressEvent::GeometryCreated { name, index, total } => (
+                    index as f32 / total as f32,
+                    format!("Created geometry for {name} ({index}/{total})"),
+                ),
+            };
+            Python::with_gil(|py| {
+                if let Err(e) = progress.call1(py, (fraction, message)) {
+                    e.print(py);
+                }
+            });
+        }
+    }
+
+    /// A single command from an LDraw file.
+    /// Less common commands use `Unhandled` with the command name.
+    #[pyclass]
+    #[derive(Debug, Clone)]
+    pub enum Command {
+        SubFileRef {
+            color: u32,
+            transform: [[f32; 4]; 4],
+            file: String,
+        },
+        Line {
+            color: u32,
+            vertices: [[f32; 3]; 2],
+        },
+        Triangle {
+            color: u32,
+            vertices: [[f32; 3]; 3],
+        },
+        Quad {
+            color: u32,
+            vertices: [[f32; 3]; 4],
+        },
+        Colour {
+            name: String,
+            code: u32,
+            value: [u8; 3],
+            edge: [u8; 3],
+            alpha: Option<u8>,
+            luminance: Option<u8>,
+        },
+        Comment {
+            text: String,
+        },
+        LPub {
+            directive: String,
+        },
+        LeoCadModel {
+            /// The property name like `"NAME"`, `"AUTHOR"`, or `"DESCRIPTION"`.
+            property: String,
+            value: String,
+        },
+        LeoCadPieceStepHide {
+            step: u32,
+        },
+        LeoCadPieceHidden {},
+        BufExchgStore {
+            buffer: char,
+        },
+        BufExchgRetrieve {
+            buffer: char,
+        },
+        Step {},
+        RotStep {
+            angles: [f32; 3],
+            mode: String,
+        },
+        RotStepEnd {},
+        Unhandled {
+            name: String,
+        },
+    }
+
+    impl From<ldr_tools::ldraw::Command> for Command {
+        fn from(value: ldr_tools::ldraw::Command) -> Self {
+            use ldr_tools::ldraw::{
+                BufExchgCommand, Command as C, LeoCadModelCmd, LeoCadPieceCmd, RotStepCommand,
+                RotStepMode,
+            };
+
+            let rgb = |c: ldr_tools::ldraw::Color| [c.red, c.green, c.blue];
+            let name = match value {
+                C::SubFileRef(c) => {
+                    return Self::SubFileRef {
+                        color: c.color,
+                        transform: c.transform.to_matrix().to_cols_array_2d(),
+                        file: c.file,
+                    }
+                }
+                C::Line(c) => {
+                    return Self::Line {
+                        color: c.color,
+                        vertices: c.vertices.map(|v| v.to_array()),
+                    }
+                }
+                C::Triangle(c) => {
+                    return Self::Triangle {
+                        color: c.color,
+                        vertices: c.vertices.map(|v| v.to_array()),
+                    }
+                }
+                C::Quad(c) => {
+                    return Self::Quad {
+                        color: c.color,
+                        vertices: c.vertices.map(|v| v.to_array()),
+                    }
+                }
+                C::Colour(c) => {
+                    return Self::Colour {
+                        name: c.name,
+                        code: c.code,
+                        value: rgb(c.value),
+                        edge: rgb(c.edge),
+                        alpha: c.alpha,
+                        luminance: c.luminance,
+                    }
+                }
+                C::Comment(c) => return Self::Comment { text: c.text },
+                C::LPub(c) => {
+                    return Self::LPub {
+                        directive: c.directive,
+                    }
+                }
+                C::LeoCadModel(c) => {
+                    let (property, value) = match c {
+                        LeoCadModelCmd::Name(v) => ("NAME", v),
+                        LeoCadModelCmd::Author(v) => ("AUTHOR", v),
+                        LeoCadModelCmd::Description(v) => ("DESCRIPTION", v),
+                    };
+                    return Self::LeoCadModel {
+                        property: property.to_string(),
+                        value,
+                    };
+                }
+                C::LeoCadPiece(LeoCadPieceCmd::StepHide(step)) => {
+                    return Self::LeoCadPieceStepHide { step }
+                }
+                C::LeoCadPiece(LeoCadPieceCmd::Hidden) => return Self::LeoCadPieceHidden {},
+                C::Step => return Self::Step {},
+                C::RotStep(RotStepCommand::Rotation(r)) => {
+                    let mode = match r.mode {
+                        RotStepMode::Rel => "REL",
+                        RotStepMode::Add => "ADD",
+                        RotStepMode::Abs => "ABS",
+                    };
+                    return Self::RotStep {
+                        angles: r.angles.to_array(),
+                        mode: mode.to_string(),
+                    };
+                }
+                C::RotStep(RotStepCommand::End) => return Self::RotStepEnd {},
+                C::BufExchg(BufExchgCommand::Store(buffer)) => {
+                    return Self::BufExchgStore { buffer }
+                }
+                C::BufExchg(BufExchgCommand::Retrieve(buffer)) => {
+                    return Self::BufExchgRetrieve { buffer }
+                }
+                C::Category(_) => "Category",
+                C::Keywords(_) => "Keywords",
+                C::Name(_) => "Name",
+                C::Author(_) => "Author",
+                C::License(_) => "License",
+                C::Help(_) => "Help",
+                C::History(_) => "History",
+                C::File(_) => "File",
+                C::NoFile => "NoFile",
+                C::Data(_) => "Data",
+                C::Base64Data(_) => "Base64Data",
+                C::OptLine(_) => "OptLine",
+                C::Bfc(_) => "Bfc",
+                C::PeTexPath(_) => "PeTexPath",
+                C::PeTexInfo(_) => "PeTexInfo",
+                C::TexMap(_) => "TexMap",
+            };
+            Self::Unhandled {
+                name: name.to_string(),
+            }
+        }
+    }
+
+    /// Parse the commands for a single file without loading any subfiles.
     #[pyfunction]
+    fn parse_file_commands(py: Python, bytes: &[u8]) -> PyResult<Vec<Command>> {
+        let cmds = py
+            .allow_threads(|| ldr_tools::ldraw::parse_raw(bytes))
+            .map_err(|e| PyException::new_err(e.to_string()))?;
+        Ok(cmds.into_iter().map(Into::into).collect())
+    }
+
+    fn load_error(e: ldr_tools::LoadError) -> PyErr {
+        match e {
+            ldr_tools::LoadError::Cancelled => LoadCancelledError::new_err(e.to_string()),
+            ldr_tools::LoadError::IoUnsupported | ldr_tools::LoadError::Parse(_) => {
+                PyException::new_err(e.to_string())
+            }
+        }
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (path, ldraw_path, additional_paths, settings, cancel=None, progress=None, source_map=None, main_model=None))]
+    #[allow(clippy::too_many_arguments)]
     fn load_file(
         py: Python,
         path: String,
         ldraw_path: String,
         additional_paths: Vec<String>,
         settings: &GeometrySettings,
+        cancel: Option<CancelToken>,
+        progress: Option<PyObject>,
+        source_map: Option<PyRefMut<SourceMap>>,
+        main_model: Option<String>,
     ) -> PyResult<LDrawScene> {
-        // TODO: This timing code doesn't need to be here.
-        let start = std::time::Instant::now();
-        let scene = ldr_tools::load_file(&path, &ldraw_path, &additional_paths, &settings.into());
+        let scene = load_scene(
+            py,
+            ldr_tools::try_load_file,
+            path,
+            ldraw_path,
+            additional_paths,
+            settings,
+            cancel,
+            progress,
+            source_map,
+            main_model,
+        )?;
 
         let geometry_cache = scene
             .geometry_cache
             .into_iter()
             .map(|(k, v)| (k, LDrawGeometry::from_geometry(py, v)))
             .collect();
-        println!("load_file: {:?}", start.elapsed());
-
         Ok(LDrawScene {
+            main_model_name: scene.main_model_name,
             root_node: scene.root_node.into(),
             geometry_cache,
+            custom_colors: color_table(scene.custom_colors),
+            stats: scene.stats.into(),
         })
     }
 
+    /// Load the file and combine all parts into a single geometry in world space.
+    /// This copies the geometry for every instance and is best for smaller models.
     #[pyfunction]
+    #[pyo3(signature = (path, ldraw_path, additional_paths, settings, cancel=None, progress=None, source_map=None, main_model=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn load_file_flattened(
+        py: Python,
+        path: String,
+        ldraw_path: String,
+        additional_paths: Vec<String>,
+        settings: &GeometrySettings,
+        cancel: Option<CancelToken>,
+        progress: Option<PyObject>,
+        source_map: Option<PyRefMut<SourceMap>>,
+        main_model: Option<String>,
+    ) -> PyResult<LDrawGeometry> {
+        let scene = load_scene(
+            py,
+            ldr_tools::try_load_file,
+            path,
+            ldraw_path,
+            additional_paths,
+            settings,
+            cancel,
+            progress,
+            source_map,
+            main_model,
+        )?;
+        let geometry = py.allow_threads(|| scene.flatten());
+        Ok(LDrawGeometry::from_geometry(py, geometry))
+    }
+
+    type LoadFn<T> = fn(
+        &str,
+        &str,
+        &[String],
+        &ldr_tools::GeometrySettings,
+        &mut ldr_tools::ldraw::SourceMap,
+        ldr_tools::LoadOptions,
+    ) -> Result<T, ldr_tools::LoadError>;
+
+    #[allow(clippy::too_many_arguments)]
+    fn load_scene<T: Send>(
+        py: Python,
+        load: LoadFn<T>,
+        path: String,
+        ldraw_path: String,
+        additional_paths: Vec<String>,
+        settings: &GeometrySettings,
+        cancel: Option<CancelToken>,
+        progress: Option<PyObject>,
+        mut source_map: Option<PyRefMut<SourceMap>>,
+        main_model: Option<String>,
+    ) -> PyResult<T> {
+        let settings = settings.into();
+        let mut new_source_map = ldr_tools::ldraw::SourceMap::new();
+        let source_map = match &mut source_map {
+            Some(s) => &mut s.source_map,
+            None => &mut new_source_map,
+        };
+        py.allow_threads(|| {
+            let on_progress = progress.as_ref().map(progress_callback);
+            let options = ldr_tools::LoadOptions {
+                cancel: cancel.as_ref().map(|c| c.cancelled.as_ref()),
+                on_progress: on_progress.as_ref().map(|f| f as _),
+                main_model: main_model.as_deref(),
+            };
+            load(
+                &path,
+                &ldraw_path,
+                &additional_paths,
+                &settings,
+                source_map,
+                options,
+            )
+        })
+        .map_err(load_error)
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (path, ldraw_path, additional_paths, settings, cancel=None, progress=None, source_map=None, main_model=None))]
+    #[allow(clippy::too_many_arguments)]
     fn load_file_instanced(
         py: Python,
         path: String,
         ldraw_path: String,
         additional_paths: Vec<String>,
         settings: &GeometrySettings,
+        cancel: Option<CancelToken>,
+        progress: Option<PyObject>,
+        source_map: Option<PyRefMut<SourceMap>>,
+        main_model: Option<String>,
     ) -> PyResult<LDrawSceneInstanced> {
-        let start = std::time::Instant::now();
-        let scene =
-            ldr_tools::load_file_instanced(&path, &ldraw_path, &additional_paths, &settings.into());
+        let scene = load_scene(
+            py,
+            ldr_tools::try_load_file_instanced,
+            path,
+            ldraw_path,
+            additional_paths,
+            settings,
+            cancel,
+            progress,
+            source_map,
+            main_model,
+        )?;
 
         let geometry_cache = scene
             .geometry_cache
@@ -317,46 +1183,45 @@ mod ldr_tools_py {
             .map(|(k, v)| {
                 // Create a single numpy array of transforms for each geometry.
                 // This means Python code can avoid overhead from for loops.
-                // This flatten will be optimized in Release mode.
-                // This avoids needing unsafe code.
-                let transform_count = v.len();
-                let transforms = v
-                    .into_iter()
-                    .flat_map(|v| v.to_cols_array())
-                    .collect::<Vec<f32>>()
-                    .into_pyarray(py)
-                    .reshape((transform_count, 4, 4))
-                    .unwrap()
-                    .into();
-
-                (k, transforms)
+                (k, pyarray_mat4(py, v))
             })
             .collect();
 
-        println!("load_file_instanced: {:?}", start.elapsed());
-
         Ok(LDrawSceneInstanced {
             main_model_name: scene.main_model_name,
             geometry_world_transforms,
             geometry_cache,
+            custom_colors: color_table(scene.custom_colors),
+            stats: scene.stats.into(),
         })
     }
 
     #[pyfunction]
+    #[pyo3(signature = (path, ldraw_path, additional_paths, settings, cancel=None, progress=None, source_map=None, main_model=None))]
+    #[allow(clippy::too_many_arguments)]
     fn load_file_instanced_points(
         py: Python,
         path: String,
         ldraw_path: String,
         additional_paths: Vec<String>,
         settings: &GeometrySettings,
+        cancel: Option<CancelToken>,
+        progress: Option<PyObject>,
+        source_map: Option<PyRefMut<SourceMap>>,
+        main_model: Option<String>,
     ) -> PyResult<LDrawSceneInstancedPoints> {
-        let start = std::time::Instant::now();
-        let scene = ldr_tools::load_file_instanced_points(
-            &path,
-            &ldraw_path,
-            &additional_paths,
-            &settings.into(),
-        );
+        let scene = load_scene(
+            py,
+            ldr_tools::try_load_file_instanced_points,
+            path,
+            ldraw_path,
+            additional_paths,
+            settings,
+            cancel,
+            progress,
+            source_map,
+            main_model,
+        )?;
 
         let geometry_cache = scene
             .geometry_cache
@@ -370,22 +1235,249 @@ mod ldr_tools_py {
             .map(|(k, v)| (k, PointInstances::from_instances(py, v)))
             .collect();
 
-        println!("load_file_instanced_points: {:?}", start.elapsed());
-
         Ok(LDrawSceneInstancedPoints {
             main_model_name: scene.main_model_name,
             geometry_point_instances,
             geometry_cache,
+            custom_colors: color_table(scene.custom_colors),
+            stats: scene.stats.into(),
         })
     }
 
+    /// Send log messages to `callback` as `(level, message)` with Python `logging` levels.
+    /// Pass `None` to stop forwarding messages.
+    #[pyfunction]
+    #[pyo3(signature = (callback, level=30))]
+    fn set_log_callback(callback: Option<PyObject>, level: u32) {
+        // Initializing the logger fails if it was already set by a previous call.
+        let _ = log::set_logger(&super::PY_LOGGER);
+
+        let max_level = match callback {
+            Some(_) => super::level_filter(level),
+            None => log::LevelFilter::Off,
+        };
+        log::set_max_level(max_level);
+        *super::LOG_CALLBACK.lock().unwrap() = callback;
+    }
+
+    #[pyclass(get_all)]
+    #[derive(Debug, Clone)]
+    pub struct ValidationReport {
+        missing_files: Vec<String>,
+        /// The file name, line number, and error message for each invalid line.
+        parse_errors: Vec<(String, usize, String)>,
+        unknown_colors: Vec<u32>,
+        conflicting_colors: Vec<u32>,
+        part_count: usize,
+        submodel_count: usize,
+    }
+
+    impl From<ldr_tools::ValidationReport> for ValidationReport {
+        fn from(report: ldr_tools::ValidationReport) -> Self {
+            Self {
+                missing_files: report.missing_files,
+                parse_errors: report
+                    .parse_errors
+                    .into_iter()
+                    .map(|e| {
+                        let message = e.parse_error.map(|e| e.to_string()).unwrap_or_default();
+                        (e.filename, e.line_number, message)
+                    })
+                    .collect(),
+                unknown_colors: report.unknown_colors,
+                conflicting_colors: report.conflicting_colors,
+                part_count: report.part_count,
+                submodel_count: report.submodel_count,
+            }
+        }
+    }
+
+    /// Check for missing files, parse errors, and unknown colors without loading geometry.
     #[pyfunction]
-    fn load_color_table(ldraw_path: &str) -> PyResult<HashMap<u32, LDrawColor>> {
-        Ok(ldr_tools::load_color_table(ldraw_path)
+    fn validate_file(
+        py: Python,
+        path: &str,
+        ldraw_path: &str,
+        additional_paths: Vec<String>,
+    ) -> ValidationReport {
+        py.allow_threads(|| ldr_tools::validate_file(path, ldraw_path, &additional_paths))
+            .into()
+    }
+
+    /// The `(width, depth, height)` in studs and plates for a bounding box in LDU.
+    #[pyfunction]
+    fn stud_footprint(min: [f32; 3], max: [f32; 3]) -> (u32, u32, u32) {
+        let footprint = ldr_tools::stud_footprint(min.into(), max.into());
+        (footprint.width, footprint.depth, footprint.height)
+    }
+
+    /// Find the names of all files referenced directly or indirectly by the file at `path`.
+    #[pyfunction]
+    fn file_dependencies(
+        py: Python,
+        path: &str,
+        ldraw_path: &str,
+        additional_paths: Vec<String>,
+    ) -> PyResult<Vec<String>> {
+        py.allow_threads(|| ldr_tools::file_dependencies(path, ldraw_path, &additional_paths))
+            .map_err(load_error)
+    }
+
+    /// Find lines in the file at `path` that can't be parsed.
+    /// Each error has the line number starting from 1, the line text, and an error message.
+    #[pyfunction]
+    fn find_parse_errors(py: Python, path: &str) -> PyResult<Vec<(usize, String, String)>> {
+        let bytes = std::fs::read(path)?;
+        let (_, errors) = py.allow_threads(|| ldr_tools::ldraw::parse_raw_with_errors(&bytes));
+        Ok(errors
             .into_iter()
-            .map(|(k, v)| (k, v.into()))
+            .map(|(line, e)| {
+                let message = e.parse_error.map(|e| e.to_string()).unwrap_or_default();
+                (e.line_number, line, message)
+            })
             .collect())
     }
+
+    /// The vertex and triangle counts for each geometry
+    /// sorted from the most to the fewest triangles.
+    #[pyfunction]
+    fn geometry_stats(
+        py: Python,
+        geometry_cache: BTreeMap<String, PyRef<LDrawGeometry>>,
+    ) -> PyResult<Vec<GeometryStats>> {
+        let geometry_cache = geometry_cache
+            .into_iter()
+            .map(|(name, geometry)| Ok((name, geometry.to_geometry(py)?)))
+            .collect::<PyResult<_>>()?;
+        Ok(ldr_tools::geometry_stats(&geometry_cache)
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Build the `(filename, description, category, keywords)` index of library parts.
+    /// The index is cached and only rebuilt for different paths or if `rebuild` is `true`.
+    #[pyfunction]
+    #[pyo3(signature = (ldraw_path, additional_library_paths, rebuild=false))]
+    fn build_part_index(
+        py: Python,
+        ldraw_path: &str,
+        additional_library_paths: Vec<String>,
+        rebuild: bool,
+    ) -> Vec<(String, String, String, Vec<String>)> {
+        let index =
+            py.allow_threads(|| cached_part_index(ldraw_path, additional_library_paths, rebuild));
+        index.iter().map(part_info).collect()
+    }
+
+    /// Find parts matching all the whitespace separated terms in `query` ignoring case.
+    /// This uses the cached index from [build_part_index] and builds it if needed.
+    #[pyfunction]
+    fn search_parts(
+        py: Python,
+        query: &str,
+        ldraw_path: &str,
+        additional_library_paths: Vec<String>,
+    ) -> Vec<(String, String, String, Vec<String>)> {
+        let index =
+            py.allow_threads(|| cached_part_index(ldraw_path, additional_library_paths, false));
+        ldr_tools::search_part_index(&index, query)
+            .into_iter()
+            .map(part_info)
+            .collect()
+    }
+
+    fn cached_part_index(
+        ldraw_path: &str,
+        additional_library_paths: Vec<String>,
+        rebuild: bool,
+    ) -> Arc<Vec<ldr_tools::PartInfo>> {
+        let mut paths = vec![ldraw_path.to_string()];
+        paths.extend(additional_library_paths);
+
+        let mut cache = PART_INDEX.lock().unwrap();
+        match cache.as_ref() {
+            Some(cached) if !rebuild && cached.paths == paths => cached.index.clone(),
+            _ => {
+                let index = Arc::new(ldr_tools::part_index(&paths[0], &paths[1..]));
+                *cache = Some(PartIndexCache {
+                    paths,
+                    index: index.clone(),
+                });
+                index
+            }
+        }
+    }
+
+    fn part_info(info: &ldr_tools::PartInfo) -> (String, String, String, Vec<String>) {
+        (
+            info.filename.clone(),
+            info.description.clone(),
+            info.category.clone(),
+            info.keywords.clone(),
+        )
+    }
+
+    #[pyfunction]
+    fn load_part_substitutions(path: &str) -> PyResult<HashMap<String, String>> {
+        Ok(ldr_tools::load_part_substitutions(path)?)
+    }
+
+    #[pyfunction]
+    #[pyo3(signature = (ldraw_path, path=None, realistic=false))]
+    fn load_color_table(
+        ldraw_path: &str,
+        path: Option<&str>,
+        realistic: bool,
+    ) -> PyResult<HashMap<u32, LDrawColor>> {
+        let color_table = match (path, realistic) {
+            (Some(path), false) => ldr_tools::load_color_table_for_file(ldraw_path, path),
+            (Some(path), true) => ldr_tools::load_alt_color_table_for_file(ldraw_path, path),
+            (None, false) => ldr_tools::load_color_table(ldraw_path),
+            (None, true) => ldr_tools::load_alt_color_table(ldraw_path),
+        };
+        Ok(self::color_table(color_table))
+    }
+
+    /// Load only the custom colors embedded in a Studio `.io` file without an LDraw library.
+    #[pyfunction]
+    fn load_io_color_table(io_path: &str) -> HashMap<u32, LDrawColor> {
+        self::color_table(ldr_tools::load_io_color_table(io_path))
+    }
+
+    fn color_table(color_table: HashMap<u32, ldr_tools::LDrawColor>) -> HashMap<u32, LDrawColor> {
+        color_table
+            .into_iter()
+            .map(|(k, v)| (k, v.into()))
+            .collect()
+    }
+}
+
+fn pyarray_mat4(py: Python, values: Vec<ldr_tools::glam::Mat4>) -> Py<PyArray3<f32>> {
+    // This flatten will be optimized in Release mode.
+    // This avoids needing unsafe code.
+    let count = values.len();
+    values
+        .into_iter()
+        .flat_map(|v| v.to_cols_array())
+        .collect::<Vec<f32>>()
+        .into_pyarray(py)
+        .reshape((count, 4, 4))
+        .unwrap()
+        .into()
+}
+
+fn pyarray_vec3_pairs(py: Python, values: Vec<[ldr_tools::glam::Vec3; 2]>) -> Py<PyArray3<f32>> {
+    let count = values.len();
+    values
+        .into_iter()
+        .flatten()
+        .flat_map(|v| [v.x, v.y, v.z])
+        .collect::<Vec<f32>>()
+        .into_pyarray(py)
+        .reshape((count, 2, 3))
+        .unwrap()
+        .into()
 }
 
 fn pyarray_vec3(py: Python, values: Vec<ldr_tools::glam::Vec3>) -> Py<PyArray2<f32>> {
@@ -401,3 +1493,66 @@ fn pyarray_vec3(py: Python, values: Vec<ldr_tools::glam::Vec3>) -> Py<PyArray2<f
         .unwrap()
         .into()
 }
+
+fn vec3s(values: &[f32]) -> Vec<ldr_tools::glam::Vec3> {
+    values
+        .chunks_exact(3)
+        .map(ldr_tools::glam::Vec3::from_slice)
+        .collect()
+}
+
+static PY_LOGGER: PyLogger = PyLogger;
+static LOG_CALLBACK: Mutex<Option<PyObject>> = Mutex::new(None);
+static PART_INDEX: Mutex<Option<PartIndexCache>> = Mutex::new(None);
+
+/// The most recently built part index and its library paths.
+struct PartIndexCache {
+    paths: Vec<String>,
+    index: Arc<Vec<ldr_tools::PartInfo>>,
+}
+
+struct PyLogger;
+
+impl log::Log for PyLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        // Acquire the GIL before the lock to avoid deadlocks with set_log_callback.
+        Python::with_gil(|py| {
+            if let Some(callback) = LOG_CALLBACK.lock().unwrap().as_ref() {
+                let level = python_level(record.level());
+                if let Err(e) = callback.call1(py, (level, record.args().to_string())) {
+                    e.print(py);
+                }
+            }
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+fn python_level(level: log::Level) -> u32 {
+    match level {
+        log::Level::Error => 40,
+        log::Level::Warn => 30,
+        log::Level::Info => 20,
+        log::Level::Debug => 10,
+        log::Level::Trace => 5,
+    }
+}
+
+fn level_filter(level: u32) -> log::LevelFilter {
+    match level {
+        0..=5 => log::LevelFilter::Trace,
+        6..=10 => log::LevelFilter::Debug,
+        11..=20 => log::LevelFilter::Info,
+        21..=30 => log::LevelFilter::Warn,
+        _ => log::LevelFilter::Error,
+    }
+}